@@ -0,0 +1,950 @@
+// ###############################################################
+// analysis.rs
+// can-blf-parser (WASM)
+// Bus/message analyses over the parsed frames (pure Rust, no JS types)
+// ###############################################################
+
+use std::collections::{BTreeMap, HashSet};
+
+use serde::Serialize;
+
+use crate::store::FrameStore;
+use crate::{id_matches, Bus, EventType, Frame, CAN_EFF_MASK};
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MessageKey {
+    pub channel_num: u16, // first so rows sort numerically (CAN2 before CAN10)
+    pub channel: String,
+    pub id: u32,
+    pub is_extended: bool,
+    pub name: String,
+}
+
+// -------------------------------
+// Activity matrix: frame counts per (message, time bucket), row-major
+// -------------------------------
+pub(crate) struct ActivityMatrix {
+    pub messages: Vec<MessageKey>,
+    pub t0: f64,
+    pub buckets: usize,
+    pub counts: Vec<u32>,
+}
+
+pub(crate) fn activity_matrix(frames: &FrameStore, bucket_s: f64, max_cells: usize) -> Result<ActivityMatrix, String> {
+    if bucket_s <= 0.0 || !bucket_s.is_finite() {
+        return Err("bucket_ms must be > 0".to_string());
+    }
+    let (t0, t1) = match (frames.first(), frames.last()) {
+        (Some(f), Some(l)) => (f.timestamp, l.timestamp),
+        _ => return Ok(ActivityMatrix { messages: vec![], t0: 0.0, buckets: 0, counts: vec![] }),
+    };
+    let buckets = ((t1 - t0) / bucket_s).floor() as usize + 1;
+
+    // error frames would share a row with the id they disturbed
+    let frames = || frames.iter().filter(|f| !f.is_error());
+    let mut rows: BTreeMap<(u16, u32), (&str, &str)> = BTreeMap::new();
+    for f in frames() {
+        rows.entry((f.channel_num, f.raw_id())).or_insert((f.channel, f.name));
+    }
+    if rows.len().saturating_mul(buckets) > max_cells {
+        return Err(format!(
+            "activity matrix too large ({} messages x {} buckets); use a larger bucket_ms",
+            rows.len(),
+            buckets
+        ));
+    }
+    let row_of: BTreeMap<(u16, u32), usize> = rows.keys().enumerate().map(|(i, k)| (*k, i)).collect();
+
+    let mut counts = vec![0u32; rows.len() * buckets];
+    for f in frames() {
+        let row = row_of[&(f.channel_num, f.raw_id())];
+        let col = (((f.timestamp - t0) / bucket_s).floor() as usize).min(buckets - 1);
+        counts[row * buckets + col] += 1;
+    }
+
+    let messages = rows
+        .into_iter()
+        .map(|((channel_num, raw), (channel, name))| {
+            let (id, is_extended) = crate::split_id(raw);
+            MessageKey { channel_num, channel: channel.to_string(), id, is_extended, name: name.to_string() }
+        })
+        .collect();
+    Ok(ActivityMatrix { messages, t0, buckets, counts })
+}
+
+// -------------------------------
+// Byte-change statistics for one CAN ID (reverse-engineering view).
+// Consecutive frames are compared per channel.
+// -------------------------------
+#[derive(Serialize, Debug, Clone)]
+pub struct ByteTransition {
+    pub timestamp: f64,
+    pub from: u8,
+    pub to: u8,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ByteChangeStats {
+    pub index: usize,
+    pub changes: u32,
+    pub change_rate: f64, // changes / compared frame pairs
+    pub distinct_values: u32,
+    pub bit_changes: [u32; 8], // bit 0 = LSB
+    pub examples: Vec<ByteTransition>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ByteChangeMatrix {
+    pub id: u32,
+    pub frames: usize,
+    pub bytes: Vec<ByteChangeStats>,
+}
+
+// `id`: bit 31 set selects the extended id only, see id_matches()
+pub(crate) fn byte_change_matrix(frames: &FrameStore, id: u32, max_examples: usize) -> ByteChangeMatrix {
+    let mut bytes: Vec<ByteChangeStats> = Vec::new();
+    let mut seen_values: Vec<[bool; 256]> = Vec::new();
+    let mut prev: BTreeMap<u16, &[u8]> = BTreeMap::new();
+    let mut pairs: Vec<u32> = Vec::new();
+    let mut count = 0usize;
+
+    for f in frames.iter().filter(|f| f.is_can_message() && id_matches(id, f.raw_id())) {
+        count += 1;
+        if bytes.len() < f.data.len() {
+            for i in bytes.len()..f.data.len() {
+                bytes.push(ByteChangeStats { index: i, ..Default::default() });
+                seen_values.push([false; 256]);
+                pairs.push(0);
+            }
+        }
+        for (i, b) in f.data.iter().enumerate() {
+            seen_values[i][*b as usize] = true;
+        }
+        if let Some(p) = prev.insert(f.channel_num, f.data) {
+            for (i, (a, b)) in p.iter().zip(f.data.iter()).enumerate() {
+                pairs[i] += 1;
+                if a != b {
+                    let st = &mut bytes[i];
+                    st.changes += 1;
+                    let diff = a ^ b;
+                    for (bit, n) in st.bit_changes.iter_mut().enumerate() {
+                        if diff & (1 << bit) != 0 {
+                            *n += 1;
+                        }
+                    }
+                    if st.examples.len() < max_examples {
+                        st.examples.push(ByteTransition { timestamp: f.timestamp, from: *a, to: *b });
+                    }
+                }
+            }
+        }
+    }
+
+    for (i, st) in bytes.iter_mut().enumerate() {
+        st.distinct_values = seen_values[i].iter().filter(|v| **v).count() as u32;
+        st.change_rate = if pairs[i] > 0 { st.changes as f64 / pairs[i] as f64 } else { 0.0 };
+    }
+    ByteChangeMatrix { id: id & CAN_EFF_MASK, frames: count, bytes }
+}
+
+// -------------------------------
+// Unmapped bits: payload bits no DBC signal covers that are ever set or toggle.
+// `coverage` gives the covered-bit mask (per byte, bit 0 = LSB) for a frame's
+// DBC message; frames without one are skipped.
+// -------------------------------
+#[derive(Serialize, Debug, Clone)]
+pub struct UnmappedBits {
+    pub channel: String,
+    pub channel_num: u16,
+    pub id: u32,
+    pub is_extended: bool,
+    pub name: String,
+    pub frames: usize,
+    pub nonzero_frames: usize, // frames with any unmapped bit set
+    pub set_bits: Vec<usize>, // byte * 8 + bit, ever 1
+    pub changing_bits: Vec<usize>, // toggled between consecutive frames
+    pub first_nonzero: Option<f64>,
+}
+
+pub(crate) fn unmapped_bits<'a>(
+    frames: &'a FrameStore,
+    coverage: impl Fn(&Frame) -> Option<&'a [u8]>,
+) -> Vec<UnmappedBits> {
+    struct Acc<'f> {
+        out: UnmappedBits,
+        set: Vec<u8>, // OR of unmapped bits
+        changed: Vec<u8>, // OR of unmapped toggles
+        prev: Option<&'f [u8]>,
+    }
+    let mut acc: BTreeMap<(u16, u32), Acc> = BTreeMap::new();
+
+    for f in frames.iter() {
+        let Some(mask) = coverage(&f) else { continue };
+        let a = acc.entry((f.channel_num, f.raw_id())).or_insert_with(|| Acc {
+            out: UnmappedBits {
+                channel: f.channel.to_string(),
+                channel_num: f.channel_num,
+                id: f.id,
+                is_extended: f.is_extended,
+                name: f.name.to_string(),
+                frames: 0,
+                nonzero_frames: 0,
+                set_bits: Vec::new(),
+                changing_bits: Vec::new(),
+                first_nonzero: None,
+            },
+            set: Vec::new(),
+            changed: Vec::new(),
+            prev: None,
+        });
+        a.out.frames += 1;
+        if a.set.len() < f.data.len() {
+            a.set.resize(f.data.len(), 0);
+            a.changed.resize(f.data.len(), 0);
+        }
+
+        let free = |i: usize| !mask.get(i).copied().unwrap_or(0);
+        let mut nonzero = false;
+        for (i, b) in f.data.iter().enumerate() {
+            let bits = b & free(i);
+            a.set[i] |= bits;
+            nonzero |= bits != 0;
+        }
+        if nonzero {
+            a.out.nonzero_frames += 1;
+            a.out.first_nonzero.get_or_insert(f.timestamp);
+        }
+        if let Some(p) = a.prev {
+            for (i, (x, y)) in p.iter().zip(f.data).enumerate() {
+                a.changed[i] |= (x ^ y) & free(i);
+            }
+        }
+        a.prev = Some(f.data);
+    }
+
+    let bit_list = |bytes: &[u8]| -> Vec<usize> {
+        (0..bytes.len() * 8).filter(|b| bytes[b / 8] & (1 << (b % 8)) != 0).collect()
+    };
+    acc.into_values()
+        .filter(|a| a.out.nonzero_frames > 0)
+        .map(|mut a| {
+            a.out.set_bits = bit_list(&a.set);
+            a.out.changing_bits = bit_list(&a.changed);
+            a.out
+        })
+        .collect()
+}
+
+// -------------------------------
+// Channel mapping suggestion: for each DBC, the share of sampled frames per logged CAN
+// channel whose id the DBC defines. `dbc_ids` holds each DBC's ids already passed
+// through `key` (raw id, or PGN in J1939 mode).
+// -------------------------------
+#[derive(Serialize, Debug, Clone)]
+pub struct MappingCandidate {
+    pub dbc: usize,
+    pub channel: u16,
+    pub sampled: usize,
+    pub matched: usize,
+    pub distinct_ids: usize, // ids seen in the sample
+    pub matched_ids: usize, // of those, defined in the DBC
+    pub hit_rate: f64, // matched / sampled
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MappingSuggestion {
+    pub channel_map: Vec<Option<u16>>, // per DBC; None when no channel had a single hit
+    pub candidates: Vec<MappingCandidate>, // every (DBC, channel) pair, best first per DBC
+}
+
+pub(crate) fn suggest_mapping(
+    frames: &FrameStore,
+    dbc_ids: &[HashSet<u32>],
+    key: impl Fn(u32) -> u32,
+    sample_per_channel: usize,
+) -> MappingSuggestion {
+    // first frames of every channel: a long log's tail adds time, not evidence
+    let mut samples: BTreeMap<u16, Vec<u32>> = BTreeMap::new();
+    for f in frames.iter().filter(|f| f.is_can_message()) {
+        let s = samples.entry(f.channel_num).or_default();
+        if s.len() < sample_per_channel {
+            s.push(key(f.raw_id()));
+        }
+    }
+
+    let mut channel_map = Vec::with_capacity(dbc_ids.len());
+    let mut candidates = Vec::new();
+    for (dbc, ids) in dbc_ids.iter().enumerate() {
+        let mut per_dbc: Vec<MappingCandidate> = samples
+            .iter()
+            .map(|(&channel, sample)| {
+                let matched = sample.iter().filter(|id| ids.contains(id)).count();
+                let distinct: HashSet<&u32> = sample.iter().collect();
+                MappingCandidate {
+                    dbc,
+                    channel,
+                    sampled: sample.len(),
+                    matched,
+                    distinct_ids: distinct.len(),
+                    matched_ids: distinct.iter().filter(|id| ids.contains(id)).count(),
+                    hit_rate: if sample.is_empty() { 0.0 } else { matched as f64 / sample.len() as f64 },
+                }
+            })
+            .collect();
+        // stable: equal rates keep the lower channel first
+        per_dbc.sort_by(|a, b| b.hit_rate.total_cmp(&a.hit_rate).then(b.matched_ids.cmp(&a.matched_ids)));
+        channel_map.push(per_dbc.first().filter(|c| c.matched > 0).map(|c| c.channel));
+        candidates.extend(per_dbc);
+    }
+    MappingSuggestion { channel_map, candidates }
+}
+
+// -------------------------------
+// Decode coverage per CAN channel. `coverage` is the same covered-bit mask as for
+// unmapped_bits(); frames without one are unmatched.
+// -------------------------------
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ChannelCoverage {
+    pub channel: String,
+    pub channel_num: u16,
+    pub frames: usize,
+    pub matched_frames: usize,
+    pub frame_pct: f64,
+    pub unmatched_ids: usize, // distinct ids without a DBC message
+    pub payload_bits: usize, // over all frames, matched or not
+    pub covered_bits: usize,
+    pub bit_pct: f64,
+}
+
+pub(crate) fn coverage<'a>(
+    frames: &'a FrameStore,
+    coverage: impl Fn(&Frame) -> Option<&'a [u8]>,
+) -> Vec<ChannelCoverage> {
+    let mut acc: BTreeMap<u16, (ChannelCoverage, HashSet<u32>)> = BTreeMap::new();
+    for f in frames.iter().filter(|f| f.is_can_message()) {
+        let (c, unmatched) = acc.entry(f.channel_num).or_insert_with(|| {
+            let c = ChannelCoverage { channel: f.channel.to_string(), channel_num: f.channel_num, ..Default::default() };
+            (c, HashSet::new())
+        });
+        c.frames += 1;
+        c.payload_bits += f.data.len() * 8;
+        match coverage(&f) {
+            Some(mask) => {
+                c.matched_frames += 1;
+                c.covered_bits += mask.iter().map(|b| b.count_ones() as usize).sum::<usize>();
+            }
+            None => {
+                unmatched.insert(f.raw_id());
+            }
+        }
+    }
+    let pct = |part: usize, whole: usize| if whole == 0 { 0.0 } else { 100.0 * part as f64 / whole as f64 };
+    acc.into_values()
+        .map(|(mut c, unmatched)| {
+            c.unmatched_ids = unmatched.len();
+            c.frame_pct = pct(c.matched_frames, c.frames);
+            c.bit_pct = pct(c.covered_bits, c.payload_bits);
+            c
+        })
+        .collect()
+}
+
+// -------------------------------
+// Cycle times per (channel, id) against the DBC GenMsgCycleTime. Jitter is the
+// standard deviation of the periods; a period off the expected cycle by more than the
+// tolerance is a violation, and a message violates when its mean period is off, or
+// more than max_violation share of its periods are.
+// -------------------------------
+#[derive(Serialize, Debug, Clone)]
+pub struct MessageTiming {
+    pub channel: String,
+    pub channel_num: u16,
+    pub id: u32,
+    pub is_extended: bool,
+    pub name: String,
+    pub frames: usize,
+    pub mean_ms: Option<f64>, // None below two frames
+    pub min_ms: Option<f64>,
+    pub max_ms: Option<f64>,
+    pub jitter_ms: Option<f64>,
+    pub expected_ms: Option<f64>, // GenMsgCycleTime; None for event-driven messages
+    pub deviation_pct: Option<f64>, // mean vs expected
+    pub violations: usize, // periods outside the tolerance
+    pub violates: bool,
+}
+
+pub(crate) struct TimingLimits {
+    pub tolerance: f64, // fraction of the expected cycle
+    pub max_violation: f64, // fraction of the periods
+}
+
+pub(crate) fn message_timing(
+    frames: &FrameStore,
+    expected_ms: impl Fn(&Frame) -> Option<f64>,
+    limits: &TimingLimits,
+) -> Vec<MessageTiming> {
+    let mut groups: BTreeMap<(u16, u32), (MessageTiming, Vec<f64>)> = BTreeMap::new();
+    for f in frames.iter().filter(|f| f.is_can_message()) {
+        let (_, times) = groups.entry((f.channel_num, f.raw_id())).or_insert_with(|| {
+            let t = MessageTiming {
+                channel: f.channel.to_string(),
+                channel_num: f.channel_num,
+                id: f.id,
+                is_extended: f.is_extended,
+                name: f.name.to_string(),
+                frames: 0,
+                mean_ms: None,
+                min_ms: None,
+                max_ms: None,
+                jitter_ms: None,
+                expected_ms: expected_ms(&f).filter(|c| *c > 0.0),
+                deviation_pct: None,
+                violations: 0,
+                violates: false,
+            };
+            (t, Vec::new())
+        });
+        times.push(f.timestamp);
+    }
+
+    groups
+        .into_values()
+        .map(|(mut t, times)| {
+            t.frames = times.len();
+            let periods: Vec<f64> = times.windows(2).map(|w| (w[1] - w[0]) * 1000.0).collect();
+            if periods.is_empty() {
+                return t;
+            }
+            let n = periods.len() as f64;
+            let mean = periods.iter().sum::<f64>() / n;
+            t.mean_ms = Some(mean);
+            t.min_ms = periods.iter().copied().reduce(f64::min);
+            t.max_ms = periods.iter().copied().reduce(f64::max);
+            t.jitter_ms = Some((periods.iter().map(|p| (p - mean).powi(2)).sum::<f64>() / n).sqrt());
+            if let Some(cycle) = t.expected_ms {
+                let allowed = cycle * limits.tolerance;
+                t.deviation_pct = Some((mean - cycle) / cycle * 100.0);
+                t.violations = periods.iter().filter(|p| (*p - cycle).abs() > allowed).count();
+                t.violates = (mean - cycle).abs() > allowed || t.violations as f64 > n * limits.max_violation;
+            }
+            t
+        })
+        .collect()
+}
+
+// -------------------------------
+// Timeouts: gaps longer than max_gap_factor x the expected period of a periodic
+// message (DBC GenMsgCycleTime, else the median period when it has enough frames to
+// learn one). A message that stops before the end of the log gets a gap up to there.
+// -------------------------------
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PeriodSource {
+    Dbc,
+    Learned,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MessageTimeout {
+    pub channel: String,
+    pub channel_num: u16,
+    pub id: u32,
+    pub is_extended: bool,
+    pub name: String,
+    pub gap_start: f64, // last frame before the gap
+    pub gap_end: f64, // next frame, or the end of the log
+    pub gap_ms: f64,
+    pub resumed: bool, // false: no frame after gap_start
+    pub period_ms: f64,
+    pub period_source: PeriodSource,
+}
+
+const MIN_LEARN_FRAMES: usize = 10;
+
+struct Track<'a> {
+    first: Frame<'a>,
+    cycle_ms: Option<f64>,
+    times: Vec<f64>,
+}
+
+pub(crate) fn detect_timeouts(
+    frames: &FrameStore,
+    expected_ms: impl Fn(&Frame) -> Option<f64>,
+    max_gap_factor: f64,
+) -> Result<Vec<MessageTimeout>, String> {
+    if !(max_gap_factor > 1.0 && max_gap_factor.is_finite()) {
+        return Err("max_gap_factor must be > 1".to_string());
+    }
+    let Some(log_end) = frames.last().map(|f| f.timestamp) else { return Ok(Vec::new()) };
+    let mut groups: BTreeMap<(u16, u32), Track> = BTreeMap::new();
+    for f in frames.iter().filter(|f| f.is_can_message()) {
+        let track = groups.entry((f.channel_num, f.raw_id())).or_insert_with(|| Track {
+            first: f,
+            cycle_ms: expected_ms(&f).filter(|c| *c > 0.0),
+            times: Vec::new(),
+        });
+        track.times.push(f.timestamp);
+    }
+
+    let mut out = Vec::new();
+    for Track { first, cycle_ms, times } in groups.into_values() {
+        let (period_ms, period_source) = match cycle_ms {
+            Some(c) => (c, PeriodSource::Dbc),
+            None if times.len() >= MIN_LEARN_FRAMES => {
+                let mut d: Vec<f64> = times.windows(2).map(|w| (w[1] - w[0]) * 1000.0).collect();
+                d.sort_by(|a, b| a.total_cmp(b));
+                (d[d.len() / 2], PeriodSource::Learned)
+            }
+            None => continue,
+        };
+        if period_ms <= 0.0 {
+            continue;
+        }
+        let limit_s = period_ms * max_gap_factor / 1000.0;
+        let ends = times.windows(2).map(|w| (w[0], w[1], true));
+        let tail = std::iter::once((times[times.len() - 1], log_end, false));
+        for (start, end, resumed) in ends.chain(tail).filter(|(s, e, _)| e - s > limit_s) {
+            out.push(MessageTimeout {
+                channel: first.channel.to_string(),
+                channel_num: first.channel_num,
+                id: first.id,
+                is_extended: first.is_extended,
+                name: first.name.to_string(),
+                gap_start: start,
+                gap_end: end,
+                gap_ms: (end - start) * 1000.0,
+                resumed,
+                period_ms,
+                period_source,
+            });
+        }
+    }
+    out.sort_by(|a, b| a.gap_start.total_cmp(&b.gap_start).then(a.channel_num.cmp(&b.channel_num)));
+    Ok(out)
+}
+
+// -------------------------------
+// Per-message statistics. Messages with an alive counter (a signal that steps by one
+// per frame, wrapping at 2^bits) also get lost frames estimated from counter skips:
+// a step of k counts k - 1 lost frames, a step of 0 is a repeat.
+// -------------------------------
+#[derive(Serialize, Debug, Clone)]
+pub struct MessageStats {
+    pub channel: String,
+    pub channel_num: u16,
+    pub id: u32,
+    pub is_extended: bool,
+    pub name: String,
+    pub frames: usize,
+    pub first_time: f64,
+    pub last_time: f64,
+    pub rate_hz: Option<f64>, // None below two distinct times
+    pub counter: Option<CounterLoss>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CounterLoss {
+    pub signal: String, // DBC signal name
+    pub bits: u32,
+    pub lost_frames: u64,
+    pub repeats: usize,
+    pub loss_pct: f64, // lost / (frames + lost)
+}
+
+// the counter of one frame: (signal name, bits, raw value)
+pub(crate) type CounterSample<'c> = (&'c str, u32, u64);
+
+pub(crate) fn message_stats<'c>(frames: &FrameStore, mut counter: impl FnMut(&Frame) -> Option<CounterSample<'c>>) -> Vec<MessageStats> {
+    let mut rows: BTreeMap<(u16, u32), (MessageStats, Option<u64>)> = BTreeMap::new();
+    for f in frames.iter().filter(|f| f.is_can_message()) {
+        let (row, last) = rows.entry((f.channel_num, f.raw_id())).or_insert_with(|| {
+            let row = MessageStats {
+                channel: f.channel.to_string(),
+                channel_num: f.channel_num,
+                id: f.id,
+                is_extended: f.is_extended,
+                name: f.name.to_string(),
+                frames: 0,
+                first_time: f.timestamp,
+                last_time: f.timestamp,
+                rate_hz: None,
+                counter: None,
+            };
+            (row, None)
+        });
+        row.frames += 1;
+        row.last_time = f.timestamp;
+        let Some((signal, bits, raw)) = counter(&f) else { continue };
+        let c = row.counter.get_or_insert_with(|| CounterLoss {
+            signal: signal.to_string(),
+            bits,
+            lost_frames: 0,
+            repeats: 0,
+            loss_pct: 0.0,
+        });
+        if let Some(prev) = last.replace(raw) {
+            let modulus = 1u64 << bits;
+            match (raw + modulus - prev % modulus) % modulus {
+                0 => c.repeats += 1,
+                step => c.lost_frames += step - 1,
+            }
+        }
+    }
+    rows.into_values()
+        .map(|(mut r, _)| {
+            let span = r.last_time - r.first_time;
+            r.rate_hz = (span > 0.0).then(|| (r.frames - 1) as f64 / span);
+            if let Some(c) = r.counter.as_mut() {
+                c.loss_pct = c.lost_frames as f64 / (r.frames as u64 + c.lost_frames) as f64 * 100.0;
+            }
+            r
+        })
+        .collect()
+}
+
+// -------------------------------
+// Bus load per CAN channel: wire time of each frame from its format (classic / FD
+// with bit rate switch, standard / extended id, worst-case stuff bits optional) summed
+// per time bucket, in % of the bucket. Reassembled J1939 TP rows are not on the wire.
+// -------------------------------
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Bitrates {
+    pub nominal: f64, // bit/s, arbitration phase and classic CAN
+    pub data: f64, // bit/s, CAN FD data phase with BRS
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ChannelLoad {
+    pub channel: String,
+    pub channel_num: u16,
+    pub bitrate: f64,
+    pub data_bitrate: f64,
+    pub frames: usize,
+    pub mean_pct: f64, // over all buckets
+    pub peak_pct: f64,
+    #[serde(skip)]
+    pub load: Vec<f64>, // % per bucket
+}
+
+pub(crate) struct BusLoad {
+    pub t0: f64,
+    pub buckets: usize,
+    pub channels: Vec<ChannelLoad>,
+}
+
+// error flag, echo, delimiter and intermission at their longest
+const ERROR_FRAME_BITS: f64 = 23.0;
+
+// (bits at the nominal rate, bits at the data rate)
+fn frame_bits(f: &Frame, stuffing: bool) -> (f64, f64) {
+    if f.is_error() {
+        return (ERROR_FRAME_BITS, 0.0);
+    }
+    let payload = if f.flags.rtr { 0 } else { f.data.len() * 8 };
+    let stuff = |bits: usize| if stuffing { (bits.saturating_sub(1) / 4) as f64 } else { 0.0 };
+    if !f.flags.fd {
+        // SOF..CRC (without the data) is stuffed; CRC delimiter, ACK, EOF and
+        // intermission (13 bits) are not
+        let head = if f.is_extended { 54 } else { 34 };
+        return ((head + payload + 13) as f64 + stuff(head + payload), 0.0);
+    }
+    // FD: SOF..BRS nominal; ESI, DLC, data, stuff count, CRC at the data rate;
+    // the CRC field carries a fixed stuff bit every 4 bits
+    let arbitration = if f.is_extended { 36 } else { 17 };
+    let crc = if f.data.len() <= 16 { 17 } else { 21 };
+    let data = 5 + payload + 4 + crc;
+    let fixed = if stuffing { (4 + crc).div_ceil(4) as f64 } else { 0.0 };
+    let nominal = arbitration as f64 + 13.0 + stuff(arbitration);
+    (nominal, data as f64 + stuff(5 + payload) + fixed)
+}
+
+pub(crate) fn bus_load(
+    frames: &FrameStore,
+    bucket_s: f64,
+    rates: impl Fn(u16) -> Bitrates,
+    stuffing: bool,
+    max_cells: usize,
+) -> Result<BusLoad, String> {
+    if bucket_s <= 0.0 || !bucket_s.is_finite() {
+        return Err("bucket_ms must be > 0".to_string());
+    }
+    let (t0, t1) = match (frames.first(), frames.last()) {
+        (Some(f), Some(l)) => (f.timestamp, l.timestamp),
+        _ => return Ok(BusLoad { t0: 0.0, buckets: 0, channels: vec![] }),
+    };
+    let buckets = ((t1 - t0) / bucket_s).floor() as usize + 1;
+
+    let mut channels: BTreeMap<u16, ChannelLoad> = BTreeMap::new();
+    for f in frames.iter().filter(|f| f.bus == Bus::Can && f.event_type != EventType::J1939Tp) {
+        if !channels.contains_key(&f.channel_num) && (channels.len() + 1).saturating_mul(buckets) > max_cells {
+            return Err(format!("{} buckets per channel is too many; use a larger bucket_ms", buckets));
+        }
+        let c = channels.entry(f.channel_num).or_insert_with(|| {
+            let r = rates(f.channel_num);
+            ChannelLoad {
+                channel: f.channel.to_string(),
+                channel_num: f.channel_num,
+                bitrate: r.nominal,
+                data_bitrate: r.data,
+                frames: 0,
+                mean_pct: 0.0,
+                peak_pct: 0.0,
+                load: vec![0.0; buckets],
+            }
+        });
+        let (nominal, data) = frame_bits(&f, stuffing);
+        let data_rate = if f.flags.brs { c.data_bitrate } else { c.bitrate };
+        let b = (((f.timestamp - t0) / bucket_s) as usize).min(buckets - 1);
+        c.load[b] += (nominal / c.bitrate + data / data_rate) / bucket_s * 100.0;
+        c.frames += 1;
+    }
+    let mut channels: Vec<ChannelLoad> = channels.into_values().collect();
+    for c in channels.iter_mut() {
+        c.mean_pct = c.load.iter().sum::<f64>() / buckets as f64;
+        c.peak_pct = c.load.iter().copied().fold(0.0, f64::max);
+    }
+    Ok(BusLoad { t0, buckets, channels })
+}
+
+// -------------------------------
+// Active time range for previews: logs often open with minutes of silence or a lone
+// boot message. Frames are counted in equal time buckets; the range runs from the first
+// to the last bucket reaching a tenth of a busy (90th percentile) bucket.
+// -------------------------------
+const ACTIVITY_BUCKETS: usize = 200;
+
+pub(crate) fn active_range(frames: &FrameStore) -> Option<(f64, f64)> {
+    let (t0, t1) = (frames.first()?.timestamp, frames.last()?.timestamp);
+    let width = (t1 - t0) / ACTIVITY_BUCKETS as f64;
+    if width <= 0.0 {
+        return Some((t0, t1));
+    }
+    let mut counts = vec![0usize; ACTIVITY_BUCKETS];
+    for f in frames.iter() {
+        counts[(((f.timestamp - t0) / width) as usize).min(ACTIVITY_BUCKETS - 1)] += 1;
+    }
+    let mut sorted = counts.clone();
+    sorted.sort_unstable();
+    let threshold = (sorted[ACTIVITY_BUCKETS * 9 / 10] / 10).max(1);
+    let first = counts.iter().position(|c| *c >= threshold)?;
+    let last = counts.iter().rposition(|c| *c >= threshold)?;
+    Some((t0 + first as f64 * width, (t0 + (last + 1) as f64 * width).min(t1)))
+}
+
+// -------------------------------
+// Message sets and rates of two time windows of one log (e.g. before / after an
+// ECU reset). Rates are frames per second of the window.
+// -------------------------------
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Presence {
+    OnlyA,
+    OnlyB,
+    Both,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MessageDiff {
+    pub channel: String,
+    pub channel_num: u16,
+    pub id: u32,
+    pub is_extended: bool,
+    pub name: String,
+    pub presence: Presence,
+    pub frames_a: usize,
+    pub frames_b: usize,
+    pub rate_a_hz: f64,
+    pub rate_b_hz: f64,
+    pub rate_change_pct: Option<f64>, // None unless in both
+}
+
+pub(crate) fn diff_messages(frames: &FrameStore, a: (f64, f64), b: (f64, f64)) -> Vec<MessageDiff> {
+    let mut rows: BTreeMap<(u16, u32), MessageDiff> = BTreeMap::new();
+    for (window, in_a) in [(a, true), (b, false)] {
+        for f in frames.range(frames.window(window.0, window.1)).filter(|f| !f.is_error()) {
+            let row = rows.entry((f.channel_num, f.raw_id())).or_insert_with(|| MessageDiff {
+                channel: f.channel.to_string(),
+                channel_num: f.channel_num,
+                id: f.id,
+                is_extended: f.is_extended,
+                name: f.name.to_string(),
+                presence: Presence::Both,
+                frames_a: 0,
+                frames_b: 0,
+                rate_a_hz: 0.0,
+                rate_b_hz: 0.0,
+                rate_change_pct: None,
+            });
+            if in_a {
+                row.frames_a += 1;
+            } else {
+                row.frames_b += 1;
+            }
+        }
+    }
+    let rate = |n: usize, (t0, t1): (f64, f64)| if t1 > t0 { n as f64 / (t1 - t0) } else { 0.0 };
+    rows.into_values()
+        .map(|mut r| {
+            r.rate_a_hz = rate(r.frames_a, a);
+            r.rate_b_hz = rate(r.frames_b, b);
+            r.presence = match (r.frames_a, r.frames_b) {
+                (_, 0) => Presence::OnlyA,
+                (0, _) => Presence::OnlyB,
+                _ => Presence::Both,
+            };
+            if r.presence == Presence::Both && r.rate_a_hz > 0.0 {
+                r.rate_change_pct = Some((r.rate_b_hz - r.rate_a_hz) / r.rate_a_hz * 100.0);
+            }
+            r
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::Payload;
+    use crate::{ErrorInfo, FrameRow};
+
+    fn row(t: f64, data: &[u8]) -> FrameRow {
+        FrameRow {
+            timestamp: t,
+            channel: "CAN1".to_string(),
+            channel_num: 1,
+            id: 0x100,
+            event_type: EventType::Can,
+            dlc: data.len() as u8,
+            data: Payload::from(data),
+            ..FrameRow::default()
+        }
+    }
+
+    #[test]
+    fn frame_lengths_and_load() {
+        // classic 8 bytes: 111 bits + 24 worst-case stuff bits; extended 131 + 29
+        let mut r = row(0.0, &[0; 8]);
+        assert_eq!(frame_bits(&r.view(), true), (135.0, 0.0));
+        assert_eq!(frame_bits(&r.view(), false), (111.0, 0.0));
+        r.is_extended = true;
+        assert_eq!(frame_bits(&r.view(), true), (160.0, 0.0));
+        // FD 64 bytes: 30 nominal + 4 stuff; 542 in the data phase + 129 stuff + 7 fixed
+        let mut r = row(0.0, &[0; 64]);
+        r.flags.fd = true;
+        assert_eq!(frame_bits(&r.view(), true), (34.0, 678.0));
+
+        // two 135-bit frames at 500 kbit/s in the first 1 ms bucket, one in the second
+        let mut store = FrameStore::default();
+        for t in [0.0, 0.0005, 0.0015] {
+            store.push(row(t, &[0; 8]).view());
+        }
+        let rates = |_| Bitrates { nominal: 500_000.0, data: 2_000_000.0 };
+        let load = bus_load(&store, 0.001, rates, true, 1000).unwrap();
+        assert_eq!(load.buckets, 2);
+        let c = &load.channels[0];
+        assert!((c.load[0] - 54.0).abs() < 1e-9 && (c.load[1] - 27.0).abs() < 1e-9);
+        assert!((c.peak_pct - 54.0).abs() < 1e-9 && (c.mean_pct - 40.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn timeouts_from_learned_period() {
+        // 10 ms message with a 100 ms hole at 0.5 s; stops at 1 s in a 2 s log
+        let mut store = FrameStore::default();
+        let times = (0..100).map(|i| i as f64 * 0.01).filter(|t| !(0.5..0.6).contains(t));
+        for t in times {
+            store.push(row(t, &[0]).view());
+        }
+        let mut other = row(2.0, &[0]);
+        other.id = 0x200;
+        store.push(other.view());
+        let gaps = detect_timeouts(&store, |_| None, 3.0).unwrap();
+        let found: Vec<(f64, f64, bool)> = gaps.iter().map(|g| (g.gap_start, g.gap_end, g.resumed)).collect();
+        assert_eq!(found.len(), 2);
+        assert!((found[0].0 - 0.49).abs() < 1e-9 && (found[0].1 - 0.6).abs() < 1e-9 && found[0].2);
+        assert!((found[1].0 - 0.99).abs() < 1e-9 && found[1].1 == 2.0 && !found[1].2);
+        assert_eq!(gaps[0].period_source, PeriodSource::Learned);
+        // the DBC period wins over the learned one
+        assert!(detect_timeouts(&store, |_| Some(50.0), 3.0).unwrap().iter().all(|g| g.id == 0x100 && !g.resumed));
+    }
+
+    #[test]
+    fn counter_skips() {
+        // 4-bit counter: 14, 15, 0 (wrap), 3 (2 lost), 3 (repeat), 5 (1 lost)
+        let mut store = FrameStore::default();
+        for (i, v) in [14u8, 15, 0, 3, 3, 5].iter().enumerate() {
+            store.push(row(i as f64 * 0.01, &[*v]).view());
+        }
+        let stats = message_stats(&store, |f| Some(("Alive", 4, f.data[0] as u64)));
+        let c = stats[0].counter.as_ref().unwrap();
+        assert_eq!((stats[0].frames, c.lost_frames, c.repeats), (6, 3, 1));
+        assert!((c.loss_pct - 100.0 / 3.0).abs() < 1e-9);
+        assert!((stats[0].rate_hz.unwrap() - 100.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn timing_period_jitter_and_gaps() {
+        // 10 ms cycle: periods 10, 12, 8 and one 30 ms gap
+        let mut store = FrameStore::default();
+        for t in [0.0, 0.010, 0.022, 0.030, 0.060] {
+            store.push(row(t, &[0]).view());
+        }
+        let limits = TimingLimits { tolerance: 0.1, max_violation: 0.2 };
+        let t = &message_timing(&store, |_| Some(10.0), &limits)[0];
+        assert_eq!(t.frames, 5);
+        assert!((t.mean_ms.unwrap() - 15.0).abs() < 1e-9);
+        assert!((t.min_ms.unwrap() - 8.0).abs() < 1e-9 && (t.max_ms.unwrap() - 30.0).abs() < 1e-9);
+        // population std dev of 10, 12, 8, 30 around 15
+        assert!((t.jitter_ms.unwrap() - 77.0f64.sqrt()).abs() < 1e-9);
+        assert!((t.deviation_pct.unwrap() - 50.0).abs() < 1e-9);
+        assert_eq!(t.violations, 3);
+        assert!(t.violates);
+
+        // event-driven (no cycle time) and single-frame messages
+        let t = &message_timing(&store, |_| None, &limits)[0];
+        assert!(t.expected_ms.is_none() && t.deviation_pct.is_none() && !t.violates);
+        let mut one = FrameStore::default();
+        one.push(row(1.0, &[0]).view());
+        let t = &message_timing(&one, |_| Some(10.0), &limits)[0];
+        assert!(t.frames == 1 && t.mean_ms.is_none() && t.jitter_ms.is_none() && !t.violates);
+    }
+
+    #[test]
+    fn diff_of_two_windows() {
+        // window a [0, 1): 0x100 at 10 Hz, 0x200 once; window b [1, 2): 0x100 at 20 Hz, 0x300
+        let mut store = FrameStore::default();
+        let mut push = |t: f64, id: u32| {
+            let mut r = row(t, &[0]);
+            r.id = id;
+            store.push(r.view());
+        };
+        for i in 0..10 {
+            push(i as f64 * 0.1, 0x100);
+        }
+        push(0.55, 0x200);
+        for i in 0..20 {
+            push(1.0 + i as f64 * 0.05, 0x100);
+        }
+        push(1.5, 0x300);
+        store.push(FrameRow { event_type: EventType::Error, error: Some(ErrorInfo::default()), ..row(1.6, &[]) }.view());
+
+        let diff = diff_messages(&store, (0.0, 0.99), (1.0, 1.99));
+        let by_id: Vec<(u32, Presence, usize, usize)> = diff.iter().map(|d| (d.id, d.presence, d.frames_a, d.frames_b)).collect();
+        assert_eq!(by_id, [(0x100, Presence::Both, 10, 20), (0x200, Presence::OnlyA, 1, 0), (0x300, Presence::OnlyB, 0, 1)]);
+        assert!((diff[0].rate_change_pct.unwrap() - 100.0).abs() < 1e-6);
+        assert!(diff[1].rate_change_pct.is_none() && diff[2].rate_change_pct.is_none());
+    }
+
+    #[test]
+    fn active_range_skips_quiet_start() {
+        // one boot frame per second for 60 s, then 100 frames/s for 40 s
+        let mut store = FrameStore::default();
+        for i in 0..60 {
+            store.push(row(i as f64, &[0]).view());
+        }
+        for i in 0..4000 {
+            store.push(row(60.0 + i as f64 * 0.01, &[0]).view());
+        }
+        let (start, end) = active_range(&store).unwrap();
+        assert!((59.0..=60.0).contains(&start), "{}", start);
+        assert!(end > 99.0);
+    }
+}
@@ -0,0 +1,343 @@
+// ###############################################################
+// arrow.rs
+// can-blf-parser (WASM)
+// Apache Arrow IPC stream writer for to_arrow(): one Schema message, one
+// RecordBatch with all rows, end-of-stream marker. apache-arrow's
+// tableFromIPC(), DuckDB-WASM and Perspective load it without JSON parsing.
+// ###############################################################
+
+use crate::table::{bits, Column, Values};
+
+// -------------------------------
+// Stream: per message 0xFFFFFFFF | i32 metadata length | Message flatbuffer | body,
+// each part padded to 8 bytes; then 0xFFFFFFFF 00000000. Metadata is written with
+// the small flatbuffer builder below (Schema.fbs / Message.fbs, format V5).
+// -------------------------------
+const CONTINUATION: u32 = 0xFFFF_FFFF;
+const METADATA_V5: i16 = 4;
+const HEADER_SCHEMA: u8 = 1;
+const HEADER_RECORD_BATCH: u8 = 3;
+
+// Type union
+const TYPE_INT: u8 = 2;
+const TYPE_FLOATING_POINT: u8 = 3;
+const TYPE_BINARY: u8 = 4;
+const TYPE_UTF8: u8 = 5;
+const TYPE_BOOL: u8 = 6;
+const PRECISION_DOUBLE: i16 = 2;
+
+// Flatbuffer value; tables are (slot, value) lists in schema field order
+enum Fb {
+    Bool(bool),
+    U8(u8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    Str(String),
+    Table(Vec<(u16, Fb)>),
+    Tables(Vec<Fb>),
+    Structs(Vec<[i64; 2]>), // FieldNode / Buffer: two longs each
+}
+
+impl Fb {
+    // inline size in a table; references are 4-byte offsets
+    fn size(&self) -> usize {
+        match self {
+            Fb::Bool(_) | Fb::U8(_) => 1,
+            Fb::I16(_) => 2,
+            Fb::I64(_) => 8,
+            _ => 4,
+        }
+    }
+}
+
+// Writes front to back: a table first, then what its offsets point to (offsets are
+// unsigned and point forward), patching each offset once its target is placed.
+// Tables start 8-aligned with fields largest first, so every scalar is aligned.
+#[derive(Default)]
+struct FbWriter {
+    buf: Vec<u8>,
+}
+
+impl FbWriter {
+    // zero padding until (len + ahead) is a multiple of `align`
+    fn pad(&mut self, align: usize, ahead: usize) {
+        while !(self.buf.len() + ahead).is_multiple_of(align) {
+            self.buf.push(0);
+        }
+    }
+
+    fn patch(&mut self, at: usize, target: usize) {
+        self.buf[at..at + 4].copy_from_slice(&((target - at) as u32).to_le_bytes());
+    }
+
+    fn table(&mut self, fields: &[(u16, Fb)]) -> usize {
+        let mut order: Vec<&(u16, Fb)> = fields.iter().collect();
+        order.sort_by_key(|(_, v)| std::cmp::Reverse(v.size()));
+        let mut inline: usize = 4; // soffset to the vtable
+        let mut placed = Vec::with_capacity(order.len());
+        for (slot, v) in order {
+            inline = inline.next_multiple_of(v.size());
+            placed.push((*slot, v, inline));
+            inline += v.size();
+        }
+
+        let slots = fields.iter().map(|(s, _)| *s as usize + 1).max().unwrap_or(0);
+        let vtable_len = 4 + 2 * slots;
+        self.pad(8, vtable_len);
+        let mut vtable = vec![0u16; 2 + slots];
+        vtable[0] = vtable_len as u16;
+        vtable[1] = inline as u16;
+        for (slot, _, at) in &placed {
+            vtable[2 + *slot as usize] = *at as u16;
+        }
+        vtable.iter().for_each(|x| self.buf.extend_from_slice(&x.to_le_bytes()));
+
+        let start = self.buf.len();
+        self.buf.resize(start + inline, 0);
+        self.buf[start..start + 4].copy_from_slice(&(vtable_len as i32).to_le_bytes());
+        let mut refs = Vec::new();
+        for (_, v, at) in placed {
+            let at = start + at;
+            let bytes: &[u8] = match v {
+                Fb::Bool(b) => &[*b as u8],
+                Fb::U8(x) => &[*x],
+                Fb::I16(x) => &x.to_le_bytes(),
+                Fb::I32(x) => &x.to_le_bytes(),
+                Fb::I64(x) => &x.to_le_bytes(),
+                _ => {
+                    refs.push((at, v));
+                    continue;
+                }
+            };
+            self.buf[at..at + bytes.len()].copy_from_slice(bytes);
+        }
+        for (at, v) in refs {
+            let target = self.reference(v);
+            self.patch(at, target);
+        }
+        start
+    }
+
+    fn reference(&mut self, v: &Fb) -> usize {
+        match v {
+            Fb::Table(fields) => self.table(fields),
+            Fb::Str(s) => {
+                self.pad(4, 0);
+                let at = self.buf.len();
+                self.buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+                self.buf.extend_from_slice(s.as_bytes());
+                self.buf.push(0);
+                at
+            }
+            Fb::Tables(tables) => {
+                self.pad(4, 0);
+                let at = self.buf.len();
+                self.buf.extend_from_slice(&(tables.len() as u32).to_le_bytes());
+                self.buf.resize(at + 4 + 4 * tables.len(), 0);
+                for (i, t) in tables.iter().enumerate() {
+                    let target = self.reference(t);
+                    self.patch(at + 4 + 4 * i, target);
+                }
+                at
+            }
+            Fb::Structs(items) => {
+                self.pad(8, 4);
+                let at = self.buf.len();
+                self.buf.extend_from_slice(&(items.len() as u32).to_le_bytes());
+                items.iter().flatten().for_each(|x| self.buf.extend_from_slice(&x.to_le_bytes()));
+                at
+            }
+            _ => unreachable!("scalars are written inline"),
+        }
+    }
+
+    fn finish(root: &[(u16, Fb)]) -> Vec<u8> {
+        let mut w = FbWriter { buf: vec![0; 4] };
+        let at = w.table(root);
+        w.patch(0, at);
+        w.pad(8, 0);
+        w.buf
+    }
+}
+
+fn message(out: &mut Vec<u8>, header_type: u8, header: Vec<(u16, Fb)>, body: &[u8]) {
+    let meta = FbWriter::finish(&[
+        (0, Fb::I16(METADATA_V5)),
+        (1, Fb::U8(header_type)),
+        (2, Fb::Table(header)),
+        (3, Fb::I64(body.len() as i64)),
+    ]);
+    out.extend_from_slice(&CONTINUATION.to_le_bytes());
+    out.extend_from_slice(&(meta.len() as i32).to_le_bytes());
+    out.extend(meta);
+    out.extend_from_slice(body);
+}
+
+fn field(c: &Column) -> Fb {
+    let (type_id, ty) = match c.values {
+        Values::Double(_) | Values::OptDouble(_) => (TYPE_FLOATING_POINT, vec![(0, Fb::I16(PRECISION_DOUBLE))]),
+        Values::Int32(_) => (TYPE_INT, vec![(0, Fb::I32(32)), (1, Fb::Bool(true))]),
+        Values::Bool(_) => (TYPE_BOOL, Vec::new()),
+        Values::Utf8(_) => (TYPE_UTF8, Vec::new()),
+        Values::Bytes(_) => (TYPE_BINARY, Vec::new()),
+    };
+    Fb::Table(vec![
+        (0, Fb::Str(c.name.clone())),
+        (1, Fb::Bool(matches!(c.values, Values::OptDouble(_)))),
+        (2, Fb::U8(type_id)),
+        (3, Fb::Table(ty)),
+        (5, Fb::Tables(Vec::new())),
+    ])
+}
+
+// RecordBatch body: buffers 8-aligned, with their (offset, length)
+#[derive(Default)]
+struct Body {
+    data: Vec<u8>,
+    buffers: Vec<[i64; 2]>,
+}
+
+impl Body {
+    fn push(&mut self, bytes: &[u8]) {
+        self.buffers.push([self.data.len() as i64, bytes.len() as i64]);
+        self.data.extend_from_slice(bytes);
+        self.data.resize(self.data.len().next_multiple_of(8), 0);
+    }
+
+    // validity bitmap (empty when nothing is null), then the value buffers
+    fn column(&mut self, values: &Values) -> i64 {
+        let mut nulls = 0;
+        match values {
+            Values::Double(v) => {
+                self.push(&[]);
+                let mut out = Vec::with_capacity(v.len() * 8);
+                v.iter().for_each(|x| out.extend_from_slice(&x.to_le_bytes()));
+                self.push(&out);
+            }
+            Values::OptDouble(v) => {
+                let valid: Vec<bool> = v.iter().map(Option::is_some).collect();
+                nulls = valid.iter().filter(|b| !**b).count() as i64;
+                self.push(&if nulls > 0 { bits(&valid) } else { Vec::new() });
+                let mut out = Vec::with_capacity(v.len() * 8);
+                v.iter().for_each(|x| out.extend_from_slice(&x.unwrap_or(0.0).to_le_bytes()));
+                self.push(&out);
+            }
+            Values::Int32(v) => {
+                self.push(&[]);
+                let mut out = Vec::with_capacity(v.len() * 4);
+                v.iter().for_each(|x| out.extend_from_slice(&x.to_le_bytes()));
+                self.push(&out);
+            }
+            Values::Bool(v) => {
+                self.push(&[]);
+                self.push(&bits(v));
+            }
+            Values::Utf8(v) => self.binary(v.iter().map(|s| s.as_bytes())),
+            Values::Bytes(v) => self.binary(v.iter().map(|b| b.as_slice())),
+        }
+        nulls
+    }
+
+    fn binary<'a>(&mut self, items: impl Iterator<Item = &'a [u8]>) {
+        let (mut offsets, mut data) = (0i32.to_le_bytes().to_vec(), Vec::new());
+        for b in items {
+            data.extend_from_slice(b);
+            offsets.extend_from_slice(&(data.len() as i32).to_le_bytes());
+        }
+        self.push(&[]);
+        self.push(&offsets);
+        self.push(&data);
+    }
+}
+
+// IPC stream of the columns (all the same length) as one record batch
+pub(crate) fn encode(columns: &[Column]) -> Vec<u8> {
+    let rows = columns.first().map_or(0, |c| c.values.len());
+    let mut out = Vec::new();
+    let schema = vec![(0, Fb::I16(0)), (1, Fb::Tables(columns.iter().map(field).collect()))]; // little endian
+    message(&mut out, HEADER_SCHEMA, schema, &[]);
+
+    let mut body = Body::default();
+    let nodes = columns.iter().map(|c| [c.values.len() as i64, body.column(&c.values)]).collect();
+    let batch = vec![(0, Fb::I64(rows as i64)), (1, Fb::Structs(nodes)), (2, Fb::Structs(std::mem::take(&mut body.buffers)))];
+    message(&mut out, HEADER_RECORD_BATCH, batch, &body.data);
+
+    out.extend_from_slice(&CONTINUATION.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_at(b: &[u8], at: usize) -> usize {
+        u32::from_le_bytes(b[at..at + 4].try_into().unwrap()) as usize
+    }
+
+    // absolute position of a table field, None when absent
+    fn slot(b: &[u8], table: usize, slot: usize) -> Option<usize> {
+        let vtable = table - i32::from_le_bytes(b[table..table + 4].try_into().unwrap()) as usize;
+        let len = u16::from_le_bytes([b[vtable], b[vtable + 1]]) as usize;
+        let entry = 4 + 2 * slot;
+        let off = if entry < len { u16::from_le_bytes([b[vtable + entry], b[vtable + entry + 1]]) } else { 0 };
+        (off > 0).then_some(table + off as usize)
+    }
+
+    fn deref(b: &[u8], at: usize) -> usize {
+        at + u32_at(b, at)
+    }
+
+    #[test]
+    fn stream_layout() {
+        let columns = [
+            Column::new("time_s", Values::Double(vec![0.0, 0.5, 1.0])),
+            Column::new("name", Values::Utf8(vec!["A".into(), "".into(), "Speed".into()])),
+            Column::new("CAN1.Speed", Values::OptDouble(vec![Some(1.0), None, Some(2.0)])),
+        ];
+        let ipc = encode(&columns);
+        assert_eq!(&ipc[ipc.len() - 8..], &[0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0]);
+
+        // schema: three fields, the signal nullable, names as strings
+        assert_eq!(u32_at(&ipc, 0), CONTINUATION as usize);
+        let len = u32_at(&ipc, 4);
+        assert_eq!(len % 8, 0);
+        let meta = &ipc[8..8 + len];
+        let root = deref(meta, 0);
+        assert_eq!(meta[slot(meta, root, 1).unwrap()], HEADER_SCHEMA);
+        let schema = deref(meta, slot(meta, root, 2).unwrap());
+        let fields = deref(meta, slot(meta, schema, 1).unwrap());
+        assert_eq!(u32_at(meta, fields), 3);
+        let signal = deref(meta, fields + 4 + 8);
+        let name = deref(meta, slot(meta, signal, 0).unwrap());
+        assert_eq!(&meta[name + 4..name + 4 + u32_at(meta, name)], b"CAN1.Speed");
+        assert_eq!(meta[slot(meta, signal, 1).unwrap()], 1);
+        assert_eq!(meta[slot(meta, signal, 2).unwrap()], TYPE_FLOATING_POINT);
+
+        // record batch: 3 rows, one null, buffers 8-aligned inside the body
+        let at = 8 + len;
+        assert_eq!(u32_at(&ipc, at), CONTINUATION as usize);
+        let len = u32_at(&ipc, at + 4);
+        let meta = &ipc[at + 8..at + 8 + len];
+        let root = deref(meta, 0);
+        assert_eq!(meta[slot(meta, root, 1).unwrap()], HEADER_RECORD_BATCH);
+        let body_len = i64::from_le_bytes(meta[slot(meta, root, 3).unwrap()..][..8].try_into().unwrap()) as usize;
+        let body = &ipc[at + 8 + len..at + 8 + len + body_len];
+        let batch = deref(meta, slot(meta, root, 2).unwrap());
+        let nodes = deref(meta, slot(meta, batch, 1).unwrap());
+        assert_eq!(u32_at(meta, nodes), 3);
+        assert_eq!((nodes + 4) % 8, 0);
+        let long = |at: usize| i64::from_le_bytes(meta[at..at + 8].try_into().unwrap());
+        assert_eq!((long(nodes + 4 + 32), long(nodes + 4 + 40)), (3, 1));
+        let buffers = deref(meta, slot(meta, batch, 2).unwrap());
+        assert_eq!(u32_at(meta, buffers), 2 + 3 + 2);
+        let buffer = |i: usize| (long(buffers + 4 + 16 * i) as usize, long(buffers + 12 + 16 * i) as usize);
+        let (offsets, _) = buffer(3);
+        let (data, data_len) = buffer(4);
+        assert_eq!(&body[offsets..offsets + 16], &[0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 6, 0, 0, 0]);
+        assert_eq!(&body[data..data + data_len], b"ASpeed");
+        assert_eq!(body[buffer(5).0], 0b101);
+        assert!((0..7).all(|i| buffer(i).0 % 8 == 0));
+    }
+}
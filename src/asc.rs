@@ -0,0 +1,387 @@
+// ###############################################################
+// asc.rs
+// can-blf-parser (WASM)
+// Vector ASCII trace (.asc) writer for export_asc(), readable by CANoe /
+// CANalyzer offline mode and python-can, and reader for .asc input
+// (input.rs). CAN, CAN FD and error frames only; other buses have no line
+// format here and are skipped.
+// ###############################################################
+
+use std::fmt::Write;
+
+use crate::blf::{BlfObject, CanError, CanFrame, LogStart};
+use crate::{EventType, Frame};
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+// CAN FD flags column: EDL, BRS, ESI
+const FD_EDL: u32 = 1 << 12;
+const FD_BRS: u32 = 1 << 13;
+const FD_ESI: u32 = 1 << 14;
+
+pub(crate) const FOOTER: &str = "End TriggerBlock\n";
+
+// "Thu Oct 16 10:00:00.000 am 2026"; the Unix epoch when the BLF header has no start time
+fn date(start: Option<&LogStart>) -> String {
+    let Some(s) = start else {
+        return "Thu Jan 01 12:00:00.000 am 1970".to_string();
+    };
+    let hour12 = if s.hour % 12 == 0 { 12 } else { s.hour % 12 };
+    format!(
+        "{} {} {:02} {:02}:{:02}:{:02}.{:03} {} {}",
+        WEEKDAYS[s.day_of_week as usize % 7],
+        MONTHS[(s.month as usize).clamp(1, 12) - 1],
+        s.day,
+        hour12,
+        s.minute,
+        s.second,
+        s.millisecond,
+        if s.hour < 12 { "am" } else { "pm" },
+        s.year
+    )
+}
+
+// Timestamps stay relative to the measurement start ("timestamps absolute" in ASC terms)
+pub(crate) fn header(start: Option<&LogStart>) -> String {
+    let date = date(start);
+    format!(
+        "date {date}\nbase hex  timestamps absolute\nno internal events logged\n// version 9.0.0\n\
+         Begin Triggerblock {date}\n   0.000000 Start of measurement\n"
+    )
+}
+
+fn hex_bytes(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len() * 3);
+    for (i, b) in data.iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        let _ = write!(out, "{:02X}", b);
+    }
+    out
+}
+
+// One trace line (with newline), None for frames ASC cannot hold
+pub(crate) fn line(f: &Frame) -> Option<String> {
+    let t = f.timestamp;
+    let ch = f.channel_num;
+    let id = if f.is_extended { format!("{:X}x", f.id) } else { format!("{:X}", f.id) };
+    let line = match f.event_type {
+        EventType::Error => format!("{t:>11.6} {ch}  ErrorFrame\n"),
+        EventType::CanFd => {
+            let mut flags = FD_EDL;
+            if f.flags.brs {
+                flags |= FD_BRS;
+            }
+            if f.flags.esi {
+                flags |= FD_ESI;
+            }
+            format!(
+                "{t:>11.6} CANFD {ch:>3} {:<4} {id:>8}  {:>32} {} {} {:X} {:>2} {} {:>8} {:>4} {flags:>8X} {:>8} {:>8} {:>8} {:>8} {:>8}\n",
+                f.dir,
+                f.name,
+                f.flags.brs as u8,
+                f.flags.esi as u8,
+                f.dlc,
+                f.data.len(),
+                hex_bytes(f.data),
+                0, 0, 0, 0, 0, 0, 0
+            )
+        }
+        EventType::CanRemote => format!("{t:>11.6} {ch}  {id:<15} {:<4} r {:X}\n", f.dir, f.dlc),
+        EventType::Can => format!("{t:>11.6} {ch}  {id:<15} {:<4} d {:X} {}\n", f.dir, f.dlc, hex_bytes(f.data)),
+        _ => return None,
+    };
+    Some(line)
+}
+
+// -------------------------------
+// Reader: the CAN, CAN FD and error frame lines of a trace become the BLF objects they
+// were logged from, so the session pipeline is the same for both formats. Status,
+// statistics, LIN and comment lines are skipped; ids and data follow the "base" line
+// (hex or dec), "timestamps relative" ones add up.
+// -------------------------------
+// A trace opens with its "date" / "base" lines, possibly after comments
+pub(crate) fn detect(bytes: &[u8]) -> bool {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(4096)]);
+    head.lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty() && !l.starts_with("//"))
+        .is_some_and(|l| l.starts_with("date ") || l.starts_with("base "))
+}
+
+// Day of week (0 = Sunday) of a Gregorian date
+fn weekday(year: u16, month: u16, day: u16) -> u16 {
+    const OFFSETS: [u32; 12] = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+    let y = if month < 3 { year as u32 - 1 } else { year as u32 };
+    ((y + y / 4 - y / 100 + y / 400 + OFFSETS[month as usize - 1] + day as u32) % 7) as u16
+}
+
+// "Thu Oct 16 10:00:00.000 am 2026", or 24-hour without am/pm; English names only
+fn parse_date(tok: &[&str]) -> Option<LogStart> {
+    let month = MONTHS.iter().position(|m| tok.get(1).is_some_and(|t| t.eq_ignore_ascii_case(m)))? as u16 + 1;
+    let day: u16 = tok.get(2)?.parse().ok()?;
+    let (hms, fraction) = tok.get(3)?.split_once('.').unwrap_or((tok[3], "0"));
+    let mut hms = hms.split(':').map(|p| p.parse::<u16>().ok());
+    let (hour, minute, second) = (hms.next()??, hms.next()??, hms.next()??);
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let millisecond: u16 = format!("{:0<3}", fraction)[..3].parse().ok()?;
+    let (hour, year_at) = match tok.get(4).map(|s| s.to_ascii_lowercase()).as_deref() {
+        Some("am") => (hour % 12, 5),
+        Some("pm") => (hour % 12 + 12, 5),
+        _ => (hour, 4),
+    };
+    let year: u16 = tok.get(year_at)?.parse().ok()?;
+    if !(1..=31).contains(&day) || year == 0 {
+        return None;
+    }
+    Some(LogStart { year, month, day_of_week: weekday(year, month, day), day, hour, minute, second, millisecond })
+}
+
+fn number(s: &str, hex: bool) -> Option<u32> {
+    if hex { u32::from_str_radix(s, 16).ok() } else { s.parse().ok() }
+}
+
+// "1A0" / "18FEF100x": raw id with the IDE bit for extended ones, as blf.rs reads them
+fn frame_id(s: &str, hex: bool) -> Option<u32> {
+    match s.strip_suffix('x') {
+        Some(ext) => Some(number(ext, hex)? | 0x8000_0000),
+        None => number(s, hex),
+    }
+}
+
+// (tx, tx_request)
+fn direction(s: &str) -> Option<(bool, bool)> {
+    match s {
+        "Rx" => Some((false, false)),
+        "Tx" => Some((true, false)),
+        "TxRq" => Some((false, true)),
+        _ => None,
+    }
+}
+
+fn data(tok: &[&str], len: usize, hex: bool) -> Option<Vec<u8>> {
+    let bytes = tok.get(..len)?;
+    bytes.iter().map(|b| number(b, hex).and_then(|v| u8::try_from(v).ok())).collect()
+}
+
+fn error_frame(timestamp_ns: u64, channel: u16, fd: bool) -> BlfObject {
+    BlfObject::Error(CanError {
+        timestamp_ns,
+        channel,
+        id: 0,
+        dlc: 0,
+        data: Vec::new(),
+        fd,
+        error_type: None,
+        tx: None,
+        position: None,
+        ecc: None,
+        tx_errors: None,
+        rx_errors: None,
+    })
+}
+
+fn can_frame(timestamp_ns: u64, channel: u16, id: u32, (tx, tx_request): (bool, bool), dlc: u8, data: Vec<u8>) -> CanFrame {
+    CanFrame {
+        timestamp_ns,
+        channel,
+        id,
+        dlc,
+        data,
+        tx,
+        tx_request,
+        rtr: false,
+        wakeup: false,
+        nerr: false,
+        fd: false,
+        brs: false,
+        esi: false,
+    }
+}
+
+// `1  1A0  Rx  d 8 01 02 ...`, `1  1A0  Rx  r 8` (older writers omit the DLC), `1  ErrorFrame` (after the timestamp)
+fn classic(tok: &[&str], ts: u64, hex: bool) -> Option<BlfObject> {
+    let channel: u16 = tok.first()?.parse().ok()?;
+    if tok.get(1) == Some(&"ErrorFrame") {
+        return Some(error_frame(ts, channel, false));
+    }
+    let id = frame_id(tok.get(1)?, hex)?;
+    let dir = direction(tok.get(2)?)?;
+    let dlc = tok.get(4).and_then(|d| u8::from_str_radix(d, 16).ok());
+    match *tok.get(3)? {
+        "d" => {
+            let dlc = dlc?;
+            let bytes = data(tok.get(5..)?, (dlc as usize).min(8), hex)?;
+            Some(BlfObject::Can(can_frame(ts, channel, id, dir, dlc, bytes)))
+        }
+        "r" => Some(BlfObject::Can(CanFrame { rtr: true, ..can_frame(ts, channel, id, dir, dlc.unwrap_or(0), Vec::new()) })),
+        _ => None,
+    }
+}
+
+// `CANFD 1 Rx 1A0 [name] brs esi dlc len data... duration bits flags ...`
+fn can_fd(tok: &[&str], ts: u64, hex: bool) -> Option<BlfObject> {
+    let channel: u16 = tok.first()?.parse().ok()?;
+    let dir = direction(tok.get(1)?)?;
+    if tok.get(2) == Some(&"ErrorFrame") {
+        return Some(error_frame(ts, channel, true));
+    }
+    let id = frame_id(tok.get(2)?, hex)?;
+    // the symbolic name column is empty for messages without one
+    let bit = |t: Option<&&str>| matches!(t, Some(&"0") | Some(&"1"));
+    let at = if bit(tok.get(3)) && bit(tok.get(4)) { 3 } else { 4 };
+    let brs = *tok.get(at)? == "1";
+    let esi = *tok.get(at + 1)? == "1";
+    let dlc = u8::from_str_radix(tok.get(at + 2)?, 16).ok()?;
+    // no CAN FD frame carries more than 64 bytes: longer lengths are corrupt lines
+    let len = tok.get(at + 3)?.parse::<usize>().ok().filter(|&l| l <= 64)?;
+    let bytes = data(tok.get(at + 4..)?, len, hex)?;
+    // a classic frame logged on an FD channel has EDL clear in the flags column
+    let flags = len.checked_add(at + 6).and_then(|i| tok.get(i)).and_then(|f| u32::from_str_radix(f, 16).ok());
+    let fd = flags.is_none_or(|f| f & FD_EDL != 0);
+    Some(BlfObject::Can(CanFrame { fd, brs: fd && brs, esi: fd && esi, ..can_frame(ts, channel, id, dir, dlc, bytes) }))
+}
+
+pub(crate) fn read(bytes: &[u8]) -> Vec<BlfObject> {
+    let text = String::from_utf8_lossy(bytes);
+    let (mut hex, mut relative) = (true, false);
+    let mut last = 0.0;
+    let mut objects = Vec::new();
+    for line in text.lines() {
+        let tok: Vec<&str> = line.split_whitespace().collect();
+        let Some(&first) = tok.first() else { continue };
+        match first {
+            "base" => {
+                hex = tok.get(1) != Some(&"dec");
+                relative = tok.get(3) == Some(&"relative");
+            }
+            _ => {
+                // "date", "Begin Triggerblock", comments and such have no timestamp
+                let Ok(t) = first.parse::<f64>() else { continue };
+                let t = if relative { last + t } else { t };
+                last = t;
+                let ts = (t.max(0.0) * 1e9).round() as u64;
+                let obj = if tok.get(1) == Some(&"CANFD") { can_fd(&tok[2..], ts, hex) } else { classic(&tok[1..], ts, hex) };
+                objects.extend(obj);
+            }
+        }
+    }
+    objects
+}
+
+// Measurement start from the "date" line
+pub(crate) fn start(bytes: &[u8]) -> Option<LogStart> {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(4096)]);
+    let line = head.lines().map(str::trim).find(|l| l.starts_with("date "))?;
+    parse_date(&line.split_whitespace().skip(1).collect::<Vec<_>>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FrameFlags, FrameRow, Payload};
+
+    #[test]
+    fn header_and_lines() {
+        let start = LogStart { year: 2026, month: 10, day_of_week: 5, day: 16, hour: 13, minute: 5, second: 9, millisecond: 7 };
+        assert!(header(Some(&start)).starts_with("date Fri Oct 16 01:05:09.007 pm 2026\n"));
+        assert!(header(None).contains("Begin Triggerblock Thu Jan 01 12:00:00.000 am 1970\n"));
+
+        let mut row = FrameRow {
+            timestamp: 1.5,
+            channel: "CAN2".to_string(),
+            channel_num: 2,
+            id: 0x18FEF100,
+            is_extended: true,
+            event_type: EventType::Can,
+            dir: "Rx".to_string(),
+            dlc: 3,
+            data: Payload::from(&[0x01, 0xAB, 0xFF][..]),
+            ..FrameRow::default()
+        };
+        assert_eq!(line(&row.view()).unwrap(), "   1.500000 2  18FEF100x       Rx   d 3 01 AB FF\n");
+
+        row.event_type = EventType::CanFd;
+        row.flags = FrameFlags { fd: true, brs: true, ..FrameFlags::default() };
+        let fd = line(&row.view()).unwrap();
+        assert!(fd.starts_with("   1.500000 CANFD   2 Rx   18FEF100x "));
+        assert!(fd.contains(" 1 0 3  3 01 AB FF "));
+        assert!(fd.contains("    3000 "));
+
+        row.event_type = EventType::CanRemote;
+        row.data = Payload::default();
+        row.dlc = 8;
+        assert_eq!(line(&row.view()).unwrap(), "   1.500000 2  18FEF100x       Rx   r 8\n");
+
+        row.event_type = EventType::Lin;
+        assert!(line(&row.view()).is_none());
+    }
+
+    #[test]
+    fn reads_written_and_canoe_traces() {
+        let start = LogStart { year: 2026, month: 10, day_of_week: 5, day: 16, hour: 13, minute: 5, second: 9, millisecond: 7 };
+        let mut row = FrameRow {
+            timestamp: 1.5,
+            channel_num: 2,
+            id: 0x18FEF100,
+            is_extended: true,
+            event_type: EventType::CanFd,
+            dir: "Tx".to_string(),
+            dlc: 9,
+            data: Payload::from(&[1u8, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12][..]),
+            flags: FrameFlags { fd: true, brs: true, ..FrameFlags::default() },
+            ..FrameRow::default()
+        };
+        let mut text = header(Some(&start));
+        text += &line(&row.view()).unwrap();
+        row.name = "EngineData".to_string();
+        text += &line(&row.view()).unwrap();
+        text += FOOTER;
+        assert!(detect(text.as_bytes()));
+        assert_eq!(self::start(text.as_bytes()), Some(start));
+        let objects = read(text.as_bytes());
+        assert_eq!(objects.len(), 2);
+        for obj in &objects {
+            let BlfObject::Can(cf) = obj else { panic!("CAN FD frame expected") };
+            assert_eq!((cf.timestamp_ns, cf.channel, cf.id, cf.dlc), (1_500_000_000, 2, 0x18FEF100 | 0x8000_0000, 9));
+            assert_eq!(cf.data.len(), 12);
+            assert!(cf.fd && cf.brs && !cf.esi && cf.tx);
+        }
+
+        let canoe = "// exported by CANoe\ndate Mon Jan 22 14:09:23 2024\nbase dec  timestamps relative\n\
+                     Begin Triggerblock Mon Jan 22 14:09:23 2024\n   0.000000 Start of measurement\n\
+                        0.010000 1  416             Rx   d 2 1 255  Length = 0 BitCount = 0\n\
+                        0.005000 1  SV: 0 1 CAN1::Speed = 3\n\
+                        0.005000 1  416             TxRq r\n   0.010000 3  ErrorFrame\nEnd TriggerBlock\n";
+        let s = self::start(canoe.as_bytes()).unwrap();
+        assert_eq!((s.hour, s.day_of_week, s.year), (14, 1, 2024));
+        let objects = read(canoe.as_bytes());
+        assert_eq!(objects.len(), 3);
+        let BlfObject::Can(data) = &objects[0] else { panic!("data frame expected") };
+        assert_eq!((data.timestamp_ns, data.id, data.data.as_slice()), (10_000_000, 416, &[1u8, 255][..]));
+        let BlfObject::Can(remote) = &objects[1] else { panic!("remote frame expected") };
+        assert!(remote.rtr && remote.tx_request && remote.timestamp_ns == 20_000_000);
+        assert!(matches!(&objects[2], BlfObject::Error(e) if e.channel == 3 && e.timestamp_ns == 30_000_000));
+        assert!(!detect(b"LOGG\x90\x00\x00\x00"));
+        assert!(parse_date(&["Mon", "Jan", "22", "10:00:00.a\u{FF}", "2024"]).is_none());
+        assert_eq!(parse_date(&["Mon", "Jan", "22", "10:00:00.5", "2024"]).map(|s| s.millisecond), Some(500));
+    }
+
+    #[test]
+    fn fd_lengths_past_64_bytes() {
+        let fd_line = |len: &str, bytes: usize| {
+            format!("   0.100000 CANFD   1 Rx   1A0  1 0 f {} {}\n", len, vec!["01"; bytes].join(" "))
+        };
+        let objects = read(fd_line("64", 64).as_bytes());
+        let [BlfObject::Can(cf)] = objects.as_slice() else { panic!("one CAN FD frame expected") };
+        assert_eq!((cf.dlc, cf.data.len(), cf.fd), (15, 64, true));
+        // a corrupt length column is dropped, not cut to 64 bytes or indexed past the line
+        for (len, bytes) in [("65", 70), ("18446744073709551615", 70), ("70", 0)] {
+            assert!(read(fd_line(len, bytes).as_bytes()).is_empty(), "length {}", len);
+        }
+    }
+}
@@ -0,0 +1,200 @@
+// ###############################################################
+// binary.rs
+// can-blf-parser (WASM)
+// Binary encoding of decimation results (decimated() with encoding "binary"):
+// one Uint8Array whose buffer can be transferred to a worker / main thread
+// instead of structured-cloning a large nested object
+// ###############################################################
+
+use std::collections::HashMap;
+
+use crate::decimate::{Decimator, EdgeTrace, GroupedDecimator};
+use crate::ranked;
+
+// -------------------------------
+// Layout (little endian):
+//   "BDEC" | u32 version | u32 entry_count | u32 array_count | u32 data_offset | u32 names_offset
+//   entries:      entry_count x (u32 kind | u32 channel_num | u32 id | u32 time_array | u32 value_array)
+//   array_len:    array_count x u32 (elements)
+//   padding to a multiple of 8 (data_offset)
+//   data:         arrays concatenated as f64, so each is a Float64Array view of the buffer
+//   names_offset: entry_count x (u32 len | name utf8)
+// Kinds: 0 signal on the shared time array (NaN before its first sample), 1 digital edge
+// trace (0/1), 2 discrete edge trace, 3 message signal (group_by_message; name
+// "{Message}.{Signal}", channel_num/id set with bit 31 of id marking extended ids, NaN
+// where a kept frame lacks the signal).
+// Entries follow options.order within each kind (and within each message for kind 3).
+// Identical time arrays are stored once.
+// -------------------------------
+const MAGIC: &[u8; 4] = b"BDEC";
+pub(crate) const VERSION: u32 = 1;
+const HEADER: usize = 24;
+const ENTRY: usize = 20;
+
+const KIND_SIGNAL: u32 = 0;
+const KIND_DIGITAL: u32 = 1;
+const KIND_DISCRETE: u32 = 2;
+const KIND_MESSAGE_SIGNAL: u32 = 3;
+
+struct Entry {
+    kind: u32,
+    channel_num: u32,
+    id: u32,
+    time: u32,
+    values: u32,
+    name: String,
+}
+
+#[derive(Default)]
+struct Writer {
+    arrays: Vec<Vec<f64>>,
+    times: HashMap<Vec<u64>, u32>, // f64 bit patterns -> array index
+    entries: Vec<Entry>,
+}
+
+impl Writer {
+    fn time(&mut self, time: Vec<f64>) -> u32 {
+        let key: Vec<u64> = time.iter().map(|t| t.to_bits()).collect();
+        *self.times.entry(key).or_insert_with(|| {
+            self.arrays.push(time);
+            (self.arrays.len() - 1) as u32
+        })
+    }
+
+    fn values(&mut self, values: Vec<f64>) -> u32 {
+        self.arrays.push(values);
+        (self.arrays.len() - 1) as u32
+    }
+
+    fn traces(&mut self, kind: u32, traces: HashMap<String, EdgeTrace>, rank: &HashMap<String, usize>) {
+        for (name, trace) in ranked(traces, rank) {
+            let time = self.time(trace.time);
+            let values = self.values(trace.values);
+            self.entries.push(Entry { kind, channel_num: 0, id: 0, time, values, name });
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let tables = HEADER + self.entries.len() * ENTRY + self.arrays.len() * 4;
+        let data_offset = tables.div_ceil(8) * 8;
+        let data_len: usize = self.arrays.iter().map(|a| a.len() * 8).sum();
+        let names_offset = data_offset + data_len;
+
+        let mut out = Vec::with_capacity(names_offset + self.entries.iter().map(|e| 4 + e.name.len()).sum::<usize>());
+        out.extend_from_slice(MAGIC);
+        for v in [VERSION, self.entries.len() as u32, self.arrays.len() as u32, data_offset as u32, names_offset as u32] {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        for e in &self.entries {
+            for v in [e.kind, e.channel_num, e.id, e.time, e.values] {
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        for a in &self.arrays {
+            out.extend_from_slice(&(a.len() as u32).to_le_bytes());
+        }
+        out.resize(data_offset, 0);
+        for a in &self.arrays {
+            for v in a {
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        for e in &self.entries {
+            out.extend_from_slice(&(e.name.len() as u32).to_le_bytes());
+            out.extend_from_slice(e.name.as_bytes());
+        }
+        out
+    }
+}
+
+pub(crate) fn encode_decimation(dec: Decimator, rank: &HashMap<String, usize>) -> Vec<u8> {
+    let mut w = Writer::default();
+    let time = w.time(dec.time);
+    for (name, vals) in ranked(dec.signals, rank) {
+        let values = w.values(vals.into_iter().map(|v| v.unwrap_or(f64::NAN)).collect());
+        w.entries.push(Entry { kind: KIND_SIGNAL, channel_num: 0, id: 0, time, values, name });
+    }
+    w.traces(KIND_DIGITAL, dec.digital, rank);
+    w.traces(KIND_DISCRETE, dec.discrete_traces, rank);
+    w.finish()
+}
+
+pub(crate) fn encode_grouped(dec: GroupedDecimator, rank: &HashMap<String, usize>) -> Vec<u8> {
+    let mut w = Writer::default();
+    let mut groups: Vec<_> = dec.groups.into_values().collect();
+    groups.sort_by_key(|g| (g.channel_num, g.is_extended, g.id));
+    for g in groups {
+        let time = w.time(g.time);
+        let message = if g.name.is_empty() { format!("0x{:X}", g.id) } else { g.name };
+        for (sig, vals) in ranked(g.signals, rank) {
+            let values = w.values(vals);
+            w.entries.push(Entry {
+                kind: KIND_MESSAGE_SIGNAL,
+                channel_num: g.channel_num as u32,
+                id: if g.is_extended { g.id | crate::CAN_EFF_FLAG } else { g.id },
+                time,
+                values,
+                name: format!("{}.{}", message, sig),
+            });
+        }
+    }
+    w.traces(KIND_DIGITAL, dec.digital, rank);
+    w.traces(KIND_DISCRETE, dec.discrete_traces, rank);
+    w.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SignalRow;
+
+    // (kind, name, time, values) of each entry
+    fn read(buf: &[u8]) -> Vec<(u32, String, Vec<f64>, Vec<f64>)> {
+        let u32_at = |at: usize| u32::from_le_bytes(buf[at..at + 4].try_into().unwrap()) as usize;
+        assert_eq!(&buf[..4], MAGIC);
+        assert_eq!(u32_at(4), VERSION as usize);
+        let (entries, arrays, data_offset, mut names) = (u32_at(8), u32_at(12), u32_at(16), u32_at(20));
+        assert_eq!(data_offset % 8, 0);
+        let mut starts = vec![data_offset];
+        let lens: Vec<usize> = (0..arrays).map(|a| u32_at(HEADER + entries * ENTRY + a * 4)).collect();
+        for len in &lens {
+            starts.push(starts.last().unwrap() + len * 8);
+        }
+        assert_eq!(starts[arrays], names);
+        let array = |a: usize| -> Vec<f64> {
+            buf[starts[a]..starts[a + 1]].chunks_exact(8).map(|c| f64::from_le_bytes(c.try_into().unwrap())).collect()
+        };
+        let mut out = Vec::new();
+        for e in 0..entries {
+            let at = HEADER + e * ENTRY;
+            let len = u32_at(names);
+            let name = String::from_utf8(buf[names + 4..names + 4 + len].to_vec()).unwrap();
+            names += 4 + len;
+            out.push((u32_at(at) as u32, name, array(u32_at(at + 12)), array(u32_at(at + 16))));
+        }
+        assert_eq!(names, buf.len());
+        out
+    }
+
+    #[test]
+    fn decimation_round_trip() {
+        let row = |signal: &str, value: f64| SignalRow { signal: signal.to_string(), value, unit: String::new(), original_unit: None, value_text: None };
+        let mut dec = Decimator::new(1, None, Default::default(), None, HashMap::from([("CAN1.Door".to_string(), true)]));
+        dec.push(0.0, &[row("CAN1.Speed", 1.0), row("CAN1.Door", 0.0)]);
+        dec.push(0.5, &[row("CAN1.Speed", 2.0), row("CAN1.Rpm", 800.0)]);
+        dec.push(1.0, &[row("CAN1.Speed", 3.0), row("CAN1.Door", 1.0)]);
+        let rank = HashMap::from([("CAN1.Speed".to_string(), 1), ("CAN1.Rpm".to_string(), 0), ("CAN1.Door".to_string(), 2)]);
+        let entries = read(&encode_decimation(dec.finish(), &rank));
+
+        let summary: Vec<(u32, &str)> = entries.iter().map(|(k, n, _, _)| (*k, n.as_str())).collect();
+        assert_eq!(summary, [(KIND_SIGNAL, "CAN1.Rpm"), (KIND_SIGNAL, "CAN1.Speed"), (KIND_DIGITAL, "CAN1.Door")]);
+        let (_, _, time, rpm) = &entries[0];
+        assert_eq!(time, &[0.0, 0.5, 1.0]);
+        assert!(rpm[0].is_nan() && rpm[1..] == [800.0, 800.0]);
+        assert_eq!(entries[1].3, [1.0, 2.0, 3.0]);
+        let (_, _, door_time, door) = &entries[2];
+        assert_eq!(door.first(), Some(&0.0));
+        assert_eq!(door.last(), Some(&1.0));
+        assert_eq!(door_time.last(), Some(&1.0));
+    }
+}
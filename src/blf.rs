@@ -0,0 +1,1271 @@
+// ###############################################################
+// blf.rs
+// can-blf-parser (WASM)
+// Minimal BLF object reader: file header, (compressed) log containers,
+// classic CAN and CAN FD message and error frame objects, driver error
+// counters, LIN messages, FlexRay and Ethernet frames. Other objects are skipped.
+// BlfWriter writes those objects back (export_blf()).
+// ###############################################################
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use zune_inflate::{DeflateDecoder, DeflateOptions};
+
+// object types we decode (Vector binlog object ids)
+const CAN_MESSAGE: u32 = 1;
+const CAN_ERROR: u32 = 2;
+const LOG_CONTAINER: u32 = 10;
+const LIN_MESSAGE: u32 = 11;
+const CAN_DRIVER_ERROR: u32 = 31;
+const LIN_MESSAGE2: u32 = 57;
+const ETHERNET_FRAME: u32 = 71;
+const CAN_ERROR_EXT: u32 = 73;
+const CAN_DRIVER_ERROR_EXT: u32 = 74;
+const FR_RCVMESSAGE: u32 = 50;
+const FR_RCVMESSAGE_EX: u32 = 66;
+const CAN_MESSAGE2: u32 = 86;
+const CAN_FD_MESSAGE: u32 = 100;
+const CAN_FD_MESSAGE_64: u32 = 101;
+const CAN_FD_ERROR_64: u32 = 104;
+const ETHERNET_FRAME_EX: u32 = 120;
+
+// Object types read by parse_object(), for version()
+pub(crate) const SUPPORTED_OBJECTS: [(u32, &str); 16] = [
+    (CAN_MESSAGE, "CAN_MESSAGE"),
+    (CAN_ERROR, "CAN_ERROR"),
+    (LOG_CONTAINER, "LOG_CONTAINER"),
+    (LIN_MESSAGE, "LIN_MESSAGE"),
+    (CAN_DRIVER_ERROR, "CAN_DRIVER_ERROR"),
+    (FR_RCVMESSAGE, "FR_RCVMESSAGE"),
+    (LIN_MESSAGE2, "LIN_MESSAGE2"),
+    (FR_RCVMESSAGE_EX, "FR_RCVMESSAGE_EX"),
+    (ETHERNET_FRAME, "ETHERNET_FRAME"),
+    (CAN_ERROR_EXT, "CAN_ERROR_EXT"),
+    (CAN_DRIVER_ERROR_EXT, "CAN_DRIVER_ERROR_EXT"),
+    (CAN_MESSAGE2, "CAN_MESSAGE2"),
+    (CAN_FD_MESSAGE, "CAN_FD_MESSAGE"),
+    (CAN_FD_MESSAGE_64, "CAN_FD_MESSAGE_64"),
+    (CAN_FD_ERROR_64, "CAN_FD_ERROR_64"),
+    (ETHERNET_FRAME_EX, "ETHERNET_FRAME_EX"),
+];
+
+const BASE_HEADER: usize = 16; // "LOBJ", header size/version, object size/type
+const MAX_OBJECT_SIZE: usize = 64 << 20; // far above any container; larger sizes are corrupt
+
+#[derive(Debug, Clone)]
+pub(crate) struct CanFrame {
+    pub timestamp_ns: u64,
+    pub channel: u16,
+    pub id: u32, // bit 31 set for extended ids (as in the DBC)
+    pub dlc: u8, // as logged; CAN FD codes 9..15 mean 12..64 bytes
+    pub data: Vec<u8>,
+    pub tx: bool,
+    pub tx_request: bool, // transmit request (CAN_FD_MESSAGE_64 dir 2), not yet on the bus
+    pub rtr: bool, // remote frame
+    pub wakeup: bool, // single-wire CAN high-voltage wake-up
+    pub nerr: bool, // single-wire / low-speed transceiver error line
+    pub fd: bool, // EDL: CAN FD frame format
+    pub brs: bool, // bit rate switch
+    pub esi: bool, // error state indicator
+}
+
+// Error frame; details are only present in the extended object types
+#[derive(Debug, Clone)]
+pub(crate) struct CanError {
+    pub timestamp_ns: u64,
+    pub channel: u16,
+    pub id: u32, // frame being transmitted when the error hit (CAN core only), else 0
+    pub dlc: u8,
+    pub data: Vec<u8>,
+    pub fd: bool,
+    pub error_type: Option<&'static str>, // "Bit", "Form", "Stuff", "CRC", ...
+    pub tx: Option<bool>, // error while transmitting (SJA1000 direction bit)
+    pub position: Option<u16>, // bit position in the frame
+    pub ecc: Option<u8>, // raw error code capture
+    pub tx_errors: Option<u8>, // last driver TEC/REC of the channel, if logged
+    pub rx_errors: Option<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct LinFrame {
+    pub timestamp_ns: u64,
+    pub channel: u16,
+    pub id: u8, // frame identifier 0..63 (parity bits stripped)
+    pub dlc: u8,
+    pub data: Vec<u8>,
+    pub tx: bool,
+    pub tx_request: bool,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct FlexRayFrame {
+    pub timestamp_ns: u64,
+    pub channel: u16,
+    pub channel_mask: u16, // 1 A, 2 B, 3 A+B
+    pub slot_id: u16,
+    pub cycle: u8,
+    pub data: Vec<u8>,
+    pub tx: bool,
+    pub tx_request: bool,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct EthernetFrame {
+    pub timestamp_ns: u64,
+    pub channel: u16,
+    pub source: [u8; 6],
+    pub destination: [u8; 6],
+    pub ethertype: u16, // inner type for VLAN-tagged frames
+    pub vlan_tci: Option<u16>,
+    pub payload: Vec<u8>,
+    pub tx: bool,
+    pub tx_request: bool,
+}
+
+#[derive(Debug)]
+pub(crate) enum BlfObject {
+    Can(CanFrame),
+    Error(CanError),
+    Lin(LinFrame),
+    FlexRay(FlexRayFrame),
+    Ethernet(EthernetFrame),
+    DriverError { channel: u16, tx_errors: u8, rx_errors: u8 },
+    Other, // object type not decoded here
+}
+
+enum Parsed {
+    Object(BlfObject, usize), // object, bytes consumed incl. padding
+    Container(Vec<u8>, usize), // uncompressed container payload
+    Incomplete,
+    Invalid,
+}
+
+fn u16_at(b: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(b.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(b: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(b.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u64_at(b: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(b.get(at..at + 8)?.try_into().ok()?))
+}
+
+// Objects are padded to a 4-byte boundary (by object_size % 4, as Vector writes them)
+fn parse_object(buf: &[u8]) -> Parsed {
+    if buf.len() < BASE_HEADER {
+        return Parsed::Incomplete;
+    }
+    if &buf[0..4] != b"LOBJ" {
+        return Parsed::Invalid;
+    }
+    let header_size = u16_at(buf, 4).unwrap_or(0) as usize;
+    let object_size = u32_at(buf, 8).unwrap_or(0) as usize;
+    let object_type = u32_at(buf, 12).unwrap_or(0);
+    if !(BASE_HEADER..=MAX_OBJECT_SIZE).contains(&object_size) {
+        return Parsed::Invalid;
+    }
+    let consumed = object_size + object_size % 4;
+    if buf.len() < object_size {
+        return Parsed::Incomplete;
+    }
+    let obj = &buf[..object_size];
+    let consumed = consumed.min(buf.len());
+
+    if object_type == LOG_CONTAINER {
+        let method = u16_at(obj, BASE_HEADER).unwrap_or(0);
+        let uncompressed = u32_at(obj, BASE_HEADER + 8).unwrap_or(0) as usize;
+        let payload = obj.get(BASE_HEADER + 16..).unwrap_or(&[]);
+        let data = match method {
+            0 => payload.to_vec(),
+            2 => {
+                let opts = DeflateOptions::default().set_limit(uncompressed.max(1));
+                match DeflateDecoder::new_with_options(payload, opts).decode_zlib() {
+                    Ok(d) => d,
+                    Err(_) => return Parsed::Invalid,
+                }
+            }
+            _ => Vec::new(),
+        };
+        return Parsed::Container(data, consumed);
+    }
+
+    // header v1/v2: flags at +0, timestamp at +8 (relative to the end of the base header)
+    let flags = u32_at(obj, BASE_HEADER).unwrap_or(0);
+    let raw_ts = u64_at(obj, BASE_HEADER + 8).unwrap_or(0);
+    let timestamp_ns = if flags & 0x1 != 0 { raw_ts * 10_000 } else { raw_ts };
+    let body = obj.get(header_size.max(BASE_HEADER)..).unwrap_or(&[]);
+
+    let obj = match object_type {
+        CAN_MESSAGE | CAN_MESSAGE2 => can_message(body, timestamp_ns).map(BlfObject::Can),
+        CAN_FD_MESSAGE => can_fd_message(body, timestamp_ns).map(BlfObject::Can),
+        CAN_FD_MESSAGE_64 => can_fd_message_64(body, timestamp_ns).map(BlfObject::Can),
+        CAN_ERROR => can_error(body, timestamp_ns).map(BlfObject::Error),
+        CAN_ERROR_EXT => can_error_ext(body, timestamp_ns).map(BlfObject::Error),
+        CAN_FD_ERROR_64 => can_fd_error_64(body, timestamp_ns).map(BlfObject::Error),
+        CAN_DRIVER_ERROR | CAN_DRIVER_ERROR_EXT => can_driver_error(body),
+        LIN_MESSAGE => lin_message(body, timestamp_ns).map(BlfObject::Lin),
+        LIN_MESSAGE2 => lin_message2(body, timestamp_ns).map(BlfObject::Lin),
+        FR_RCVMESSAGE => flexray_message(body, timestamp_ns, 44).map(BlfObject::FlexRay),
+        FR_RCVMESSAGE_EX => flexray_message(body, timestamp_ns, 84).map(BlfObject::FlexRay),
+        ETHERNET_FRAME => ethernet_frame(body, timestamp_ns).map(BlfObject::Ethernet),
+        ETHERNET_FRAME_EX => ethernet_frame_ex(body, timestamp_ns).map(BlfObject::Ethernet),
+        _ => None,
+    };
+    Parsed::Object(obj.unwrap_or(BlfObject::Other), consumed)
+}
+
+// flags byte of CAN_MESSAGE(2) / CAN_FD_MESSAGE
+const FLAG_TX: u8 = 0x01;
+const FLAG_NERR: u8 = 0x20;
+const FLAG_WU: u8 = 0x40;
+const FLAG_RTR: u8 = 0x80;
+
+// CAN_MESSAGE / CAN_MESSAGE2: channel u16, flags u8, dlc u8, id u32, data[8]
+fn can_message(b: &[u8], timestamp_ns: u64) -> Option<CanFrame> {
+    let flags = *b.get(2)?;
+    let dlc = *b.get(3)?;
+    let len = (dlc as usize).min(8);
+    Some(CanFrame {
+        timestamp_ns,
+        channel: u16_at(b, 0)?,
+        id: u32_at(b, 4)?,
+        dlc,
+        data: if flags & FLAG_RTR != 0 { Vec::new() } else { b.get(8..8 + len)?.to_vec() },
+        tx: flags & FLAG_TX != 0,
+        tx_request: false,
+        rtr: flags & FLAG_RTR != 0,
+        wakeup: flags & FLAG_WU != 0,
+        nerr: flags & FLAG_NERR != 0,
+        fd: false,
+        brs: false,
+        esi: false,
+    })
+}
+
+// CAN_FD_MESSAGE: channel u16, flags u8, dlc u8, id u32, frame_length u32,
+// arb_bit_count u8, fd_flags u8, valid_bytes u8, 5 reserved, data[64]
+fn can_fd_message(b: &[u8], timestamp_ns: u64) -> Option<CanFrame> {
+    let flags = *b.get(2)?;
+    let fd_flags = *b.get(13)?;
+    let len = (*b.get(14)? as usize).min(64);
+    Some(CanFrame {
+        timestamp_ns,
+        channel: u16_at(b, 0)?,
+        id: u32_at(b, 4)?,
+        dlc: *b.get(3)?,
+        data: b.get(20..20 + len)?.to_vec(),
+        tx: flags & FLAG_TX != 0,
+        tx_request: false,
+        rtr: flags & FLAG_RTR != 0,
+        wakeup: flags & FLAG_WU != 0,
+        nerr: flags & FLAG_NERR != 0,
+        fd: fd_flags & 0x01 != 0,
+        brs: fd_flags & 0x02 != 0,
+        esi: fd_flags & 0x04 != 0,
+    })
+}
+
+// CAN_FD_MESSAGE_64: channel u8, dlc u8, valid_bytes u8, tx_count u8, id u32,
+// frame_length u32, flags u32, btr x2, time offsets x2, bit_count u16, dir u8,
+// ext_data_offset u8, crc u32, data[valid_bytes]
+fn can_fd_message_64(b: &[u8], timestamp_ns: u64) -> Option<CanFrame> {
+    let len = (*b.get(2)? as usize).min(64);
+    let flags = u32_at(b, 12)?;
+    let dir = *b.get(34)?; // 0 Rx, 1 Tx, 2 TxRq
+    Some(CanFrame {
+        timestamp_ns,
+        channel: *b.first()? as u16,
+        id: u32_at(b, 4)?,
+        dlc: *b.get(1)?,
+        data: b.get(40..40 + len)?.to_vec(),
+        tx: dir == 1,
+        tx_request: dir == 2,
+        rtr: flags & 0x0010 != 0,
+        wakeup: false,
+        nerr: false,
+        fd: flags & 0x1000 != 0,
+        brs: flags & 0x2000 != 0,
+        esi: flags & 0x4000 != 0,
+    })
+}
+
+// "valid" flags of CAN_ERROR_EXT / CAN_FD_ERROR_64
+const ERR_SJA1000_ECC: u32 = 0x01;
+const ERR_CORE_CODE: u32 = 0x02;
+const ERR_CORE_POSITION: u32 = 0x04;
+
+// every error_type error_kind() reports
+pub(crate) const ERROR_TYPES: [&str; 7] = ["Bit", "Form", "Stuff", "CRC", "Ack Delimiter", "Ack", "Other"];
+
+// error type and direction from the ECC byte
+fn error_kind(flags: u32, ecc: u8) -> (Option<&'static str>, Option<bool>) {
+    if flags & ERR_SJA1000_ECC != 0 {
+        // SJA1000 ECC: bits 7-6 error code, bit 5 direction (1 = Rx), bits 4-0 segment
+        let kind = ["Bit", "Form", "Stuff", "Other"][(ecc >> 6) as usize];
+        (Some(kind), Some(ecc & 0x20 == 0))
+    } else if flags & ERR_CORE_CODE != 0 {
+        // Vector CAN core error codes
+        let kind = match ecc {
+            0 => "Bit",
+            1 => "Form",
+            2 => "Stuff",
+            3 => "Other",
+            4 => "CRC",
+            5 => "Ack Delimiter",
+            7 => "Ack",
+            _ => "Other", // 6 and past 7 are unassigned
+        };
+        (Some(kind), None)
+    } else {
+        (None, None)
+    }
+}
+
+// CAN_ERROR: channel u16, length u16 (no details)
+fn can_error(b: &[u8], timestamp_ns: u64) -> Option<CanError> {
+    Some(CanError {
+        timestamp_ns,
+        channel: u16_at(b, 0)?,
+        id: 0,
+        dlc: 0,
+        data: Vec::new(),
+        fd: false,
+        error_type: None,
+        tx: None,
+        position: None,
+        ecc: None,
+        tx_errors: None,
+        rx_errors: None,
+    })
+}
+
+// CAN_ERROR_EXT: channel u16, length u16, flags u32, ecc u8, position u8, dlc u8,
+// reserved u8, frame_length_ns u32, id u32, flags_ext u16, reserved u16, data[8]
+fn can_error_ext(b: &[u8], timestamp_ns: u64) -> Option<CanError> {
+    let flags = u32_at(b, 4)?;
+    let ecc = *b.get(8)?;
+    let (error_type, tx) = error_kind(flags, ecc);
+    // id and payload come from the Vector CAN core only
+    let core = flags & ERR_CORE_CODE != 0;
+    let dlc = *b.get(10)? & 0x0F;
+    Some(CanError {
+        timestamp_ns,
+        channel: u16_at(b, 0)?,
+        id: if core { u32_at(b, 16)? } else { 0 },
+        dlc: if core { dlc } else { 0 },
+        data: if core { b.get(24..24 + (dlc as usize).min(8))?.to_vec() } else { Vec::new() },
+        fd: false,
+        error_type,
+        tx,
+        position: (flags & ERR_CORE_POSITION != 0).then_some(*b.get(9)? as u16),
+        ecc: (error_type.is_some()).then_some(ecc),
+        tx_errors: None,
+        rx_errors: None,
+    })
+}
+
+// CAN_FD_ERROR_64: channel u8, dlc u8, valid_bytes u8, ecc u8, flags u16,
+// error_code_ext u16, ext_flags u16 (as CAN_FD_MESSAGE_64 flags), ext_data_offset u8,
+// reserved u8, id u32, frame_length u32, btr x2, time offsets x2, crc u32,
+// error_position u16, reserved u16, data[valid_bytes]
+fn can_fd_error_64(b: &[u8], timestamp_ns: u64) -> Option<CanError> {
+    let flags = u16_at(b, 4)? as u32;
+    let ecc = *b.get(3)?;
+    let (error_type, tx) = error_kind(flags, ecc);
+    let core = flags & ERR_CORE_CODE != 0;
+    let len = (*b.get(2)? as usize).min(64);
+    Some(CanError {
+        timestamp_ns,
+        channel: *b.first()? as u16,
+        id: if core { u32_at(b, 12)? } else { 0 },
+        dlc: if core { *b.get(1)? } else { 0 },
+        data: if core { b.get(44..44 + len)?.to_vec() } else { Vec::new() },
+        fd: u16_at(b, 8)? & 0x1000 != 0,
+        error_type,
+        tx,
+        position: (flags & ERR_CORE_POSITION != 0).then_some(u16_at(b, 40)?),
+        ecc: (error_type.is_some()).then_some(ecc),
+        tx_errors: None,
+        rx_errors: None,
+    })
+}
+
+// CAN_DRIVER_ERROR(_EXT): channel u16, tx_errors u8, rx_errors u8, error_code u32, ...
+fn can_driver_error(b: &[u8]) -> Option<BlfObject> {
+    Some(BlfObject::DriverError { channel: u16_at(b, 0)?, tx_errors: *b.get(2)?, rx_errors: *b.get(3)? })
+}
+
+fn lin_frame(timestamp_ns: u64, channel: u16, id: u8, dlc: u8, data: &[u8], dir: u8) -> LinFrame {
+    LinFrame {
+        timestamp_ns,
+        channel,
+        id: id & 0x3F,
+        dlc,
+        data: data[..(dlc as usize).min(8)].to_vec(),
+        tx: dir == 1,
+        tx_request: dir == 2,
+    }
+}
+
+// LIN_MESSAGE: channel u16, id u8, dlc u8, data[8], fsm_id u8, fsm_state u8,
+// header_time u8, full_time u8, crc u16, dir u8 (0 Rx, 1 Tx, 2 TxRq), reserved u8
+fn lin_message(b: &[u8], timestamp_ns: u64) -> Option<LinFrame> {
+    Some(lin_frame(timestamp_ns, u16_at(b, 0)?, *b.get(2)?, *b.get(3)?, b.get(4..12)?, *b.get(18)?))
+}
+
+// LIN_MESSAGE2: bus event (sof u64, baudrate u32, channel u16, 2 reserved),
+// sync break/delimiter u64 x2, supplier_id u16, message_id u16, nad u8, id u8, dlc u8,
+// checksum_model u8, data byte timestamps u64 x9, data[8], crc u16, dir u8, ...
+fn lin_message2(b: &[u8], timestamp_ns: u64) -> Option<LinFrame> {
+    Some(lin_frame(timestamp_ns, u16_at(b, 12)?, *b.get(37)?, *b.get(38)?, b.get(112..120)?, *b.get(122)?))
+}
+
+// FR_RCVMESSAGE / FR_RCVMESSAGE_EX: channel u16, version u16, channel_mask u16, dir u16
+// (0 Rx, 1 Tx, 2 TxRq), client_index u32, cluster_no u32, frame_id u16, header_crc u16 x2,
+// byte_count u16, data_count u16, cycle u16, tag u32, data u32, frame_flags u32,
+// app_parameter u32, then (_EX: frame_crc, frame_length_ns, frame_id1, pdu_offset,
+// blf_log_mask, reserved) data bytes at `data_at`
+fn flexray_message(b: &[u8], timestamp_ns: u64, data_at: usize) -> Option<FlexRayFrame> {
+    let dir = u16_at(b, 6)?;
+    let len = (u16_at(b, 24)? as usize).min(254);
+    Some(FlexRayFrame {
+        timestamp_ns,
+        channel: u16_at(b, 0)?,
+        channel_mask: u16_at(b, 4)?,
+        slot_id: u16_at(b, 16)?,
+        cycle: u16_at(b, 26)? as u8,
+        data: b.get(data_at..data_at + len)?.to_vec(),
+        tx: dir == 1,
+        tx_request: dir == 2,
+    })
+}
+
+const ETHERTYPE_VLAN: u16 = 0x8100;
+
+fn mac_at(b: &[u8], at: usize) -> Option<[u8; 6]> {
+    b.get(at..at + 6)?.try_into().ok()
+}
+
+// ETHERNET_FRAME: source MAC, channel u16, destination MAC, dir u16, type u16,
+// tpid u16, tci u16, payload_length u16, 8 reserved, payload
+fn ethernet_frame(b: &[u8], timestamp_ns: u64) -> Option<EthernetFrame> {
+    let dir = u16_at(b, 14)?;
+    let tpid = u16_at(b, 18)?;
+    let len = u16_at(b, 22)? as usize;
+    Some(EthernetFrame {
+        timestamp_ns,
+        channel: u16_at(b, 6)?,
+        source: mac_at(b, 0)?,
+        destination: mac_at(b, 8)?,
+        ethertype: u16_at(b, 16)?,
+        vlan_tci: (tpid == ETHERTYPE_VLAN).then_some(u16_at(b, 20)?),
+        payload: b.get(32..32 + len)?.to_vec(),
+        tx: dir == 1,
+        tx_request: dir == 2,
+    })
+}
+
+// ETHERNET_FRAME_EX: struct_length u16, flags u16, channel u16, hw_channel u16,
+// frame_duration u64, checksum u32, dir u16, frame_length u16, frame_handle u32,
+// reserved u32, then the whole frame from the destination MAC (big-endian header)
+fn ethernet_frame_ex(b: &[u8], timestamp_ns: u64) -> Option<EthernetFrame> {
+    let dir = u16_at(b, 20)?;
+    let frame = b.get(32..32 + u16_at(b, 22)? as usize)?;
+    let be16 = |at: usize| Some(u16::from_be_bytes(frame.get(at..at + 2)?.try_into().ok()?));
+    let (ethertype, vlan_tci, payload_at) = match be16(12)? {
+        ETHERTYPE_VLAN => (be16(16)?, Some(be16(14)?), 18),
+        t => (t, None, 14),
+    };
+    Some(EthernetFrame {
+        timestamp_ns,
+        channel: u16_at(b, 4)?,
+        source: mac_at(frame, 6)?,
+        destination: mac_at(frame, 0)?,
+        ethertype,
+        vlan_tci,
+        payload: frame[payload_at..].to_vec(),
+        tx: dir == 1,
+        tx_request: dir == 2,
+    })
+}
+
+// Objects unpacked from log containers, shared by BlfReader and BlfStream.
+// Objects may straddle container boundaries, so container payloads are
+// concatenated before parsing. Error frames get the error counters of the
+// latest driver error object on their channel.
+#[derive(Default)]
+struct Unpacker {
+    inner: Vec<u8>,
+    inner_pos: usize,
+    counters: HashMap<u16, (u8, u8)>, // channel -> (tx_errors, rx_errors)
+}
+
+impl Unpacker {
+    // next object already unpacked; None when the next container is needed
+    fn next_inner(&mut self) -> Option<BlfObject> {
+        while self.inner_pos < self.inner.len() {
+            match parse_object(&self.inner[self.inner_pos..]) {
+                Parsed::Object(obj, n) => {
+                    self.inner_pos += n;
+                    return Some(obj);
+                }
+                Parsed::Container(_, n) => self.inner_pos += n,
+                Parsed::Invalid => {
+                    // resync on the next object signature
+                    let rest = &self.inner[self.inner_pos + 1..];
+                    match rest.windows(4).position(|w| w == b"LOBJ") {
+                        Some(p) => self.inner_pos += 1 + p,
+                        None => self.inner_pos = self.inner.len(),
+                    }
+                }
+                Parsed::Incomplete => return None,
+            }
+        }
+        None
+    }
+
+    fn push_container(&mut self, data: &[u8]) {
+        self.inner.drain(..self.inner_pos.min(self.inner.len()));
+        self.inner_pos = 0;
+        self.inner.extend_from_slice(data);
+    }
+
+    fn annotate(&mut self, mut obj: BlfObject) -> BlfObject {
+        match &mut obj {
+            BlfObject::DriverError { channel, tx_errors, rx_errors } => {
+                self.counters.insert(*channel, (*tx_errors, *rx_errors));
+            }
+            BlfObject::Error(e) => {
+                if let Some((tx, rx)) = self.counters.get(&e.channel) {
+                    e.tx_errors = Some(*tx);
+                    e.rx_errors = Some(*rx);
+                }
+            }
+            _ => {}
+        }
+        obj
+    }
+}
+
+// "LOGG" signature and header size; the first object follows the header
+fn file_header(file: &[u8]) -> Result<usize, String> {
+    if file.len() < 8 || &file[0..4] != b"LOGG" {
+        return Err("not a BLF file (missing LOGG signature)".to_string());
+    }
+    let stats_size = u32_at(file, 4).unwrap_or(0) as usize;
+    if stats_size < 32 {
+        return Err(format!("invalid BLF header size {}", stats_size));
+    }
+    Ok(stats_size)
+}
+
+// Measurement start from the file header (SYSTEMTIME at +40), in the logger's local
+// time; no time zone is recorded
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct LogStart {
+    pub year: u16,
+    pub month: u16, // 1-12
+    pub day_of_week: u16, // 0 = Sunday
+    pub day: u16,
+    pub hour: u16,
+    pub minute: u16,
+    pub second: u16,
+    pub millisecond: u16,
+}
+
+// None when the header is too short or the logger left the time empty
+pub(crate) fn log_start(file: &[u8]) -> Option<LogStart> {
+    let stats_size = u32_at(file, 4)? as usize;
+    if stats_size < 56 {
+        return None;
+    }
+    let f = |i: usize| u16_at(file, 40 + 2 * i);
+    let start = LogStart {
+        year: f(0)?,
+        month: f(1)?,
+        day_of_week: f(2)?,
+        day: f(3)?,
+        hour: f(4)?,
+        minute: f(5)?,
+        second: f(6)?,
+        millisecond: f(7)?,
+    };
+    ((1..=12).contains(&start.month) && start.day >= 1 && start.year > 0).then_some(start)
+}
+
+// Iterates every object of a BLF buffer, descending into log containers
+pub(crate) struct BlfReader<'a> {
+    file: &'a [u8],
+    pos: usize,
+    unpacker: Unpacker,
+}
+
+impl<'a> BlfReader<'a> {
+    pub(crate) fn new(file: &'a [u8]) -> Result<BlfReader<'a>, String> {
+        let stats_size = file_header(file)?;
+        if stats_size > file.len() {
+            return Err(format!("invalid BLF header size {}", stats_size));
+        }
+        Ok(BlfReader { file, pos: stats_size, unpacker: Unpacker::default() })
+    }
+
+    fn next_object(&mut self) -> Option<BlfObject> {
+        loop {
+            if let Some(obj) = self.unpacker.next_inner() {
+                return Some(obj);
+            }
+            match parse_object(self.file.get(self.pos..)?) {
+                Parsed::Container(data, n) => {
+                    self.pos += n;
+                    self.unpacker.push_container(&data);
+                }
+                Parsed::Object(obj, n) => {
+                    self.pos += n;
+                    return Some(obj);
+                }
+                Parsed::Incomplete | Parsed::Invalid => return None,
+            }
+        }
+    }
+}
+
+impl Iterator for BlfReader<'_> {
+    type Item = BlfObject;
+
+    fn next(&mut self) -> Option<BlfObject> {
+        let obj = self.next_object()?;
+        Some(self.unpacker.annotate(obj))
+    }
+}
+
+// Incremental reader for BLF data arriving in slices (BlfSessionBuilder): keeps
+// only the bytes of the outer object not complete yet plus unparsed container data
+#[derive(Default)]
+pub(crate) struct BlfStream {
+    buf: Vec<u8>,
+    header: Option<usize>, // header size once known; bytes of it still to skip
+    pub start: Option<LogStart>, // when the first slice holds the header's start time
+    done: bool, // invalid data: the file reader stops there too
+    unpacker: Unpacker,
+}
+
+impl BlfStream {
+    // Objects completed by `chunk`, in file order
+    pub(crate) fn push(&mut self, chunk: &[u8]) -> Result<Vec<BlfObject>, String> {
+        if self.done {
+            return Ok(Vec::new());
+        }
+        self.buf.extend_from_slice(chunk);
+        if self.header.is_none() {
+            if self.buf.len() < 8 {
+                return Ok(Vec::new());
+            }
+            self.header = Some(file_header(&self.buf)?);
+            self.start = log_start(&self.buf);
+        }
+        // header bytes may span several chunks
+        let skip = self.header.unwrap_or(0).min(self.buf.len());
+        self.buf.drain(..skip);
+        self.header = self.header.map(|h| h - skip);
+        if self.header != Some(0) {
+            return Ok(Vec::new());
+        }
+        Ok(self.objects(false))
+    }
+
+    // End of data: a last object without its padding still counts, as in BlfReader; Err
+    // when the file header never completed
+    pub(crate) fn finish(&mut self) -> Result<Vec<BlfObject>, String> {
+        match self.header {
+            None => Err("not a BLF file (missing LOGG signature)".to_string()),
+            Some(0) if self.done => Ok(Vec::new()),
+            Some(0) => Ok(self.objects(true)),
+            Some(missing) => Err(format!("BLF file header truncated ({} bytes missing)", missing)),
+        }
+    }
+
+    fn objects(&mut self, last: bool) -> Vec<BlfObject> {
+        let mut out = Vec::new();
+        let mut pos = 0;
+        loop {
+            while let Some(obj) = self.unpacker.next_inner() {
+                out.push(self.unpacker.annotate(obj));
+            }
+            let rest = &self.buf[pos..];
+            // wait for the padding too, or the next object would start misaligned
+            if !last && rest.len() >= BASE_HEADER && &rest[0..4] == b"LOBJ" {
+                let size = u32_at(rest, 8).unwrap_or(0) as usize;
+                if size <= MAX_OBJECT_SIZE && rest.len() < size + size % 4 {
+                    break;
+                }
+            }
+            match parse_object(rest) {
+                Parsed::Container(data, n) => {
+                    pos += n;
+                    self.unpacker.push_container(&data);
+                }
+                Parsed::Object(obj, n) => {
+                    pos += n;
+                    out.push(self.unpacker.annotate(obj));
+                }
+                Parsed::Incomplete => break,
+                Parsed::Invalid => {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+        self.buf.drain(..pos);
+        out
+    }
+}
+
+// -------------------------------
+// Writer (export_blf()): objects are packed into LOG_CONTAINERs of about
+// CONTAINER_SIZE uncompressed bytes, zlib-compressed unless `compress` is off.
+// Classic CAN goes out as CAN_MESSAGE, CAN FD (or TxRq) as CAN_FD_MESSAGE_64,
+// errors as CAN_ERROR_EXT / CAN_FD_ERROR_64 after a CAN_DRIVER_ERROR when the
+// TEC/REC changed, LIN as LIN_MESSAGE, FlexRay as FR_RCVMESSAGE and Ethernet
+// as ETHERNET_FRAME: the objects this reader gives back unchanged.
+// -------------------------------
+const FILE_HEADER: usize = 144;
+// file header +8: application id (0: none of Vector's), application version, and the
+// BL API version the objects follow (the one python-can's writer records)
+const APPLICATION_ID: u8 = 0;
+const BL_API_VERSION: [u8; 4] = [2, 6, 8, 1];
+const OBJECT_HEADER: usize = 32; // base header + header v1, timestamps in ns
+const CONTAINER_SIZE: usize = 128 * 1024;
+const TIME_ONE_NANS: u32 = 0x02;
+
+pub(crate) struct BlfWriter {
+    out: Vec<u8>,
+    pending: Vec<u8>, // objects of the next container
+    compress: bool,
+    objects: u32,
+    uncompressed: u64,
+    counters: HashMap<u16, (u8, u8)>, // channel -> TEC/REC last written
+}
+
+// CAN_FD_MESSAGE_64 / CAN_FD_ERROR_64 hold the channel in one byte
+fn fd_channel(channel: u16) -> Result<u8, String> {
+    u8::try_from(channel).map_err(|_| format!("CAN{}: CAN FD objects hold channels 0..255 only", channel))
+}
+
+fn dir_code(tx: bool, tx_request: bool) -> u8 {
+    if tx { 1 } else if tx_request { 2 } else { 0 }
+}
+
+// error code back to the CAN core value error_kind() reads ("Other" for unknown names)
+fn core_code(kind: &str) -> u8 {
+    match kind {
+        "Bit" => 0,
+        "Form" => 1,
+        "Stuff" => 2,
+        "CRC" => 4,
+        "Ack Delimiter" => 5,
+        "Ack" => 7,
+        _ => 3,
+    }
+}
+
+impl BlfWriter {
+    pub(crate) fn new(compress: bool) -> BlfWriter {
+        BlfWriter {
+            out: vec![0; FILE_HEADER],
+            pending: Vec::new(),
+            compress,
+            objects: 0,
+            uncompressed: FILE_HEADER as u64,
+            counters: HashMap::new(),
+        }
+    }
+
+    fn object(&mut self, object_type: u32, timestamp_ns: u64, body: &[u8]) {
+        let size = OBJECT_HEADER + body.len();
+        let o = &mut self.pending;
+        o.extend_from_slice(b"LOBJ");
+        o.extend_from_slice(&(OBJECT_HEADER as u16).to_le_bytes());
+        o.extend_from_slice(&1u16.to_le_bytes());
+        o.extend_from_slice(&(size as u32).to_le_bytes());
+        o.extend_from_slice(&object_type.to_le_bytes());
+        o.extend_from_slice(&TIME_ONE_NANS.to_le_bytes());
+        o.extend_from_slice(&[0; 4]); // client index, object version
+        o.extend_from_slice(&timestamp_ns.to_le_bytes());
+        o.extend_from_slice(body);
+        o.resize(o.len() + size % 4, 0);
+        self.objects += 1;
+        if self.pending.len() >= CONTAINER_SIZE {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let data = std::mem::take(&mut self.pending);
+        let (method, payload) = if self.compress { (2u16, crate::deflate::zlib(&data)) } else { (0, data.clone()) };
+        let size = 32 + payload.len();
+        self.out.extend_from_slice(b"LOBJ");
+        self.out.extend_from_slice(&16u16.to_le_bytes());
+        self.out.extend_from_slice(&1u16.to_le_bytes());
+        self.out.extend_from_slice(&(size as u32).to_le_bytes());
+        self.out.extend_from_slice(&LOG_CONTAINER.to_le_bytes());
+        self.out.extend_from_slice(&method.to_le_bytes());
+        self.out.extend_from_slice(&[0; 6]);
+        self.out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        self.out.extend_from_slice(&[0; 4]);
+        self.out.extend_from_slice(&payload);
+        self.out.resize(self.out.len() + size % 4, 0);
+        self.uncompressed += (32 + data.len()) as u64;
+    }
+
+    // Err for a frame no object can hold (CAN FD on a channel above 255); nothing is written
+    pub(crate) fn push(&mut self, obj: &BlfObject) -> Result<(), String> {
+        match obj {
+            BlfObject::Can(cf) => self.can(cf)?,
+            BlfObject::Error(e) => self.error(e)?,
+            BlfObject::Lin(lf) => {
+                let mut b = lf.channel.to_le_bytes().to_vec();
+                b.extend_from_slice(&[lf.id, lf.dlc]);
+                let mut data = lf.data.clone();
+                data.resize(8, 0);
+                b.extend_from_slice(&data);
+                b.extend_from_slice(&[0; 6]); // fsm id/state, header/full time, crc
+                b.extend_from_slice(&[dir_code(lf.tx, lf.tx_request), 0]);
+                self.object(LIN_MESSAGE, lf.timestamp_ns, &b);
+            }
+            BlfObject::FlexRay(fr) => {
+                let mut b = Vec::with_capacity(44 + 254);
+                b.extend_from_slice(&fr.channel.to_le_bytes());
+                b.extend_from_slice(&[0; 2]); // version
+                b.extend_from_slice(&fr.channel_mask.to_le_bytes());
+                b.extend_from_slice(&(dir_code(fr.tx, fr.tx_request) as u16).to_le_bytes());
+                b.extend_from_slice(&[0; 8]); // client index, cluster
+                b.extend_from_slice(&fr.slot_id.to_le_bytes());
+                b.extend_from_slice(&[0; 4]); // header crcs
+                b.extend_from_slice(&(fr.data.len() as u16).to_le_bytes()); // byte count
+                b.extend_from_slice(&(fr.data.len() as u16).to_le_bytes()); // data count
+                b.extend_from_slice(&(fr.cycle as u16).to_le_bytes());
+                b.extend_from_slice(&[0; 16]); // tag, data, frame flags, app parameter
+                b.extend_from_slice(&fr.data);
+                b.resize(44 + 254, 0);
+                self.object(FR_RCVMESSAGE, fr.timestamp_ns, &b);
+            }
+            BlfObject::Ethernet(e) => {
+                let mut b = e.source.to_vec();
+                b.extend_from_slice(&e.channel.to_le_bytes());
+                b.extend_from_slice(&e.destination);
+                b.extend_from_slice(&(dir_code(e.tx, e.tx_request) as u16).to_le_bytes());
+                b.extend_from_slice(&e.ethertype.to_le_bytes());
+                let tpid = if e.vlan_tci.is_some() { ETHERTYPE_VLAN } else { 0 };
+                b.extend_from_slice(&tpid.to_le_bytes());
+                b.extend_from_slice(&e.vlan_tci.unwrap_or(0).to_le_bytes());
+                b.extend_from_slice(&(e.payload.len() as u16).to_le_bytes());
+                b.extend_from_slice(&[0; 8]);
+                b.extend_from_slice(&e.payload);
+                self.object(ETHERNET_FRAME, e.timestamp_ns, &b);
+            }
+            // counters go out with the error frames that carry them
+            BlfObject::DriverError { .. } | BlfObject::Other => {}
+        }
+        Ok(())
+    }
+
+    fn can(&mut self, cf: &CanFrame) -> Result<(), String> {
+        if !cf.fd && !cf.tx_request && cf.data.len() <= 8 {
+            let mut flags = 0;
+            for (set, bit) in [(cf.tx, FLAG_TX), (cf.nerr, FLAG_NERR), (cf.wakeup, FLAG_WU), (cf.rtr, FLAG_RTR)] {
+                if set {
+                    flags |= bit;
+                }
+            }
+            let mut b = cf.channel.to_le_bytes().to_vec();
+            b.extend_from_slice(&[flags, cf.dlc]);
+            b.extend_from_slice(&cf.id.to_le_bytes());
+            let mut data = cf.data.clone();
+            data.resize(8, 0);
+            b.extend_from_slice(&data);
+            self.object(CAN_MESSAGE, cf.timestamp_ns, &b);
+            return Ok(());
+        }
+        let mut flags = 0u32;
+        for (set, bit) in [(cf.rtr, 0x0010), (cf.fd, 0x1000), (cf.brs, 0x2000), (cf.esi, 0x4000)] {
+            if set {
+                flags |= bit;
+            }
+        }
+        let mut b = vec![fd_channel(cf.channel)?, cf.dlc, cf.data.len() as u8, 0];
+        b.extend_from_slice(&cf.id.to_le_bytes());
+        b.extend_from_slice(&[0; 4]); // frame length
+        b.extend_from_slice(&flags.to_le_bytes());
+        b.extend_from_slice(&[0; 16]); // bit timings, time offsets
+        b.extend_from_slice(&[0, 0, dir_code(cf.tx, cf.tx_request), 0]); // bit count, dir, ext offset
+        b.extend_from_slice(&[0; 4]); // crc
+        b.extend_from_slice(&cf.data);
+        self.object(CAN_FD_MESSAGE_64, cf.timestamp_ns, &b);
+        Ok(())
+    }
+
+    fn error(&mut self, e: &CanError) -> Result<(), String> {
+        // checked before the counters go out
+        let fd = if e.fd || e.data.len() > 8 { Some(fd_channel(e.channel)?) } else { None };
+        if let (Some(tx), Some(rx)) = (e.tx_errors, e.rx_errors) {
+            if self.counters.get(&e.channel) != Some(&(tx, rx)) {
+                self.counters.insert(e.channel, (tx, rx));
+                let mut b = e.channel.to_le_bytes().to_vec();
+                b.extend_from_slice(&[tx, rx, 0, 0, 0, 0]);
+                self.object(CAN_DRIVER_ERROR, e.timestamp_ns, &b);
+            }
+        }
+        // an ECC the CAN core could not have produced (direction bit, Tx) is SJA1000's
+        let (flags, ecc) = match (e.error_type, e.ecc) {
+            (Some(_), Some(ecc)) if e.tx == Some(true) || ecc & 0x20 != 0 => (ERR_SJA1000_ECC, ecc),
+            (Some(kind), _) => (ERR_CORE_CODE, core_code(kind)),
+            _ => (0, 0),
+        };
+        let flags = flags | if e.position.is_some() { ERR_CORE_POSITION } else { 0 };
+        let position = e.position.unwrap_or(0);
+        let Some(channel) = fd else {
+            let mut b = e.channel.to_le_bytes().to_vec();
+            b.extend_from_slice(&[0; 2]); // length
+            b.extend_from_slice(&flags.to_le_bytes());
+            b.extend_from_slice(&[ecc, position as u8, e.dlc, 0]);
+            b.extend_from_slice(&[0; 4]); // frame length
+            b.extend_from_slice(&e.id.to_le_bytes());
+            b.extend_from_slice(&[0; 4]); // flags ext, reserved
+            let mut data = e.data.clone();
+            data.resize(8, 0);
+            b.extend_from_slice(&data);
+            self.object(CAN_ERROR_EXT, e.timestamp_ns, &b);
+            return Ok(());
+        };
+        let mut b = vec![channel, e.dlc, e.data.len() as u8, ecc];
+        b.extend_from_slice(&(flags as u16).to_le_bytes());
+        b.extend_from_slice(&[0; 2]); // error code ext
+        b.extend_from_slice(&(if e.fd { 0x1000u16 } else { 0 }).to_le_bytes());
+        b.extend_from_slice(&[0; 2]); // ext data offset, reserved
+        b.extend_from_slice(&e.id.to_le_bytes());
+        b.extend_from_slice(&[0; 24]); // frame length, bit timings, time offsets, crc
+        b.extend_from_slice(&position.to_le_bytes());
+        b.extend_from_slice(&[0; 2]);
+        b.extend_from_slice(&e.data);
+        self.object(CAN_FD_ERROR_64, e.timestamp_ns, &b);
+        Ok(())
+    }
+
+    // File bytes; start/stop go to the header as SYSTEMTIME (zero when unknown)
+    pub(crate) fn finish(mut self, start: Option<LogStart>, stop: Option<LogStart>) -> Vec<u8> {
+        self.flush();
+        let file_size = self.out.len() as u64;
+        let h = &mut self.out[..FILE_HEADER];
+        h[0..4].copy_from_slice(b"LOGG");
+        h[4..8].copy_from_slice(&(FILE_HEADER as u32).to_le_bytes());
+        h[8] = APPLICATION_ID;
+        for (i, part) in env!("CARGO_PKG_VERSION").split('.').take(3).enumerate() {
+            h[9 + i] = part.parse().unwrap_or(0);
+        }
+        h[12..16].copy_from_slice(&BL_API_VERSION);
+        h[16..24].copy_from_slice(&file_size.to_le_bytes());
+        h[24..32].copy_from_slice(&self.uncompressed.to_le_bytes());
+        h[32..36].copy_from_slice(&self.objects.to_le_bytes());
+        h[36..40].copy_from_slice(&self.objects.to_le_bytes());
+        for (at, time) in [(40, start), (56, stop)] {
+            let Some(t) = time else { continue };
+            let fields = [t.year, t.month, t.day_of_week, t.day, t.hour, t.minute, t.second, t.millisecond];
+            for (i, v) in fields.iter().enumerate() {
+                h[at + 2 * i..at + 2 * i + 2].copy_from_slice(&v.to_le_bytes());
+            }
+        }
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(object_type: u32, body: &[u8]) -> Vec<u8> {
+        let size = 32 + body.len();
+        let mut o = b"LOBJ".to_vec();
+        o.extend_from_slice(&32u16.to_le_bytes());
+        o.extend_from_slice(&1u16.to_le_bytes());
+        o.extend_from_slice(&(size as u32).to_le_bytes());
+        o.extend_from_slice(&object_type.to_le_bytes());
+        o.extend_from_slice(&[0; 16]); // flags, client index, version, timestamp
+        o.extend_from_slice(body);
+        o.resize(size + size % 4, 0);
+        o
+    }
+
+    fn can(id: u32) -> Vec<u8> {
+        let mut body = vec![1, 0, 0, 8];
+        body.extend_from_slice(&id.to_le_bytes());
+        body.extend_from_slice(&[id as u8; 8]);
+        object(CAN_MESSAGE, &body)
+    }
+
+    // header, CAN 0x100, odd-sized unknown object (padded), container with 0x200 + 0x300
+    fn file() -> Vec<u8> {
+        let mut f = b"LOGG".to_vec();
+        f.extend_from_slice(&144u32.to_le_bytes());
+        f.resize(144, 0);
+        f.extend(can(0x100));
+        f.extend(object(999, &[0; 2]));
+        let inner = [can(0x200), can(0x300)].concat();
+        let mut c = b"LOBJ".to_vec();
+        c.extend_from_slice(&16u16.to_le_bytes());
+        c.extend_from_slice(&1u16.to_le_bytes());
+        c.extend_from_slice(&(32 + inner.len() as u32).to_le_bytes());
+        c.extend_from_slice(&LOG_CONTAINER.to_le_bytes());
+        c.extend_from_slice(&0u16.to_le_bytes()); // uncompressed
+        c.extend_from_slice(&[0; 6]);
+        c.extend_from_slice(&(inner.len() as u32).to_le_bytes());
+        c.extend_from_slice(&[0; 4]);
+        c.extend(inner);
+        f.extend(c);
+        f
+    }
+
+    fn ids(objs: impl IntoIterator<Item = BlfObject>) -> Vec<Option<u32>> {
+        objs.into_iter()
+            .map(|o| match o {
+                BlfObject::Can(cf) => Some(cf.id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stream_matches_reader_at_any_split() {
+        let f = file();
+        let expected = ids(BlfReader::new(&f).unwrap());
+        assert_eq!(expected, [Some(0x100), None, Some(0x200), Some(0x300)]);
+        for chunk in [1, 3, 7, 50, f.len()] {
+            let mut s = BlfStream::default();
+            let mut got = Vec::new();
+            for c in f.chunks(chunk) {
+                got.extend(s.push(c).unwrap());
+            }
+            got.extend(s.finish().unwrap());
+            assert_eq!(ids(got), expected, "chunk size {}", chunk);
+        }
+
+        // header cut short; object claiming 4 GiB
+        let mut s = BlfStream::default();
+        s.push(&f[..20]).unwrap();
+        assert!(s.finish().unwrap_err().contains("truncated"));
+        let mut huge = f.clone();
+        let first = u32_at(&huge, 4).unwrap() as usize;
+        huge[first + 8..first + 12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(parse_object(&huge[first..]), Parsed::Invalid));
+        let mut s = BlfStream::default();
+        assert!(s.push(&huge).unwrap().is_empty());
+        assert!(s.finish().unwrap().is_empty());
+    }
+
+    fn parsed_can(obj: &[u8]) -> Option<CanFrame> {
+        match parse_object(obj) {
+            Parsed::Object(BlfObject::Can(cf), _) => Some(cf),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn can_fd_bodies() {
+        let payload: Vec<u8> = (0..64).collect();
+
+        // CAN_FD_MESSAGE, 12 bytes (dlc 9), Tx, EDL + ESI without BRS
+        let mut body = vec![2, 0, FLAG_TX, 9];
+        body.extend_from_slice(&0x8000_0123u32.to_le_bytes());
+        body.extend_from_slice(&[0; 5]); // frame_length, arb_bit_count
+        body.extend_from_slice(&[0x01 | 0x04, 12, 0, 0, 0, 0, 0]);
+        body.extend_from_slice(&payload);
+        let cf = parsed_can(&object(CAN_FD_MESSAGE, &body)).unwrap();
+        assert_eq!((cf.channel, cf.id, cf.dlc, cf.data.as_slice()), (2, 0x8000_0123, 9, &payload[..12]));
+        assert_eq!((cf.tx, cf.rtr, cf.fd, cf.brs, cf.esi), (true, false, true, false, true));
+        // valid_bytes past the body
+        assert!(parsed_can(&object(CAN_FD_MESSAGE, &body[..28])).is_none());
+
+        // CAN_FD_MESSAGE_64, 64 bytes (dlc 15), EDL + BRS, dir Tx
+        let fd64 = |flags: u32, dir: u8, len: u8| {
+            let mut body = vec![3, 15, len, 0];
+            body.extend_from_slice(&0x1A0u32.to_le_bytes());
+            body.extend_from_slice(&0u32.to_le_bytes()); // frame_length
+            body.extend_from_slice(&flags.to_le_bytes());
+            body.extend_from_slice(&[0; 18]); // btr x2, time offsets x2, bit_count
+            body.extend_from_slice(&[dir, 0, 0, 0, 0, 0]); // dir, ext_data_offset, crc
+            body.extend_from_slice(&payload[..len as usize]);
+            object(CAN_FD_MESSAGE_64, &body)
+        };
+        let cf = parsed_can(&fd64(0x1000 | 0x2000, 1, 64)).unwrap();
+        assert_eq!((cf.channel, cf.id, cf.dlc, cf.data.as_slice()), (3, 0x1A0, 15, &payload[..]));
+        assert_eq!((cf.tx, cf.tx_request, cf.fd, cf.brs, cf.esi), (true, false, true, true, false));
+        let cf = parsed_can(&fd64(0x1000 | 0x4000, 2, 12)).unwrap();
+        assert_eq!(cf.data, &payload[..12]);
+        assert_eq!((cf.tx, cf.tx_request, cf.fd, cf.brs, cf.esi), (false, true, true, false, true));
+        // EDL clear: a classic frame carried in the FD object
+        let cf = parsed_can(&fd64(0, 0, 8)).unwrap();
+        assert_eq!((cf.fd, cf.brs, cf.esi, cf.data.len()), (false, false, false, 8));
+    }
+
+    #[test]
+    fn core_error_codes_round_trip() {
+        for kind in ERROR_TYPES {
+            assert_eq!(error_kind(ERR_CORE_CODE, core_code(kind)).0, Some(kind));
+        }
+        assert_eq!(error_kind(ERR_CORE_CODE, 7).0, Some("Ack"));
+        assert_eq!(error_kind(ERR_CORE_CODE, 6).0, Some("Other"));
+        assert_eq!(error_kind(ERR_SJA1000_ECC, 0x80 | 0x20), (Some("Stuff"), Some(false)));
+    }
+
+    #[test]
+    fn writer_round_trips_through_reader() {
+        let can = |i: u64, fd: bool| CanFrame {
+            timestamp_ns: i * 1_000_000,
+            channel: 1 + (i % 2) as u16,
+            id: if fd { 0x8000_0000 | 0x18FE_F100 } else { 0x100 + (i % 16) as u32 },
+            dlc: if fd { 9 } else { 8 },
+            data: vec![i as u8; if fd { 12 } else { 8 }],
+            tx: i.is_multiple_of(3),
+            tx_request: false,
+            rtr: false,
+            wakeup: false,
+            nerr: false,
+            fd,
+            brs: fd,
+            esi: false,
+        };
+        let error = |fd: bool| CanError {
+            timestamp_ns: 5,
+            channel: 1,
+            id: 0x123,
+            dlc: if fd { 9 } else { 2 },
+            data: vec![1; if fd { 12 } else { 2 }],
+            fd,
+            error_type: Some(if fd { "Form" } else { "CRC" }),
+            tx: None,
+            position: Some(if fd { 300 } else { 12 }),
+            ecc: Some(if fd { 1 } else { 4 }),
+            tx_errors: Some(8),
+            rx_errors: Some(if fd { 16 } else { 0 }),
+        };
+        let mut objects = vec![
+            BlfObject::Error(error(false)),
+            BlfObject::Error(error(true)),
+            BlfObject::Error(CanError {
+                id: 0,
+                dlc: 0,
+                data: Vec::new(),
+                error_type: Some("Bit"),
+                tx: Some(true),
+                position: None,
+                ecc: Some(0),
+                ..error(false)
+            }),
+            BlfObject::Lin(LinFrame { timestamp_ns: 7, channel: 1, id: 0x21, dlc: 2, data: vec![3, 4], tx: false, tx_request: true }),
+            BlfObject::FlexRay(FlexRayFrame {
+                timestamp_ns: 8,
+                channel: 1,
+                channel_mask: 2,
+                slot_id: 17,
+                cycle: 5,
+                data: vec![9; 16],
+                tx: true,
+                tx_request: false,
+            }),
+            BlfObject::Ethernet(EthernetFrame {
+                timestamp_ns: 9,
+                channel: 1,
+                source: [1, 2, 3, 4, 5, 6],
+                destination: [0xFF; 6],
+                ethertype: 0x0800,
+                vlan_tci: Some(0x2005),
+                payload: vec![0x45; 40],
+                tx: false,
+                tx_request: false,
+            }),
+        ];
+        // enough frames for several containers
+        objects.extend((0..20_000).map(|i| BlfObject::Can(can(i, i.is_multiple_of(100)))));
+
+        let start = LogStart { year: 2026, month: 10, day_of_week: 5, day: 16, hour: 8, minute: 0, second: 0, millisecond: 0 };
+        for compress in [true, false] {
+            let mut w = BlfWriter::new(compress);
+            for o in &objects {
+                w.push(o).unwrap();
+            }
+            let bytes = w.finish(Some(start), None);
+            assert_eq!(log_start(&bytes), Some(start));
+            assert_eq!(u64_at(&bytes, 16), Some(bytes.len() as u64));
+            assert_eq!(&bytes[12..16], &BL_API_VERSION);
+            let back: Vec<String> = BlfReader::new(&bytes)
+                .unwrap()
+                .filter(|o| !matches!(o, BlfObject::DriverError { .. }))
+                .map(|o| format!("{:?}", o))
+                .collect();
+            let expected: Vec<String> = objects.iter().map(|o| format!("{:?}", o)).collect();
+            assert_eq!(back, expected, "compress {}", compress);
+            if compress {
+                assert!(bytes.len() < 20_000 * 48 / 3);
+            }
+        }
+    }
+
+    #[test]
+    fn channels_above_255() {
+        let frame = |channel, fd| CanFrame {
+            timestamp_ns: 1,
+            channel,
+            id: 0x100,
+            dlc: if fd { 9 } else { 8 },
+            data: vec![1; if fd { 12 } else { 8 }],
+            tx: false,
+            tx_request: false,
+            rtr: false,
+            wakeup: false,
+            nerr: false,
+            fd,
+            brs: fd,
+            esi: false,
+        };
+        let error = |channel| CanError {
+            timestamp_ns: 2,
+            channel,
+            id: 0x100,
+            dlc: 9,
+            data: vec![1; 12],
+            fd: true,
+            error_type: Some("Form"),
+            tx: None,
+            position: None,
+            ecc: None,
+            tx_errors: Some(8),
+            rx_errors: Some(0),
+        };
+        // CAN_MESSAGE and CAN_ERROR_EXT keep the u16 channel, the FD objects one byte
+        let mut w = BlfWriter::new(true);
+        for o in [frame(300, false), frame(255, true)] {
+            w.push(&BlfObject::Can(o)).unwrap();
+        }
+        w.push(&BlfObject::Error(CanError { fd: false, dlc: 8, data: vec![1; 8], ..error(300) })).unwrap();
+        let back: Vec<(u16, bool)> = BlfReader::new(&w.finish(None, None))
+            .unwrap()
+            .filter_map(|o| match o {
+                BlfObject::Can(cf) => Some((cf.channel, cf.fd)),
+                BlfObject::Error(e) => Some((e.channel, e.fd)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(back, [(300, false), (255, true), (300, false)]);
+
+        // CAN300 must not come back as CAN44: refused, nothing written
+        let mut w = BlfWriter::new(false);
+        assert!(w.push(&BlfObject::Can(frame(300, true))).unwrap_err().starts_with("CAN300:"));
+        assert!(w.push(&BlfObject::Error(error(300))).is_err());
+        assert_eq!(w.objects, 0);
+    }
+}
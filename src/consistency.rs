@@ -0,0 +1,130 @@
+// ###############################################################
+// consistency.rs
+// can-blf-parser (WASM)
+// DBC vs observed traffic: DLC and cycle-time checks with a compatibility score
+// ###############################################################
+
+use std::collections::{BTreeMap, HashSet};
+
+use serde::Serialize;
+
+use crate::split_id;
+use crate::store::FrameStore;
+
+// What the DBC says about one message on one channel
+pub(crate) struct Expected {
+    pub channel_num: u16,
+    pub id: u32, // raw: bit 31 set for extended ids
+    pub name: String,
+    pub dlc: usize, // payload bytes
+    pub cycle_ms: Option<f64>, // GenMsgCycleTime; None/0 = event-driven
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscrepancyKind {
+    Dlc,
+    CycleTime,
+    UnknownId, // traffic for an id the DBC does not define
+    NotObserved, // DBC message never seen (informational, not scored)
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Discrepancy {
+    pub kind: DiscrepancyKind,
+    pub channel: String,
+    pub channel_num: u16,
+    pub id: u32,
+    pub is_extended: bool,
+    pub name: String,
+    pub expected: Option<f64>, // bytes or ms
+    pub observed: Option<f64>,
+    pub frames: usize,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ConsistencyReport {
+    pub score: f64, // % of passed checks, 100 when nothing could be checked
+    pub checks: usize,
+    pub passed: usize,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+pub(crate) fn check(frames: &FrameStore, expected: &[Expected], cycle_tolerance: f64, min_frames: usize) -> ConsistencyReport {
+    // observed traffic per (channel, id): payload lengths and timestamps
+    let mut seen: BTreeMap<(u16, u32), (HashSet<usize>, Vec<f64>)> = BTreeMap::new();
+    for f in frames.iter().filter(|f| f.is_can_message()) {
+        let e = seen.entry((f.channel_num, f.raw_id())).or_default();
+        e.0.insert(f.data.len());
+        e.1.push(f.timestamp);
+    }
+
+    let mut checks = 0;
+    let mut passed = 0;
+    let mut discrepancies = Vec::new();
+    let mut known: HashSet<(u16, u32)> = HashSet::new();
+    let channels: HashSet<u16> = expected.iter().map(|e| e.channel_num).collect();
+
+    for exp in expected {
+        known.insert((exp.channel_num, exp.id));
+        let (id, is_extended) = split_id(exp.id);
+        let base = |kind, expected, observed, frames| Discrepancy {
+            kind,
+            channel: format!("CAN{}", exp.channel_num),
+            channel_num: exp.channel_num,
+            id,
+            is_extended,
+            name: exp.name.clone(),
+            expected,
+            observed,
+            frames,
+        };
+        let Some((lens, times)) = seen.get(&(exp.channel_num, exp.id)) else {
+            discrepancies.push(base(DiscrepancyKind::NotObserved, None, None, 0));
+            continue;
+        };
+
+        checks += 1;
+        let wrong_len = lens.iter().copied().filter(|l| *l != exp.dlc).max();
+        match wrong_len {
+            None => passed += 1,
+            Some(l) => discrepancies.push(base(DiscrepancyKind::Dlc, Some(exp.dlc as f64), Some(l as f64), times.len())),
+        }
+
+        if let Some(cycle) = exp.cycle_ms.filter(|c| *c > 0.0) {
+            if times.len() >= min_frames.max(2) {
+                checks += 1;
+                let mut d: Vec<f64> = times.windows(2).map(|w| (w[1] - w[0]) * 1000.0).collect();
+                d.sort_by(|a, b| a.total_cmp(b));
+                let median = d[d.len() / 2];
+                if (median - cycle).abs() <= cycle * cycle_tolerance {
+                    passed += 1;
+                } else {
+                    discrepancies.push(base(DiscrepancyKind::CycleTime, Some(cycle), Some(median), times.len()));
+                }
+            }
+        }
+    }
+
+    // ids on DBC-covered channels the DBC does not know
+    for ((ch, id), (_, times)) in &seen {
+        if channels.contains(ch) && !known.contains(&(*ch, *id)) {
+            checks += 1;
+            let (id, is_extended) = split_id(*id);
+            discrepancies.push(Discrepancy {
+                kind: DiscrepancyKind::UnknownId,
+                channel: format!("CAN{}", ch),
+                channel_num: *ch,
+                id,
+                is_extended,
+                name: String::new(),
+                expected: None,
+                observed: None,
+                frames: times.len(),
+            });
+        }
+    }
+
+    let score = if checks == 0 { 100.0 } else { passed as f64 / checks as f64 * 100.0 };
+    ConsistencyReport { score, checks, passed, discrepancies }
+}
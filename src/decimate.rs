@@ -0,0 +1,590 @@
+// ###############################################################
+// decimate.rs
+// can-blf-parser (WASM)
+// Frame-stride, LTTB and min/max envelope decimation shared by decimated() and decimated_stream()
+// ###############################################################
+
+use std::collections::{HashMap, HashSet};
+
+use crate::SignalRow;
+
+// Transition-preserving trace for a discrete signal:
+// first sample, every value change, and the final sample.
+#[derive(Debug, Default)]
+pub(crate) struct EdgeTrace {
+    pub time: Vec<f64>,
+    pub values: Vec<f64>,
+    last: Option<(f64, f64)>,
+}
+
+impl EdgeTrace {
+    fn push(&mut self, t: f64, v: f64) {
+        if self.values.last() != Some(&v) {
+            self.time.push(t);
+            self.values.push(v);
+        }
+        self.last = Some((t, v));
+    }
+
+    fn finish(&mut self) {
+        if let Some((t, v)) = self.last {
+            if self.time.last() != Some(&t) {
+                self.time.push(t);
+                self.values.push(v);
+            }
+        }
+    }
+}
+
+// Frame indices that must survive decimation: first and last sample of each
+// continuous signal (discrete traces keep their endpoints on their own).
+pub(crate) struct EndpointTracker {
+    keys: Option<HashSet<String>>,
+    discrete: HashMap<String, bool>,
+    count: usize,
+    first: HashMap<String, usize>,
+    last: HashMap<String, usize>,
+    pub t_first: Option<f64>,
+    pub t_last: f64,
+}
+
+impl EndpointTracker {
+    pub(crate) fn new(keys: Option<&[String]>, discrete: &HashMap<String, bool>) -> EndpointTracker {
+        EndpointTracker {
+            keys: keys.map(|k| k.iter().cloned().collect()),
+            discrete: discrete.clone(),
+            count: 0,
+            first: HashMap::new(),
+            last: HashMap::new(),
+            t_first: None,
+            t_last: 0.0,
+        }
+    }
+
+    pub(crate) fn push(&mut self, timestamp: f64, signals: &[SignalRow]) {
+        for s in signals {
+            if self.discrete.contains_key(&s.signal) || self.keys.as_ref().is_some_and(|k| !k.contains(&s.signal)) {
+                continue;
+            }
+            self.first.entry(s.signal.clone()).or_insert(self.count);
+            self.last.insert(s.signal.clone(), self.count);
+        }
+        self.t_first.get_or_insert(timestamp);
+        self.t_last = timestamp;
+        self.count += 1;
+    }
+
+    pub(crate) fn frames(&self) -> usize {
+        self.count
+    }
+
+    pub(crate) fn finish(self) -> HashSet<usize> {
+        self.first.into_values().chain(self.last.into_values()).collect()
+    }
+}
+
+pub(crate) struct Decimator {
+    step: usize,
+    bucket_s: Option<f64>, // Some(..) -> time buckets at round timestamps instead of stride
+    forced: HashSet<usize>,
+    keys: Option<HashSet<String>>, // None -> every signal seen
+    discrete: HashMap<String, bool>, // discrete signal -> is 1-bit
+    count: usize,
+    cur_bucket: Option<i64>,
+    last_frame: Option<(usize, f64)>,
+    last_kept: Option<usize>,
+    last_seen: HashMap<String, f64>,
+    pub time: Vec<f64>,
+    pub signals: HashMap<String, Vec<Option<f64>>>,
+    pub digital: HashMap<String, EdgeTrace>,
+    pub discrete_traces: HashMap<String, EdgeTrace>,
+}
+
+impl Decimator {
+    // Keep every `step`-th frame (or one held value per `bucket_s` time bucket,
+    // stamped at the bucket start), forward-filling continuous signals onto the
+    // kept timestamps; `forced` frame indices and the final frame are always
+    // kept. Discrete signals bypass the stride and keep all changes.
+    pub(crate) fn new(
+        step: usize,
+        bucket_s: Option<f64>,
+        forced: HashSet<usize>,
+        keys: Option<&[String]>,
+        discrete: HashMap<String, bool>,
+    ) -> Decimator {
+        let mut signals = HashMap::new();
+        if let Some(keys) = keys {
+            for k in keys.iter().filter(|k| !discrete.contains_key(*k)) {
+                signals.insert(k.clone(), Vec::new());
+            }
+        }
+        Decimator {
+            step: step.max(1),
+            bucket_s: bucket_s.filter(|b| *b > 0.0),
+            forced,
+            keys: keys.map(|k| k.iter().cloned().collect()),
+            discrete,
+            count: 0,
+            cur_bucket: None,
+            last_frame: None,
+            last_kept: None,
+            last_seen: HashMap::new(),
+            time: Vec::new(),
+            signals,
+            digital: HashMap::new(),
+            discrete_traces: HashMap::new(),
+        }
+    }
+
+    fn keep_point(&mut self, t: f64) {
+        self.time.push(t);
+        for (k, arr) in self.signals.iter_mut() {
+            arr.push(self.last_seen.get(k).copied());
+        }
+    }
+
+    pub(crate) fn push(&mut self, timestamp: f64, signals: &[SignalRow]) {
+        // entering a new time bucket: emit the value held at the bucket start
+        if let Some(width) = self.bucket_s {
+            let bucket = (timestamp / width).floor() as i64;
+            if self.cur_bucket != Some(bucket) {
+                self.cur_bucket = Some(bucket);
+                if !self.last_seen.is_empty() {
+                    self.keep_point(bucket as f64 * width);
+                }
+            }
+        }
+
+        for s in signals {
+            if let Some(keys) = &self.keys {
+                if !keys.contains(&s.signal) {
+                    continue;
+                }
+            }
+            if let Some(is_digital) = self.discrete.get(&s.signal) {
+                let traces = if *is_digital { &mut self.digital } else { &mut self.discrete_traces };
+                traces.entry(s.signal.clone()).or_default().push(timestamp, s.value);
+                continue;
+            }
+            if !self.signals.contains_key(&s.signal) {
+                // first appearance in stream mode: back-fill earlier kept points
+                self.signals.insert(s.signal.clone(), vec![None; self.time.len()]);
+            }
+            self.last_seen.insert(s.signal.clone(), s.value);
+        }
+
+        let on_stride = self.bucket_s.is_none() && self.count.is_multiple_of(self.step);
+        if on_stride || self.forced.contains(&self.count) {
+            self.keep_point(timestamp);
+            self.last_kept = Some(self.count);
+        }
+        self.last_frame = Some((self.count, timestamp));
+        self.count += 1;
+    }
+
+    pub(crate) fn finish(mut self) -> Decimator {
+        // never let the plot end early
+        if let Some((idx, t)) = self.last_frame {
+            if self.last_kept != Some(idx) {
+                self.keep_point(t);
+            }
+        }
+        for trace in self.digital.values_mut().chain(self.discrete_traces.values_mut()) {
+            trace.finish();
+        }
+        self
+    }
+}
+
+// Bucket width for anchored decimation: a multiple of anchor_s wide enough to
+// keep roughly max_points buckets over the span.
+pub(crate) fn anchored_bucket(anchor_s: f64, span_s: f64, max_points: usize) -> f64 {
+    let raw = span_s / max_points.max(1) as f64;
+    (raw / anchor_s).ceil().max(1.0) * anchor_s
+}
+
+// Per-message decimation: each message keeps every `step`-th of its own frames
+// (plus its last), so its signals share one time array and carry real samples.
+// Discrete signals still go to edge traces.
+#[derive(Debug, Default)]
+pub(crate) struct MessageGroup {
+    pub channel: String,
+    pub channel_num: u16,
+    pub id: u32,
+    pub is_extended: bool,
+    pub name: String,
+    pub time: Vec<f64>,
+    pub signals: HashMap<String, Vec<f64>>, // NaN where a kept frame lacks the signal (mux)
+    total: usize,
+    step: usize,
+    count: usize,
+}
+
+pub(crate) struct GroupedDecimator {
+    keys: Option<HashSet<String>>,
+    discrete: HashMap<String, bool>,
+    pub groups: HashMap<(u16, u32), MessageGroup>,
+    pub digital: HashMap<String, EdgeTrace>,
+    pub discrete_traces: HashMap<String, EdgeTrace>,
+}
+
+impl GroupedDecimator {
+    // `counts`: frames per (channel, raw id), so each message gets its own stride
+    pub(crate) fn new(
+        counts: HashMap<(u16, u32), usize>,
+        max_points: usize,
+        keys: Option<&[String]>,
+        discrete: HashMap<String, bool>,
+    ) -> GroupedDecimator {
+        let groups = counts
+            .into_iter()
+            .map(|(key, total)| {
+                let step = (total / max_points.max(1)).max(1);
+                (key, MessageGroup { total, step, ..Default::default() })
+            })
+            .collect();
+        GroupedDecimator {
+            keys: keys.map(|k| k.iter().cloned().collect()),
+            discrete,
+            groups,
+            digital: HashMap::new(),
+            discrete_traces: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, frame: &crate::Frame, signals: &[SignalRow]) {
+        let wanted = |s: &SignalRow| self.keys.as_ref().is_none_or(|k| k.contains(&s.signal));
+        for s in signals.iter().filter(|s| wanted(s)) {
+            if let Some(is_digital) = self.discrete.get(&s.signal) {
+                let traces = if *is_digital { &mut self.digital } else { &mut self.discrete_traces };
+                traces.entry(s.signal.clone()).or_default().push(frame.timestamp, s.value);
+            }
+        }
+
+        let Some(g) = self.groups.get_mut(&(frame.channel_num, frame.raw_id())) else { return };
+        let idx = g.count;
+        g.count += 1;
+        if !(idx.is_multiple_of(g.step) || idx + 1 == g.total) {
+            return;
+        }
+        if g.time.is_empty() {
+            g.channel = frame.channel.to_string();
+            g.channel_num = frame.channel_num;
+            g.id = frame.id;
+            g.is_extended = frame.is_extended;
+            g.name = frame.name.to_string();
+        }
+        let n = g.time.len();
+        g.time.push(frame.timestamp);
+        for s in signals.iter().filter(|s| wanted(s) && !self.discrete.contains_key(&s.signal)) {
+            g.signals.entry(s.signal.clone()).or_insert_with(|| vec![f64::NAN; n]).push(s.value);
+        }
+        for col in g.signals.values_mut() {
+            col.resize(n + 1, f64::NAN);
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> GroupedDecimator {
+        self.groups.retain(|_, g| !g.signals.is_empty());
+        for trace in self.digital.values_mut().chain(self.discrete_traces.values_mut()) {
+            trace.finish();
+        }
+        self
+    }
+}
+
+// Largest-Triangle-Three-Buckets: `threshold` samples (first and last kept) chosen so
+// the line keeps its visual shape, spikes included, unlike stride sampling
+pub(crate) fn lttb(times: &[f64], values: &[f64], threshold: usize) -> (Vec<f64>, Vec<f64>) {
+    let n = times.len();
+    let threshold = threshold.max(3);
+    if n <= threshold {
+        return (times.to_vec(), values.to_vec());
+    }
+    let every = (n - 2) as f64 / (threshold - 2) as f64;
+    let (mut out_t, mut out_v) = (vec![times[0]], vec![values[0]]);
+    let mut a = 0;
+    for i in 0..threshold - 2 {
+        // average of the next bucket is the triangle's third corner
+        let next = ((i + 1) as f64 * every) as usize + 1..(((i + 2) as f64 * every) as usize + 1).min(n);
+        let len = next.len().max(1) as f64;
+        let avg_t = times[next.clone()].iter().sum::<f64>() / len;
+        let avg_v = values[next].iter().sum::<f64>() / len;
+
+        let bucket = (i as f64 * every) as usize + 1..((i + 1) as f64 * every) as usize + 1;
+        let mut best = (bucket.start, -1.0);
+        for j in bucket {
+            let area = ((times[a] - avg_t) * (values[j] - values[a]) - (times[a] - times[j]) * (avg_v - values[a])).abs();
+            if area > best.1 {
+                best = (j, area);
+            }
+        }
+        a = best.0;
+        out_t.push(times[a]);
+        out_v.push(values[a]);
+    }
+    out_t.push(times[n - 1]);
+    out_v.push(values[n - 1]);
+    (out_t, out_v)
+}
+
+// options.method = "lttb": every sample of each continuous signal is collected, then
+// reduced to max_points per signal by lttb(); discrete signals go to edge traces
+pub(crate) struct LttbDecimator {
+    keys: Option<HashSet<String>>,
+    discrete: HashMap<String, bool>,
+    pub series: HashMap<String, (Vec<f64>, Vec<f64>)>,
+    pub digital: HashMap<String, EdgeTrace>,
+    pub discrete_traces: HashMap<String, EdgeTrace>,
+}
+
+impl LttbDecimator {
+    pub(crate) fn new(keys: Option<&[String]>, discrete: HashMap<String, bool>) -> LttbDecimator {
+        LttbDecimator {
+            keys: keys.map(|k| k.iter().cloned().collect()),
+            discrete,
+            series: HashMap::new(),
+            digital: HashMap::new(),
+            discrete_traces: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, timestamp: f64, signals: &[SignalRow]) {
+        for s in signals {
+            if self.keys.as_ref().is_some_and(|k| !k.contains(&s.signal)) {
+                continue;
+            }
+            if let Some(is_digital) = self.discrete.get(&s.signal) {
+                let traces = if *is_digital { &mut self.digital } else { &mut self.discrete_traces };
+                traces.entry(s.signal.clone()).or_default().push(timestamp, s.value);
+                continue;
+            }
+            let (t, v) = self.series.entry(s.signal.clone()).or_default();
+            t.push(timestamp);
+            v.push(s.value);
+        }
+    }
+
+    pub(crate) fn finish(mut self, max_points: usize) -> LttbDecimator {
+        for (t, v) in self.series.values_mut() {
+            (*t, *v) = lttb(t, v, max_points);
+        }
+        for trace in self.digital.values_mut().chain(self.discrete_traces.values_mut()) {
+            trace.finish();
+        }
+        self
+    }
+}
+
+// options.method = "envelope": per continuous signal, time buckets of bucket_s (stamped
+// at their start) with min / max / mean of the samples inside; empty buckets are left out
+#[derive(Debug, Default)]
+pub(crate) struct Envelope {
+    pub time: Vec<f64>,
+    pub min: Vec<f64>,
+    pub max: Vec<f64>,
+    pub mean: Vec<f64>,
+    open: Option<(i64, f64, f64, f64, usize)>, // (bucket, min, max, sum, count)
+}
+
+impl Envelope {
+    fn push(&mut self, bucket: i64, width: f64, v: f64) {
+        match &mut self.open {
+            Some((b, min, max, sum, count)) if *b == bucket => {
+                *min = min.min(v);
+                *max = max.max(v);
+                *sum += v;
+                *count += 1;
+            }
+            _ => {
+                self.flush(width);
+                self.open = Some((bucket, v, v, v, 1));
+            }
+        }
+    }
+
+    fn flush(&mut self, width: f64) {
+        if let Some((b, min, max, sum, count)) = self.open.take() {
+            self.time.push(b as f64 * width);
+            self.min.push(min);
+            self.max.push(max);
+            self.mean.push(sum / count as f64);
+        }
+    }
+}
+
+pub(crate) struct EnvelopeDecimator {
+    bucket_s: f64,
+    keys: Option<HashSet<String>>,
+    discrete: HashMap<String, bool>,
+    pub envelopes: HashMap<String, Envelope>,
+    pub digital: HashMap<String, EdgeTrace>,
+    pub discrete_traces: HashMap<String, EdgeTrace>,
+}
+
+impl EnvelopeDecimator {
+    pub(crate) fn new(bucket_s: f64, keys: Option<&[String]>, discrete: HashMap<String, bool>) -> EnvelopeDecimator {
+        EnvelopeDecimator {
+            bucket_s: if bucket_s > 0.0 { bucket_s } else { 1.0 },
+            keys: keys.map(|k| k.iter().cloned().collect()),
+            discrete,
+            envelopes: HashMap::new(),
+            digital: HashMap::new(),
+            discrete_traces: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, timestamp: f64, signals: &[SignalRow]) {
+        let bucket = (timestamp / self.bucket_s).floor() as i64;
+        for s in signals {
+            if self.keys.as_ref().is_some_and(|k| !k.contains(&s.signal)) {
+                continue;
+            }
+            if let Some(is_digital) = self.discrete.get(&s.signal) {
+                let traces = if *is_digital { &mut self.digital } else { &mut self.discrete_traces };
+                traces.entry(s.signal.clone()).or_default().push(timestamp, s.value);
+                continue;
+            }
+            self.envelopes.entry(s.signal.clone()).or_default().push(bucket, self.bucket_s, s.value);
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> EnvelopeDecimator {
+        for e in self.envelopes.values_mut() {
+            e.flush(self.bucket_s);
+        }
+        for trace in self.digital.values_mut().chain(self.discrete_traces.values_mut()) {
+            trace.finish();
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(signal: &str, value: f64) -> SignalRow {
+        SignalRow { signal: signal.to_string(), value, unit: String::new(), original_unit: None, value_text: None }
+    }
+
+    // 1000 frames, one per second: Speed ramps, Brake (1 bit) pulses and Gear (4 bit)
+    // shifts for the single frame 437 only
+    fn pulse_frames() -> Vec<(f64, Vec<SignalRow>)> {
+        (0..1000)
+            .map(|i| {
+                let on = i == 437;
+                let signals = vec![
+                    row("Speed", i as f64),
+                    row("Brake", if on { 1.0 } else { 0.0 }),
+                    row("Gear", if on { 4.0 } else { 3.0 }),
+                ];
+                (i as f64, signals)
+            })
+            .collect()
+    }
+
+    fn discrete() -> HashMap<String, bool> {
+        HashMap::from([("Brake".to_string(), true), ("Gear".to_string(), false)])
+    }
+
+    // (time, values) of an edge trace
+    fn edges(trace: &EdgeTrace) -> (Vec<f64>, Vec<f64>) {
+        (trace.time.clone(), trace.values.clone())
+    }
+
+    #[test]
+    fn stride_keeps_digital_edges() {
+        let mut dec = Decimator::new(100, None, HashSet::new(), None, discrete());
+        for (t, signals) in pulse_frames() {
+            dec.push(t, &signals);
+        }
+        let dec = dec.finish();
+        // every 100th frame plus the last
+        assert_eq!(dec.time.len(), 11);
+        assert!(!dec.time.contains(&437.0));
+        // the one-sample pulse keeps its rising and falling edge
+        assert_eq!(edges(&dec.digital["Brake"]), (vec![0.0, 437.0, 438.0, 999.0], vec![0.0, 1.0, 0.0, 0.0]));
+    }
+
+    // Gear's short shift survives each method while Speed is thinned
+    fn gear_shift() -> (Vec<f64>, Vec<f64>) {
+        (vec![0.0, 437.0, 438.0, 999.0], vec![3.0, 4.0, 3.0, 3.0])
+    }
+
+    #[test]
+    fn stride_keeps_discrete_changes() {
+        let mut dec = Decimator::new(100, None, HashSet::new(), None, discrete());
+        for (t, signals) in pulse_frames() {
+            dec.push(t, &signals);
+        }
+        let dec = dec.finish();
+        assert_eq!(dec.signals["Speed"].len(), 11);
+        assert_eq!(edges(&dec.discrete_traces["Gear"]), gear_shift());
+    }
+
+    #[test]
+    fn grouped_keeps_discrete_changes() {
+        let counts = HashMap::from([((1, 0x100), 1000)]);
+        let mut dec = GroupedDecimator::new(counts, 10, None, discrete());
+        for (t, signals) in pulse_frames() {
+            let row = crate::FrameRow { timestamp: t, channel: "CAN1".to_string(), channel_num: 1, id: 0x100, ..Default::default() };
+            dec.push(&row.view(), &signals);
+        }
+        let dec = dec.finish();
+        let group = &dec.groups[&(1, 0x100)];
+        assert_eq!((group.time.len(), group.signals["Speed"].len()), (11, 11));
+        assert!(!group.signals.contains_key("Gear"));
+        assert_eq!(edges(&dec.discrete_traces["Gear"]), gear_shift());
+    }
+
+    #[test]
+    fn lttb_keeps_discrete_changes() {
+        let mut dec = LttbDecimator::new(None, discrete());
+        for (t, signals) in pulse_frames() {
+            dec.push(t, &signals);
+        }
+        let dec = dec.finish(20);
+        assert_eq!(dec.series["Speed"].0.len(), 20);
+        assert_eq!(edges(&dec.discrete_traces["Gear"]), gear_shift());
+    }
+
+    #[test]
+    fn envelope_keeps_discrete_changes() {
+        let mut dec = EnvelopeDecimator::new(100.0, None, discrete());
+        for (t, signals) in pulse_frames() {
+            dec.push(t, &signals);
+        }
+        let dec = dec.finish();
+        assert_eq!(dec.envelopes["Speed"].time.len(), 10);
+        assert!(!dec.envelopes.contains_key("Gear"));
+        assert_eq!(edges(&dec.discrete_traces["Gear"]), gear_shift());
+    }
+
+    #[test]
+    fn lttb_keeps_spikes() {
+        let times: Vec<f64> = (0..1000).map(|i| i as f64).collect();
+        let mut values = vec![0.0; 1000];
+        values[437] = 50.0;
+        values[801] = -20.0;
+        let (t, v) = lttb(&times, &values, 20);
+        assert_eq!(t.len(), 20);
+        assert_eq!((t[0], t[19]), (0.0, 999.0));
+        assert!(t.contains(&437.0) && v.contains(&50.0) && v.contains(&-20.0));
+        // short series pass through
+        assert_eq!(lttb(&times[..5], &values[..5], 20).0.len(), 5);
+    }
+
+    #[test]
+    fn envelope_buckets() {
+        let mut dec = EnvelopeDecimator::new(1.0, None, HashMap::new());
+        for (t, v) in [(0.1, 2.0), (0.5, -1.0), (0.9, 5.0), (3.2, 7.0)] {
+            dec.push(t, &[row("S", v)]);
+        }
+        let e = &dec.finish().envelopes["S"];
+        // bucket [1, 3) has no samples and is left out
+        assert_eq!(e.time, [0.0, 3.0]);
+        assert_eq!((e.min[0], e.max[0], e.mean[0]), (-1.0, 5.0, 2.0));
+        assert_eq!((e.min[1], e.max[1], e.mean[1]), (7.0, 7.0, 7.0));
+    }
+}
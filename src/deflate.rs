@@ -0,0 +1,189 @@
+// ###############################################################
+// deflate.rs
+// can-blf-parser (WASM)
+// Small DEFLATE compressor for BLF log containers (export_blf(), zlib) and
+// XLSX parts (zip.rs): LZ77 over a 32 KiB window with hash chains, one
+// fixed-Huffman block. zune-inflate only decompresses; CAN traces and sheet
+// XML repeat enough that fixed codes get most of the gain of dynamic ones.
+// ###############################################################
+
+const WINDOW: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64; // candidates tried per position
+const HASH_BITS: u32 = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+// LSB-first bit packing as DEFLATE wants it
+#[derive(Default)]
+struct Bits {
+    out: Vec<u8>,
+    acc: u64,
+    n: u32,
+}
+
+impl Bits {
+    fn put(&mut self, value: u32, count: u32) {
+        self.acc |= (value as u64) << self.n;
+        self.n += count;
+        while self.n >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.n -= 8;
+        }
+    }
+
+    // Huffman codes are defined MSB first
+    fn code(&mut self, code: u32, len: u32) {
+        self.put(code.reverse_bits() >> (32 - len), len);
+    }
+
+    // fixed literal/length code (RFC 1951 3.2.6)
+    fn symbol(&mut self, sym: u16) {
+        let sym = sym as u32;
+        match sym {
+            0..=143 => self.code(0x30 + sym, 8),
+            144..=255 => self.code(0x190 + sym - 144, 9),
+            256..=279 => self.code(sym - 256, 7),
+            _ => self.code(0xC0 + sym - 280, 8),
+        }
+    }
+
+    fn length(&mut self, len: usize) {
+        let i = LENGTH_BASE.partition_point(|b| *b as usize <= len) - 1;
+        self.symbol(257 + i as u16);
+        self.put((len - LENGTH_BASE[i] as usize) as u32, LENGTH_EXTRA[i] as u32);
+    }
+
+    fn distance(&mut self, dist: usize) {
+        let i = DIST_BASE.partition_point(|b| *b as usize <= dist) - 1;
+        self.code(i as u32, 5);
+        self.put((dist - DIST_BASE[i] as usize) as u32, DIST_EXTRA[i] as u32);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.n > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+fn hash(data: &[u8], i: usize) -> usize {
+    let v = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+    (v.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+}
+
+// Earlier positions with the same 3-byte hash, newest first; `prev` is a ring over the
+// window, so links are only followed while they stay inside it
+struct Chains {
+    head: Vec<usize>,
+    prev: Vec<usize>,
+}
+
+impl Chains {
+    // first candidate for position i; i becomes the newest entry of its chain
+    fn insert(&mut self, data: &[u8], i: usize) -> usize {
+        let h = hash(data, i);
+        let first = self.head[h];
+        self.prev[i % WINDOW] = first;
+        self.head[h] = i;
+        first
+    }
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &x in chunk {
+            a += x as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+// raw DEFLATE stream (RFC 1951) of `data`
+pub(crate) fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut bits = Bits::default();
+    bits.put(1, 1); // BFINAL
+    bits.put(1, 2); // BTYPE 01: fixed Huffman
+
+    let mut chains = Chains { head: vec![usize::MAX; 1 << HASH_BITS], prev: vec![usize::MAX; WINDOW] };
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = (0, 0); // (length, distance)
+        if i + MIN_MATCH <= data.len() {
+            let max = MAX_MATCH.min(data.len() - i);
+            let mut cand = chains.insert(data, i);
+            let mut tries = 0;
+            while cand != usize::MAX && i - cand < WINDOW && tries < MAX_CHAIN {
+                let len = data[cand..].iter().zip(&data[i..i + max]).take_while(|(a, b)| a == b).count();
+                if len > best.0 {
+                    best = (len, i - cand);
+                    if len == max {
+                        break;
+                    }
+                }
+                cand = chains.prev[cand % WINDOW];
+                tries += 1;
+            }
+        }
+        if best.0 >= MIN_MATCH {
+            bits.length(best.0);
+            bits.distance(best.1);
+            // index the skipped positions so later matches can start inside this one
+            for j in i + 1..(i + best.0).min(data.len().saturating_sub(MIN_MATCH - 1)) {
+                chains.insert(data, j);
+            }
+            i += best.0;
+        } else {
+            bits.symbol(data[i] as u16);
+            i += 1;
+        }
+    }
+    bits.symbol(256);
+    bits.finish()
+}
+
+// zlib stream (RFC 1950) of `data`
+pub(crate) fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x9C];
+    out.extend(deflate(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zune_inflate::DeflateDecoder;
+
+    #[test]
+    fn round_trips_through_inflate() {
+        let mut trace = Vec::new();
+        for i in 0..4000u32 {
+            trace.extend_from_slice(b"LOBJ");
+            trace.extend_from_slice(&(i * 977).to_le_bytes());
+            trace.extend_from_slice(&[0x12, 0x34, (i % 7) as u8, 0, 0, 0, 0, 0]);
+        }
+        let inputs: [&[u8]; 5] = [b"", b"a", b"abcabcabcabcabcabc", &[0; 70_000], &trace];
+        for data in inputs {
+            let z = zlib(data);
+            assert_eq!(DeflateDecoder::new(&z).decode_zlib().unwrap(), data);
+            assert_eq!(DeflateDecoder::new(&deflate(data)).decode_deflate().unwrap(), data);
+        }
+        assert!(zlib(&trace).len() < trace.len() / 3);
+    }
+}
@@ -0,0 +1,41 @@
+// ###############################################################
+// error.rs
+// can-blf-parser (WASM)
+// Errors thrown to JS as Error objects with a stable `code` next to the
+// message, so hosts can branch on the kind of failure without matching text.
+// Failures without a code are still thrown as plain message strings.
+// ###############################################################
+
+use wasm_bindgen::JsValue;
+
+// `code` values; the message carries the details (offending entry, parser output)
+pub(crate) const INVALID_ARGUMENT: &str = "invalid_argument"; // wrong JS type for a positional argument
+pub(crate) const INVALID_OPTIONS: &str = "invalid_options";
+pub(crate) const INVALID_CHANNEL_MAP: &str = "invalid_channel_map";
+pub(crate) const INVALID_DBC: &str = "invalid_dbc";
+pub(crate) const INVALID_LIN_DATABASE: &str = "invalid_lin_database";
+pub(crate) const INVALID_LOG: &str = "invalid_log"; // neither BLF, ASC nor TRC, or a broken header
+pub(crate) const DECODE_BUDGET: &str = "decode_budget_exceeded";
+pub(crate) const READ_ONLY: &str = "read_only_view"; // mutating call on a clone_view()/slice() handle
+pub(crate) const RELEASED: &str = "session_released"; // call after free_memory() on the session or its origin
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ApiError {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ApiError {
+    pub(crate) fn new(code: &'static str, message: impl Into<String>) -> ApiError {
+        ApiError { code, message: message.into() }
+    }
+}
+
+// `new Error(message)` with `code` set; String(e) still reads "Error: {message}"
+impl From<ApiError> for JsValue {
+    fn from(e: ApiError) -> JsValue {
+        let err = js_sys::Error::new(&e.message);
+        let _ = js_sys::Reflect::set(&err, &JsValue::from_str("code"), &JsValue::from_str(e.code));
+        err.into()
+    }
+}
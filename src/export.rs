@@ -0,0 +1,225 @@
+// ###############################################################
+// export.rs
+// can-blf-parser (WASM)
+// Manifest for chunked CSV exports, so an interrupted export can resume
+// from the last chunk the caller confirmed as written; wall-clock time of
+// session timestamps for absolute-time / per-day exports
+// ###############################################################
+
+use serde::{Deserialize, Serialize};
+
+use crate::blf::LogStart;
+
+pub(crate) const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChunkInfo {
+    pub index: usize,
+    pub first_frame: usize,
+    pub last_frame: usize, // inclusive
+    pub byte_start: u64,
+    pub byte_end: u64, // exclusive
+    #[serde(default)]
+    pub day: Option<String>, // split_by_day: calendar day of every row, "2026-10-16"
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExportManifest {
+    pub version: u32,
+    pub total_frames: usize,
+    pub chunk_frames: usize,
+    #[serde(default)]
+    pub split_by_day: bool, // chunks end at midnight; a new day's first chunk repeats the header
+    pub columns: Vec<String>, // CSV header; a resume must produce the same layout
+    pub chunks: Vec<ChunkInfo>,
+    pub last_frame_index: Option<usize>,
+    pub bytes_written: u64,
+    pub complete: bool,
+}
+
+impl ExportManifest {
+    pub(crate) fn new(total_frames: usize, chunk_frames: usize, columns: Vec<String>) -> Self {
+        ExportManifest {
+            version: MANIFEST_VERSION,
+            total_frames,
+            chunk_frames,
+            split_by_day: false,
+            columns,
+            chunks: Vec::new(),
+            last_frame_index: None,
+            bytes_written: 0,
+            complete: total_frames == 0,
+        }
+    }
+
+    // A resume manifest must describe this very export (same frames, layout, chunking)
+    pub(crate) fn check_resume(&self, fresh: &ExportManifest) -> Result<(), String> {
+        if self.version != MANIFEST_VERSION {
+            return Err(format!("manifest version {} not supported", self.version));
+        }
+        if self.total_frames != fresh.total_frames {
+            return Err(format!("manifest covers {} frames, session has {}", self.total_frames, fresh.total_frames));
+        }
+        if self.chunk_frames != fresh.chunk_frames {
+            return Err(format!("manifest chunk_frames {} != {}", self.chunk_frames, fresh.chunk_frames));
+        }
+        if self.split_by_day != fresh.split_by_day {
+            return Err(format!("manifest split_by_day {} != {}", self.split_by_day, fresh.split_by_day));
+        }
+        if self.columns != fresh.columns {
+            return Err("manifest columns differ from this export".to_string());
+        }
+        // chunks must be back-to-back in frames and bytes
+        let mut next_frame = 0;
+        let mut next_byte = 0;
+        for (i, c) in self.chunks.iter().enumerate() {
+            if c.index != i || c.first_frame != next_frame || c.byte_start != next_byte || c.last_frame < c.first_frame {
+                return Err(format!("manifest chunk {} is not contiguous", i));
+            }
+            next_frame = c.last_frame + 1;
+            next_byte = c.byte_end;
+        }
+        if next_frame > self.total_frames || self.last_frame_index != next_frame.checked_sub(1) || self.bytes_written != next_byte {
+            return Err("manifest totals do not match its chunks".to_string());
+        }
+        Ok(())
+    }
+
+    pub(crate) fn next_frame(&self) -> usize {
+        self.last_frame_index.map_or(0, |i| i + 1)
+    }
+
+    pub(crate) fn record(&mut self, first_frame: usize, last_frame: usize, bytes: u64, day: Option<String>) {
+        self.chunks.push(ChunkInfo {
+            index: self.chunks.len(),
+            first_frame,
+            last_frame,
+            byte_start: self.bytes_written,
+            byte_end: self.bytes_written + bytes,
+            day,
+        });
+        self.last_frame_index = Some(last_frame);
+        self.bytes_written += bytes;
+        self.complete = last_frame + 1 >= self.total_frames;
+    }
+}
+
+const US_PER_DAY: i64 = 86_400_000_000;
+
+// Session timestamps as wall-clock time: the BLF measurement start (logger local
+// time) plus the offset in seconds, rolling over days, months and years
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct WallClock {
+    start_us: i64, // microseconds since 1970-01-01 00:00 in the logger's time
+}
+
+impl WallClock {
+    pub(crate) fn new(start: &LogStart) -> WallClock {
+        let days = days_from_civil(start.year as i64, start.month as i64, start.day as i64);
+        let secs = days * 86_400 + start.hour as i64 * 3_600 + start.minute as i64 * 60 + start.second as i64;
+        WallClock { start_us: secs * 1_000_000 + start.millisecond as i64 * 1_000 }
+    }
+
+    fn micros(&self, t: f64) -> i64 {
+        self.start_us + (t * 1e6).round() as i64
+    }
+
+    // nanoseconds since 1970-01-01 00:00 in the logger's time (export_influx())
+    pub(crate) fn nanos(&self, t: f64) -> i64 {
+        self.start_us * 1_000 + (t * 1e9).round() as i64
+    }
+
+    // days since 1970-01-01, for comparing rows
+    pub(crate) fn day_number(&self, t: f64) -> i64 {
+        self.micros(t).div_euclid(US_PER_DAY)
+    }
+
+    // session time (s) of the midnight ending the day of `t`
+    pub(crate) fn next_midnight(&self, t: f64) -> f64 {
+        ((self.day_number(t) + 1) * US_PER_DAY - self.start_us) as f64 / 1e6
+    }
+
+    // as a BLF header SYSTEMTIME (export_blf())
+    pub(crate) fn system_time(&self, t: f64) -> LogStart {
+        let days = self.day_number(t);
+        let (year, month, day) = civil_from_days(days);
+        let ms = self.micros(t).rem_euclid(US_PER_DAY) / 1_000;
+        LogStart {
+            year: year as u16,
+            month: month as u16,
+            day_of_week: (days + 4).rem_euclid(7) as u16, // 1970-01-01 was a Thursday
+            day: day as u16,
+            hour: (ms / 3_600_000) as u16,
+            minute: (ms / 60_000 % 60) as u16,
+            second: (ms / 1_000 % 60) as u16,
+            millisecond: (ms % 1_000) as u16,
+        }
+    }
+
+    // "2026-10-16"
+    pub(crate) fn day(&self, t: f64) -> String {
+        let (y, m, d) = civil_from_days(self.day_number(t));
+        format!("{:04}-{:02}-{:02}", y, m, d)
+    }
+
+    // "2026-10-16 23:59:59.999500"
+    pub(crate) fn datetime(&self, t: f64) -> String {
+        let us = self.micros(t).rem_euclid(US_PER_DAY);
+        let secs = us / 1_000_000;
+        format!(
+            "{} {:02}:{:02}:{:02}.{:06}",
+            self.day(t),
+            secs / 3_600,
+            secs / 60 % 60,
+            secs % 60,
+            us % 1_000_000
+        )
+    }
+}
+
+// proleptic Gregorian date <-> days since 1970-01-01 (H. Hinnant's algorithms)
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(z: i64) -> (i64, i64, i64) {
+    let z = z + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + if m <= 2 { 1 } else { 0 }, m, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wall_clock_rollover() {
+        // New Year's Eve 2024, 23:59:58.500: rolls into 2025, and across a leap day
+        let start = LogStart { year: 2024, month: 12, day_of_week: 2, day: 31, hour: 23, minute: 59, second: 58, millisecond: 500 };
+        let clock = WallClock::new(&start);
+        assert_eq!(clock.datetime(0.0), "2024-12-31 23:59:58.500000");
+        assert_eq!(clock.datetime(1.5), "2025-01-01 00:00:00.000000");
+        assert_eq!(clock.day(1.499999), "2024-12-31");
+        assert_eq!(clock.next_midnight(0.25), 1.5);
+        assert_eq!(clock.day_number(1.5) - clock.day_number(0.0), 1);
+        let next = clock.system_time(1.5);
+        assert_eq!((next.year, next.month, next.day, next.day_of_week, next.hour), (2025, 1, 1, 3, 0));
+
+        let leap = LogStart { year: 2024, month: 2, day_of_week: 3, day: 28, hour: 12, minute: 0, second: 0, millisecond: 0 };
+        let clock = WallClock::new(&leap);
+        assert_eq!(clock.day(12.0 * 3_600.0), "2024-02-29");
+        assert_eq!(clock.day(36.0 * 3_600.0), "2024-03-01");
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+    }
+}
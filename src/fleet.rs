@@ -0,0 +1,134 @@
+// ###############################################################
+// fleet.rs
+// can-blf-parser (WASM)
+// Fleet statistics for multi-file dashboards: per-file reports (one object per
+// analyzed log, assembled by the host) merged into totals, error rates and DTC
+// frequencies. No session needed; see aggregate_reports().
+// ###############################################################
+
+use std::collections::{BTreeMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::numeric;
+
+// One log's report; every field may be missing and only counts toward the totals it
+// feeds (e.g. error_rate only over files that give frames)
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct FileReport {
+    pub file: Option<String>, // name shown in DTC file lists
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub duration_s: Option<f64>,
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub distance_km: Option<f64>,
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub frames: Option<f64>,
+    #[serde(deserialize_with = "numeric::f64")]
+    pub error_frames: f64,
+    pub dtcs: Vec<String>, // one entry per occurrence, e.g. "P0420"
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DtcCount {
+    pub code: String,
+    pub occurrences: usize,
+    pub files: usize, // files reporting it at least once
+    pub file_share: f64, // files / all files
+    pub file_names: Vec<String>, // of those files that have a name
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct FleetReport {
+    pub files: usize,
+    pub total_duration_s: f64,
+    pub total_distance_km: f64,
+    pub total_frames: f64,
+    pub error_frames: f64,
+    pub error_rate: Option<f64>, // error frames / frames, files with frame counts
+    pub errors_per_hour: Option<f64>, // files with durations
+    pub errors_per_100km: Option<f64>, // files with distances
+    pub files_with_dtcs: usize,
+    pub dtcs: Vec<DtcCount>, // most widespread first
+}
+
+pub(crate) fn aggregate(reports: &[FileReport]) -> FleetReport {
+    let sum = |f: fn(&FileReport) -> Option<f64>| reports.iter().filter_map(f).sum::<f64>();
+    // error frames of the files that also give the denominator
+    let errors_where = |f: fn(&FileReport) -> Option<f64>| {
+        let (errors, base) = reports
+            .iter()
+            .filter_map(|r| f(r).map(|b| (r.error_frames, b)))
+            .fold((0.0, 0.0), |(e, b), (re, rb)| (e + re, b + rb));
+        (base > 0.0).then_some((errors, base))
+    };
+
+    let mut by_code: BTreeMap<String, DtcCount> = BTreeMap::new();
+    for r in reports {
+        let mut seen = HashSet::new();
+        for dtc in &r.dtcs {
+            // "p0420 " and "P0420" are one code
+            let code = dtc.trim().to_uppercase();
+            if code.is_empty() {
+                continue;
+            }
+            let entry = by_code.entry(code.clone()).or_insert_with(|| DtcCount {
+                code: code.clone(),
+                occurrences: 0,
+                files: 0,
+                file_share: 0.0,
+                file_names: Vec::new(),
+            });
+            entry.occurrences += 1;
+            if seen.insert(code) {
+                entry.files += 1;
+                entry.file_names.extend(r.file.clone());
+            }
+        }
+    }
+    let mut dtcs: Vec<DtcCount> = by_code.into_values().collect();
+    for d in dtcs.iter_mut() {
+        d.file_share = d.files as f64 / reports.len() as f64;
+    }
+    dtcs.sort_by(|a, b| b.files.cmp(&a.files).then(b.occurrences.cmp(&a.occurrences)));
+
+    FleetReport {
+        files: reports.len(),
+        total_duration_s: sum(|r| r.duration_s),
+        total_distance_km: sum(|r| r.distance_km),
+        total_frames: sum(|r| r.frames),
+        error_frames: reports.iter().map(|r| r.error_frames).sum(),
+        error_rate: errors_where(|r| r.frames).map(|(e, n)| e / n),
+        errors_per_hour: errors_where(|r| r.duration_s).map(|(e, s)| e / (s / 3600.0)),
+        errors_per_100km: errors_where(|r| r.distance_km).map(|(e, km)| e / km * 100.0),
+        files_with_dtcs: reports.iter().filter(|r| r.dtcs.iter().any(|d| !d.trim().is_empty())).count(),
+        dtcs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn totals_rates_and_dtcs() {
+        let reports: Vec<FileReport> = serde_json::from_value(serde_json::json!([
+            { "file": "a.blf", "duration_s": 1800, "distance_km": 40, "frames": 1000, "error_frames": 2,
+              "dtcs": ["P0420", "p0420 ", "U0100"] },
+            { "file": "b.blf", "duration_s": "1800", "frames": 3000, "error_frames": 6, "dtcs": ["P0420"] },
+            { "error_frames": 100 }
+        ]))
+        .unwrap();
+        let r = aggregate(&reports);
+        assert_eq!((r.files, r.total_duration_s, r.total_distance_km, r.total_frames), (3, 3600.0, 40.0, 4000.0));
+        // the third file has no denominators and stays out of the rates
+        assert_eq!(r.error_frames, 108.0);
+        assert_eq!(r.error_rate, Some(8.0 / 4000.0));
+        assert_eq!(r.errors_per_hour, Some(8.0));
+        assert_eq!(r.errors_per_100km, Some(5.0));
+        assert_eq!(r.files_with_dtcs, 2);
+        let codes: Vec<(&str, usize, usize)> = r.dtcs.iter().map(|d| (d.code.as_str(), d.occurrences, d.files)).collect();
+        assert_eq!(codes, [("P0420", 3, 2), ("U0100", 1, 1)]);
+        assert_eq!(r.dtcs[0].file_names, ["a.blf", "b.blf"]);
+    }
+}
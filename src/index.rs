@@ -0,0 +1,74 @@
+// ###############################################################
+// index.rs
+// can-blf-parser (WASM)
+// Session index: signal list + DBC message lookup carried over to the
+// next log from the same vehicle (warm start)
+// ###############################################################
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+pub(crate) const INDEX_VERSION: u32 = 2;
+
+// Serialized by session_index(), handed back via options.warm_start
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SessionIndex {
+    pub version: u32,
+    pub dbc_hash: String, // hex FNV-1a of DBC texts + channel map; u64 does not fit a JS number
+    pub signals: Vec<String>,
+    pub messages: Vec<(u8, u32, usize, usize)>, // (channel, raw id, dbc index, position in its messages)
+}
+
+impl SessionIndex {
+    // usable only for the exact same DBC set
+    pub(crate) fn matches(&self, dbc_hash: u64) -> bool {
+        self.version == INDEX_VERSION && self.dbc_hash == format!("{:016x}", dbc_hash)
+    }
+
+    // candidate order is re-ranked by the caller (priority is not part of the hash)
+    pub(crate) fn message_index(&self) -> HashMap<(u8, u32), Vec<(usize, usize)>> {
+        let mut idx: HashMap<(u8, u32), Vec<(usize, usize)>> = HashMap::new();
+        for &(chan, id, dbc, pos) in &self.messages {
+            idx.entry((chan, id)).or_default().push((dbc, pos));
+        }
+        idx
+    }
+}
+
+// FNV-1a 64; stable across builds (unlike DefaultHasher)
+pub(crate) struct Fnv(u64);
+
+impl Fnv {
+    pub(crate) fn new() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(crate) fn feed(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+pub(crate) fn dbc_hash(texts: &[String], channels: &[u8]) -> u64 {
+    let mut h = Fnv::new();
+    for (text, chan) in texts.iter().zip(channels) {
+        h.feed(&[*chan]);
+        h.feed(&(text.len() as u64).to_le_bytes());
+        h.feed(text.as_bytes());
+    }
+    h.finish()
+}
+
+// hex FNV-1a of one input (BLF file, DBC text) for provenance records
+pub(crate) fn content_hash(bytes: &[u8]) -> String {
+    let mut h = Fnv::new();
+    h.feed(bytes);
+    format!("{:016x}", h.finish())
+}
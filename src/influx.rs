@@ -0,0 +1,94 @@
+// ###############################################################
+// influx.rs
+// can-blf-parser (WASM)
+// InfluxDB line protocol for export_influx(): one line per frame,
+// measurement = message, tag = channel, one field per decoded signal.
+// ###############################################################
+
+use std::fmt::Write;
+
+pub(crate) enum Field<'a> {
+    Float(f64),
+    Text(&'a str),
+}
+
+// measurement names: commas and spaces escaped
+fn measurement(out: &mut String, s: &str) {
+    for c in s.chars() {
+        if matches!(c, ',' | ' ' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+}
+
+// tag keys / values and field keys: equals signs too
+fn key(out: &mut String, s: &str) {
+    for c in s.chars() {
+        if matches!(c, ',' | '=' | ' ' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+}
+
+// `measurement,tag=v field=1.5,label="Park" 1697450000000000000`, with newline;
+// None when no field is representable (line protocol has no NaN / infinity)
+pub(crate) fn line(name: &str, tags: &[(&str, &str)], fields: &[(&str, Field)], ns: i64) -> Option<String> {
+    let mut out = String::new();
+    measurement(&mut out, name);
+    for (k, v) in tags.iter().filter(|(_, v)| !v.is_empty()) {
+        out.push(',');
+        key(&mut out, k);
+        out.push('=');
+        key(&mut out, v);
+    }
+    let mut sep = ' ';
+    for (k, v) in fields {
+        if matches!(v, Field::Float(x) if !x.is_finite()) {
+            continue;
+        }
+        out.push(sep);
+        sep = ',';
+        key(&mut out, k);
+        out.push('=');
+        match v {
+            Field::Float(x) => {
+                let _ = write!(out, "{}", x);
+            }
+            Field::Text(s) => {
+                out.push('"');
+                for c in s.chars() {
+                    if matches!(c, '"' | '\\') {
+                        out.push('\\');
+                    }
+                    out.push(c);
+                }
+                out.push('"');
+            }
+        }
+    }
+    if sep == ' ' {
+        return None;
+    }
+    let _ = writeln!(out, " {}", ns);
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaping_and_skipped_fields() {
+        let fields = [
+            ("Vehicle Speed", Field::Float(88.5)),
+            ("Bad", Field::Float(f64::NAN)),
+            ("Gear", Field::Float(1.0)),
+            ("Gear_text", Field::Text("\"P\" ark")),
+        ];
+        let l = line("Engine,Data", &[("channel", "CAN1"), ("bus", "")], &fields, 1_697_450_000_000_000_123).unwrap();
+        assert_eq!(l, "Engine\\,Data,channel=CAN1 Vehicle\\ Speed=88.5,Gear=1,Gear_text=\"\\\"P\\\" ark\" 1697450000000000123\n");
+        assert!(line("M", &[], &[("x", Field::Float(f64::INFINITY))], 0).is_none());
+    }
+}
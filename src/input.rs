@@ -0,0 +1,50 @@
+// ###############################################################
+// input.rs
+// can-blf-parser (WASM)
+// Log input formats behind one object iterator: BLF (blf.rs), a Vector
+// ASC trace (asc.rs) or a PEAK PCAN trace (trc.rs), told apart by their
+// first bytes.
+// ###############################################################
+
+use crate::{asc, trc};
+use crate::blf::{self, BlfObject, BlfReader, LogStart};
+
+pub(crate) enum LogReader<'a> {
+    Blf(BlfReader<'a>),
+    Text(std::vec::IntoIter<BlfObject>), // text traces are read in one go
+}
+
+impl<'a> LogReader<'a> {
+    // Err as BlfReader::new() for input that is neither format
+    pub(crate) fn new(bytes: &'a [u8]) -> Result<LogReader<'a>, String> {
+        if asc::detect(bytes) {
+            return Ok(LogReader::Text(asc::read(bytes).into_iter()));
+        }
+        if trc::detect(bytes) {
+            return Ok(LogReader::Text(trc::read(bytes)?.into_iter()));
+        }
+        BlfReader::new(bytes).map(LogReader::Blf)
+    }
+}
+
+impl Iterator for LogReader<'_> {
+    type Item = BlfObject;
+
+    fn next(&mut self) -> Option<BlfObject> {
+        match self {
+            LogReader::Blf(r) => r.next(),
+            LogReader::Text(r) => r.next(),
+        }
+    }
+}
+
+// Measurement start: the BLF header's, or the one a trace's header gives
+pub(crate) fn log_start(bytes: &[u8]) -> Option<LogStart> {
+    if asc::detect(bytes) {
+        asc::start(bytes)
+    } else if trc::detect(bytes) {
+        trc::start(bytes)
+    } else {
+        blf::log_start(bytes)
+    }
+}
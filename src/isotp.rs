@@ -0,0 +1,233 @@
+// ###############################################################
+// isotp.rs
+// can-blf-parser (WASM)
+// ISO 15765-2 (ISO-TP) reassembly for transport_messages(): single, first and
+// consecutive frames on configured request/response id pairs become complete
+// payloads; flow control frames pace the transfer and are only counted
+// ###############################################################
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{id_matches, Frame};
+
+// One diagnostic connection, e.g. tester 0x7E0 <-> ECU 0x7E8
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct IsoTpPair {
+    pub channel: Option<u16>, // None: any CAN channel
+    pub request_id: u32, // bit 31 set: that extended id only (as other id arguments)
+    pub response_id: u32,
+    pub extended_addressing: bool, // first payload byte is the target address
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Request,
+    Response,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TransportMessage {
+    pub pair: usize, // index into options.pairs
+    pub direction: Direction,
+    pub channel: String,
+    pub id: u32, // CAN id of the sender, IDE bit masked off
+    pub is_extended: bool,
+    pub dir: String, // "Rx" / "Tx" of the first frame, as logged
+    pub timestamp: f64, // first frame (single or first frame)
+    pub end_timestamp: f64, // last consecutive frame
+    pub address: Option<u8>, // extended addressing byte
+    pub length: usize, // announced length
+    pub data: Vec<u8>, // reassembled payload (shorter than length when incomplete)
+    pub frames: usize,
+    pub complete: bool,
+    pub error: Option<String>, // why an incomplete message stopped
+}
+
+// Reassembly in progress for one (pair, direction, channel)
+struct Partial {
+    msg: TransportMessage,
+    next_seq: u8,
+}
+
+#[derive(Default)]
+pub(crate) struct Reassembler {
+    partial: HashMap<(usize, Direction, u16), Partial>,
+    out: Vec<TransportMessage>,
+    pub flow_control_frames: usize,
+}
+
+impl Reassembler {
+    pub(crate) fn push(&mut self, pairs: &[IsoTpPair], f: &Frame) {
+        if !f.is_can_message() || f.flags.rtr {
+            return;
+        }
+        let raw = f.raw_id();
+        for (i, p) in pairs.iter().enumerate() {
+            if p.channel.is_some_and(|c| c != f.channel_num) {
+                continue;
+            }
+            if id_matches(p.request_id, raw) {
+                self.frame(i, Direction::Request, p.extended_addressing, f);
+            } else if id_matches(p.response_id, raw) {
+                self.frame(i, Direction::Response, p.extended_addressing, f);
+            }
+        }
+    }
+
+    fn frame(&mut self, pair: usize, direction: Direction, ext: bool, f: &Frame) {
+        let (address, pci) = if ext { (f.data.first().copied(), f.data.get(1..)) } else { (None, Some(f.data)) };
+        let Some(pci) = pci.filter(|d| !d.is_empty()) else { return };
+        let key = (pair, direction, f.channel_num);
+
+        let start = |length: usize| TransportMessage {
+            pair,
+            direction,
+            channel: f.channel.to_string(),
+            id: f.id,
+            is_extended: f.is_extended,
+            dir: f.dir.to_string(),
+            timestamp: f.timestamp,
+            end_timestamp: f.timestamp,
+            address,
+            length,
+            data: Vec::new(),
+            frames: 1,
+            complete: false,
+            error: None,
+        };
+
+        match pci[0] >> 4 {
+            // single frame; CAN FD escape: length 0 with the length in the next byte
+            0 => {
+                let (length, body) = match pci[0] & 0x0F {
+                    0 if pci.len() > 8 => (pci[1] as usize, &pci[2..]),
+                    n => (n as usize, &pci[1..]),
+                };
+                self.interrupt(key);
+                let mut msg = start(length);
+                msg.data = body[..length.min(body.len())].to_vec();
+                msg.complete = msg.data.len() == length && length > 0;
+                if !msg.complete {
+                    msg.error = Some("single frame shorter than its length".to_string());
+                }
+                self.out.push(msg);
+            }
+            // first frame; 12-bit length, or 0 followed by a 32-bit length
+            1 => {
+                if pci.len() < 2 {
+                    return;
+                }
+                let short = ((pci[0] & 0x0F) as usize) << 8 | pci[1] as usize;
+                let (length, body) = match (short, pci.get(2..6)) {
+                    (0, Some(l)) => (u32::from_be_bytes([l[0], l[1], l[2], l[3]]) as usize, &pci[6..]),
+                    _ => (short, &pci[2..]),
+                };
+                self.interrupt(key);
+                let mut msg = start(length);
+                msg.data = body[..length.min(body.len())].to_vec();
+                self.partial.insert(key, Partial { msg, next_seq: 1 });
+            }
+            2 => {
+                let Some(p) = self.partial.get_mut(&key) else { return };
+                let seq = pci[0] & 0x0F;
+                if seq != p.next_seq {
+                    let mut p = self.partial.remove(&key).expect("present");
+                    p.msg.error = Some(format!("consecutive frame {} where {} was expected", seq, p.next_seq));
+                    self.out.push(p.msg);
+                    return;
+                }
+                let missing = p.msg.length - p.msg.data.len();
+                p.msg.data.extend_from_slice(&pci[1..][..missing.min(pci.len() - 1)]);
+                p.msg.frames += 1;
+                p.msg.end_timestamp = f.timestamp;
+                p.next_seq = (p.next_seq + 1) & 0x0F;
+                if p.msg.data.len() == p.msg.length {
+                    let mut p = self.partial.remove(&key).expect("present");
+                    p.msg.complete = true;
+                    self.out.push(p.msg);
+                }
+            }
+            3 => self.flow_control_frames += 1,
+            _ => {}
+        }
+    }
+
+    // a new single / first frame ends an unfinished transfer in the same direction
+    fn interrupt(&mut self, key: (usize, Direction, u16)) {
+        if let Some(mut p) = self.partial.remove(&key) {
+            p.msg.error = Some("interrupted by a new transfer".to_string());
+            self.out.push(p.msg);
+        }
+    }
+
+    // Messages by first-frame time; transfers still open at the end of the log are
+    // reported incomplete
+    pub(crate) fn finish(mut self) -> Vec<TransportMessage> {
+        for (_, mut p) in self.partial.drain() {
+            p.msg.error = Some("log ends before the last consecutive frame".to_string());
+            self.out.push(p.msg);
+        }
+        self.out.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::Payload;
+    use crate::FrameRow;
+
+    fn frame(t: f64, id: u32, data: &[u8]) -> FrameRow {
+        FrameRow {
+            timestamp: t,
+            channel: "CAN1".to_string(),
+            channel_num: 1,
+            id,
+            data: Payload::from(data),
+            ..FrameRow::default()
+        }
+    }
+
+    fn run(frames: &[FrameRow]) -> Vec<TransportMessage> {
+        let pairs = [IsoTpPair { request_id: 0x7E0, response_id: 0x7E8, ..IsoTpPair::default() }];
+        let mut r = Reassembler::default();
+        for f in frames {
+            r.push(&pairs, &f.view());
+        }
+        r.finish()
+    }
+
+    #[test]
+    fn single_and_multi_frame() {
+        let out = run(&[
+            frame(0.0, 0x7E0, &[0x02, 0x10, 0x03, 0, 0, 0, 0, 0]),
+            frame(0.1, 0x7E0, &[0x03, 0x22, 0xF1, 0x90, 0, 0, 0, 0]),
+            frame(0.2, 0x7E8, &[0x10, 0x0A, 0x62, 0xF1, 0x90, 1, 2, 3]),
+            frame(0.3, 0x7E0, &[0x30, 0x00, 0x00, 0, 0, 0, 0, 0]),
+            frame(0.4, 0x7E8, &[0x21, 4, 5, 6, 7, 0xAA, 0xAA, 0xAA]),
+        ]);
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[0].data, [0x10, 0x03]);
+        assert_eq!(out[2].direction, Direction::Response);
+        assert_eq!(out[2].data, [0x62, 0xF1, 0x90, 1, 2, 3, 4, 5, 6, 7]);
+        assert!(out[2].complete);
+        assert_eq!((out[2].frames, out[2].end_timestamp), (2, 0.4));
+    }
+
+    #[test]
+    fn sequence_error_and_truncation() {
+        let out = run(&[
+            frame(0.0, 0x7E8, &[0x10, 0x14, 1, 2, 3, 4, 5, 6]),
+            frame(0.1, 0x7E8, &[0x22, 7, 8, 9, 10, 11, 12, 13]),
+            frame(0.2, 0x7E8, &[0x10, 0x08, 1, 2, 3, 4, 5, 6]),
+        ]);
+        assert_eq!(out.len(), 2);
+        assert!(!out[0].complete && out[0].error.as_deref().unwrap().contains("expected"));
+        assert!(!out[1].complete && out[1].data.len() == 6);
+    }
+}
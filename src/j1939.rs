@@ -0,0 +1,240 @@
+// ###############################################################
+// j1939.rs
+// can-blf-parser (WASM)
+// J1939 mode (options.j1939): PGN / source / destination / priority of 29-bit
+// ids, and transport protocol reassembly (TP.CM + TP.DT, BAM and RTS/CTS) so
+// multi-packet messages decode like single frames
+// ###############################################################
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::blf::{BlfObject, CanFrame};
+use crate::{CAN_EFF_FLAG, CAN_EFF_MASK};
+
+const PGN_TP_CM: u32 = 0xEC00;
+const PGN_TP_DT: u32 = 0xEB00;
+
+const CM_RTS: u8 = 16;
+const CM_BAM: u8 = 32;
+const CM_ABORT: u8 = 255;
+
+pub(crate) const GLOBAL: u8 = 0xFF;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct J1939Info {
+    pub pgn: u32,
+    pub priority: u8,
+    pub source: u8,
+    pub destination: u8, // PS of PDU1 messages; 255 (global) for PDU2
+    pub reassembled: bool, // payload rebuilt from a TP.CM / TP.DT sequence
+}
+
+impl J1939Info {
+    // 29-bit identifier (IDE bit ignored)
+    pub(crate) fn from_id(id: u32) -> J1939Info {
+        let id = id & CAN_EFF_MASK;
+        let pgn = pgn(id);
+        let pdu1 = (pgn >> 8) & 0xFF < 240;
+        J1939Info {
+            pgn,
+            priority: ((id >> 26) & 0x7) as u8,
+            source: (id & 0xFF) as u8,
+            destination: if pdu1 { ((id >> 8) & 0xFF) as u8 } else { GLOBAL },
+            reassembled: false,
+        }
+    }
+}
+
+// PGN of a 29-bit identifier: EDP, DP, PF and PS, with PS zeroed for PDU1 (PF < 240),
+// where it is the destination address
+pub(crate) fn pgn(id: u32) -> u32 {
+    let pgn = (id & CAN_EFF_MASK) >> 8 & 0x3_FFFF;
+    if (pgn >> 8) & 0xFF < 240 { pgn & 0x3_FF00 } else { pgn }
+}
+
+// One transfer in progress, keyed by (channel, source, destination)
+struct Transfer {
+    pgn: u32,
+    priority: u8,
+    size: usize,
+    data: Vec<u8>, // packets x 7 bytes, trimmed to size when complete
+    received: Vec<bool>,
+}
+
+#[derive(Default)]
+pub(crate) struct Transport {
+    transfers: HashMap<(u16, u8, u8), Transfer>,
+}
+
+impl Transport {
+    // Feeds one frame; returns the reassembled message when its last TP.DT arrives
+    pub(crate) fn push(&mut self, cf: &CanFrame) -> Option<CanFrame> {
+        if cf.id & CAN_EFF_FLAG == 0 || cf.rtr || cf.data.len() < 8 {
+            return None;
+        }
+        let info = J1939Info::from_id(cf.id);
+        let key = (cf.channel, info.source, info.destination);
+        match info.pgn {
+            PGN_TP_CM => {
+                match cf.data[0] {
+                    CM_RTS | CM_BAM => {
+                        let size = u16::from_le_bytes([cf.data[1], cf.data[2]]) as usize;
+                        let packets = cf.data[3] as usize;
+                        if packets == 0 || size > packets * 7 {
+                            self.transfers.remove(&key);
+                            return None;
+                        }
+                        // a new announcement replaces an unfinished transfer
+                        self.transfers.insert(
+                            key,
+                            Transfer {
+                                pgn: u32::from_le_bytes([cf.data[5], cf.data[6], cf.data[7], 0]),
+                                priority: info.priority,
+                                size,
+                                data: vec![0; packets * 7],
+                                received: vec![false; packets],
+                            },
+                        );
+                    }
+                    // either side may abort
+                    CM_ABORT => {
+                        self.transfers.remove(&key);
+                        self.transfers.remove(&(cf.channel, info.destination, info.source));
+                    }
+                    _ => {} // CTS / EndOfMsgAck: flow control only
+                }
+                None
+            }
+            PGN_TP_DT => {
+                let t = self.transfers.get_mut(&key)?;
+                let seq = cf.data[0] as usize;
+                if seq == 0 || seq > t.received.len() {
+                    return None;
+                }
+                // CTS may ask for packets again; a resend just overwrites them
+                t.data[(seq - 1) * 7..seq * 7].copy_from_slice(&cf.data[1..8]);
+                t.received[seq - 1] = true;
+                if !t.received.iter().all(|r| *r) {
+                    return None;
+                }
+                let mut t = self.transfers.remove(&key)?;
+                t.data.truncate(t.size);
+                let ps = if (t.pgn >> 8) & 0xFF < 240 { info.destination as u32 } else { 0 };
+                let id = (t.priority as u32) << 26 | (t.pgn | ps) << 8 | info.source as u32;
+                Some(CanFrame {
+                    timestamp_ns: cf.timestamp_ns,
+                    channel: cf.channel,
+                    id: id | CAN_EFF_FLAG,
+                    dlc: t.size.min(u8::MAX as usize) as u8,
+                    data: t.data,
+                    tx: cf.tx,
+                    tx_request: false,
+                    rtr: false,
+                    wakeup: false,
+                    nerr: false,
+                    fd: false,
+                    brs: false,
+                    esi: false,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+// Classic (non-FD) frame with more than 8 bytes: only reassembly produces those
+pub(crate) fn is_reassembled(cf: &CanFrame) -> bool {
+    !cf.fd && cf.data.len() > 8
+}
+
+// BLF objects with each completed TP message inserted right after its last TP.DT
+// frame (the TP frames themselves stay in the log). Pass-through when disabled.
+pub(crate) struct J1939Objects<I> {
+    inner: I,
+    transport: Option<Transport>,
+    pending: Option<BlfObject>,
+}
+
+impl<I: Iterator<Item = BlfObject>> J1939Objects<I> {
+    pub(crate) fn new(inner: I, enabled: bool) -> Self {
+        J1939Objects { inner, transport: enabled.then(Transport::default), pending: None }
+    }
+}
+
+impl<I: Iterator<Item = BlfObject>> Iterator for J1939Objects<I> {
+    type Item = BlfObject;
+
+    fn next(&mut self) -> Option<BlfObject> {
+        if let Some(obj) = self.pending.take() {
+            return Some(obj);
+        }
+        let obj = self.inner.next()?;
+        if let (Some(tp), BlfObject::Can(cf)) = (&mut self.transport, &obj) {
+            self.pending = tp.push(cf).map(BlfObject::Can);
+        }
+        Some(obj)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(id: u32, data: [u8; 8]) -> BlfObject {
+        BlfObject::Can(CanFrame {
+            timestamp_ns: 0,
+            channel: 1,
+            id: id | CAN_EFF_FLAG,
+            dlc: 8,
+            data: data.to_vec(),
+            tx: false,
+            tx_request: false,
+            rtr: false,
+            wakeup: false,
+            nerr: false,
+            fd: false,
+            brs: false,
+            esi: false,
+        })
+    }
+
+    #[test]
+    fn id_fields() {
+        // PDU2: EEC1 (61444) from 0x00, priority 3
+        let i = J1939Info::from_id(0x0CF0_0400);
+        assert_eq!((i.pgn, i.priority, i.source, i.destination), (61444, 3, 0x00, GLOBAL));
+        // PDU1: request (59904) from 0xF9 to 0x00
+        let i = J1939Info::from_id(0x18EA_00F9);
+        assert_eq!((i.pgn, i.priority, i.source, i.destination), (59904, 6, 0xF9, 0x00));
+    }
+
+    #[test]
+    fn bam_reassembly() {
+        // BAM of 10 bytes of PGN 65226 (DM1, 0xFECA) from 0x00: 2 packets
+        let objs = vec![
+            frame(0x1CEC_FF00, [CM_BAM, 10, 0, 2, 0xFF, 0xCA, 0xFE, 0x00]),
+            frame(0x1CEB_FF00, [1, 1, 2, 3, 4, 5, 6, 7]),
+            frame(0x1CEB_FF00, [2, 8, 9, 10, 0xFF, 0xFF, 0xFF, 0xFF]),
+        ];
+        let out: Vec<BlfObject> = J1939Objects::new(objs.into_iter(), true).collect();
+        assert_eq!(out.len(), 4);
+        let BlfObject::Can(cf) = &out[3] else { panic!("reassembled frame expected") };
+        assert_eq!(cf.data, [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        assert!(is_reassembled(cf));
+        let i = J1939Info::from_id(cf.id);
+        assert_eq!((i.pgn, i.priority, i.source), (0xFECA, 7, 0x00));
+    }
+
+    #[test]
+    fn incomplete_or_aborted() {
+        let objs = vec![
+            frame(0x1CEC_0300, [CM_RTS, 9, 0, 2, 2, 0x00, 0xEF, 0x00]),
+            frame(0x1CEB_0300, [1, 1, 2, 3, 4, 5, 6, 7]),
+            frame(0x1CEC_0003, [CM_ABORT, 1, 0xFF, 0xFF, 0xFF, 0x00, 0xEF, 0x00]),
+            frame(0x1CEB_0300, [2, 8, 9, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]),
+        ];
+        assert_eq!(J1939Objects::new(objs.into_iter(), true).count(), 4);
+    }
+}
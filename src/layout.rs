@@ -0,0 +1,93 @@
+// ###############################################################
+// layout.rs
+// can-blf-parser (WASM)
+// Bit placement of DBC signals inside a frame (bit-matrix diagram data)
+// ###############################################################
+
+use can_dbc::{ByteOrder, Message, MultiplexIndicator};
+use serde::Serialize;
+
+// Frame bit positions of a signal, MSB first. Positions are byte * 8 + bit (bit 0 = LSB).
+// Intel: start_bit is the LSB, bits run upwards. Motorola: start_bit is the MSB and the
+// signal walks the DBC "sawtooth" (down within a byte, then to bit 7 of the next byte).
+pub(crate) fn signal_bits(start_bit: u64, size: u64, byte_order: ByteOrder) -> Vec<u64> {
+    match byte_order {
+        ByteOrder::LittleEndian => (start_bit..start_bit + size).rev().collect(),
+        ByteOrder::BigEndian => motorola_bits(start_bit, size).collect(),
+    }
+}
+
+// Motorola positions MSB first, without allocating (used by the decoder)
+pub(crate) fn motorola_bits(start_bit: u64, size: u64) -> impl Iterator<Item = u64> {
+    std::iter::successors(Some(start_bit), |&pos| Some(if pos.is_multiple_of(8) { pos + 15 } else { pos - 1 }))
+        .take(size as usize)
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SignalLayout {
+    pub name: String,
+    pub start_bit: u64,
+    pub size: u64,
+    pub byte_order: &'static str, // "intel" | "motorola"
+    pub mux: Option<String>, // "M" for the multiplexor, "m{n}" for multiplexed signals
+    pub msb: u64,
+    pub lsb: u64,
+    pub bits: Vec<u64>, // MSB first, byte * 8 + bit
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MessageLayout {
+    pub id: u32,
+    pub is_extended: bool,
+    pub name: String,
+    pub size: u64,
+    pub signals: Vec<SignalLayout>,
+    // grid[byte][bit] -> indices into signals (bit 0 = LSB); more than one entry is an
+    // overlap, which is legitimate only between different mux values
+    pub grid: Vec<Vec<Vec<usize>>>,
+    pub out_of_frame: Vec<usize>, // signals with bits beyond `size`
+}
+
+pub(crate) fn message_layout(msg: &Message) -> MessageLayout {
+    let size = *msg.message_size();
+    let mut grid: Vec<Vec<Vec<usize>>> = vec![vec![Vec::new(); 8]; size as usize];
+    let mut out_of_frame = Vec::new();
+
+    let signals: Vec<SignalLayout> = msg
+        .signals()
+        .iter()
+        .enumerate()
+        .map(|(i, sig)| {
+            let bits = signal_bits(*sig.start_bit(), *sig.signal_size(), *sig.byte_order());
+            for &b in &bits {
+                match grid.get_mut((b / 8) as usize) {
+                    Some(row) => row[(b % 8) as usize].push(i),
+                    None if !out_of_frame.contains(&i) => out_of_frame.push(i),
+                    None => {}
+                }
+            }
+            let mux = match sig.multiplexer_indicator() {
+                MultiplexIndicator::Plain => None,
+                MultiplexIndicator::Multiplexor => Some("M".to_string()),
+                MultiplexIndicator::MultiplexedSignal(n) => Some(format!("m{}", n)),
+                MultiplexIndicator::MultiplexorAndMultiplexedSignal(n) => Some(format!("m{}M", n)),
+            };
+            SignalLayout {
+                name: sig.name().clone(),
+                start_bit: *sig.start_bit(),
+                size: *sig.signal_size(),
+                byte_order: match sig.byte_order() {
+                    ByteOrder::LittleEndian => "intel",
+                    ByteOrder::BigEndian => "motorola",
+                },
+                mux,
+                msb: bits.first().copied().unwrap_or(0),
+                lsb: bits.last().copied().unwrap_or(0),
+                bits,
+            }
+        })
+        .collect();
+
+    let (id, is_extended) = crate::split_id(msg.message_id().raw());
+    MessageLayout { id, is_extended, name: msg.message_name().clone(), size, signals, grid, out_of_frame }
+}
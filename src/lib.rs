@@ -20,17 +20,78 @@ results (they accept a JS progress callback).
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsValue;
 
-use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
-use serde_wasm_bindgen;
 
-use std::collections::HashMap;
-use std::io::Cursor;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::cell::Cell;
+use std::rc::Rc;
 
-use ablf::{BlfFile, ObjectTypes};
-use can_dbc::{DBC, Signal, ByteOrder, ValueType};
+use can_dbc::{AttributeValue, AttributeValuedForObjectType, DBC, Message, MultiplexIndicator, Signal, ByteOrder, ValueType};
 
-use js_sys::Function;
+use js_sys::{Float64Array, Function, Uint32Array, Uint8Array};
+
+mod analysis;
+mod arrow;
+mod asc;
+mod binary;
+mod blf;
+mod consistency;
+mod decimate;
+mod deflate;
+mod error;
+mod export;
+mod fleet;
+mod index;
+mod input;
+mod influx;
+mod isotp;
+mod j1939;
+mod layout;
+mod lin;
+mod mdf;
+mod merge;
+mod mux;
+mod npy;
+mod numeric;
+mod obd;
+mod overrides;
+mod parquet;
+mod payload;
+mod provenance;
+mod pyramid;
+mod report;
+mod signals;
+mod sqlite;
+mod store;
+mod table;
+mod timing;
+mod trc;
+mod uds;
+mod units;
+mod xlsx;
+mod zip;
+use blf::{BlfObject, BlfStream, BlfWriter, CanError, CanFrame, EthernetFrame, FlexRayFrame, LinFrame, LogStart};
+use error::ApiError;
+use decimate::{Decimator, EndpointTracker, EnvelopeDecimator, GroupedDecimator, LttbDecimator};
+use export::{ExportManifest, WallClock};
+use index::SessionIndex;
+use input::LogReader;
+use isotp::{IsoTpPair, TransportMessage};
+use j1939::{J1939Info, J1939Objects};
+use mux::MuxPlan;
+use overrides::{DbcPatch, SignalOverride};
+use payload::Payload;
+use lin::LinDatabase;
+use provenance::{DbcSource, LogSource, MergeRecord, SessionConfig};
+use merge::ClockFit;
+use pyramid::Pyramid;
+use signals::Interpolation;
+use store::{FrameStore, Signals};
+use table::{Column, Values};
+use timing::Phase;
+use units::{UnitConversion, UnitTable};
 
 // -------------------------------
 // SECTION 1: Data structures returned to JS (serde-serializable)
@@ -40,28 +101,330 @@ pub struct SignalRow {
     pub signal: String, // "CAN{channel}.{SignalName}"
     pub value: f64,
     pub unit: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_unit: Option<String>, // DBC unit when value was normalized to SI
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_text: Option<String>, // VAL_ description of the value, e.g. "IGNITION_ON"
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, Default)]
 pub struct FrameRow {
     pub timestamp: f64,
     pub channel: String, // e.g., "CAN1"
-    pub id: u32,
+    pub channel_num: u16, // same channel as a number, for filtering
+    pub bus: Bus, // bus type of channel_num
+    pub id: u32, // 11- or 29-bit identifier, IDE bit masked off
+    pub is_extended: bool,
     pub name: String,
-    pub event_type: String,
+    pub event_type: EventType,
     pub dir: String,
-    pub dlc: u8,
-    pub data: Vec<u8>,
+    pub dlc: u8, // as logged; CAN FD codes 9..15 stand for 12..64 bytes
+    pub data: Payload, // up to 64 bytes for CAN FD; repeats of an ID share their bytes
+    pub flags: FrameFlags,
     pub signals: Vec<SignalRow>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorInfo>, // error frames (event_type "Error Frame") only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flexray: Option<FlexRayInfo>, // FlexRay frames only (id = slot id)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ethernet: Option<EthernetInfo>, // Ethernet frames only (id = EtherType)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub j1939: Option<J1939Info>, // options.j1939, extended CAN ids only
+}
+
+// A stored frame, borrowed from the session's FrameStore; fields and serialized
+// form as FrameRow
+#[derive(Serialize, Debug, Clone, Copy)]
+pub(crate) struct Frame<'a> {
+    pub timestamp: f64,
+    pub channel: &'a str,
+    pub channel_num: u16,
+    pub bus: Bus,
+    pub id: u32,
+    pub is_extended: bool,
+    pub name: &'a str,
+    pub event_type: EventType,
+    pub dir: &'a str,
+    pub dlc: u8,
+    pub data: &'a [u8],
+    pub flags: FrameFlags,
+    pub signals: Signals<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<&'a ErrorInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flexray: Option<&'a FlexRayInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ethernet: Option<&'a EthernetInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub j1939: Option<J1939Info>,
+}
+
+// FrameRow.event_type; serialized as its label ("CAN FD Frame"), options accept the
+// label or the snake_case name ("can_fd")
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum EventType {
+    #[default]
+    #[serde(rename = "CAN Frame", alias = "can")]
+    Can,
+    #[serde(rename = "CAN FD Frame", alias = "can_fd")]
+    CanFd,
+    #[serde(rename = "CAN Remote Frame", alias = "can_remote")]
+    CanRemote, // RTR set, no payload
+    #[serde(rename = "Error Frame", alias = "error")]
+    Error,
+    #[serde(rename = "J1939 TP Message", alias = "j1939_tp")]
+    J1939Tp, // reassembled TP transfer (options.j1939)
+    #[serde(rename = "LIN Frame", alias = "lin")]
+    Lin,
+    #[serde(rename = "FlexRay Frame", alias = "flexray")]
+    FlexRay,
+    #[serde(rename = "Ethernet Frame", alias = "ethernet")]
+    Ethernet,
+}
+
+impl EventType {
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            EventType::Can => "CAN Frame",
+            EventType::CanFd => "CAN FD Frame",
+            EventType::CanRemote => "CAN Remote Frame",
+            EventType::Error => "Error Frame",
+            EventType::J1939Tp => "J1939 TP Message",
+            EventType::Lin => "LIN Frame",
+            EventType::FlexRay => "FlexRay Frame",
+            EventType::Ethernet => "Ethernet Frame",
+        }
+    }
+}
+
+// options.event_types: empty keeps every frame
+pub(crate) fn event_filter(types: &[EventType]) -> impl Fn(&Frame) -> bool + '_ {
+    move |f| types.is_empty() || types.contains(&f.event_type)
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Bus {
+    #[default]
+    Can,
+    Lin, // "LIN{n}" channel, event_type "LIN Frame"
+    FlexRay, // "FR{n}" channel, event_type "FlexRay Frame"
+    Ethernet, // "ETH{n}" channel, event_type "Ethernet Frame"
+}
+
+// Extended ids carry the IDE bit as bit 31, both in BLF objects and in can-dbc's
+// MessageId::raw(); that "raw" form is the internal lookup key.
+pub(crate) const CAN_EFF_FLAG: u32 = 0x8000_0000;
+pub(crate) const CAN_EFF_MASK: u32 = 0x1FFF_FFFF;
+// Other buses are keyed with bits 29-30 (unused by CAN ids), so per-(channel, id)
+// maps never mix LIN1 0x21, FR1 slot 0x21 or EtherType 0x800 with a CAN id
+pub(crate) const LIN_FLAG: u32 = 0x4000_0000;
+pub(crate) const FLEXRAY_FLAG: u32 = 0x2000_0000;
+pub(crate) const ETHERNET_FLAG: u32 = 0x6000_0000;
+
+// raw id -> (identifier, is_extended)
+pub(crate) fn split_id(raw: u32) -> (u32, bool) {
+    (raw & CAN_EFF_MASK, raw & CAN_EFF_FLAG != 0)
+}
+
+// id argument from JS: with bit 31 set only that extended id matches, otherwise
+// the identifier matches in either format
+pub(crate) fn id_matches(query: u32, raw: u32) -> bool {
+    if query & CAN_EFF_FLAG != 0 { query == raw } else { query == raw & CAN_EFF_MASK }
+}
+
+impl FrameRow {
+    pub(crate) fn view(&self) -> Frame<'_> {
+        Frame {
+            timestamp: self.timestamp,
+            channel: &self.channel,
+            channel_num: self.channel_num,
+            id: self.id,
+            is_extended: self.is_extended,
+            name: &self.name,
+            event_type: self.event_type,
+            dir: &self.dir,
+            dlc: self.dlc,
+            data: &self.data,
+            flags: self.flags,
+            signals: Signals::Rows(&self.signals),
+            error: self.error.as_ref(),
+            flexray: self.flexray.as_ref(),
+            ethernet: self.ethernet.as_ref(),
+            j1939: self.j1939,
+            bus: self.bus,
+        }
+    }
+}
+
+impl Frame<'_> {
+    pub(crate) fn raw_id(&self) -> u32 {
+        match self.bus {
+            Bus::Lin => self.id | LIN_FLAG,
+            Bus::FlexRay => self.id | FLEXRAY_FLAG,
+            Bus::Ethernet => self.id | ETHERNET_FLAG,
+            Bus::Can if self.is_extended => self.id | CAN_EFF_FLAG,
+            Bus::Can => self.id,
+        }
+    }
+
+    // error frames carry no message (their id, if any, is the disturbed frame's)
+    pub(crate) fn is_error(&self) -> bool {
+        self.error.is_some()
+    }
+
+    // CAN data/remote frames: what DBC-based checks look at
+    pub(crate) fn is_can_message(&self) -> bool {
+        self.bus == Bus::Can && self.error.is_none()
+    }
+
+    // CSV "Flags" column, e.g. "EXT FD BRS"
+    fn flags_label(&self) -> String {
+        let f = &self.flags;
+        [
+            (self.is_extended, "EXT"),
+            (f.rtr, "RTR"),
+            (f.fd, "FD"),
+            (f.brs, "BRS"),
+            (f.esi, "ESI"),
+            (f.wakeup, "WU"),
+            (f.nerr, "NERR"),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+}
+
+// BLF message flags beyond the direction (dir: "Tx" / "Rx" / "TxRq")
+#[derive(Serialize, Debug, Clone, Copy, Default)]
+pub struct FrameFlags {
+    pub rtr: bool, // remote frame (no payload)
+    pub fd: bool, // CAN FD frame format
+    pub brs: bool, // CAN FD bit rate switch
+    pub esi: bool, // CAN FD error state indicator
+    pub wakeup: bool, // single-wire CAN wake-up
+    pub nerr: bool, // transceiver error line active
+}
+
+// Details of an error frame; None where the logger did not record them
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ErrorInfo {
+    pub error_type: Option<String>, // "Bit", "Form", "Stuff", "CRC", "Ack Delimiter", "Ack", "Other"
+    pub position: Option<u16>, // bit position of the error in the frame
+    pub ecc: Option<u8>, // raw error code capture byte
+    pub tx_errors: Option<u8>, // transmit error counter (latest driver report)
+    pub rx_errors: Option<u8>, // receive error counter (latest driver report)
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct FlexRayInfo {
+    pub cycle: u8, // communication cycle 0..63
+    pub channels: String, // "A", "B" or "AB"
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct EthernetInfo {
+    pub source: String, // MAC "aa:bb:cc:dd:ee:ff"
+    pub destination: String,
+    pub ethertype: u16,
+    pub vlan_id: Option<u16>, // 802.1Q tagged frames
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MergeReport {
+    pub frames_read: usize,
+    pub frames_added: usize,
+    pub duplicates_removed: usize,
+    pub clock: Option<ClockFit>,
+}
+
+// One DBC conflict: same (channel, id), different definitions
+#[derive(Serialize, Debug, Clone)]
+pub struct Ambiguity {
+    pub channel: String,
+    pub channel_num: u16,
+    pub id: u32,
+    pub is_extended: bool,
+    pub frames: usize,
+    pub candidates: Vec<AmbiguityCandidate>, // in priority order
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AmbiguityCandidate {
+    pub dbc: usize, // index into dbc_texts
+    pub name: String,
+    pub size: u64,
+    pub signals: Vec<String>,
+    pub frames_decoded: usize,
+}
+
+// One signal name defined by several messages on a channel (see options.name_collisions)
+#[derive(Serialize, Debug, Clone)]
+pub struct SignalCollision {
+    pub signal: String, // shared name, "CAN1.Speed"
+    pub channel_num: u16,
+    pub messages: Vec<CollisionMessage>, // by id
+    pub qualified: bool, // decoded as "CAN1.{Message}.Speed" per message
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CollisionMessage {
+    pub dbc: usize, // index into dbc_texts
+    pub name: String,
+    pub id: u32,
+    pub is_extended: bool,
+    pub signal: String, // name its values are decoded under
+}
+
+// diff_windows(): window a vs window b
+#[derive(Serialize, Debug, Clone)]
+pub struct WindowDiff {
+    pub messages: Vec<analysis::MessageDiff>,
+    pub signals: Vec<SignalDiff>, // by channel, message and position
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SignalDiff {
+    pub signal: String,
+    pub unit: String,
+    pub last_a: Option<f64>, // None: not sampled in that window
+    pub last_b: Option<f64>,
+    pub changed: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SnapshotValue {
+    pub signal: String,
+    pub value: f64,
+    pub unit: String,
+    pub time: Option<f64>, // sample the value came from; None for defaults
+    pub is_default: bool, // DBC start value, signal not seen yet
 }
 
+// (timestamps, values) of one signal
+type Series = (Vec<f64>, Vec<f64>);
+
 // -------------------------------
 // SECTION 2: BlfSession (WASM-visible)
 // -------------------------------
 #[wasm_bindgen]
 pub struct BlfSession {
-    frames: Vec<FrameRow>,
+    // shared with clone_view() handles; merge() copies on write
+    frames: Rc<FrameStore>,
     signal_names: Vec<String>,
+    decoder: Rc<Decoder>,
+    pyramids: Rc<HashMap<String, Pyramid>>,
+    lazy: bool, // frames carry no SignalRows; decode on demand
+    pinned: Rc<HashMap<String, Series>>, // eagerly decoded (timestamps, values); read by collect_series()
+    config: SessionConfig, // inputs and effective options, for session_config()
+    read_only: bool, // clone_view() handle
+    freed: Rc<Cell<bool>>, // free_memory() was called; shared with views of this session
+    origins: Vec<Rc<Cell<bool>>>, // views: `freed` of the sessions this one was made from
+    generation: u32, // bumped by merge() and free_memory()
+    timings: timing::Slot, // see last_operation_timings()
 }
 
 #[wasm_bindgen]
@@ -69,47 +432,21 @@ impl BlfSession {
     // ---------------------------
     // 2.1 Constructor
     // ---------------------------
+    // channel_map is checked up front (one channel 1..255 per DBC, no DBC text twice on a
+    // channel, one lin_databases entry per LIN channel); errors name the offending entry.
+    // Signals are decoded eagerly unless options.pinned_signals / lazy_signals say
+    // otherwise: repeated queries then read the stored rows, and
+    // options.max_decoded_values fails here rather than mid-export.
     #[wasm_bindgen(constructor)]
     pub fn new(
         blf_bytes: &[u8],
         dbc_texts: JsValue,
         channel_map: JsValue,
+        options: JsValue,
     ) -> Result<BlfSession, JsValue> {
-        // Deserialize input JS arrays into Rust types
-        let dbc_texts_vec: Vec<String> = serde_wasm_bindgen::from_value(dbc_texts)
-            .map_err(|e| JsValue::from_str(&format!("dbc_texts must be array of strings: {:?}", e)))?;
-        let channel_map_vec: Vec<u8> = serde_wasm_bindgen::from_value(channel_map)
-            .map_err(|e| JsValue::from_str(&format!("channel_map must be array of u8: {:?}", e)))?;
-
-        if dbc_texts_vec.len() != channel_map_vec.len() {
-            return Err(JsValue::from_str("dbc_texts and channel_map must have same length"));
-        }
-
-        // Build DBC map: channel -> DBC
-        let mut dbc_map: HashMap<u8, DBC> = HashMap::new();
-        for (text, chan) in dbc_texts_vec.iter().zip(channel_map_vec.iter()) {
-            let dbc = DBC::try_from(text.as_str())
-                .map_err(|e| JsValue::from_str(&format!("Failed to parse DBC for channel {}: {:?}", chan, e)))?;
-            dbc_map.insert(*chan, dbc);
-        }
-
-        // Create BlfFile reader
-        let cursor = Cursor::new(blf_bytes);
-        let blf = BlfFile::from_reader(cursor)
-            .map_err(|(e, _)| JsValue::from_str(&format!("Failed to parse BLF: {:?}", e)))?;
-
-        let mut frames: Vec<FrameRow> = Vec::new();
-        let mut seen_signals: Vec<String> = Vec::new();
-
-        // Iterate and build frames
-        for obj in blf {
-            if let Some(frame) = frame_from_obj(&obj.data, &dbc_map, Some(&mut seen_signals)) {
-                frames.push(frame);
-            }
-        }
-
-        seen_signals.sort();
-        Ok(BlfSession { frames, signal_names: seen_signals })
+        let opts: SessionOptions = parse_options(options, "session options")?;
+        let decoder = Decoder::from_js(dbc_texts, channel_map, &opts)?;
+        Ok(BlfSession::open(blf_bytes, decoder, opts)?)
     }
 
     // ---------------------------
@@ -117,6 +454,7 @@ impl BlfSession {
     // ---------------------------
     #[wasm_bindgen(js_name = stats)]
     pub fn stats(&self) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
         let count = self.frames.len() as u32;
         let (first, last) = if let (Some(f), Some(l)) = (self.frames.first(), self.frames.last()) {
             (f.timestamp, l.timestamp)
@@ -131,11 +469,14 @@ impl BlfSession {
     // ---------------------------
     // 2.3 preview()
     // ---------------------------
+    // First n frames (of options.event_types, if given); options.strategy = "active"
+    // spreads them over the log's active time range instead
     #[wasm_bindgen(js_name = preview)]
-    pub fn preview(&self, n: usize) -> Result<JsValue, JsValue> {
-        let take_n = std::cmp::min(n, self.frames.len());
-        serde_wasm_bindgen::to_value(&self.frames[0..take_n])
-            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    pub fn preview(&self, n: usize, options: JsValue) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let _timed = timing::start("preview", &self.timings);
+        let opts: PreviewOptions = parse_options(options, "preview options")?;
+        self.frames_to_js(self.preview_frames(n, &opts).into_iter())
     }
 
     // ---------------------------
@@ -143,6 +484,7 @@ impl BlfSession {
     // ---------------------------
     #[wasm_bindgen(js_name = signals)]
     pub fn signals(&self) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
         serde_wasm_bindgen::to_value(&self.signal_names)
             .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
     }
@@ -150,18 +492,17 @@ impl BlfSession {
     // ---------------------------
     // 2.5 decimated()
     // ---------------------------
+    // Continuous signals thinned to about max_points; 1-bit signals come back under
+    // "digital" with every edge and other discrete ones (<=8 bit or value table) under
+    // "discrete" with every change, so short pulses survive any max_points.
     #[wasm_bindgen(js_name = decimated)]
     pub fn decimated(
         &self,
         max_points: usize,
         keep_signals: JsValue,
+        options: JsValue,
     ) -> Result<JsValue, JsValue> {
-        let total = self.frames.len();
-        if total == 0 {
-            return serde_wasm_bindgen::to_value(&json!({"time": [], "signals": {}}))
-                .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)));
-        }
-
+        self.check_alive()?;
         let keep_opt: Option<Vec<String>> =
             if keep_signals.is_null() || keep_signals.is_undefined() {
                 None
@@ -169,110 +510,129 @@ impl BlfSession {
                 Some(serde_wasm_bindgen::from_value(keep_signals)
                     .map_err(|e| JsValue::from_str(&format!("keep_signals must be array of strings: {:?}", e)))?)
             };
+        let opts: DecimateOptions = parse_options(options, "decimation options")?;
+        let _timed = timing::start("decimated", &self.timings);
 
         let keys: Vec<String> = keep_opt.unwrap_or_else(|| self.signal_names.clone());
+        let discrete = self.decoder.discrete_signals();
+        let rank = self.decoder.signal_rank(&keys, opts.order);
+        let wanted: HashSet<String> = keys.iter().cloned().collect();
+        let mut cache = DecodeCache::new(Some(&wanted));
 
-        let step = std::cmp::max(1, total / max_points);
-        let mut dec_time = Vec::new();
-        let mut dec_signals: HashMap<String, Vec<Option<f64>>> =
-            keys.iter().map(|k| (k.clone(), Vec::new())).collect();
-        let mut last_seen: HashMap<String, Option<f64>> =
-            keys.iter().map(|k| (k.clone(), None)).collect();
-
-        for (i, frame) in self.frames.iter().enumerate() {
-            for s in &frame.signals {
-                last_seen.insert(s.signal.clone(), Some(s.value));
-            }
-            if i % step == 0 {
-                dec_time.push(frame.timestamp);
-                for k in &keys {
-                    if let Some(arr) = dec_signals.get_mut(k) {
-                        arr.push(last_seen.get(k).cloned().unwrap_or(None));
-                    }
-                }
+        if opts.method == DecimateMethod::Lttb {
+            opts.check_per_signal()?;
+            let mut dec = LttbDecimator::new(Some(&keys), discrete);
+            for frame in self.frames.iter() {
+                dec.push(frame.timestamp, &self.frame_signals(&frame, &mut cache));
+            }
+            let result = dec.finish(max_points);
+            return timing::span(Phase::Serialize, || lttb_to_js(result, opts.share_times, &rank));
+        }
+        if opts.method == DecimateMethod::Envelope {
+            opts.check_per_signal()?;
+            let span = match (self.frames.first(), self.frames.last()) {
+                (Some(f), Some(l)) => l.timestamp - f.timestamp,
+                _ => 0.0,
+            };
+            let mut dec = EnvelopeDecimator::new(opts.envelope_bucket(span, max_points), Some(&keys), discrete);
+            for frame in self.frames.iter() {
+                dec.push(frame.timestamp, &self.frame_signals(&frame, &mut cache));
             }
+            let result = dec.finish();
+            return timing::span(Phase::Serialize, || envelope_to_js(result, opts.share_times, &rank));
         }
 
-        let mut out_signals = serde_json::Map::new();
-        for (k, vec_opt) in dec_signals.into_iter() {
-            let arr_values: Vec<serde_json::Value> = vec_opt
-                .into_iter()
-                .map(|o| o.map_or(serde_json::Value::Null, |v| json!(v)))
-                .collect();
-            out_signals.insert(k, serde_json::Value::Array(arr_values));
+        if opts.group_by_message {
+            let mut counts: HashMap<(u16, u32), usize> = HashMap::new();
+            for f in self.frames.iter() {
+                *counts.entry((f.channel_num, f.raw_id())).or_default() += 1;
+            }
+            let mut dec = GroupedDecimator::new(counts, max_points, Some(&keys), discrete);
+            for frame in self.frames.iter() {
+                dec.push(&frame, &self.frame_signals(&frame, &mut cache));
+            }
+            let result = dec.finish();
+            return timing::span(Phase::Serialize, || {
+                if opts.encoding == DecimateEncoding::Binary {
+                    return Ok(Uint8Array::from(binary::encode_grouped(result, &rank).as_slice()).into());
+                }
+                grouped_decimation_to_js(result, opts.share_times, &rank)
+            });
         }
 
-        serde_wasm_bindgen::to_value(&json!({
-            "time": dec_time,
-            "signals": serde_json::Value::Object(out_signals)
-        })).map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+        let mut ends = EndpointTracker::new(Some(&keys), &discrete);
+        if opts.include_endpoints || opts.anchor_ms.is_some() {
+            for frame in self.frames.iter() {
+                ends.push(frame.timestamp, &self.frame_signals(&frame, &mut cache));
+            }
+        }
+        let bucket_s = opts.anchor_ms.map(|a| {
+            decimate::anchored_bucket(a / 1000.0, ends.t_last - ends.t_first.unwrap_or(0.0), max_points)
+        });
+        let forced = if opts.include_endpoints { ends.finish() } else { HashSet::new() };
+
+        let step = std::cmp::max(1, self.frames.len() / max_points.max(1));
+        let mut dec = Decimator::new(step, bucket_s, forced, Some(&keys), discrete);
+        for frame in self.frames.iter() {
+            dec.push(frame.timestamp, &self.frame_signals(&frame, &mut cache));
+        }
+        let result = dec.finish();
+        timing::span(Phase::Serialize, || {
+            if opts.encoding == DecimateEncoding::Binary {
+                return Ok(Uint8Array::from(binary::encode_decimation(result, &rank).as_slice()).into());
+            }
+            let typed = opts.encoding == DecimateEncoding::Typed;
+            decimation_to_js(result, opts.share_times, typed, &rank)
+        })
     }
 
     // ---------------------------
     // 2.6 export_csv()
     // ---------------------------
+    // options.query ({ids, channels, names, text, start_s, end_s}) keeps only the
+    // matching frames, e.g. an incident extract, without building a slice() first.
     #[wasm_bindgen(js_name = export_csv)]
-    pub fn export_csv(&self, applied_signals: JsValue) -> Result<Vec<u8>, JsValue> {
-        let selected: Option<Vec<String>> = if applied_signals.is_null() || applied_signals.is_undefined() {
-            None
-        } else {
-            Some(serde_wasm_bindgen::from_value(applied_signals)
-                .map_err(|e| JsValue::from_str(&format!("applied_signals must be array of strings: {:?}", e)))?)
-        };
+    pub fn export_csv(&self, applied_signals: JsValue, options: JsValue) -> Result<Vec<u8>, JsValue> {
+        self.check_alive()?;
+        let _timed = timing::start("export_csv", &self.timings);
+        let opts: CsvOptions = parse_options(options, "csv options")?;
+        let layout = self.csv_layout(applied_signals, &opts)?;
 
         let mut wtr = csv::WriterBuilder::new().has_headers(true).from_writer(vec![]);
-        let mut header = vec![
-            "Time [s]".to_string(),
-            "Channel".to_string(),
-            "ID".to_string(),
-            "Name".to_string(),
-            "Event Type".to_string(),
-            "Dir".to_string(),
-            "DLC".to_string(),
-            "Data".to_string(),
-        ];
-        if let Some(ref sel) = selected {
-            header.extend(sel.iter().cloned());
-        }
-        wtr.write_record(&header)
+        wtr.write_record(layout.header())
             .map_err(|e| JsValue::from_str(&format!("csv write failed: {:?}", e)))?;
 
-        for f in &self.frames {
-            let mut row: Vec<String> = vec![
-                format!("{:.6}", f.timestamp),
-                f.channel.clone(),
-                format!("0x{:X}", f.id),
-                f.name.clone(),
-                f.event_type.clone(),
-                f.dir.clone(),
-                f.dlc.to_string(),
-                f.data.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" "),
-            ];
-
-            let sig_map: HashMap<&str, f64> =
-                f.signals.iter().map(|s| (s.signal.as_str(), s.value)).collect();
-
-            if let Some(ref sel) = selected {
-                for sname in sel {
-                    row.push(sig_map.get(sname.as_str()).map_or(String::new(), |v| v.to_string()));
-                }
-            }
-
-            wtr.write_record(&row)
+        let mut state = layout.state();
+        let wanted = layout.wanted();
+        let mut cache = DecodeCache::new(Some(&wanted));
+        let mut keep = opts.frame_filter()?;
+        for f in self.frames.iter().filter(|f| keep(f)) {
+            let row = self.csv_row(&layout, &mut state, &mut cache, &f);
+            timing::sampled(Phase::Serialize, || wtr.write_record(row))
                 .map_err(|e| JsValue::from_str(&format!("csv write failed: {:?}", e)))?;
         }
 
-        wtr.into_inner()
-            .map_err(|e| JsValue::from_str(&format!("csv finalize failed: {:?}", e)))
+        timing::span(Phase::Serialize, || {
+            wtr.into_inner().map_err(|e| JsValue::from_str(&format!("csv finalize failed: {:?}", e)))
+        })
     }
 
     // ---------------------------
     // 2.7 free_memory()
     // ---------------------------
+    // Releases frames, decoder and signal stores; every later call fails with
+    // "session released" instead of returning empty results, on this session and on
+    // the clone_view() / slice() handles made from it. Storage shared with those
+    // handles is freed once the last of them lets go.
     #[wasm_bindgen(js_name = free_memory)]
     pub fn free_memory(&mut self) {
-        self.frames.clear();
+        self.frames = Rc::default();
         self.signal_names.clear();
+        self.decoder = Rc::new(Decoder::empty());
+        self.pyramids = Rc::default();
+        self.pinned = Rc::default();
+        self.freed.set(true);
+        self.generation += 1;
     }
 
     // ---------------------------
@@ -284,6 +644,7 @@ impl BlfSession {
         dbc_texts: JsValue,
         channel_map: JsValue,
         file_size: u64,
+        options: JsValue,
     ) -> Result<JsValue, JsValue> {
         let slice_len: usize = if file_size <= 20 * 1024 * 1024 {
             blf_bytes.len()
@@ -293,13 +654,12 @@ impl BlfSession {
         };
 
         let slice = &blf_bytes[0..std::cmp::min(slice_len, blf_bytes.len())];
-        let session = BlfSession::new(slice, dbc_texts, channel_map)?;
-        let frame_count = session.frames.len();
+        // the same options object also carries the preview options (strategy, event_types)
+        let preview: PreviewOptions = parse_options(options.clone(), "preview options")?;
+        let session = BlfSession::new(slice, dbc_texts, channel_map, options)?;
 
         // Always return up to 50 frames for preview (channel-tagged signal names).
-        let take_n = std::cmp::min(50usize, frame_count);
-        serde_wasm_bindgen::to_value(&session.frames[0..take_n])
-            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+        session.frames_to_js(session.preview_frames(50, &preview).into_iter())
     }
 
     // ---------------------------
@@ -311,47 +671,40 @@ impl BlfSession {
         dbc_texts: JsValue,
         channel_map: JsValue,
         progress_cb: &Function,
+        options: JsValue,
     ) -> Result<Vec<u8>, JsValue> {
         // parse DBCs (same pattern as constructor)
-        let dbc_texts_vec: Vec<String> = serde_wasm_bindgen::from_value(dbc_texts)
-            .map_err(|e| JsValue::from_str(&format!("dbc_texts must be array of strings: {:?}", e)))?;
-        let channel_map_vec: Vec<u8> = serde_wasm_bindgen::from_value(channel_map)
-            .map_err(|e| JsValue::from_str(&format!("channel_map must be array of u8: {:?}", e)))?;
-
-        let mut dbc_map: HashMap<u8, DBC> = HashMap::new();
-        for (text, chan) in dbc_texts_vec.iter().zip(channel_map_vec.iter()) {
-            let dbc = DBC::try_from(text.as_str())
-                .map_err(|e| JsValue::from_str(&format!("Failed to parse DBC: {:?}", e)))?;
-            dbc_map.insert(*chan, dbc);
-        }
+        let opts: SessionOptions = parse_options(options, "session options")?;
+        let decoder = Decoder::from_js(dbc_texts, channel_map, &opts)?;
 
         // Stream-parse the full BLF (use the full buffer supplied)
-        let cursor = Cursor::new(blf_bytes);
-        let blf = BlfFile::from_reader(cursor)
-            .map_err(|(e, _)| JsValue::from_str(&format!("Failed to parse BLF: {:?}", e)))?;
+        let blf = LogReader::new(blf_bytes).map_err(log_error)?;
 
         let mut wtr = csv::WriterBuilder::new().has_headers(true).from_writer(vec![]);
-        wtr.write_record(&[
-            "Time [s]", "Channel", "ID", "Name", "Event Type", "Dir", "DLC", "Data"
+        wtr.write_record([
+            "Time [s]", "Channel", "Channel Num", "ID", "Name", "Event Type", "Dir", "Flags", "DLC", "Data"
         ]).map_err(|e| JsValue::from_str(&format!("csv write failed: {:?}", e)))?;
 
         let mut frame_count: usize = 0;
-        for obj in blf {
-            if let Some(frame) = frame_from_obj(&obj.data, &dbc_map, None) {
+        for obj in J1939Objects::new(blf, decoder.j1939) {
+            if let Some(frame) = frame_from_obj(&obj, &decoder, None, false) {
                 frame_count += 1;
+                let flags = frame.view().flags_label();
                 wtr.write_record(&[
                     format!("{:.6}", frame.timestamp),
                     frame.channel,
+                    frame.channel_num.to_string(),
                     format!("0x{:X}", frame.id),
                     frame.name,
-                    frame.event_type,
+                    frame.event_type.label().to_string(),
                     frame.dir,
+                    flags,
                     frame.dlc.to_string(),
                     frame.data.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" "),
                 ]).map_err(|e| JsValue::from_str(&format!("csv write failed: {:?}", e)))?;
 
                 // call progress callback every N frames
-                if frame_count % 10_000 == 0 {
+                if frame_count.is_multiple_of(10_000) {
                     let _ = progress_cb.call1(&JsValue::NULL, &JsValue::from_f64(frame_count as f64));
                 }
             }
@@ -371,209 +724,5316 @@ impl BlfSession {
         channel_map: JsValue,
         max_points: usize,
         progress_cb: &Function,
+        options: JsValue,
     ) -> Result<JsValue, JsValue> {
-        // parse DBCs
-        let dbc_texts_vec: Vec<String> = serde_wasm_bindgen::from_value(dbc_texts)
-            .map_err(|e| JsValue::from_str(&format!("dbc_texts must be array of strings: {:?}", e)))?;
-        let channel_map_vec: Vec<u8> = serde_wasm_bindgen::from_value(channel_map)
-            .map_err(|e| JsValue::from_str(&format!("channel_map must be array of u8: {:?}", e)))?;
+        // parse DBCs; options may carry both session and decimation keys
+        let options_js = options.clone();
+        let opts: SessionOptions = parse_options(options, "session options")?;
+        let decoder = Decoder::from_js(dbc_texts, channel_map, &opts)?;
 
-        let mut dbc_map: HashMap<u8, DBC> = HashMap::new();
-        for (text, chan) in dbc_texts_vec.iter().zip(channel_map_vec.iter()) {
-            let dbc = DBC::try_from(text.as_str())
-                .map_err(|e| JsValue::from_str(&format!("Failed to parse DBC: {:?}", e)))?;
-            dbc_map.insert(*chan, dbc);
-        }
+        let dec_opts: DecimateOptions = parse_options(options_js, "decimation options")?;
+        let discrete = decoder.discrete_signals();
 
-        // First pass: count frames of interest
-        let cursor = Cursor::new(blf_bytes);
-        let blf = BlfFile::from_reader(cursor)
-            .map_err(|(e, _)| JsValue::from_str(&format!("Failed to parse BLF: {:?}", e)))?;
-        let total_frames = blf.into_iter()
-            .filter(|o| matches!(o.data, ObjectTypes::CanMessage86(_)))
-            .count();
+        // First pass: count frames of interest (and find per-signal first/last samples,
+        // the only reason to decode here)
+        let blf = LogReader::new(blf_bytes).map_err(log_error)?;
+        let mut ends = EndpointTracker::new(None, &discrete);
+        for obj in J1939Objects::new(blf, decoder.j1939) {
+            if let Some(frame) = frame_from_obj(&obj, &decoder, None, dec_opts.include_endpoints) {
+                ends.push(frame.timestamp, &frame.signals);
+            }
+        }
+        let span = ends.t_last - ends.t_first.unwrap_or(0.0);
+        let total_frames = ends.frames();
+        let bucket_s = dec_opts.anchor_ms.map(|a| decimate::anchored_bucket(a / 1000.0, span, max_points));
+        let forced = if dec_opts.include_endpoints { ends.finish() } else { HashSet::new() };
 
         // Second pass: decimate
-        let cursor2 = Cursor::new(blf_bytes);
-        let blf2 = BlfFile::from_reader(cursor2)
-            .map_err(|(e, _)| JsValue::from_str(&format!("Failed to parse BLF (2): {:?}", e)))?;
-
-        let step = std::cmp::max(1, total_frames / max_points.max(1));
-        let mut times: Vec<f64> = Vec::new();
-        let mut signals_map: HashMap<String, Vec<f64>> = HashMap::new();
+        let blf2 = LogReader::new(blf_bytes).map_err(log_error)?;
 
         let mut count = 0usize;
-        for obj in blf2 {
-            if let Some(frame) = frame_from_obj(&obj.data, &dbc_map, None) {
-                if count % step == 0 {
-                    times.push(frame.timestamp);
-                    for s in frame.signals {
-                        signals_map.entry(s.signal).or_default().push(s.value);
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut names: Vec<String> = Vec::new(); // first-seen order, for options.order
+        let mut see = |signals: &[SignalRow]| {
+            for s in signals {
+                if !seen.contains(&s.signal) {
+                    seen.insert(s.signal.clone());
+                    names.push(s.signal.clone());
+                }
+            }
+        };
+        if matches!(dec_opts.method, DecimateMethod::Lttb | DecimateMethod::Envelope) {
+            dec_opts.check_per_signal()?;
+            let mut lttb = LttbDecimator::new(None, discrete.clone());
+            let mut envelope = EnvelopeDecimator::new(dec_opts.envelope_bucket(span, max_points), None, discrete);
+            for obj in J1939Objects::new(blf2, decoder.j1939) {
+                if let Some(frame) = frame_from_obj(&obj, &decoder, None, true) {
+                    see(&frame.signals);
+                    if dec_opts.method == DecimateMethod::Lttb {
+                        lttb.push(frame.timestamp, &frame.signals);
+                    } else {
+                        envelope.push(frame.timestamp, &frame.signals);
+                    }
+                    count += 1;
+                    if count.is_multiple_of(50_000) {
+                        let _ = progress_cb.call1(&JsValue::NULL, &JsValue::from_f64(count as f64));
                     }
                 }
+            }
+            let rank = decoder.signal_rank(&names, dec_opts.order);
+            return if dec_opts.method == DecimateMethod::Lttb {
+                lttb_to_js(lttb.finish(max_points), dec_opts.share_times, &rank)
+            } else {
+                envelope_to_js(envelope.finish(), dec_opts.share_times, &rank)
+            };
+        }
+
+        let step = std::cmp::max(1, total_frames / max_points.max(1));
+        let mut dec = Decimator::new(step, bucket_s, forced, None, discrete);
+
+        for obj in J1939Objects::new(blf2, decoder.j1939) {
+            if let Some(frame) = frame_from_obj(&obj, &decoder, None, true) {
+                see(&frame.signals);
+                dec.push(frame.timestamp, &frame.signals);
                 count += 1;
 
-                if count % 50_000 == 0 {
+                if count.is_multiple_of(50_000) {
                     let _ = progress_cb.call1(&JsValue::NULL, &JsValue::from_f64(count as f64));
                 }
             }
         }
 
-        // Build a serde-serializable object and convert to JsValue
-        let mut signals_json_map = serde_json::Map::new();
-        for (k, v) in signals_map.into_iter() {
-            let arr = serde_json::Value::Array(v.into_iter().map(|x| json!(x)).collect());
-            signals_json_map.insert(k, arr);
+        let rank = decoder.signal_rank(&names, dec_opts.order);
+        decimation_to_js(dec.finish(), dec_opts.share_times, dec_opts.encoding == DecimateEncoding::Typed, &rank)
+    }
+
+    // ---------------------------
+    // 2.11 merge()
+    // ---------------------------
+    // Merge a second BLF (e.g. another logger on the same bus) into this session.
+    // The incoming clock is fitted (offset + ppm drift) against ours and corrected,
+    // then frames already present (same channel/id/data within dedup_tolerance_ms)
+    // are dropped so overlapping captures don't double-count traffic.
+    #[wasm_bindgen(js_name = merge)]
+    pub fn merge(&mut self, blf_bytes: &[u8], options: JsValue) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        self.check_writable("merge()")?;
+        let _timed = timing::start("merge", &self.timings);
+        let opts: MergeOptions = parse_options(options, "merge options")?;
+
+        let blf = LogReader::new(blf_bytes).map_err(log_error)?;
+
+        // budget covers the merged session; lazy sessions re-decode only pinned signals below
+        let mut decoded: usize = self.frames.iter().map(|f| f.signals.len()).sum();
+        // a rejected merge leaves signal_names as it was
+        let mut seen_signals = self.signal_names.clone();
+        let mut incoming = FrameStore::default();
+        for obj in J1939Objects::new(blf, self.decoder.j1939) {
+            if let Some(frame) = frame_from_obj(&obj, &self.decoder, Some(&mut seen_signals), !self.lazy) {
+                decoded += frame.signals.len();
+                incoming.push(frame.view());
+                if decoded > self.decoder.max_values {
+                    return Err(self.decoder.budget_error(&incoming, None).into());
+                }
+            }
+        }
+        seen_signals.sort();
+        self.signal_names = seen_signals;
+
+        let frames_read = incoming.len();
+
+        // Fit the incoming logger's clock against ours and shift it onto our timebase
+        let mut clock = merge::estimate_clock(
+            &self.frames,
+            &incoming,
+            opts.max_clock_offset_ms / 1000.0,
+            opts.clock_match_tolerance_ms / 1000.0,
+            opts.min_clock_pairs,
+        );
+        if let Some(fit) = clock.as_mut() {
+            if opts.correct_clock {
+                incoming.map_timestamps(|t| fit.correct(t));
+                fit.applied = true;
+            }
         }
 
-        serde_wasm_bindgen::to_value(&json!({
-            "time": times,
-            "signals": serde_json::Value::Object(signals_json_map)
-        })).map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
-    }
-}
+        let (kept, duplicates_removed) = if opts.dedup {
+            merge::dedup_incoming(&self.frames, &incoming, opts.dedup_tolerance_ms / 1000.0)
+        } else {
+            (incoming, 0)
+        };
 
-// -------------------------------
-// SECTION 3: Helper - decode a single signal (from can_dbc::Signal)
-// -------------------------------
-fn decode_signal_value(sig: &Signal, data: &[u8]) -> Option<f64> {
-    // Read up to first 8 bytes into little-endian u64
-    let mut buf = [0u8; 8];
-    for i in 0..std::cmp::min(8, data.len()) {
-        buf[i] = data[i];
+        let frames_added = kept.len();
+        // views made before the merge keep the frames they were created with;
+        // the stable sort keeps per-logger order for equal timestamps
+        self.frames = Rc::new(self.frames.merged(&kept));
+
+        // derived per-signal stores follow the new frame list
+        // (pinned is emptied first so collect_series re-decodes instead of reusing it)
+        let pinned_names: Vec<String> = self.pinned.keys().cloned().collect();
+        self.pinned = Rc::default();
+        self.pinned = Rc::new(self.collect_series(&pinned_names));
+        let pyramid_names: Vec<String> = self.pyramids.keys().cloned().collect();
+        self.pyramids = Rc::new(
+            self.collect_series(&pyramid_names)
+                .into_iter()
+                .map(|(name, (t, v))| (name, Pyramid::build(&t, &v)))
+                .collect(),
+        );
+
+        self.config.logs.push(LogSource {
+            hash: index::content_hash(blf_bytes),
+            bytes: blf_bytes.len(),
+            frames_read,
+            start: input::log_start(blf_bytes),
+            merge: Some(MergeRecord { options: opts, clock: clock.clone(), frames_added, duplicates_removed }),
+        });
+        self.generation += 1;
+        let report = MergeReport { frames_read, frames_added, duplicates_removed, clock };
+        timing::span(Phase::Serialize, || serde_wasm_bindgen::to_value(&report))
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
     }
 
-    let start = *sig.start_bit() as usize;
-    let len = *sig.signal_size() as usize;
-    if len == 0 || len > 64 || start + len > 64 {
-        return None;
+    // ---------------------------
+    // 2.12 pyramid_query()
+    // ---------------------------
+    // Min/max envelope of a pyramid signal over [t0, t1] with at most max_points buckets.
+    #[wasm_bindgen(js_name = pyramid_query)]
+    pub fn pyramid_query(&self, signal: &str, t0: f64, t1: f64, max_points: usize) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let pyr = self.pyramids.get(signal).ok_or_else(|| {
+            JsValue::from_str(&format!("no pyramid for signal {} (add it to options.pyramid_signals)", signal))
+        })?;
+        let slice = pyr.query(t0, t1, max_points);
+
+        let out = js_sys::Object::new();
+        set_entry(&out, "level", &JsValue::from_f64(slice.level as f64))?;
+        set_entry(&out, "bucket_size", &JsValue::from_f64(slice.bucket_size as f64))?;
+        set_entry(&out, "time", &Float64Array::from(slice.time.as_slice()))?;
+        set_entry(&out, "min", &Float64Array::from(slice.min.as_slice()))?;
+        set_entry(&out, "max", &Float64Array::from(slice.max.as_slice()))?;
+        Ok(out.into())
     }
 
-    // raw little-endian u64 from buf
-    let mut raw: u64 = 0;
-    for (i, b) in buf.iter().enumerate().take(8) {
-        raw |= (*b as u64) << (i * 8);
+    // ---------------------------
+    // 2.13 export_pyramid() / from_pyramid_cache()
+    // ---------------------------
+    // skip_levels drops that many of the finest levels (level 0 = raw samples)
+    // to keep the cached blob small.
+    #[wasm_bindgen(js_name = export_pyramid)]
+    pub fn export_pyramid(&self, skip_levels: usize) -> Result<Vec<u8>, JsValue> {
+        self.check_alive()?;
+        let mut entries: Vec<(&String, &Pyramid)> = self.pyramids.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        Ok(pyramid::encode(entries.into_iter(), skip_levels))
     }
 
-    // Byte order handling
-    let val_u64: u64 = if *sig.byte_order() == ByteOrder::BigEndian {
-        // Motorola bit extraction (simple bit-by-bit gather)
-        let mut acc: u64 = 0;
-        for i in 0..len {
-            let bitpos = start + i;
-            let bit = (raw >> bitpos) & 1;
-            acc |= bit << i;
-        }
-        acc
-    } else {
-        // Intel: straightforward mask+shift
-        (raw >> start) & ((1u64 << len) - 1)
-    };
+    // Session holding only cached pyramids: pyramid_query() works, frame APIs are empty.
+    #[wasm_bindgen(js_name = from_pyramid_cache)]
+    pub fn from_pyramid_cache(bytes: &[u8]) -> Result<BlfSession, JsValue> {
+        let pyramids: HashMap<String, Pyramid> = pyramid::decode(bytes)
+            .map_err(|e| JsValue::from_str(&format!("Failed to load pyramid cache: {}", e)))?
+            .into_iter()
+            .collect();
+        let mut signal_names: Vec<String> = pyramids.keys().cloned().collect();
+        signal_names.sort();
+        let mut config = SessionConfig::new(Vec::new(), 0, false, SessionOptions::default());
+        config.pyramid_cache = Some(index::content_hash(bytes));
+        Ok(BlfSession {
+            frames: Rc::default(),
+            signal_names,
+            decoder: Rc::new(Decoder::empty()),
+            pyramids: Rc::new(pyramids),
+            lazy: false,
+            pinned: Rc::default(),
+            config,
+            read_only: false,
+            freed: Rc::default(),
+            origins: Vec::new(),
+            generation: 0,
+            timings: timing::Slot::default(),
+        })
+    }
 
-    // Signed vs unsigned
-    let signed_val: i64 = if *sig.value_type() == ValueType::Signed {
-        let shift = 64usize - len;
-        ((val_u64 << shift) as i64) >> shift
-    } else {
-        val_u64 as i64
-    };
+    // ---------------------------
+    // 2.14 activity_matrix()
+    // ---------------------------
+    // (message x time-bucket) frame counts; counts is a row-major Uint32Array
+    // with one row per entry of messages.
+    #[wasm_bindgen(js_name = activity_matrix)]
+    pub fn activity_matrix(&self, bucket_ms: f64) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let m = analysis::activity_matrix(&self.frames, bucket_ms / 1000.0, 50_000_000)
+            .map_err(|e| JsValue::from_str(&e))?;
 
-    Some(signed_val as f64 * *sig.factor() + *sig.offset())
-}
+        let out = js_sys::Object::new();
+        let messages = serde_wasm_bindgen::to_value(&m.messages)
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))?;
+        set_entry(&out, "messages", &messages)?;
+        set_entry(&out, "t0", &JsValue::from_f64(m.t0))?;
+        set_entry(&out, "bucket_ms", &JsValue::from_f64(bucket_ms))?;
+        set_entry(&out, "buckets", &JsValue::from_f64(m.buckets as f64))?;
+        set_entry(&out, "counts", &Uint32Array::from(m.counts.as_slice()))?;
+        Ok(out.into())
+    }
 
-// -------------------------------
-// SECTION 4: Helper - decode one BLF object into a FrameRow (if CAN frame)
-// -------------------------------
-fn frame_from_obj(
-    obj: &ObjectTypes,
-    dbc_map: &HashMap<u8, DBC>,
-    seen_signals: Option<&mut Vec<String>>,
-) -> Option<FrameRow> {
-    if let ObjectTypes::CanMessage86(cf) = obj {
-        let ts = cf.header.timestamp_ns as f64 / 1e9;
-        let channel_str = format!("CAN{}", cf.channel);
-        let id = cf.id;
-        let dlc = cf.dlc;
-        let data_vec = cf.data.to_vec();
+    // ---------------------------
+    // 2.15 byte_change_matrix()
+    // ---------------------------
+    #[wasm_bindgen(js_name = byte_change_matrix)]
+    pub fn byte_change_matrix(&self, id: u32) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        serde_wasm_bindgen::to_value(&analysis::byte_change_matrix(&self.frames, id, 5))
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
 
-        let mut frame_name = String::new();
-        let mut signal_rows: Vec<SignalRow> = Vec::new();
+    // ---------------------------
+    // 2.16 session_index()
+    // ---------------------------
+    // Signal list + message lookup for options.warm_start of the next log
+    #[wasm_bindgen(js_name = session_index)]
+    pub fn session_index(&self) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        serde_wasm_bindgen::to_value(&self.index())
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
 
-        if let Some(dbc) = dbc_map.get(&(cf.channel as u8)) {
-            if let Some(msg) = dbc.messages().iter().find(|m| m.message_id().raw() == id) {
-                frame_name = msg.message_name().to_string();
+    // ---------------------------
+    // 2.17 export_csv_chunked()
+    // ---------------------------
+    // Same CSV as export_csv(), delivered as chunk_cb(bytes, manifest) per chunk_frames frames.
+    // The manifest passed with each chunk already includes it: persist both, and on failure
+    // pass the last persisted manifest as options.resume to continue after that chunk.
+    // A throwing callback aborts the export with its error.
+    #[wasm_bindgen(js_name = export_csv_chunked)]
+    pub fn export_csv_chunked(
+        &self,
+        applied_signals: JsValue,
+        options: JsValue,
+        chunk_cb: &Function,
+    ) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let _timed = timing::start("export_csv_chunked", &self.timings);
+        let opts: CsvOptions = parse_options(options, "csv options")?;
+        let layout = self.csv_layout(applied_signals, &opts)?;
+        let to_js = |m: &ExportManifest| {
+            serde_wasm_bindgen::to_value(m).map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+        };
+        let manifest = self.csv_chunks(&layout, &opts, |bytes, m| {
+            chunk_cb.call2(&JsValue::NULL, &Uint8Array::from(bytes), &to_js(m)?).map(|_| ())
+        })?;
+        to_js(&manifest)
+    }
 
-                for sig in msg.signals() {
-                    if let Some(val) = decode_signal_value(sig, &data_vec) {
-                        let sname = format!("CAN{}.{}", cf.channel, sig.name());
-                        signal_rows.push(SignalRow {
-                            signal: sname.clone(),
-                            value: val,
-                            unit: sig.unit().to_string(),
-                        });
-                    }
-                }
+    // ---------------------------
+    // 2.18 ambiguities()
+    // ---------------------------
+    // Ids defined differently by several DBCs on one channel, with the candidates and
+    // how many of this session's frames each one decoded
+    #[wasm_bindgen(js_name = ambiguities)]
+    pub fn ambiguities(&self) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let mut chosen: HashMap<(u8, u32), HashMap<usize, usize>> = HashMap::new();
+        for f in self.frames.iter() {
+            let Ok(chan) = u8::try_from(f.channel_num) else { continue };
+            let key = (chan, f.raw_id());
+            if !self.decoder.conflicts.contains(&key) {
+                continue;
+            }
+            if let Some((dbc, _)) = self.decoder.select(f.channel_num, key.1, f.data.len()) {
+                *chosen.entry(key).or_default().entry(dbc).or_default() += 1;
             }
         }
 
-        // ✅ update seen_signals cleanly, after building signal_rows
-        if let Some(seen) = seen_signals {
-            for s in &signal_rows {
-                if !seen.contains(&s.signal) {
-                    seen.push(s.signal.clone());
+        let mut report: Vec<Ambiguity> = self
+            .decoder
+            .conflicts
+            .iter()
+            .map(|key| {
+                let counts = chosen.get(key);
+                let candidates: Vec<AmbiguityCandidate> = self.decoder.message_index[key]
+                    .iter()
+                    .filter_map(|&c| {
+                        let m = self.decoder.candidate(c)?;
+                        Some(AmbiguityCandidate {
+                            dbc: c.0,
+                            name: m.message_name().clone(),
+                            size: *m.message_size(),
+                            signals: m.signals().iter().map(|s| s.name().clone()).collect(),
+                            frames_decoded: counts.and_then(|c2| c2.get(&c.0)).copied().unwrap_or(0),
+                        })
+                    })
+                    .collect();
+                let (id, is_extended) = split_id(key.1);
+                Ambiguity {
+                    channel: format!("CAN{}", key.0),
+                    channel_num: key.0 as u16,
+                    id,
+                    is_extended,
+                    frames: candidates.iter().map(|c| c.frames_decoded).sum(),
+                    candidates,
                 }
-            }
-        }
+            })
+            .collect();
+        report.sort_by_key(|a| (a.channel_num, a.is_extended, a.id));
 
-        return Some(FrameRow {
-            timestamp: ts,
-            channel: channel_str,
-            id,
-            name: frame_name,
-            event_type: "CAN Frame".to_string(),
-            dir: "Rx".to_string(),
-            dlc,
-            data: data_vec,
-            signals: signal_rows,
-        });
+        serde_wasm_bindgen::to_value(&report)
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
     }
-    None
-}
 
+    // ---------------------------
+    // 2.19 find_lag()
+    // ---------------------------
+    // Delay of signal_b behind signal_a from the cross-correlation peak
+    // (positive lag_ms: b follows a).
+    #[wasm_bindgen(js_name = find_lag)]
+    pub fn find_lag(&self, signal_a: &str, signal_b: &str, max_lag_ms: f64) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let (ta, va) = self.series(signal_a)?;
+        let (tb, vb) = self.series(signal_b)?;
+        let lag = signals::find_lag((&ta, &va), (&tb, &vb), max_lag_ms / 1000.0)
+            .map_err(|e| JsValue::from_str(&e))?;
+        serde_wasm_bindgen::to_value(&lag)
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.20 step_metrics()
+    // ---------------------------
+    // Rise time, overshoot and settling time of `signal` after a step at t_event (s).
+    // options: window_ms, pre_ms, settle_band_pct, rise_low_pct, rise_high_pct
+    #[wasm_bindgen(js_name = step_metrics)]
+    pub fn step_metrics(&self, signal: &str, t_event: f64, options: JsValue) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let opts: StepOptions = parse_options(options, "step options")?;
+        let (t, v) = self.series(signal)?;
+        let params = signals::StepParams {
+            window_s: opts.window_ms / 1000.0,
+            pre_s: opts.pre_ms / 1000.0,
+            settle_band: opts.settle_band_pct / 100.0,
+            rise_low: opts.rise_low_pct / 100.0,
+            rise_high: opts.rise_high_pct / 100.0,
+        };
+        let m = signals::step_metrics(&t, &v, t_event, &params).map_err(|e| JsValue::from_str(&e))?;
+        serde_wasm_bindgen::to_value(&m)
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.21 drive_phases()
+    // ---------------------------
+    // Labelled idle/accel/cruise/brake intervals from a speed signal. t_start/t_end are
+    // session timestamps, so intervals can be fed to the t0/t1 arguments of other calls.
+    #[wasm_bindgen(js_name = drive_phases)]
+    pub fn drive_phases(&self, speed_signal: &str, options: JsValue) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let opts: DrivePhaseOptions = parse_options(options, "drive phase options")?;
+        let (t, v) = self.series(speed_signal)?;
+        let params = signals::DriveParams {
+            to_mps: opts.speed_to_mps,
+            idle_speed: opts.idle_speed,
+            accel_mps2: opts.accel_mps2,
+            brake_mps2: -opts.brake_mps2.abs(),
+            smooth_s: opts.smooth_ms / 1000.0,
+            min_duration_s: opts.min_duration_ms / 1000.0,
+        };
+        serde_wasm_bindgen::to_value(&signals::drive_phases(&t, &v, &params))
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.22 accumulate()
+    // ---------------------------
+    // Integral over [t0, t1] (s) of the product of the given signals, e.g. [voltage, current]
+    // -> energy. Each signal holds its last value; integration starts once all have one.
+    #[wasm_bindgen(js_name = accumulate)]
+    pub fn accumulate(&self, signals: JsValue, t0: f64, t1: f64) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let names: Vec<String> = serde_wasm_bindgen::from_value(signals)
+            .map_err(|e| JsValue::from_str(&format!("signals must be array of strings: {:?}", e)))?;
+        if names.is_empty() {
+            return Err(JsValue::from_str("accumulate needs at least one signal"));
+        }
+        let series = names.iter().map(|n| self.series(n)).collect::<Result<Vec<_>, _>>()?;
+        let units: Vec<&str> = names
+            .iter()
+            .map(|n| self.decoder.signal_meta.get(n).map_or("", |m| m.unit.as_str()))
+            .collect();
+        let refs: Vec<(&[f64], &[f64])> = series.iter().map(|(t, v)| (t.as_slice(), v.as_slice())).collect();
+        let acc = signals::accumulate(&refs, &units, t0, t1).map_err(|e| JsValue::from_str(&e))?;
+        serde_wasm_bindgen::to_value(&acc)
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.23 snapshot_at()
+    // ---------------------------
+    // Last value of each signal at or before t (s). options.signals limits the set;
+    // options.start_values fills not-yet-seen signals with their DBC start value
+    // (is_default: true), as a restbus simulation would initialize them.
+    #[wasm_bindgen(js_name = snapshot_at)]
+    pub fn snapshot_at(&self, t: f64, options: JsValue) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let opts: SnapshotOptions = parse_options(options, "snapshot options")?;
+        let names = opts.signals.unwrap_or_else(|| self.signal_names.clone());
+        let series = self.collect_series(&names);
+
+        let mut out: Vec<SnapshotValue> = Vec::new();
+        for name in &names {
+            let meta = self.decoder.signal_meta.get(name);
+            let unit = meta.map_or_else(String::new, |m| m.unit.clone());
+            let held = series.get(name).and_then(|(ts, vs)| {
+                let i = ts.partition_point(|x| *x <= t);
+                (i > 0).then(|| (ts[i - 1], vs[i - 1]))
+            });
+            match held {
+                Some((time, value)) => out.push(SnapshotValue {
+                    signal: name.clone(),
+                    value,
+                    unit,
+                    time: Some(time),
+                    is_default: false,
+                }),
+                None => {
+                    if let Some(value) = meta.and_then(|m| m.start_value).filter(|_| opts.start_values) {
+                        out.push(SnapshotValue { signal: name.clone(), value, unit, time: None, is_default: true });
+                    }
+                }
+            }
+        }
+        serde_wasm_bindgen::to_value(&out)
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.24 message_layout()
+    // ---------------------------
+    // Bit occupancy of a DBC message for the bit-matrix diagram. channel picks the DBC;
+    // without it the lowest channel defining the id is used (preferred DBC on conflicts).
+    // An id with bit 31 set selects the extended id only.
+    #[wasm_bindgen(js_name = message_layout)]
+    pub fn message_layout(&self, id: u32, channel: Option<u8>) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let mut keys: Vec<&(u8, u32)> = self
+            .decoder
+            .message_index
+            .keys()
+            .filter(|(ch, mid)| id_matches(id, *mid) && channel.is_none_or(|c| c == *ch))
+            .collect();
+        keys.sort();
+        let msg = keys
+            .first()
+            .and_then(|k| self.decoder.message_index[k].first())
+            .and_then(|&c| self.decoder.candidate(c))
+            .ok_or_else(|| JsValue::from_str(&format!("no DBC message with id 0x{:X}", id)))?;
+        serde_wasm_bindgen::to_value(&layout::message_layout(msg))
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.25 unmapped_bits()
+    // ---------------------------
+    // Messages whose payload has bits outside every DBC signal that are set or toggle:
+    // undocumented features or a stale database.
+    #[wasm_bindgen(js_name = unmapped_bits)]
+    pub fn unmapped_bits(&self) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        serde_wasm_bindgen::to_value(&self.unmapped_report())
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.26 dbc_consistency()
+    // ---------------------------
+    // DBC DLCs and GenMsgCycleTime vs observed traffic, with a compatibility score.
+    // options: cycle_tolerance_pct (default 20), min_frames for a cycle check (default 5)
+    #[wasm_bindgen(js_name = dbc_consistency)]
+    pub fn dbc_consistency(&self, options: JsValue) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let opts: ConsistencyOptions = parse_options(options, "consistency options")?;
+        serde_wasm_bindgen::to_value(&self.consistency_report(&opts))
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.27 warnings()
+    // ---------------------------
+    // Problems found while loading the DBCs that did not stop the session, e.g.
+    // messages cut to options.max_signals_per_message, signal name collisions, unmatched
+    // signal_overrides keys, channel_map / lin_databases entries whose channel never
+    // appears in the log (no signals would decode from that DBC) and logged CAN channels
+    // without a DBC.
+    #[wasm_bindgen(js_name = warnings)]
+    pub fn warnings(&self) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let mut warnings = self.decoder.warnings.clone();
+        warnings.extend(self.channel_warnings());
+        serde_wasm_bindgen::to_value(&warnings)
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.28 session_config()
+    // ---------------------------
+    // Inputs (content hashes of the BLF/DBC files, channel map), effective options with
+    // defaults resolved and every merge with its clock correction, so the analysis
+    // can be reproduced from the same files later.
+    #[wasm_bindgen(js_name = session_config)]
+    pub fn session_config(&self) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        serde_wasm_bindgen::to_value(&self.config)
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.29 clone_view()
+    // ---------------------------
+    // Read-only handle on the same frames, decoder and signal stores (no copy), e.g. one
+    // for a long export and one for UI queries. merge() is refused on the view; a merge
+    // on the original afterwards leaves the view on the frames it was created with.
+    // free_memory() on a view releases only the view's own handles; free_memory() on the
+    // original releases its views too. The stores are reference counted without locking:
+    // a view stays on the thread (worker) that created it and cannot be transferred to
+    // another one.
+    #[wasm_bindgen(js_name = clone_view)]
+    pub fn clone_view(&self) -> Result<BlfSession, JsValue> {
+        self.check_alive()?;
+        Ok(BlfSession {
+            frames: Rc::clone(&self.frames),
+            signal_names: self.signal_names.clone(),
+            decoder: Rc::clone(&self.decoder),
+            pyramids: Rc::clone(&self.pyramids),
+            lazy: self.lazy,
+            pinned: Rc::clone(&self.pinned),
+            config: self.config.clone(),
+            read_only: true,
+            freed: Rc::default(),
+            origins: self.lineage(),
+            generation: self.generation,
+            timings: timing::Slot::default(),
+        })
+    }
+
+    // ---------------------------
+    // 2.30 is_freed() / generation()
+    // ---------------------------
+    // Host-side checks before (re)using a handle. generation() changes whenever the
+    // frames change (merge, free_memory), so results fetched under an older
+    // generation, e.g. across an await, can be recognized as stale.
+    #[wasm_bindgen(js_name = is_freed)]
+    pub fn is_freed(&self) -> bool {
+        self.check_alive().is_err()
+    }
+
+    #[wasm_bindgen(js_name = generation)]
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    // ---------------------------
+    // 2.31 decode_hex()
+    // ---------------------------
+    // One frame pasted as text, e.g. decode_hex(1, "18FEF100x", "01 02 FF ..."), decoded
+    // like a logged frame. The id is hex ("0x1A0", "1A0"); it is extended when above 0x7FF
+    // or written with a trailing "x". Bytes may be separated by spaces, ',', '-' or ':'.
+    #[wasm_bindgen(js_name = decode_hex)]
+    pub fn decode_hex(&self, channel: u16, id_hex: &str, data_hex: &str) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let id = parse_hex_id(id_hex).map_err(|e| JsValue::from_str(&e))?;
+        let data = parse_hex_bytes(data_hex).map_err(|e| JsValue::from_str(&e))?;
+        let fd = data.len() > 8;
+        let frame = CanFrame {
+            timestamp_ns: 0,
+            channel,
+            id,
+            dlc: len_to_dlc(data.len()),
+            data,
+            tx: false,
+            tx_request: false,
+            rtr: false,
+            wakeup: false,
+            nerr: false,
+            fd,
+            brs: false,
+            esi: false,
+        };
+        let row = frame_from_obj(&BlfObject::Can(frame), &self.decoder, None, true);
+        serde_wasm_bindgen::to_value(&row)
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.32 suggest_channel_mapping()
+    // ---------------------------
+    // For DBC texts not yet mapped: the share of each logged CAN channel's frames (first
+    // 10k per channel) whose id the DBC defines. channel_map holds the best channel per
+    // DBC (null without any hit), ready for the constructor; candidates lists every pair.
+    #[wasm_bindgen(js_name = suggest_channel_mapping)]
+    pub fn suggest_channel_mapping(&self, dbc_texts: JsValue) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let texts: Vec<String> = serde_wasm_bindgen::from_value(dbc_texts)
+            .map_err(|e| JsValue::from_str(&format!("dbc_texts must be array of strings: {:?}", e)))?;
+        let suggestion = self.mapping_suggestion(&texts).map_err(|e| JsValue::from_str(&e))?;
+        serde_wasm_bindgen::to_value(&suggestion)
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.33 transport_messages()
+    // ---------------------------
+    // ISO-TP (ISO 15765-2) payloads reassembled on options.pairs (isotp.rs), e.g.
+    // {pairs: [{request_id: 0x7E0, response_id: 0x7E8}]}: {messages, flow_control_frames}.
+    // Broken transfers are kept with complete = false and the reason in error.
+    #[wasm_bindgen(js_name = transport_messages)]
+    pub fn transport_messages(&self, options: JsValue) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let (messages, flow_control_frames) = self.iso_tp(options)?;
+        serde_wasm_bindgen::to_value(&json!({
+            "messages": messages,
+            "flow_control_frames": flow_control_frames,
+        }))
+        .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.34 coverage()
+    // ---------------------------
+    // Per CAN channel: share of frames matched to a DBC message and share of payload
+    // bits covered by DBC signals, as a quick check of the configured databases.
+    #[wasm_bindgen(js_name = coverage)]
+    pub fn coverage(&self) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        serde_wasm_bindgen::to_value(&self.coverage_report())
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.35 diagnostics()
+    // ---------------------------
+    // UDS transactions on the ISO-TP pairs of options (same options as transport_messages()):
+    // service, sub-function, DIDs, NRC and response time per request (uds.rs).
+    #[wasm_bindgen(js_name = diagnostics)]
+    pub fn diagnostics(&self, options: JsValue) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let (messages, _) = self.iso_tp(options)?;
+        serde_wasm_bindgen::to_value(&uds::transactions(&messages))
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.36 signal_stats()
+    // ---------------------------
+    // min/max/mean/stddev per sample, first/last sample time and mean sample rate, plus
+    // time-weighted mean and, with options.threshold, duty cycle (share of time above it).
+    // options.t0/t1 (s) limit the range. `signal` may be an array of names: one decode
+    // pass, Map<name, stats> in that order, null for signals without samples.
+    #[wasm_bindgen(js_name = signal_stats)]
+    pub fn signal_stats(&self, signal: JsValue, options: JsValue) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let opts: SignalStatsOptions = parse_options(options, "signal stats options")?;
+        let stats_of = |(t, v): &Series| {
+            let stats = signals::signal_stats(t, v, opts.t0, opts.t1, opts.threshold).map_err(|e| JsValue::from_str(&e))?;
+            serde_wasm_bindgen::to_value(&stats).map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+        };
+        if let Some(name) = signal.as_string() {
+            return stats_of(&self.series(&name)?);
+        }
+        let names: Vec<String> = serde_wasm_bindgen::from_value(signal)
+            .map_err(|e| JsValue::from_str(&format!("signal must be a name or an array of names: {:?}", e)))?;
+        let series = self.collect_series(&names);
+        let out = js_sys::Map::new();
+        for name in &names {
+            let entry = match &series[name] {
+                (t, _) if t.is_empty() => JsValue::NULL,
+                s => stats_of(s)?,
+            };
+            out.set(&JsValue::from_str(name), &entry);
+        }
+        Ok(out.into())
+    }
+
+    // ---------------------------
+    // 2.37 rolling()
+    // ---------------------------
+    // Moving "mean" / "min" / "max" / "stddev" over the trailing window_ms at every
+    // sample of the signal: {time: Float64Array, values: Float64Array}.
+    #[wasm_bindgen(js_name = rolling)]
+    pub fn rolling(&self, signal: &str, window_ms: f64, stat: &str) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let stat = signals::RollingStat::parse(stat).map_err(|e| JsValue::from_str(&e))?;
+        let (t, v) = self.series(signal)?;
+        let values = signals::rolling(&t, &v, window_ms / 1000.0, stat).map_err(|e| JsValue::from_str(&e))?;
+        let out = js_sys::Object::new();
+        set_entry(&out, "time", &Float64Array::from(t.as_slice()))?;
+        set_entry(&out, "values", &Float64Array::from(values.as_slice()))?;
+        Ok(out.into())
+    }
+
+    // ---------------------------
+    // 2.38 create_streaming()
+    // ---------------------------
+    // Same inputs as the constructor minus the BLF bytes; feed the file with
+    // append_chunk() and get the session from finalize().
+    #[wasm_bindgen(js_name = create_streaming)]
+    pub fn create_streaming(dbc_texts: JsValue, channel_map: JsValue, options: JsValue) -> Result<BlfSessionBuilder, JsValue> {
+        let opts: SessionOptions = parse_options(options, "session options")?;
+        let decoder = Decoder::from_js(dbc_texts, channel_map, &opts)?;
+        Ok(BlfSessionBuilder {
+            build: Some(SessionBuild::new(decoder, opts)),
+            stream: BlfStream::default(),
+            hash: index::Fnv::new(),
+            bytes: 0,
+            timings: timing::Slot::default(),
+        })
+    }
+
+    // ---------------------------
+    // 2.39 derating_report()
+    // ---------------------------
+    // Thermal/derating report template: options.temperatures / currents are
+    // {signal, threshold} and options.derate_flags signal names. Signals without
+    // samples are errors so a mistyped name does not yield an empty report.
+    #[wasm_bindgen(js_name = derating_report)]
+    pub fn derating_report(&self, options: JsValue) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let opts: DerateOptions = parse_options(options, "derating options")?;
+        let flags: Vec<ThresholdSignal> =
+            opts.derate_flags.iter().map(|s| ThresholdSignal { signal: s.clone(), threshold: 0.5 }).collect();
+        let groups = [&opts.temperatures, &opts.currents, &flags];
+        let mut series: HashMap<&str, Series> = HashMap::new();
+        for s in groups.iter().flat_map(|g| g.iter()) {
+            if !series.contains_key(s.signal.as_str()) {
+                series.insert(&s.signal, self.series(&s.signal)?);
+            }
+        }
+        let [temperatures, currents, flags] = groups.map(|group| {
+            group
+                .iter()
+                .map(|s| {
+                    let (t, v) = &series[s.signal.as_str()];
+                    report::Tracked {
+                        signal: &s.signal,
+                        unit: self.decoder.signal_meta.get(&s.signal).map_or("", |m| m.unit.as_str()),
+                        threshold: s.threshold,
+                        times: t,
+                        values: v,
+                    }
+                })
+                .collect::<Vec<_>>()
+        });
+        let out = report::derating(&temperatures, &currents, &flags, opts.min_event_ms / 1000.0);
+        serde_wasm_bindgen::to_value(&out)
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.40 cell_spread()
+    // ---------------------------
+    // Cell voltage spread: {cells, time, min, max, delta (Float64Array), min_cell,
+    // max_cell (Uint32Array, index into cells)}; with options.threshold also
+    // imbalance (time above it, events, largest delta).
+    #[wasm_bindgen(js_name = cell_spread)]
+    pub fn cell_spread(&self, options: JsValue) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let opts: CellSpreadOptions = parse_options(options, "cell spread options")?;
+        let (names, series) = match &opts.prefix {
+            Some(prefix) if opts.cells.is_empty() => {
+                let matching: Vec<String> = self.signal_names.iter().filter(|n| n.starts_with(prefix.as_str())).cloned().collect();
+                let mut series = self.collect_series(&matching);
+                series.retain(|_, (t, _)| !t.is_empty());
+                let rank = self.decoder.signal_rank(&matching, SignalOrder::Message);
+                let ranked = ranked(series, &rank);
+                (ranked.iter().map(|(n, _)| n.clone()).collect::<Vec<_>>(), ranked.into_iter().map(|(_, s)| s).collect::<Vec<_>>())
+            }
+            _ => {
+                let series = opts.cells.iter().map(|n| self.series(n)).collect::<Result<Vec<_>, _>>()?;
+                (opts.cells.clone(), series)
+            }
+        };
+        if names.is_empty() {
+            return Err(JsValue::from_str("cell_spread needs options.cells or a prefix matching sampled signals"));
+        }
+        let refs: Vec<(&[f64], &[f64])> = series.iter().map(|(t, v)| (t.as_slice(), v.as_slice())).collect();
+        let spread = signals::cell_spread(&refs);
+
+        let out = js_sys::Object::new();
+        let names_js = serde_wasm_bindgen::to_value(&names)
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))?;
+        set_entry(&out, "cells", &names_js)?;
+        set_entry(&out, "time", &Float64Array::from(spread.time.as_slice()))?;
+        set_entry(&out, "min", &Float64Array::from(spread.min.as_slice()))?;
+        set_entry(&out, "max", &Float64Array::from(spread.max.as_slice()))?;
+        set_entry(&out, "delta", &Float64Array::from(spread.delta.as_slice()))?;
+        set_entry(&out, "min_cell", &Uint32Array::from(spread.min_cell.as_slice()))?;
+        set_entry(&out, "max_cell", &Uint32Array::from(spread.max_cell.as_slice()))?;
+        if let (Some(threshold), false) = (opts.threshold, spread.time.is_empty()) {
+            let delta = report::Tracked {
+                signal: "delta",
+                unit: self.decoder.signal_meta.get(&names[0]).map_or("", |m| m.unit.as_str()),
+                threshold,
+                times: &spread.time,
+                values: &spread.delta,
+            };
+            let imbalance = serde_wasm_bindgen::to_value(&report::summarize(&delta, opts.min_event_ms / 1000.0))
+                .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))?;
+            set_entry(&out, "imbalance", &imbalance)?;
+        }
+        Ok(out.into())
+    }
+
+    // ---------------------------
+    // 2.41 charging_sessions()
+    // ---------------------------
+    // Charging sessions from options.plug_signal and / or current_signal, with
+    // energy and power when voltage_signal is given: {sessions, total_duration_s,
+    // total_energy_kwh}.
+    #[wasm_bindgen(js_name = charging_sessions)]
+    pub fn charging_sessions(&self, options: JsValue) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let opts: ChargingOptions = parse_options(options, "charging options")?;
+        if opts.plug_signal.is_none() && opts.current_signal.is_none() {
+            return Err(JsValue::from_str("charging_sessions needs options.plug_signal or options.current_signal"));
+        }
+        let series = |name: &Option<String>| name.as_deref().map(|n| self.series(n)).transpose();
+        let [plug, current, voltage, soc] = [
+            series(&opts.plug_signal)?,
+            series(&opts.current_signal)?,
+            series(&opts.voltage_signal)?,
+            series(&opts.soc_signal)?,
+        ];
+        fn samples(s: &Option<Series>) -> Option<(&[f64], &[f64])> {
+            s.as_ref().map(|(t, v)| (t.as_slice(), v.as_slice()))
+        }
+        let params = report::ChargingParams {
+            current_threshold: opts.current_threshold,
+            invert_current: opts.invert_current,
+            min_duration_s: opts.min_duration_ms / 1000.0,
+            max_gap_s: opts.max_gap_ms / 1000.0,
+        };
+        let out = report::charging(samples(&plug), samples(&current), samples(&voltage), samples(&soc), &params);
+        serde_wasm_bindgen::to_value(&out)
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.42 slice()
+    // ---------------------------
+    // Read-only session over start_s <= timestamp <= end_s (seconds, as FrameRow.timestamp).
+    // Frames are found by binary search, so it assumes time order (merged sessions are
+    // sorted; a BLF with out-of-order objects may lose frames at the edges).
+    #[wasm_bindgen(js_name = slice)]
+    pub fn slice(&self, start_s: f64, end_s: f64) -> Result<BlfSession, JsValue> {
+        self.check_alive()?;
+        if start_s.is_nan() || end_s.is_nan() || start_s > end_s {
+            return Err(JsValue::from_str(&format!("invalid time window {}..{}", start_s, end_s)));
+        }
+        let cut = |(t, v): &Series| {
+            let r = t.partition_point(|x| *x < start_s)..t.partition_point(|x| *x <= end_s);
+            let r = r.start..r.end.max(r.start);
+            (t[r.clone()].to_vec(), v[r].to_vec())
+        };
+        let mut view = BlfSession {
+            frames: Rc::new(self.frames.sliced(self.frames.window(start_s, end_s))),
+            signal_names: self.signal_names.clone(),
+            decoder: Rc::clone(&self.decoder),
+            pyramids: Rc::default(),
+            lazy: self.lazy,
+            pinned: Rc::new(self.pinned.iter().map(|(n, s)| (n.clone(), cut(s))).collect()),
+            config: self.config.clone(),
+            read_only: true,
+            freed: Rc::default(),
+            origins: self.lineage(),
+            generation: self.generation,
+            timings: timing::Slot::default(),
+        };
+        if self.frames.is_empty() {
+            // from_pyramid_cache(): no frames to rebuild from; pyramid_query() takes the window
+            view.pyramids = Rc::clone(&self.pyramids);
+            return Ok(view);
+        }
+        let pyramid_names: Vec<String> = self.pyramids.keys().cloned().collect();
+        view.pyramids = Rc::new(
+            view.collect_series(&pyramid_names)
+                .into_iter()
+                .map(|(name, (t, v))| (name, Pyramid::build(&t, &v)))
+                .collect(),
+        );
+        Ok(view)
+    }
+
+    // ---------------------------
+    // 2.43 get_signal()
+    // ---------------------------
+    // Actual samples of one signal, no fill and no decimation:
+    // {time: Float64Array, values: Float64Array, unit}
+    #[wasm_bindgen(js_name = get_signal)]
+    pub fn get_signal(&self, name: &str) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let _timed = timing::start("get_signal", &self.timings);
+        let (t, v) = self.series(name)?;
+        timing::span(Phase::Serialize, || {
+            let out = js_sys::Object::new();
+            set_entry(&out, "time", &Float64Array::from(t.as_slice()))?;
+            set_entry(&out, "values", &Float64Array::from(v.as_slice()))?;
+            let unit = self.decoder.signal_meta.get(name).map_or("", |m| m.unit.as_str());
+            set_entry(&out, "unit", &JsValue::from_str(unit))?;
+            Ok(out.into())
+        })
+    }
+
+    // ---------------------------
+    // 2.44 export_patch()
+    // ---------------------------
+    // The DBC patch in effect (options.patch plus options.signal_overrides) as JSON text,
+    // to save and pass back as options.patch in later sessions
+    #[wasm_bindgen(js_name = export_patch)]
+    pub fn export_patch(&self) -> Result<String, JsValue> {
+        self.check_alive()?;
+        let opts = &self.config.options;
+        let patch = opts.patch.clone().unwrap_or_default().merged(&opts.signal_overrides);
+        serde_json::to_string_pretty(&patch).map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.45 signal_collisions()
+    // ---------------------------
+    // Signal names defined by more than one message on a channel, e.g. two DBCs on CAN1
+    // both carrying "Speed" in different messages. With the default
+    // options.name_collisions their values land in one "CAN1.Speed"; with "qualify"
+    // each message's values get their own "CAN1.{Message}.Speed".
+    #[wasm_bindgen(js_name = signal_collisions)]
+    pub fn signal_collisions(&self) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        serde_wasm_bindgen::to_value(&self.decoder.collisions)
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.46 resample()
+    // ---------------------------
+    // Signals on one regular time grid at rate_hz, from the earliest first sample to the
+    // latest last sample of the selection (null: all signals). mode: "hold" (alias
+    // "previous"), "linear" or "nearest" for all, or {name: mode} per signal (unlisted:
+    // hold). Returns {time: Float64Array, signals: Map<name, Float64Array>}, NaN where a
+    // signal has no value yet (see signals.rs).
+    #[wasm_bindgen(js_name = resample)]
+    pub fn resample(&self, rate_hz: f64, keep_signals: JsValue, mode: JsValue) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let names: Vec<String> = if keep_signals.is_null() || keep_signals.is_undefined() {
+            self.signal_names.clone()
+        } else {
+            serde_wasm_bindgen::from_value(keep_signals)
+                .map_err(|e| JsValue::from_str(&format!("keep_signals must be an array of strings: {:?}", e)))?
+        };
+        let mode: ResampleMode = parse_options(mode, "resample mode")?;
+        let _timed = timing::start("resample", &self.timings);
+        let (grid, columns) = self.resampled(&names, rate_hz, &mode)?;
+
+        timing::span(Phase::Serialize, || {
+            let out = js_sys::Map::new();
+            for (name, values) in names.iter().zip(columns) {
+                out.set(&JsValue::from_str(name), &Float64Array::from(values.as_slice()));
+            }
+            let result = js_sys::Object::new();
+            set_entry(&result, "time", &Float64Array::from(grid.as_slice()))?;
+            set_entry(&result, "signals", &out)?;
+            Ok(result.into())
+        })
+    }
+
+    // ---------------------------
+    // 2.47 message_timing()
+    // ---------------------------
+    // Observed cycle time per (channel, id): mean/min/max period and jitter (stddev), set
+    // against the DBC GenMsgCycleTime with violating messages flagged. options:
+    // tolerance_pct (default 20) per period, max_violation_pct (default 1) of periods.
+    #[wasm_bindgen(js_name = message_timing)]
+    pub fn message_timing(&self, options: JsValue) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let opts: TimingOptions = parse_options(options, "timing options")?;
+        let limits = analysis::TimingLimits {
+            tolerance: opts.tolerance_pct / 100.0,
+            max_violation: opts.max_violation_pct / 100.0,
+        };
+        serde_wasm_bindgen::to_value(&analysis::message_timing(&self.frames, |f| self.decoder.cycle_ms(f), &limits))
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.48 bus_load()
+    // ---------------------------
+    // Bus utilization per CAN channel over time buckets of bucket_ms, from each frame's
+    // length on the wire at options.bitrate / data_bitrate (per channel via
+    // options.channels). Returns {t0, bucket_ms, time: Float64Array (bucket starts),
+    // channels: [{channel, frames, mean_pct, peak_pct, ...}], load: Map<channel, Float64Array %>}.
+    #[wasm_bindgen(js_name = bus_load)]
+    pub fn bus_load(&self, bucket_ms: f64, options: JsValue) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let opts: BusLoadOptions = parse_options(options, "bus load options")?;
+        let positive = |r: f64| r > 0.0 && r.is_finite();
+        let given = opts.channels.values().flat_map(|c| c.bitrate.into_iter().chain(c.data_bitrate));
+        if let Some(r) = [opts.bitrate, opts.data_bitrate].into_iter().chain(given).find(|r| !positive(*r)) {
+            return Err(JsValue::from_str(&format!("bitrates must be > 0 bit/s (got {})", r)));
+        }
+        let mut per_channel: HashMap<u16, &ChannelBitrate> = HashMap::new();
+        for (name, rates) in &opts.channels {
+            match parse_channel(name) {
+                Ok((Bus::Can, num)) => per_channel.insert(num, rates),
+                _ => return Err(JsValue::from_str(&format!("options.channels: \"{}\" is not a CAN channel (CAN1, CAN2, ...)", name))),
+            };
+        }
+        let rates = |channel: u16| {
+            let own = per_channel.get(&channel);
+            analysis::Bitrates {
+                nominal: own.and_then(|r| r.bitrate).unwrap_or(opts.bitrate),
+                data: own.and_then(|r| r.data_bitrate).unwrap_or(opts.data_bitrate),
+            }
+        };
+        let load = analysis::bus_load(&self.frames, bucket_ms / 1000.0, rates, opts.stuffing, 50_000_000)
+            .map_err(|e| JsValue::from_str(&e))?;
+
+        let out = js_sys::Object::new();
+        set_entry(&out, "t0", &JsValue::from_f64(load.t0))?;
+        set_entry(&out, "bucket_ms", &JsValue::from_f64(bucket_ms))?;
+        let time: Vec<f64> = (0..load.buckets).map(|b| load.t0 + b as f64 * bucket_ms / 1000.0).collect();
+        set_entry(&out, "time", &Float64Array::from(time.as_slice()))?;
+        let series = js_sys::Map::new();
+        for c in &load.channels {
+            series.set(&JsValue::from_str(&c.channel), &Float64Array::from(c.load.as_slice()));
+        }
+        let channels = serde_wasm_bindgen::to_value(&load.channels)
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))?;
+        set_entry(&out, "channels", &channels)?;
+        set_entry(&out, "load", &series)?;
+        Ok(out.into())
+    }
+
+    // ---------------------------
+    // 2.49 diff_windows()
+    // ---------------------------
+    // Two time windows (s) of this log side by side, e.g. before and after a software
+    // reset: per message presence, frame counts and rates (analysis.rs), and per signal
+    // the last value in each window.
+    #[wasm_bindgen(js_name = diff_windows)]
+    pub fn diff_windows(&self, t0a: f64, t1a: f64, t0b: f64, t1b: f64) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        for (name, t0, t1) in [("a", t0a, t1a), ("b", t0b, t1b)] {
+            if t1 < t0 || t0.is_nan() || t1.is_nan() {
+                return Err(JsValue::from_str(&format!("window {}: t1 must be >= t0", name)));
+            }
+        }
+        serde_wasm_bindgen::to_value(&self.window_diff((t0a, t1a), (t0b, t1b)))
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.50 detect_timeouts()
+    // ---------------------------
+    // Periods where a periodic message stopped for more than max_gap_factor x its
+    // period (DBC GenMsgCycleTime, else learned from the log), e.g. ECU resets in long
+    // logs; messages that never come back report a gap up to the end of the log.
+    #[wasm_bindgen(js_name = detect_timeouts)]
+    pub fn detect_timeouts(&self, max_gap_factor: f64) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let timeouts = analysis::detect_timeouts(&self.frames, |f| self.decoder.cycle_ms(f), max_gap_factor).map_err(|e| JsValue::from_str(&e))?;
+        serde_wasm_bindgen::to_value(&timeouts)
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.51 message_stats()
+    // ---------------------------
+    // Per (channel, id): frames, first/last time and rate. Messages with an alive counter
+    // add {signal, bits, lost_frames, repeats, loss_pct} from counter skips. Counters are
+    // found by name (Alive, Counter, Cnt, ...: plain unsigned 2..8 bit signals) unless
+    // options.counter_signals lists the DBC signal names to use.
+    #[wasm_bindgen(js_name = message_stats)]
+    pub fn message_stats(&self, options: JsValue) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let opts: MessageStatsOptions = parse_options(options, "message stats options")?;
+        // counter signal per (channel, id, length), looked up once
+        let mut found: HashMap<(u16, u32, usize), Option<&Signal>> = HashMap::new();
+        let counter = |f: &Frame| {
+            let sig = *found.entry((f.channel_num, f.raw_id(), f.data.len())).or_insert_with(|| {
+                let msg = self.decoder.message(f.channel_num, f.raw_id(), f.data.len())?;
+                alive_counter(msg, &opts.counter_signals)
+            });
+            let sig = sig?;
+            Some((sig.name().as_str(), *sig.signal_size() as u32, signal_raw(sig, f.data)?))
+        };
+        serde_wasm_bindgen::to_value(&analysis::message_stats(&self.frames, counter))
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.52 export_mf4()
+    // ---------------------------
+    // ASAM MDF 4.10 bytes (mdf.rs): one channel group per decoded message named
+    // "{channel}.{Message}", holding the raw values of the signals seen in it with the DBC
+    // factor/offset (and SI normalization), unit and value table as conversions.
+    // options.signals limits the export to those signals.
+    #[wasm_bindgen(js_name = export_mf4)]
+    pub fn export_mf4(&self, options: JsValue) -> Result<Vec<u8>, JsValue> {
+        self.check_alive()?;
+        let _timed = timing::start("export_mf4", &self.timings);
+        let opts: Mf4Options = parse_options(options, "mf4 options")?;
+        let only: HashSet<String> = opts.signals.iter().cloned().collect();
+        let mut cache = DecodeCache::new((!only.is_empty()).then_some(&only));
+
+        // first pass: the signals of each message in first-seen order
+        let mut groups: Vec<(String, Vec<(String, String)>)> = Vec::new();
+        let mut index: HashMap<(u16, u32), (usize, HashMap<String, usize>)> = HashMap::new();
+        for f in self.frames.iter() {
+            let rows = self.frame_signals(&f, &mut cache);
+            let rows: Vec<&SignalRow> = rows.iter().filter(|r| only.is_empty() || only.contains(&r.signal)).collect();
+            if rows.is_empty() {
+                continue;
+            }
+            let (g, columns) = index.entry((f.channel_num, f.raw_id())).or_insert_with(|| {
+                let name = if f.name.is_empty() { format!("{}.0x{:X}", f.channel, f.id) } else { format!("{}.{}", f.channel, f.name) };
+                groups.push((name, Vec::new()));
+                (groups.len() - 1, HashMap::new())
+            });
+            for r in rows {
+                if !columns.contains_key(&r.signal) {
+                    columns.insert(r.signal.clone(), columns.len());
+                    groups[*g].1.push((r.signal.clone(), r.unit.clone()));
+                }
+            }
+        }
+
+        let mut out: Vec<mdf::Group> = groups
+            .into_iter()
+            .map(|(name, signals)| {
+                let channels = signals.into_iter().map(|(s, unit)| self.mdf_channel(s, unit)).collect();
+                mdf::Group::new(name, channels)
+            })
+            .collect();
+
+        // second pass: one record per frame
+        for f in self.frames.iter() {
+            let Some((g, columns)) = index.get(&(f.channel_num, f.raw_id())) else {
+                continue;
+            };
+            let mut values = vec![None; columns.len()];
+            for r in self.frame_signals(&f, &mut cache).iter() {
+                if let Some(&i) = columns.get(&r.signal) {
+                    values[i] = Some(match self.decoder.signal_meta.get(&r.signal) {
+                        Some(meta) => meta.raw_value(r.value) as f64,
+                        None => r.value,
+                    });
+                }
+            }
+            if values.iter().any(Option::is_some) {
+                timing::sampled(Phase::Serialize, || out[*g].push(f.timestamp, &values));
+            }
+        }
+        Ok(timing::span(Phase::Serialize, || mdf::encode(&out)))
+    }
+
+    // ---------------------------
+    // 2.53 export_asc()
+    // ---------------------------
+    // Vector ASCII trace (asc.rs) of the frames kept by options.query / event_types,
+    // timestamps relative to the measurement start as in the BLF
+    #[wasm_bindgen(js_name = export_asc)]
+    pub fn export_asc(&self, options: JsValue) -> Result<Vec<u8>, JsValue> {
+        self.check_alive()?;
+        let _timed = timing::start("export_asc", &self.timings);
+        let opts: AscOptions = parse_options(options, "asc options")?;
+        let mut keep = frame_filter(&opts.event_types, &opts.query, opts.max_frames_per_id_per_second)?;
+        let start = self.config.logs.first().and_then(|l| l.start);
+        let mut out = asc::header(start.as_ref());
+        for f in self.frames.iter().filter(|f| keep(f)) {
+            timing::sampled(Phase::Serialize, || {
+                if let Some(line) = asc::line(&f) {
+                    out.push_str(&line);
+                }
+            });
+        }
+        out.push_str(asc::FOOTER);
+        Ok(out.into_bytes())
+    }
+
+    // ---------------------------
+    // 2.54 export_blf()
+    // ---------------------------
+    // BLF file of the frames kept by options.query / event_types, in log containers
+    // (zlib unless options.uncompressed). Timestamps and the header's measurement start
+    // stay those of this session, so the slice lines up with the original log. CAN FD
+    // objects carry a one-byte channel: an FD frame above CAN255 fails the export.
+    #[wasm_bindgen(js_name = export_blf)]
+    pub fn export_blf(&self, options: JsValue) -> Result<Vec<u8>, JsValue> {
+        self.check_alive()?;
+        let _timed = timing::start("export_blf", &self.timings);
+        let opts: BlfExportOptions = parse_options(options, "blf export options")?;
+        let mut keep = frame_filter(&opts.event_types, &opts.query, opts.max_frames_per_id_per_second)?;
+        let mut writer = BlfWriter::new(!opts.uncompressed);
+        let mut last = None;
+        for f in self.frames.iter().filter(|f| keep(f)) {
+            if let Some(obj) = obj_from_frame(&f) {
+                timing::sampled(Phase::Serialize, || writer.push(&obj))
+                    .map_err(|e| JsValue::from_str(&format!("export_blf: {}", e)))?;
+                last = Some(f.timestamp);
+            }
+        }
+        let clock = self.wall_clock().ok();
+        let start = self.config.logs.first().and_then(|l| l.start);
+        let stop = clock.zip(last).map(|(c, t)| c.system_time(t));
+        Ok(timing::span(Phase::Serialize, || writer.finish(start, stop)))
+    }
+
+    // ---------------------------
+    // 2.55 export_parquet()
+    // ---------------------------
+    // Parquet bytes (parquet.rs) for the signals (null: all). layout "frames": time_s,
+    // channel, id, extended, name, event_type, dir, dlc, data, then per signal its value
+    // in that frame (null where the frame does not carry it); frames filtered as in the
+    // CSV export, at most options.max_rows of them. layout "resampled": time_s and per
+    // signal its resample() values (null before the first sample).
+    #[wasm_bindgen(js_name = export_parquet)]
+    pub fn export_parquet(&self, signals: JsValue, options: JsValue) -> Result<Vec<u8>, JsValue> {
+        self.check_alive()?;
+        let names: Vec<String> = if signals.is_null() || signals.is_undefined() {
+            self.signal_names.clone()
+        } else {
+            serde_wasm_bindgen::from_value(signals)
+                .map_err(|e| JsValue::from_str(&format!("signals must be an array of strings: {:?}", e)))?
+        };
+        let opts: TableOptions = parse_options(options, "parquet options")?;
+        let _timed = timing::start("export_parquet", &self.timings);
+        let columns = self.table_columns(&names, &opts)?;
+        Ok(timing::span(Phase::Serialize, || parquet::encode(&columns)))
+    }
+
+    // ---------------------------
+    // 2.56 last_operation_timings()
+    // ---------------------------
+    // {operation, total_ms, blf_iteration_ms, dbc_matching_ms, signal_decode_ms,
+    // serialization_ms} of the last recorded call on this session (null before any):
+    // constructor / create_streaming() (summed over its chunks, DBC loading excluded),
+    // merge(), preview(), decimated(), get_signal(), resample(), to_arrow() and the
+    // export_*() methods. Per-frame phases are sampled (timing.rs); blf_iteration_ms is
+    // the rest.
+    #[wasm_bindgen(js_name = last_operation_timings)]
+    pub fn last_operation_timings(&self) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        serde_wasm_bindgen::to_value(&self.timings.get())
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // ---------------------------
+    // 2.57 to_arrow()
+    // ---------------------------
+    // Arrow IPC stream (arrow.rs) of the export_parquet() table, same signals and
+    // options: tableFromIPC(bytes) in apache-arrow, insertArrowFromIPCStream() in
+    // DuckDB-WASM. With options.max_rows it doubles as a preview that skips JSON.
+    #[wasm_bindgen(js_name = to_arrow)]
+    pub fn to_arrow(&self, signals: JsValue, options: JsValue) -> Result<Vec<u8>, JsValue> {
+        self.check_alive()?;
+        let names: Vec<String> = if signals.is_null() || signals.is_undefined() {
+            self.signal_names.clone()
+        } else {
+            serde_wasm_bindgen::from_value(signals)
+                .map_err(|e| JsValue::from_str(&format!("signals must be an array of strings: {:?}", e)))?
+        };
+        let opts: TableOptions = parse_options(options, "arrow options")?;
+        let _timed = timing::start("to_arrow", &self.timings);
+        let columns = self.table_columns(&names, &opts)?;
+        Ok(timing::span(Phase::Serialize, || arrow::encode(&columns)))
+    }
+
+    // ---------------------------
+    // 2.58 export_jsonl_stream()
+    // ---------------------------
+    // JSON Lines, one frame object (as in preview()) per line, passed to
+    // chunk_cb(bytes, frames_so_far) whenever a chunk reaches options.chunk_bytes, so
+    // the whole export never sits in memory. options.signals keeps those decoded
+    // signals only; frames are filtered as in the CSV export. Returns the frame count;
+    // a throwing callback aborts the export with its error.
+    #[wasm_bindgen(js_name = export_jsonl_stream)]
+    pub fn export_jsonl_stream(&self, chunk_cb: &Function, options: JsValue) -> Result<usize, JsValue> {
+        self.check_alive()?;
+        let opts: JsonlOptions = parse_options(options, "jsonl options")?;
+        let _timed = timing::start("export_jsonl_stream", &self.timings);
+        let chunk_bytes = if opts.chunk_bytes == 0 { 1 << 20 } else { opts.chunk_bytes };
+        let clock = if opts.absolute_time { Some(self.wall_clock()?) } else { None };
+        let only: Option<HashSet<String>> = opts.signals.as_ref().map(|s| s.iter().cloned().collect());
+        let mut cache = DecodeCache::new(only.as_ref());
+        let mut keep = frame_filter(&opts.event_types, &opts.query, opts.max_frames_per_id_per_second)?;
+
+        let flush = |buf: &mut Vec<u8>, frames: usize| -> Result<(), JsValue> {
+            chunk_cb.call2(&JsValue::NULL, &Uint8Array::from(buf.as_slice()), &JsValue::from_f64(frames as f64))?;
+            buf.clear();
+            Ok(())
+        };
+        let mut buf = Vec::with_capacity(chunk_bytes + 4096);
+        let mut frames = 0;
+        for f in self.frames.iter().filter(|f| keep(f)) {
+            let signals = self.frame_signals(&f, &mut cache);
+            let line = JsonlLine { frame: Frame { signals: Signals::Rows(&signals), ..f }, wall_clock: clock.map(|c| c.datetime(f.timestamp)) };
+            timing::sampled(Phase::Serialize, || serde_json::to_writer(&mut buf, &line))
+                .map_err(|e| JsValue::from_str(&format!("json write failed: {:?}", e)))?;
+            buf.push(b'\n');
+            frames += 1;
+            if buf.len() >= chunk_bytes {
+                flush(&mut buf, frames)?;
+            }
+        }
+        if !buf.is_empty() {
+            flush(&mut buf, frames)?;
+        }
+        Ok(frames)
+    }
+
+    // ---------------------------
+    // 2.59 export_influx()
+    // ---------------------------
+    // InfluxDB line protocol (influx.rs) for the signals (null: all): one line per frame
+    // carrying any of them, measurement = message name, tag channel, fields named by
+    // signal without the channel / message prefix. Timestamps are epoch nanoseconds
+    // from the BLF measurement start (logger clock, corrected by
+    // options.utc_offset_minutes), or since the start with options.relative_time.
+    #[wasm_bindgen(js_name = export_influx)]
+    pub fn export_influx(&self, signals: JsValue, options: JsValue) -> Result<Vec<u8>, JsValue> {
+        self.check_alive()?;
+        let names: Option<HashSet<String>> = if signals.is_null() || signals.is_undefined() {
+            None
+        } else {
+            Some(serde_wasm_bindgen::from_value(signals)
+                .map_err(|e| JsValue::from_str(&format!("signals must be an array of strings: {:?}", e)))?)
+        };
+        let opts: InfluxOptions = parse_options(options, "influx options")?;
+        let _timed = timing::start("export_influx", &self.timings);
+        let clock = if opts.relative_time { None } else { Some(self.wall_clock()?) };
+        let offset_ns = (opts.utc_offset_minutes * 60e9).round() as i64;
+        let mut keep = frame_filter(&opts.event_types, &opts.query, opts.max_frames_per_id_per_second)?;
+        let mut cache = DecodeCache::new(names.as_ref());
+
+        let mut out = String::new();
+        for f in self.frames.iter().filter(|f| keep(f)) {
+            let rows = self.frame_signals(&f, &mut cache);
+            let mut fields = Vec::new();
+            let mut labels = Vec::new();
+            for r in rows.iter().filter(|r| names.as_ref().is_none_or(|n| n.contains(&r.signal))) {
+                let short = short_signal_name(&f, &r.signal);
+                fields.push((short, influx::Field::Float(r.value)));
+                if let Some(text) = r.value_text.as_deref().filter(|_| opts.value_labels) {
+                    labels.push((format!("{}_text", short), text));
+                }
+            }
+            if fields.is_empty() {
+                continue;
+            }
+            fields.extend(labels.iter().map(|(k, v)| (k.as_str(), influx::Field::Text(v))));
+            let measurement = if f.name.is_empty() { format!("0x{:X}", f.id) } else { f.name.to_string() };
+            let ns = match clock {
+                Some(c) => c.nanos(f.timestamp) - offset_ns,
+                None => (f.timestamp * 1e9).round() as i64,
+            };
+            timing::sampled(Phase::Serialize, || {
+                if let Some(line) = influx::line(&measurement, &[("channel", f.channel)], &fields, ns) {
+                    out.push_str(&line);
+                }
+            });
+        }
+        Ok(out.into_bytes())
+    }
+
+    // ---------------------------
+    // 2.60 export_sqlite()
+    // ---------------------------
+    // A self-contained SQLite 3 database (sqlite.rs) for sql.js or desktop tools: a
+    // "frames" table (id = frame rowid, in log order) and a "signals" table with one row
+    // per decoded signal value, joined on signals.frame_id. Indexed by time on both,
+    // by can_id on frames, by frame_id and (signal, time_s) on signals.
+    #[wasm_bindgen(js_name = export_sqlite)]
+    pub fn export_sqlite(&self, options: JsValue) -> Result<Vec<u8>, JsValue> {
+        self.check_alive()?;
+        let opts: SqliteOptions = parse_options(options, "sqlite options")?;
+        let _timed = timing::start("export_sqlite", &self.timings);
+        let wanted: Option<HashSet<String>> = opts.signals.as_ref().map(|s| s.iter().cloned().collect());
+        let mut cache = DecodeCache::new(wanted.as_ref());
+        let mut keep = frame_filter(&opts.event_types, &opts.query, opts.max_frames_per_id_per_second)?;
+
+        let text = |s: &str| if s.is_empty() { sqlite::Value::Null } else { sqlite::Value::Text(s.to_string()) };
+        let mut frames = Vec::new();
+        let mut signals = Vec::new();
+        for f in self.frames.iter().filter(|f| keep(f)) {
+            frames.push(vec![
+                sqlite::Value::Null, // INTEGER PRIMARY KEY: the rowid
+                sqlite::Value::Real(f.timestamp),
+                text(f.channel),
+                sqlite::Value::Int(f.id as i64),
+                sqlite::Value::Int(f.is_extended as i64),
+                text(f.name),
+                sqlite::Value::Text(f.event_type.label().to_string()),
+                text(f.dir),
+                sqlite::Value::Int(f.dlc as i64),
+                sqlite::Value::Blob(f.data.to_vec()),
+            ]);
+            let frame_id = frames.len() as i64;
+            for r in self.frame_signals(&f, &mut cache).iter() {
+                if wanted.as_ref().is_some_and(|w| !w.contains(&r.signal)) {
+                    continue;
+                }
+                signals.push(vec![
+                    sqlite::Value::Int(frame_id),
+                    sqlite::Value::Real(f.timestamp),
+                    sqlite::Value::Text(r.signal.clone()),
+                    sqlite::Value::Real(r.value),
+                    text(&r.unit),
+                    r.value_text.clone().map_or(sqlite::Value::Null, sqlite::Value::Text),
+                ]);
+            }
+        }
+
+        let tables = [
+            sqlite::Table {
+                name: "frames",
+                sql: "CREATE TABLE frames(id INTEGER PRIMARY KEY, time_s REAL NOT NULL, channel TEXT, can_id INTEGER, \
+                      extended INTEGER, name TEXT, event_type TEXT, dir TEXT, dlc INTEGER, data BLOB)"
+                    .to_string(),
+                rows: frames,
+                indexes: vec![
+                    sqlite::Index { name: "frames_time", columns: vec![1] },
+                    sqlite::Index { name: "frames_can_id", columns: vec![3] },
+                ],
+            },
+            sqlite::Table {
+                name: "signals",
+                sql: "CREATE TABLE signals(frame_id INTEGER NOT NULL REFERENCES frames(id), time_s REAL NOT NULL, \
+                      signal TEXT NOT NULL, value REAL, unit TEXT, value_text TEXT)"
+                    .to_string(),
+                rows: signals,
+                indexes: vec![
+                    sqlite::Index { name: "signals_time", columns: vec![1] },
+                    sqlite::Index { name: "signals_frame_id", columns: vec![0] },
+                    sqlite::Index { name: "signals_signal", columns: vec![2, 1] },
+                ],
+            },
+        ];
+        Ok(timing::span(Phase::Serialize, || sqlite::encode(&tables)))
+    }
+
+    // ---------------------------
+    // 2.61 export_xlsx()
+    // ---------------------------
+    // Excel workbook (xlsx.rs) of the signals (null: all): one sheet per message with
+    // a row per frame, "Time [s]" and "Channel" columns, then one numeric column per
+    // signal headed "Signal [unit]" (layout "messages"), or a single sheet with a row per
+    // frame carrying any of them and a column per signal (layout "pivot"). Empty cells
+    // where a frame lacks the signal. Err past Excel's 1,048,576 rows or 16,384 columns
+    // per sheet: narrow it down with options.query / max_frames_per_id_per_second.
+    #[wasm_bindgen(js_name = export_xlsx)]
+    pub fn export_xlsx(&self, signals: JsValue, options: JsValue) -> Result<Vec<u8>, JsValue> {
+        self.check_alive()?;
+        let names: Option<HashSet<String>> = if signals.is_null() || signals.is_undefined() {
+            None
+        } else {
+            Some(serde_wasm_bindgen::from_value(signals)
+                .map_err(|e| JsValue::from_str(&format!("signals must be an array of strings: {:?}", e)))?)
+        };
+        let opts: XlsxOptions = parse_options(options, "xlsx options")?;
+        let _timed = timing::start("export_xlsx", &self.timings);
+        let pivot = opts.layout == XlsxLayout::Pivot;
+        let mut keep = frame_filter(&opts.event_types, &opts.query, opts.max_frames_per_id_per_second)?;
+        let mut cache = DecodeCache::new(names.as_ref());
+
+        // per sheet: the sheet and its signal columns by name
+        let mut sheets: Vec<(xlsx::Sheet, HashMap<String, usize>)> = Vec::new();
+        let mut sheet_of: HashMap<String, usize> = HashMap::new();
+        let mut taken = HashSet::new();
+        for f in self.frames.iter().filter(|f| keep(f)) {
+            let rows = self.frame_signals(&f, &mut cache);
+            let wanted: Vec<&SignalRow> = rows.iter().filter(|r| names.as_ref().is_none_or(|n| n.contains(&r.signal))).collect();
+            if wanted.is_empty() {
+                continue;
+            }
+            let message = if f.name.is_empty() { format!("0x{:X}", f.id) } else { f.name.to_string() };
+            let key = if pivot { String::new() } else { message.clone() };
+            let s = *sheet_of.entry(key).or_insert_with(|| {
+                let mut header = vec!["Time [s]".to_string(), "Channel".to_string()];
+                if pivot {
+                    header.push("Message".to_string());
+                }
+                let name = xlsx::sheet_name(if pivot { "Signals" } else { &message }, &mut taken);
+                sheets.push((xlsx::Sheet { name, header, rows: Vec::new() }, HashMap::new()));
+                sheets.len() - 1
+            });
+            let (sheet, columns) = &mut sheets[s];
+            if sheet.rows.len() + 1 >= xlsx::MAX_ROWS {
+                return Err(JsValue::from_str(&format!("sheet \"{}\" exceeds Excel's {} rows", sheet.name, xlsx::MAX_ROWS)));
+            }
+            let mut row = vec![xlsx::Cell::Number(f.timestamp), xlsx::Cell::Text(f.channel.to_string())];
+            if pivot {
+                row.push(xlsx::Cell::Text(message));
+            }
+            for r in wanted {
+                // the message's sheet: same-named signals of other channels share a column
+                let name = if pivot { r.signal.as_str() } else { short_signal_name(&f, &r.signal) };
+                let col = match columns.get(name) {
+                    Some(&c) => c,
+                    None => {
+                        let c = sheet.header.len();
+                        if c >= xlsx::MAX_COLUMNS {
+                            return Err(JsValue::from_str(&format!("sheet \"{}\" exceeds Excel's {} columns", sheet.name, xlsx::MAX_COLUMNS)));
+                        }
+                        sheet.header.push(if r.unit.is_empty() { name.to_string() } else { format!("{} [{}]", name, r.unit) });
+                        columns.insert(name.to_string(), c);
+                        c
+                    }
+                };
+                if row.len() <= col {
+                    row.resize_with(col + 1, || xlsx::Cell::Empty);
+                }
+                row[col] = xlsx::Cell::Number(r.value);
+            }
+            sheet.rows.push(row);
+        }
+
+        let mut sheets: Vec<xlsx::Sheet> = sheets.into_iter().map(|(sheet, _)| sheet).collect();
+        if sheets.is_empty() {
+            // a workbook needs a sheet
+            let header = vec!["Time [s]".to_string(), "Channel".to_string()];
+            sheets.push(xlsx::Sheet { name: "Signals".to_string(), header, rows: Vec::new() });
+        }
+        timing::span(Phase::Serialize, || xlsx::workbook(&sheets)).map_err(|e| JsValue::from_str(&e))
+    }
+
+    // ---------------------------
+    // 2.62 export_npz()
+    // ---------------------------
+    // NumPy .npz archive (npy.rs) of the signals (null: all) on one resample() grid:
+    // a "time" float64 array plus one float64 array per signal, keyed by its name ('/'
+    // -> '_'), NaN before a signal's first sample. np.load(path)["time"] in Python, no
+    // CSV parsing; options.compressed deflates the members as np.savez_compressed().
+    #[wasm_bindgen(js_name = export_npz)]
+    pub fn export_npz(&self, signals: JsValue, options: JsValue) -> Result<Vec<u8>, JsValue> {
+        self.check_alive()?;
+        let names: Vec<String> = if signals.is_null() || signals.is_undefined() {
+            self.signal_names.clone()
+        } else {
+            serde_wasm_bindgen::from_value(signals)
+                .map_err(|e| JsValue::from_str(&format!("signals must be an array of strings: {:?}", e)))?
+        };
+        let opts: NpzOptions = parse_options(options, "npz options")?;
+        let _timed = timing::start("export_npz", &self.timings);
+        let (grid, columns) = self.resampled(&names, opts.rate_hz, &opts.mode)?;
+
+        let mut keys = HashSet::from(["time".to_string()]);
+        let mut arrays = vec![("time".to_string(), grid.as_slice())];
+        for (name, values) in names.iter().zip(&columns) {
+            let key = npy::key(name);
+            if !keys.insert(key.clone()) {
+                return Err(JsValue::from_str(&format!("signal {} would overwrite the array \"{}\" in the archive", name, key)));
+            }
+            arrays.push((key, values.as_slice()));
+        }
+        timing::span(Phase::Serialize, || npy::npz(&arrays, opts.compressed)).map_err(|e| JsValue::from_str(&e))
+    }
+
+    // ---------------------------
+    // 2.63 events()
+    // ---------------------------
+    // Bus events in time order, as preview() rows: error and remote frames by default,
+    // or the given options.event_types; options.query narrows them (e.g. to a time window)
+    // and options.limit caps the count.
+    #[wasm_bindgen(js_name = events)]
+    pub fn events(&self, options: JsValue) -> Result<JsValue, JsValue> {
+        self.check_alive()?;
+        let opts: EventOptions = parse_options(options, "event options")?;
+        let _timed = timing::start("events", &self.timings);
+        self.frames_to_js(self.event_frames(&opts)?.into_iter())
+    }
+}
+
+// -------------------------------
+// SECTION 2a: Session construction - shared by the constructor and BlfSessionBuilder
+// -------------------------------
+struct SessionBuild {
+    decoder: Decoder,
+    opts: SessionOptions,
+    lazy: bool, // lazy_signals, or pinned signals: only those are decoded up front
+    pinned_set: HashSet<String>,
+    pinned: HashMap<String, Series>,
+    frames: FrameStore,
+    seen_signals: Vec<String>,
+    decoded: usize,
+    transport: Option<j1939::Transport>, // options.j1939: TP reassembly state
+}
+
+impl SessionBuild {
+    fn new(decoder: Decoder, opts: SessionOptions) -> SessionBuild {
+        SessionBuild {
+            lazy: opts.lazy_signals || !opts.pinned_signals.is_empty(),
+            pinned_set: opts.pinned_signals.iter().cloned().collect(),
+            pinned: opts.pinned_signals.iter().map(|n| (n.clone(), (Vec::new(), Vec::new()))).collect(),
+            frames: FrameStore::default(),
+            seen_signals: Vec::new(),
+            decoded: 0,
+            transport: decoder.j1939.then(j1939::Transport::default),
+            decoder,
+            opts,
+        }
+    }
+
+    // One BLF object in file order; a completed J1939 transfer follows its last TP.DT
+    fn push(&mut self, obj: BlfObject) -> Result<(), ApiError> {
+        let reassembled = match (&mut self.transport, &obj) {
+            (Some(tp), BlfObject::Can(cf)) => tp.push(cf),
+            _ => None,
+        };
+        self.add(&obj)?;
+        match reassembled {
+            Some(cf) => self.add(&BlfObject::Can(cf)),
+            None => Ok(()),
+        }
+    }
+
+    fn add(&mut self, obj: &BlfObject) -> Result<(), ApiError> {
+        let decoder = &self.decoder;
+        let Some(frame) = frame_from_obj(obj, decoder, Some(&mut self.seen_signals), !self.lazy) else {
+            return Ok(());
+        };
+        if self.lazy {
+            for s in decoder.decode_frame(&frame.view(), Some(&self.pinned_set)) {
+                if let Some((t, v)) = self.pinned.get_mut(&s.signal) {
+                    t.push(frame.timestamp);
+                    v.push(s.value);
+                    self.decoded += 1;
+                }
+            }
+        } else {
+            self.decoded += frame.signals.len();
+        }
+        self.frames.push(frame.view());
+        if self.decoded > decoder.max_values {
+            let only = self.lazy.then_some(&self.pinned_set);
+            return Err(decoder.budget_error(&self.frames, only));
+        }
+        Ok(())
+    }
+
+    // `hash` / `bytes` / `start` describe the whole BLF input, for session_config()
+    fn finish(self, hash: String, bytes: usize, start: Option<LogStart>) -> BlfSession {
+        let SessionBuild { decoder, opts, lazy, pinned, frames, mut seen_signals, .. } = self;
+        seen_signals.sort();
+
+        let effective = SessionOptions {
+            warm_start: None,
+            max_signals_per_message: decoder.max_signals,
+            max_decoded_values: decoder.max_values,
+            ..opts.clone()
+        };
+        let mut config = SessionConfig::new(decoder.sources.clone(), decoder.dbc_hash, decoder.warm, effective);
+        config.lin_databases = decoder.lin_sources.clone();
+        config.logs.push(LogSource { hash, bytes, frames_read: frames.len(), start, merge: None });
+
+        let mut session = BlfSession {
+            frames: Rc::new(frames),
+            signal_names: seen_signals,
+            decoder: Rc::new(decoder),
+            pyramids: Rc::default(),
+            lazy,
+            pinned: Rc::new(pinned),
+            config,
+            read_only: false,
+            freed: Rc::default(),
+            origins: Vec::new(),
+            generation: 0,
+            timings: timing::Slot::default(),
+        };
+        session.pyramids = Rc::new(
+            session
+                .collect_series(&opts.pyramid_signals)
+                .into_iter()
+                .map(|(name, (t, v))| (name, Pyramid::build(&t, &v)))
+                .collect(),
+        );
+        session
+    }
+}
+
+// Session fed in slices (BlfSession::create_streaming()), e.g. Blob.slice() of a
+// multi-GB log: only the incomplete object at the end of a slice is buffered,
+// never the whole file
+#[wasm_bindgen]
+pub struct BlfSessionBuilder {
+    build: Option<SessionBuild>, // None after finalize() or a failed append
+    stream: BlfStream,
+    hash: index::Fnv,
+    bytes: usize,
+    timings: timing::Slot, // summed over append_chunk() / finalize(), handed to the session
+}
+
+#[wasm_bindgen]
+impl BlfSessionBuilder {
+    // Next slice of the file, in order; returns the frames read so far
+    #[wasm_bindgen(js_name = append_chunk)]
+    pub fn append_chunk(&mut self, chunk: &[u8]) -> Result<usize, JsValue> {
+        let build = self.build.as_mut().ok_or_else(|| JsValue::from_str("session builder already finalized"))?;
+        let _timed = timing::resume("create_streaming", &self.timings);
+        self.hash.feed(chunk);
+        self.bytes += chunk.len();
+        let objects = self.stream.push(chunk).map_err(log_error)?;
+        for obj in objects {
+            if let Err(e) = build.push(obj) {
+                // over the decode budget: the partial session is dropped
+                self.build = None;
+                return Err(e.into());
+            }
+        }
+        Ok(build.frames.len())
+    }
+
+    // All slices appended: the session, as the constructor would build it from the whole file
+    #[wasm_bindgen(js_name = finalize)]
+    pub fn finalize(&mut self) -> Result<BlfSession, JsValue> {
+        let mut build = self.build.take().ok_or_else(|| JsValue::from_str("session builder already finalized"))?;
+        let _timed = timing::resume("create_streaming", &self.timings);
+        let objects = self.stream.finish().map_err(log_error)?;
+        for obj in objects {
+            build.push(obj)?;
+        }
+        let mut session = build.finish(format!("{:016x}", self.hash.finish()), self.bytes, self.stream.start);
+        session.timings = self.timings.clone();
+        Ok(session)
+    }
+}
+
+// -------------------------------
+// SECTION 2b: Decoder - DBCs per channel plus decode-time options
+// -------------------------------
+struct Decoder {
+    dbcs: Vec<(u8, DBC)>, // (channel, DBC) in dbc_texts order; several may share a channel
+    units: Option<UnitTable>, // Some(..) when normalizing to SI
+    signal_meta: HashMap<String, SignalMeta>, // keyed by channel-tagged name
+    dbc_hash: u64, // identifies the DBC set for warm starts
+    message_index: HashMap<(u8, u32), Vec<(usize, usize)>>, // (channel, raw id) -> (dbc, position), by priority
+    conflicts: HashSet<(u8, u32)>, // ids whose candidates decode differently
+    mux: HashMap<(usize, u32), MuxPlan>, // (dbc, raw id) of multiplexed messages
+    warm: bool, // message_index came from options.warm_start
+    max_signals: usize, // per message; signals past it are not decoded
+    max_values: usize, // decoded values per session (constructor / merge)
+    unknown_name: Option<String>, // FrameRow.name template for ids without a message
+    lin: HashMap<u16, LinDatabase>, // LIN channel -> LDF / DBC
+    lin_sources: Vec<DbcSource>, // per options.lin_databases entry, for session_config()
+    include_ethernet: bool,
+    j1939: bool,
+    obd2: bool,
+    pgn_index: HashMap<(u8, u32), u32>, // j1939: (channel, PGN) -> raw id of the DBC message
+    filter: ParseFilter, // options.filter
+    names: SignalNamer, // CAN signal names, qualified where options ask for it
+    names_by_message: HashMap<(usize, u32), Vec<String>>, // (dbc, raw id) -> names of its decoded signals
+    collisions: Vec<SignalCollision>, // see signal_collisions()
+    warnings: Vec<String>, // DBC problems found while loading (see warnings())
+    sources: Vec<DbcSource>, // per dbc_texts entry, for session_config()
+}
+
+// channel_map: one BLF CAN channel (1..255) per dbc_texts entry. Several DBCs may
+// share a channel; the same DBC text twice on one channel is refused as a mistake.
+fn parse_channel_map(channel_map: JsValue, dbc_texts: &[String]) -> Result<Vec<u8>, ApiError> {
+    let raw: Vec<serde_json::Value> = serde_wasm_bindgen::from_value(channel_map).map_err(|e| {
+        ApiError::new(error::INVALID_CHANNEL_MAP, format!("channel_map must be an array of channel numbers: {:?}", e))
+    })?;
+    channel_map_entries(&raw, dbc_texts)
+}
+
+fn channel_map_entries(raw: &[serde_json::Value], dbc_texts: &[String]) -> Result<Vec<u8>, ApiError> {
+    let err = |message: String| Err(ApiError::new(error::INVALID_CHANNEL_MAP, message));
+    if raw.len() != dbc_texts.len() {
+        return err(format!(
+            "channel_map has {} entries but dbc_texts has {}; give one channel per DBC",
+            raw.len(),
+            dbc_texts.len()
+        ));
+    }
+    let mut channels: Vec<u8> = Vec::with_capacity(raw.len());
+    for (i, v) in raw.iter().enumerate() {
+        let chan = match v.as_f64() {
+            Some(n) if n.fract() == 0.0 && (1.0..=255.0).contains(&n) => n as u8,
+            Some(0.0) => return err(format!("channel_map[{}] is 0: BLF channels start at 1 (CAN1 = 1)", i)),
+            _ => return err(format!("channel_map[{}] = {}: expected a channel number 1..255", i, v)),
+        };
+        if let Some(j) = (0..i).find(|&j| channels[j] == chan && dbc_texts[j] == dbc_texts[i]) {
+            return err(format!("channel_map[{}] maps the same DBC as channel_map[{}] to CAN{} again", i, j, chan));
+        }
+        channels.push(chan);
+    }
+    Ok(channels)
+}
+
+// options.filter resolved: objects outside it are dropped before a FrameRow is built
+#[derive(Default)]
+struct ParseFilter {
+    ids: Vec<u32>, // id_matches() queries, CAN frames only
+    channels: Vec<(Bus, u16)>,
+}
+
+// "CAN1", "lin2", "FR1", "ETH1" -> (bus, channel)
+fn parse_channel(c: &str) -> Result<(Bus, u16), String> {
+    let upper = c.trim().to_uppercase();
+    let split = upper.find(|ch: char| ch.is_ascii_digit()).unwrap_or(upper.len());
+    let bus = match &upper[..split] {
+        "CAN" => Bus::Can,
+        "LIN" => Bus::Lin,
+        "FR" => Bus::FlexRay,
+        "ETH" => Bus::Ethernet,
+        _ => return Err(format!("unknown channel \"{}\" (expected CAN1, LIN1, FR1 or ETH1)", c)),
+    };
+    let num = upper[split..].parse().map_err(|_| format!("invalid channel number in \"{}\"", c))?;
+    Ok((bus, num))
+}
+
+impl ParseFilter {
+    fn from_options(f: &FrameFilter) -> Result<ParseFilter, String> {
+        let channels = f.channels.iter().map(|c| parse_channel(c)).collect::<Result<_, _>>()?;
+        Ok(ParseFilter { ids: f.ids.clone(), channels })
+    }
+
+    // Error frames and other buses carry no CAN id: with ids set they are dropped
+    fn keeps(&self, obj: &BlfObject) -> bool {
+        let (bus, channel, raw) = match obj {
+            BlfObject::Can(cf) => (Bus::Can, cf.channel, Some(cf.id)),
+            BlfObject::Error(e) => (Bus::Can, e.channel, None),
+            BlfObject::Lin(lf) => (Bus::Lin, lf.channel, None),
+            BlfObject::FlexRay(fr) => (Bus::FlexRay, fr.channel, None),
+            BlfObject::Ethernet(e) => (Bus::Ethernet, e.channel, None),
+            _ => return true,
+        };
+        (self.channels.is_empty() || self.channels.contains(&(bus, channel)))
+            && (self.ids.is_empty() || raw.is_some_and(|r| self.ids.iter().any(|q| id_matches(*q, r))))
+    }
+}
+
+// options.query of the CSV exports over stored frames; every criterion given must
+// match. Like ParseFilter, ids select CAN messages only.
+struct QueryFilter {
+    ids: Vec<u32>,
+    channels: Vec<(Bus, u16)>,
+    names: HashSet<String>,
+    text: Option<String>, // lowercased
+    start_s: f64,
+    end_s: f64,
+}
+
+impl QueryFilter {
+    fn from_query(q: &FrameQuery) -> Result<QueryFilter, String> {
+        Ok(QueryFilter {
+            ids: q.ids.clone(),
+            channels: q.channels.iter().map(|c| parse_channel(c)).collect::<Result<_, _>>()?,
+            names: q.names.iter().cloned().collect(),
+            text: q.text.as_ref().map(|t| t.trim().to_lowercase()).filter(|t| !t.is_empty()),
+            start_s: q.start_s.unwrap_or(f64::NEG_INFINITY),
+            end_s: q.end_s.unwrap_or(f64::INFINITY),
+        })
+    }
+
+    fn keeps(&self, f: &Frame) -> bool {
+        (self.start_s..=self.end_s).contains(&f.timestamp)
+            && (self.channels.is_empty() || self.channels.contains(&(f.bus, f.channel_num)))
+            && (self.ids.is_empty() || f.is_can_message() && self.ids.iter().any(|q| id_matches(*q, f.raw_id())))
+            && (self.names.is_empty() || self.names.contains(f.name))
+            && self.text.as_ref().is_none_or(|t| {
+                f.name.to_lowercase().contains(t)
+                    || f.channel.to_lowercase().contains(t)
+                    || format!("0x{:x}", f.id).contains(t)
+            })
+    }
+}
+
+// max_frames_per_id_per_second of the exports: each (channel, id) keeps a frame once
+// its next due time (previous due + 1/N s) is at most half a period away, so chatty ids
+// are thinned evenly to N per second while ids at or below N stay complete despite
+// timestamp jitter. Error frames are never dropped.
+struct RateLimit {
+    period: f64,
+    due: HashMap<(Bus, u16, u32), f64>,
+}
+
+impl RateLimit {
+    fn new(rate: Option<f64>) -> Result<Option<RateLimit>, String> {
+        match rate {
+            Some(r) if !(r.is_finite() && r > 0.0) => Err(format!("must be a positive number, got {}", r)),
+            Some(rate) => Ok(Some(RateLimit { period: 1.0 / rate, due: HashMap::new() })),
+            None => Ok(None),
+        }
+    }
+
+    fn keeps(&mut self, f: &Frame) -> bool {
+        if f.event_type == EventType::Error {
+            return true;
+        }
+        let due = self.due.entry((f.bus, f.channel_num, f.raw_id())).or_insert(f64::NEG_INFINITY);
+        if f.timestamp < *due - self.period / 2.0 {
+            return false;
+        }
+        *due = due.max(f.timestamp) + self.period;
+        true
+    }
+}
+
+// Watchdog defaults against pathological DBCs (SessionOptions 0 -> these)
+const DEFAULT_MAX_SIGNALS_PER_MESSAGE: usize = 1024;
+const DEFAULT_MAX_DECODED_VALUES: usize = 20_000_000; // ~2 GB of SignalRows
+
+// Static per-signal facts from the DBC, looked up by the output paths
+struct SignalMeta {
+    bits: u64,
+    factor: f64,
+    offset: f64,
+    si: Option<UnitConversion>, // conversion applied on top of factor/offset
+    unit: String, // as output (SI unit when normalized)
+    start_value: Option<f64>, // GenSigStartValue (or its default) as a decoded value
+    value_table: HashMap<i64, String>,
+    position: (u32, usize), // (message id, index in the message), for SignalOrder::Message
+}
+
+impl SignalMeta {
+    // decoded (physical, maybe SI) value back to the raw integer on the wire
+    fn raw_value(&self, value: f64) -> i64 {
+        let phys = match &self.si {
+            Some(conv) => (value - conv.offset) / conv.factor,
+            None => value,
+        };
+        let factor = if self.factor == 0.0 { 1.0 } else { self.factor };
+        ((phys - self.offset) / factor).round() as i64
+    }
+
+    // 1-8 bit or enumerated: plotted as steps, never decimated across a change
+    fn is_discrete(&self) -> bool {
+        (1..=8).contains(&self.bits) || !self.value_table.is_empty()
+    }
+
+    fn label(&self, value: f64) -> Option<String> {
+        self.value_table.get(&self.raw_value(value)).cloned()
+    }
+}
+
+impl Decoder {
+    fn from_js(dbc_texts: JsValue, channel_map: JsValue, opts: &SessionOptions) -> Result<Decoder, ApiError> {
+        // Deserialize input JS arrays into Rust types
+        let dbc_texts_vec: Vec<String> = serde_wasm_bindgen::from_value(dbc_texts)
+            .map_err(|e| ApiError::new(error::INVALID_ARGUMENT, format!("dbc_texts must be array of strings: {:?}", e)))?;
+        let channel_map_vec = parse_channel_map(channel_map, &dbc_texts_vec)?;
+        Decoder::from_texts(dbc_texts_vec, channel_map_vec, opts)
+    }
+
+    // DBC texts with their channels (as validated by parse_channel_map())
+    fn from_texts(
+        dbc_texts_vec: Vec<String>,
+        channel_map_vec: Vec<u8>,
+        opts: &SessionOptions,
+    ) -> Result<Decoder, ApiError> {
+        // Parse DBCs: (channel, DBC), several per channel allowed; options.patch and
+        // options.signal_overrides patch the text first
+        let patch = opts.patch.clone().unwrap_or_default().merged(&opts.signal_overrides);
+        patch.check().map_err(|e| ApiError::new(error::INVALID_OPTIONS, format!("options.patch invalid: {}", e)))?;
+        let mut dbcs: Vec<(u8, DBC)> = Vec::new();
+        let mut patched = HashSet::new();
+        for (text, chan) in dbc_texts_vec.iter().zip(channel_map_vec.iter()) {
+            let text = overrides::apply(text, *chan, &patch, &mut patched)
+                .map_err(|e| ApiError::new(error::INVALID_OPTIONS, format!("signal_overrides: {}", e)))?;
+            let dbc = DBC::try_from(text.as_str())
+                .map_err(|e| ApiError::new(error::INVALID_DBC, format!("Failed to parse DBC for channel {}: {:?}", chan, e)))?;
+            dbcs.push((*chan, dbc));
+        }
+
+        // Priority rank per DBC (lower wins): options.dbc_priority first, then later-loaded
+        // before earlier-loaded (the old "last DBC for a channel wins" behaviour)
+        let rank = |dbc: usize| -> usize {
+            opts.dbc_priority
+                .iter()
+                .position(|&p| p == dbc)
+                .unwrap_or(opts.dbc_priority.len() + (dbcs.len() - dbc))
+        };
+
+        let units = if opts.normalize_units {
+            Some(UnitTable::with_overrides(&opts.unit_conversions))
+        } else {
+            None
+        };
+
+        // lowest priority first so the preferred DBC's definition ends up in the map
+        let mut by_priority: Vec<usize> = (0..dbcs.len()).collect();
+        by_priority.sort_by_key(|&d| std::cmp::Reverse(rank(d)));
+
+        let max_signals = match opts.max_signals_per_message {
+            0 => DEFAULT_MAX_SIGNALS_PER_MESSAGE,
+            n => n,
+        };
+        let mut warnings = Vec::new();
+        for k in patch.signal_overrides.keys().filter(|k| !patched.contains(*k)) {
+            warnings.push(format!("signal_overrides: {} matches no DBC signal", k));
+        }
+        for m in patch.messages.iter().filter(|m| !patched.contains(&format!("CAN{}.{}", m.channel, m.name))) {
+            warnings.push(format!(
+                "patch: message {} not added (no DBC mapped to CAN{}, or id 0x{:X} already defined there)",
+                m.name,
+                m.channel,
+                m.id & CAN_EFF_MASK
+            ));
+        }
+        for (chan, dbc) in &dbcs {
+            for msg in dbc.messages().iter().filter(|m| m.signals().len() > max_signals) {
+                warnings.push(format!(
+                    "CAN{} {} (0x{:X}): {} signals, only the first {} are decoded (options.max_signals_per_message)",
+                    chan,
+                    msg.message_name(),
+                    msg.message_id().raw() & CAN_EFF_MASK,
+                    msg.signals().len(),
+                    max_signals
+                ));
+            }
+        }
+
+        let (names, collisions) = SignalNamer::for_dbcs(&dbcs, max_signals, opts.signal_naming, opts.name_collisions);
+        for c in collisions.iter().filter(|c| !c.qualified) {
+            let defs: Vec<String> = c.messages.iter().map(|m| format!("{} (0x{:X})", m.name, m.id)).collect();
+            warnings.push(format!(
+                "{} is defined by {}; their values share one signal (options.name_collisions = \"qualify\" separates them)",
+                c.signal,
+                defs.join(" and ")
+            ));
+        }
+
+        let mut signal_meta: HashMap<String, SignalMeta> = HashMap::new();
+        for (chan, dbc) in by_priority.iter().map(|&d| &dbcs[d]) {
+            for msg in dbc.messages() {
+                for (i, sig) in msg.signals().iter().take(max_signals).enumerate() {
+                    let value_table = dbc
+                        .value_descriptions_for_signal(*msg.message_id(), sig.name())
+                        .map(|descs| descs.iter().map(|d| (*d.a() as i64, d.b().clone())).collect())
+                        .unwrap_or_default();
+                    let si = units.as_ref().and_then(|t| t.lookup(sig.unit())).cloned();
+                    let start_value = start_raw(dbc, msg, sig).map(|raw| {
+                        let phys = raw * sig.factor() + sig.offset();
+                        si.as_ref().map_or(phys, |c| c.apply(phys))
+                    });
+                    signal_meta.insert(
+                        names.name(*chan, msg.message_name(), sig.name()),
+                        SignalMeta {
+                            bits: *sig.signal_size(),
+                            factor: *sig.factor(),
+                            offset: *sig.offset(),
+                            unit: si.as_ref().map_or_else(|| sig.unit().clone(), |c| c.si_unit.clone()),
+                            si,
+                            start_value,
+                            value_table,
+                            position: (msg.message_id().raw() & CAN_EFF_MASK, i),
+                        },
+                    );
+                }
+            }
+        }
+
+        // LIN channels: frames looked up by id in their own database, signals "LIN{n}.{Signal}"
+        let mut lin: HashMap<u16, LinDatabase> = HashMap::new();
+        let mut lin_sources = Vec::new();
+        for (index, entry) in opts.lin_databases.iter().enumerate() {
+            // one database per LIN channel: a second one would silently replace the first
+            if entry.channel == 0 {
+                return Err(ApiError::new(
+                    error::INVALID_OPTIONS,
+                    format!("lin_databases[{}].channel is 0: BLF channels start at 1 (LIN1 = 1)", index),
+                ));
+            }
+            if let Some(first) = opts.lin_databases[..index].iter().position(|e| e.channel == entry.channel) {
+                return Err(ApiError::new(
+                    error::INVALID_OPTIONS,
+                    format!("lin_databases[{}] is for LIN{}, which lin_databases[{}] already covers", index, entry.channel, first),
+                ));
+            }
+            let db = LinDatabase::parse(&entry.text)
+                .map_err(|e| {
+                    ApiError::new(error::INVALID_LIN_DATABASE, format!("Failed to parse LIN database for channel {}: {}", entry.channel, e))
+                })?;
+            for info in db.signals() {
+                let si = units.as_ref().and_then(|t| t.lookup(&info.unit)).cloned();
+                let start_value = info.start_raw.map(|raw| {
+                    let phys = raw * info.factor + info.offset;
+                    si.as_ref().map_or(phys, |c| c.apply(phys))
+                });
+                let frame = db.frame_name(info.position.0 as u8).filter(|_| info.position.0 != u32::MAX);
+                signal_meta.insert(
+                    names.lin_name(entry.channel as u16, frame, &info.name),
+                    SignalMeta {
+                        bits: info.bits,
+                        factor: info.factor,
+                        offset: info.offset,
+                        unit: si.as_ref().map_or_else(|| info.unit.clone(), |c| c.si_unit.clone()),
+                        si,
+                        start_value,
+                        value_table: info.value_table,
+                        position: info.position,
+                    },
+                );
+            }
+            lin.insert(entry.channel as u16, db);
+            lin_sources.push(DbcSource {
+                index,
+                channel: entry.channel,
+                hash: index::content_hash(entry.text.as_bytes()),
+                bytes: entry.text.len(),
+            });
+        }
+
+        // Message lookup: reuse a previous session's index for the same DBC set,
+        // otherwise match every DBC message once
+        let dbc_hash = index::dbc_hash(&dbc_texts_vec, &channel_map_vec);
+        let sources = dbc_texts_vec
+            .iter()
+            .zip(&channel_map_vec)
+            .enumerate()
+            .map(|(index, (text, chan))| DbcSource {
+                index,
+                channel: *chan,
+                hash: index::content_hash(text.as_bytes()),
+                bytes: text.len(),
+            })
+            .collect();
+        let warm_index = opts.warm_start.as_ref().filter(|w| w.matches(dbc_hash));
+        let mut message_index = match warm_index {
+            Some(w) => w.message_index(),
+            None => {
+                let mut idx: HashMap<(u8, u32), Vec<(usize, usize)>> = HashMap::new();
+                for (d, (chan, dbc)) in dbcs.iter().enumerate() {
+                    for (pos, m) in dbc.messages().iter().enumerate() {
+                        let cands = idx.entry((*chan, m.message_id().raw())).or_default();
+                        // within one DBC the first definition of an id wins
+                        if !cands.iter().any(|&(cd, _)| cd == d) {
+                            cands.push((d, pos));
+                        }
+                    }
+                }
+                idx
+            }
+        };
+        for cands in message_index.values_mut() {
+            cands.sort_by_key(|&(d, _)| rank(d));
+        }
+
+        // J1939: extended messages by PGN, so any source address / priority matches;
+        // preferred DBCs are inserted last and win
+        let mut pgn_index = HashMap::new();
+        if opts.j1939 {
+            for (chan, dbc) in by_priority.iter().map(|&d| &dbcs[d]) {
+                for m in dbc.messages().iter().filter(|m| m.message_id().raw() & CAN_EFF_FLAG != 0) {
+                    pgn_index.insert((*chan, j1939::pgn(m.message_id().raw())), m.message_id().raw());
+                }
+            }
+        }
+
+        let mut mux = HashMap::new();
+        let mut names_by_message = HashMap::new();
+        for (d, (chan, dbc)) in dbcs.iter().enumerate() {
+            for msg in dbc.messages() {
+                if let Some(plan) = MuxPlan::build(dbc, msg) {
+                    mux.entry((d, msg.message_id().raw())).or_insert(plan);
+                }
+                names_by_message.entry((d, msg.message_id().raw())).or_insert_with(|| {
+                    let sigs = msg.signals().iter().take(max_signals);
+                    sigs.map(|sig| names.name(*chan, msg.message_name(), sig.name())).collect::<Vec<_>>()
+                });
+            }
+        }
+
+        let mut decoder = Decoder {
+            dbcs,
+            units,
+            signal_meta,
+            dbc_hash,
+            message_index,
+            conflicts: HashSet::new(),
+            mux,
+            warm: warm_index.is_some(),
+            max_signals,
+            max_values: match opts.max_decoded_values {
+                0 => DEFAULT_MAX_DECODED_VALUES,
+                n => n,
+            },
+            warnings,
+            sources,
+            unknown_name: opts.unknown_name.clone(),
+            lin,
+            lin_sources,
+            include_ethernet: opts.include_ethernet,
+            j1939: opts.j1939,
+            obd2: opts.obd2,
+            pgn_index,
+            filter: ParseFilter::from_options(&opts.filter)
+                .map_err(|e| ApiError::new(error::INVALID_OPTIONS, format!("options.filter invalid: {}", e)))?,
+            names,
+            names_by_message,
+            collisions,
+        };
+        decoder.conflicts = decoder
+            .message_index
+            .iter()
+            .filter(|(_, cands)| {
+                let msgs: Vec<&Message> = cands.iter().filter_map(|&c| decoder.candidate(c)).collect();
+                msgs.windows(2).any(|w| !same_layout(w[0], w[1]))
+            })
+            .map(|(key, _)| *key)
+            .collect();
+        Ok(decoder)
+    }
+
+    // discrete signal -> is 1-bit; decimated change-preserving
+    fn discrete_signals(&self) -> HashMap<String, bool> {
+        self.signal_meta
+            .iter()
+            .filter(|(_, m)| m.is_discrete())
+            .map(|(k, m)| (k.clone(), m.bits == 1))
+            .collect()
+    }
+
+    // Output position per signal name for SignalOrder; `keys` is the selection (or all
+    // signals in first-seen order). Names outside `keys` rank last (see ranked()).
+    fn signal_rank(&self, keys: &[String], order: SignalOrder) -> HashMap<String, usize> {
+        let mut names: Vec<&String> = keys.iter().collect();
+        match order {
+            SignalOrder::Selection => {}
+            SignalOrder::Alphabetical => names.sort(),
+            SignalOrder::Message => names.sort_by_cached_key(|n| {
+                // "CAN10.Sig": bus "CAN", channel 10 (numeric, so CAN2 sorts before CAN10)
+                let bus = n.split_once('.').map_or("", |(b, _)| b);
+                let digits = bus.trim_start_matches(|c: char| !c.is_ascii_digit());
+                let position = self.signal_meta.get(*n).map_or((u32::MAX, usize::MAX), |m| m.position);
+                (&bus[..bus.len() - digits.len()], digits.parse::<u32>().unwrap_or(u32::MAX), position, *n)
+            }),
+        }
+        let mut rank = HashMap::new();
+        for n in names {
+            let next = rank.len();
+            rank.entry(n.clone()).or_insert(next);
+        }
+        rank
+    }
+
+    fn empty() -> Decoder {
+        Decoder {
+            dbcs: Vec::new(),
+            units: None,
+            signal_meta: HashMap::new(),
+            dbc_hash: 0,
+            message_index: HashMap::new(),
+            conflicts: HashSet::new(),
+            mux: HashMap::new(),
+            warm: false,
+            max_signals: DEFAULT_MAX_SIGNALS_PER_MESSAGE,
+            max_values: DEFAULT_MAX_DECODED_VALUES,
+            warnings: Vec::new(),
+            sources: Vec::new(),
+            unknown_name: None,
+            lin: HashMap::new(),
+            lin_sources: Vec::new(),
+            include_ethernet: false,
+            j1939: false,
+            obd2: false,
+            pgn_index: HashMap::new(),
+            filter: ParseFilter::default(),
+            names: SignalNamer::default(),
+            names_by_message: HashMap::new(),
+            collisions: Vec::new(),
+        }
+    }
+
+    // FrameRow.name: the DBC message name, else options.unknown_name filled in
+    fn frame_name(&self, channel: &str, raw: u32, message_name: Option<&str>) -> String {
+        match (message_name, &self.unknown_name) {
+            (Some(m), _) => m.to_string(),
+            (None, Some(template)) => template
+                .replace("{id}", &format!("0x{:X}", raw & CAN_EFF_MASK))
+                .replace("{channel}", channel),
+            (None, None) => String::new(),
+        }
+    }
+
+    fn candidate(&self, (dbc, pos): (usize, usize)) -> Option<&Message> {
+        self.dbcs.get(dbc)?.1.messages().get(pos)
+    }
+
+    // Message definition for a frame: highest-priority candidate whose length fits the
+    // payload, else the highest-priority one. Returns the chosen DBC index too.
+    fn select(&self, channel: u16, id: u32, len: usize) -> Option<(usize, &Message)> {
+        timing::sampled(Phase::Match, || {
+            let id = self.j1939_id(channel, id);
+            // DBCs sit on channels 1..255: CAN257 must not decode as CAN1
+            let cands = self.message_index.get(&(u8::try_from(channel).ok()?, id))?;
+            // a stale warm-start entry must not decode the wrong message
+            let valid = |&c: &(usize, usize)| self.candidate(c).filter(|m| m.message_id().raw() == id).map(|m| (c.0, m));
+            cands
+                .iter()
+                .filter_map(valid)
+                .find(|(_, m)| *m.message_size() as usize == len)
+                .or_else(|| cands.iter().find_map(valid))
+        })
+    }
+
+    // J1939 mode: an extended id without an exact DBC entry maps to the message of its PGN
+    fn j1939_id(&self, channel: u16, id: u32) -> u32 {
+        let Ok(channel) = u8::try_from(channel) else { return id };
+        if !self.j1939 || id & CAN_EFF_FLAG == 0 || self.message_index.contains_key(&(channel, id)) {
+            return id;
+        }
+        self.pgn_index.get(&(channel, j1939::pgn(id))).copied().unwrap_or(id)
+    }
+
+    // GenMsgCycleTime of the message a frame decodes with
+    fn cycle_ms(&self, f: &Frame) -> Option<f64> {
+        let (dbc, msg) = self.select(f.channel_num, f.raw_id(), f.data.len())?;
+        cycle_time_ms(&self.dbcs[dbc].1, msg)
+    }
+
+    fn message(&self, channel: u16, id: u32, len: usize) -> Option<&Message> {
+        self.select(channel, id, len).map(|(_, m)| m)
+    }
+
+    // Channel-tagged names of the signals a message decodes (built with the Decoder)
+    fn message_signals(&self, dbc: usize, msg: &Message) -> &[String] {
+        self.names_by_message.get(&(dbc, msg.message_id().raw())).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    // Decode the signals of (channel, id, data); `only` restricts to those names.
+    // Multiplexed signals are emitted only when their multiplexor selects them.
+    fn decode(&self, channel: u16, id: u32, data: &[u8], only: Option<&HashSet<String>>) -> Vec<SignalRow> {
+        let selected = self.select(channel, id, data.len());
+        timing::sampled(Phase::Decode, || self.decode_selected(selected, channel, id, data, only))
+    }
+
+    fn decode_selected(
+        &self,
+        selected: Option<(usize, &Message)>,
+        channel: u16,
+        id: u32,
+        data: &[u8],
+        only: Option<&HashSet<String>>,
+    ) -> Vec<SignalRow> {
+        let mut signal_rows: Vec<SignalRow> = Vec::new();
+        if let Some((dbc, msg)) = selected {
+            let mux = self.mux.get(&(dbc, msg.message_id().raw()));
+            for (i, (sig, sname)) in msg.signals().iter().zip(self.message_signals(dbc, msg)).enumerate() {
+                if only.is_some_and(|o| !o.contains(sname)) {
+                    continue;
+                }
+                if mux.is_some_and(|m| !m.active(i, msg.signals(), data)) {
+                    continue;
+                }
+                if let Some(val) = decode_signal_value(sig, data) {
+                    let (value, unit, original_unit) = self.normalize(val, sig.unit());
+                    let value_text = self.signal_meta.get(sname).and_then(|m| m.label(value));
+                    signal_rows.push(SignalRow {
+                        signal: sname.clone(),
+                        value,
+                        unit,
+                        original_unit,
+                        value_text,
+                    });
+                }
+            }
+        } else if self.obd2 && obd::is_response(id) {
+            for v in obd::decode(data) {
+                let sname = format!("CAN{}.{}", channel, v.name);
+                if only.is_some_and(|o| !o.contains(&sname)) {
+                    continue;
+                }
+                let (value, unit, original_unit) = self.normalize(v.value, v.unit);
+                signal_rows.push(SignalRow { signal: sname, value, unit, original_unit, value_text: None });
+            }
+        }
+        signal_rows
+    }
+
+    // options.obd2: frame names for OBD-II traffic without a DBC message
+    fn obd_name(&self, id: u32) -> Option<&'static str> {
+        if !self.obd2 {
+            None
+        } else if obd::is_request(id) {
+            Some("OBD2 Request")
+        } else if obd::is_response(id) {
+            Some("OBD2 Response")
+        } else {
+            None
+        }
+    }
+
+    // lazy path: re-decode a stored frame
+    fn decode_frame(&self, f: &Frame, only: Option<&HashSet<String>>) -> Vec<SignalRow> {
+        if f.is_error() {
+            return Vec::new();
+        }
+        match f.bus {
+            Bus::Can => self.decode(f.channel_num, f.raw_id(), f.data, only),
+            Bus::Lin => self.decode_lin(f.channel_num, f.id, f.data, only),
+            Bus::FlexRay | Bus::Ethernet => Vec::new(), // passed through raw
+        }
+    }
+
+    // LIN frame through its channel's LDF/DBC (options.lin_databases)
+    fn decode_lin(&self, channel: u16, id: u32, data: &[u8], only: Option<&HashSet<String>>) -> Vec<SignalRow> {
+        let Some(db) = self.lin.get(&channel) else { return Vec::new() };
+        timing::sampled(Phase::Decode, || {
+            db.decode(id as u8, data)
+                .into_iter()
+                .filter_map(|v| {
+                    let sname = self.names.lin_name(channel, db.frame_name(id as u8), &v.name);
+                    if only.is_some_and(|o| !o.contains(&sname)) {
+                        return None;
+                    }
+                    let (value, unit, original_unit) = self.normalize(v.value, &v.unit);
+                    Some(SignalRow { signal: sname, value, unit, original_unit, value_text: v.label })
+                })
+                .collect()
+        })
+    }
+
+    // max_decoded_values hit after `frames`: name the messages that used the budget
+    // (`only`: lazy sessions, where just the pinned signals count)
+    fn budget_error(&self, frames: &FrameStore, only: Option<&HashSet<String>>) -> ApiError {
+        let mut per_message: HashMap<(u16, u32), (usize, &str)> = HashMap::new();
+        for f in frames.iter() {
+            let n = match only {
+                Some(_) => self.decode_frame(&f, only).len(),
+                None => f.signals.len(),
+            };
+            let e = per_message.entry((f.channel_num, f.raw_id())).or_insert((0, f.name));
+            e.0 += n;
+        }
+        let mut busiest: Vec<_> = per_message.into_iter().filter(|(_, (n, _))| *n > 0).collect();
+        busiest.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then(a.0.cmp(&b.0)));
+        let top: Vec<String> = busiest
+            .iter()
+            .take(3)
+            .map(|((chan, id), (n, name))| format!("CAN{} 0x{:X} {} ({} values)", chan, id & CAN_EFF_MASK, name, n))
+            .collect();
+        ApiError::new(
+            error::DECODE_BUDGET,
+            format!(
+                "Decode budget exceeded: more than {} signal values after {} frames (options.max_decoded_values); \
+                 busiest messages: {}. Decode fewer signals via options.pinned_signals or raise the budget.",
+                self.max_values,
+                frames.len(),
+                top.join(", ")
+            ),
+        )
+    }
+
+    // (value, unit, original_unit) after optional SI normalization
+    fn normalize(&self, value: f64, unit: &str) -> (f64, String, Option<String>) {
+        match self.units.as_ref().and_then(|t| t.lookup(unit)) {
+            Some(conv) => (conv.apply(value), conv.si_unit.clone(), Some(unit.to_string())),
+            None => (value, unit.to_string(), None),
+        }
+    }
+}
+
+type Definers<'a> = BTreeMap<u32, (usize, &'a str)>;
+
+// Signal names: "CAN{channel}.{Signal}", or "CAN{channel}.{Message}.{Signal}" for the
+// (channel, signal) pairs in `qualify` and for every signal with options.signal_naming =
+// "message" (LIN alike, with the frame name)
+#[derive(Default)]
+struct SignalNamer {
+    qualify: HashSet<(u8, String)>,
+    messages: bool,
+}
+
+impl SignalNamer {
+    // Finds signal names that several messages (different ids) define on one channel;
+    // the same id in several DBCs is one message (see ambiguities())
+    fn for_dbcs(
+        dbcs: &[(u8, DBC)],
+        max_signals: usize,
+        naming: SignalNaming,
+        policy: CollisionPolicy,
+    ) -> (SignalNamer, Vec<SignalCollision>) {
+        // (channel, signal) -> raw id -> (dbc, message name)
+        let mut defs: BTreeMap<(u8, &str), Definers> = BTreeMap::new();
+        for (d, (chan, dbc)) in dbcs.iter().enumerate() {
+            for msg in dbc.messages() {
+                for sig in msg.signals().iter().take(max_signals) {
+                    defs.entry((*chan, sig.name().as_str()))
+                        .or_default()
+                        .entry(msg.message_id().raw())
+                        .or_insert((d, msg.message_name().as_str()));
+                }
+            }
+        }
+        let mut namer = SignalNamer { messages: naming == SignalNaming::Message, ..SignalNamer::default() };
+        let mut collisions = Vec::new();
+        for ((chan, sig), by_id) in defs.into_iter().filter(|(_, by_id)| by_id.len() > 1) {
+            let qualified = namer.messages || policy == CollisionPolicy::Qualify;
+            if policy == CollisionPolicy::Qualify {
+                namer.qualify.insert((chan, sig.to_string()));
+            }
+            let messages = by_id
+                .into_iter()
+                .map(|(raw, (dbc, name))| {
+                    let (id, is_extended) = split_id(raw);
+                    CollisionMessage {
+                        dbc,
+                        name: name.to_string(),
+                        id,
+                        is_extended,
+                        signal: namer.name(chan, name, sig),
+                    }
+                })
+                .collect();
+            collisions.push(SignalCollision {
+                signal: format!("CAN{}.{}", chan, sig),
+                channel_num: chan as u16,
+                messages,
+                qualified,
+            });
+        }
+        (namer, collisions)
+    }
+
+    fn name(&self, channel: u8, message: &str, signal: &str) -> String {
+        if self.messages || self.qualify.contains(&(channel, signal.to_string())) {
+            format!("CAN{}.{}.{}", channel, message, signal)
+        } else {
+            format!("CAN{}.{}", channel, signal)
+        }
+    }
+
+    // LIN signals are unique per database, so only message naming qualifies them;
+    // `frame` is None for LDF signals no frame carries
+    fn lin_name(&self, channel: u16, frame: Option<&str>, signal: &str) -> String {
+        match frame.filter(|_| self.messages) {
+            Some(f) => format!("LIN{}.{}.{}", channel, f, signal),
+            None => format!("LIN{}.{}", channel, signal),
+        }
+    }
+}
+
+// -------------------------------
+// SECTION 2c: Helper - decimation result -> JS
+// -------------------------------
+// Identical time arrays stored once: entries get "time_ref" (index into the top-level
+// "times" array) instead of their own "time"
+#[derive(Default)]
+struct TimePool {
+    arrays: Vec<Vec<f64>>,
+    index: HashMap<Vec<u64>, usize>, // f64 bit patterns -> position
+}
+
+impl TimePool {
+    fn intern(&mut self, time: Vec<f64>) -> usize {
+        let key: Vec<u64> = time.iter().map(|t| t.to_bits()).collect();
+        *self.index.entry(key).or_insert_with(|| {
+            self.arrays.push(time);
+            self.arrays.len() - 1
+        })
+    }
+
+    fn set_on(self, out: &JsValue) -> Result<(), JsValue> {
+        let times = js_sys::Array::new();
+        for t in &self.arrays {
+            times.push(&Float64Array::from(t.as_slice()));
+        }
+        set_entry(out, "times", &times)
+    }
+}
+
+// entry.time, or entry.time_ref when pooling
+fn set_time(entry: &JsValue, time: Vec<f64>, pool: &mut Option<TimePool>) -> Result<(), JsValue> {
+    match pool {
+        Some(p) => set_entry(entry, "time_ref", &JsValue::from_f64(p.intern(time) as f64)),
+        None => set_entry(entry, "time", &Float64Array::from(time.as_slice())),
+    }
+}
+
+// Per-signal results in Decoder::signal_rank() order; unranked names last, by name
+pub(crate) fn ranked<T>(items: HashMap<String, T>, rank: &HashMap<String, usize>) -> Vec<(String, T)> {
+    let mut items: Vec<_> = items.into_iter().collect();
+    items.sort_by(|a, b| {
+        let key = |n: &String| rank.get(n).copied().unwrap_or(usize::MAX);
+        key(&a.0).cmp(&key(&b.0)).then_with(|| a.0.cmp(&b.0))
+    });
+    items
+}
+
+// `typed`: Float64Arrays with NaN for gaps instead of arrays with nulls
+fn decimation_to_js(dec: Decimator, share_times: bool, typed: bool, rank: &HashMap<String, usize>) -> Result<JsValue, JsValue> {
+    // plain object built key by key: JS keeps insertion order, serde_json::Map would sort
+    let out_signals = js_sys::Object::new();
+    for (k, vec_opt) in ranked(dec.signals, rank) {
+        let values: JsValue = if typed {
+            let filled: Vec<f64> = vec_opt.into_iter().map(|o| o.unwrap_or(f64::NAN)).collect();
+            Float64Array::from(filled.as_slice()).into()
+        } else {
+            let arr_values: Vec<serde_json::Value> = vec_opt
+                .into_iter()
+                .map(|o| o.map_or(serde_json::Value::Null, |v| json!(v)))
+                .collect();
+            serde_wasm_bindgen::to_value(&arr_values)
+                .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))?
+        };
+        set_entry(&out_signals, &k, &values)?;
+    }
+
+    let out: JsValue = js_sys::Object::new().into();
+    let time: JsValue = if typed {
+        Float64Array::from(dec.time.as_slice()).into()
+    } else {
+        serde_wasm_bindgen::to_value(&dec.time).map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))?
+    };
+    set_entry(&out, "time", &time)?;
+    set_entry(&out, "signals", &out_signals)?;
+
+    let mut pool = share_times.then(TimePool::default);
+    set_traces(&out, dec.digital, dec.discrete_traces, &mut pool, rank)?;
+    if let Some(p) = pool {
+        p.set_on(&out)?;
+    }
+    Ok(out)
+}
+
+// method "lttb": {signals: Map<name, {time, values}>, digital, discrete}, typed arrays
+// whatever the encoding
+fn lttb_to_js(dec: LttbDecimator, share_times: bool, rank: &HashMap<String, usize>) -> Result<JsValue, JsValue> {
+    let out: JsValue = js_sys::Object::new().into();
+    let mut pool = share_times.then(TimePool::default);
+    let signals = js_sys::Map::new();
+    for (k, (t, v)) in ranked(dec.series, rank) {
+        let entry: JsValue = js_sys::Object::new().into();
+        set_time(&entry, t, &mut pool)?;
+        set_entry(&entry, "values", &Float64Array::from(v.as_slice()))?;
+        signals.set(&JsValue::from_str(&k), &entry);
+    }
+    set_entry(&out, "signals", &signals)?;
+    set_traces(&out, dec.digital, dec.discrete_traces, &mut pool, rank)?;
+    if let Some(p) = pool {
+        p.set_on(&out)?;
+    }
+    Ok(out)
+}
+
+// method "envelope": {signals: Map<name, {time, min, max, mean}>, digital, discrete}
+fn envelope_to_js(dec: EnvelopeDecimator, share_times: bool, rank: &HashMap<String, usize>) -> Result<JsValue, JsValue> {
+    let out: JsValue = js_sys::Object::new().into();
+    let mut pool = share_times.then(TimePool::default);
+    let signals = js_sys::Map::new();
+    for (k, e) in ranked(dec.envelopes, rank) {
+        let entry: JsValue = js_sys::Object::new().into();
+        set_time(&entry, e.time, &mut pool)?;
+        set_entry(&entry, "min", &Float64Array::from(e.min.as_slice()))?;
+        set_entry(&entry, "max", &Float64Array::from(e.max.as_slice()))?;
+        set_entry(&entry, "mean", &Float64Array::from(e.mean.as_slice()))?;
+        signals.set(&JsValue::from_str(&k), &entry);
+    }
+    set_entry(&out, "signals", &signals)?;
+    set_traces(&out, dec.digital, dec.discrete_traces, &mut pool, rank)?;
+    if let Some(p) = pool {
+        p.set_on(&out)?;
+    }
+    Ok(out)
+}
+
+// "digital" / "discrete" edge traces; typed arrays, which serde_json can't hold
+fn set_traces(
+    out: &JsValue,
+    digital_traces: HashMap<String, decimate::EdgeTrace>,
+    discrete_traces: HashMap<String, decimate::EdgeTrace>,
+    pool: &mut Option<TimePool>,
+    rank: &HashMap<String, usize>,
+) -> Result<(), JsValue> {
+    let digital = js_sys::Map::new();
+    for (k, trace) in ranked(digital_traces, rank) {
+        let bits: Vec<u8> = trace.values.iter().map(|v| (*v != 0.0) as u8).collect();
+        let entry = js_sys::Object::new();
+        set_time(&entry, trace.time, pool)?;
+        set_entry(&entry, "values", &Uint8Array::from(bits.as_slice()))?;
+        digital.set(&JsValue::from_str(&k), &entry);
+    }
+    set_entry(out, "digital", &digital)?;
+
+    let discrete = js_sys::Map::new();
+    for (k, trace) in ranked(discrete_traces, rank) {
+        let entry = js_sys::Object::new();
+        set_time(&entry, trace.time, pool)?;
+        set_entry(&entry, "values", &Float64Array::from(trace.values.as_slice()))?;
+        discrete.set(&JsValue::from_str(&k), &entry);
+    }
+    set_entry(out, "discrete", &discrete)
+}
+
+// grouped decimation: {messages: Map<"CAN{n}.{Message}", {channel, channel_num, id, is_extended, name,
+// time, signals: Map<name, Float64Array>}>, digital, discrete}
+fn grouped_decimation_to_js(dec: GroupedDecimator, share_times: bool, rank: &HashMap<String, usize>) -> Result<JsValue, JsValue> {
+    let out = js_sys::Object::new();
+    let mut pool = share_times.then(TimePool::default);
+    let mut groups: Vec<_> = dec.groups.into_values().collect();
+    groups.sort_by_key(|g| (g.channel_num, g.is_extended, g.id));
+
+    let messages = js_sys::Map::new();
+    for g in groups {
+        let entry = js_sys::Object::new();
+        set_entry(&entry, "channel", &JsValue::from_str(&g.channel))?;
+        set_entry(&entry, "channel_num", &JsValue::from_f64(g.channel_num as f64))?;
+        set_entry(&entry, "id", &JsValue::from_f64(g.id as f64))?;
+        set_entry(&entry, "is_extended", &JsValue::from_bool(g.is_extended))?;
+        set_entry(&entry, "name", &JsValue::from_str(&g.name))?;
+        set_time(&entry, g.time, &mut pool)?;
+        let signals = js_sys::Map::new();
+        for (k, v) in ranked(g.signals, rank) {
+            signals.set(&JsValue::from_str(&k), &Float64Array::from(v.as_slice()));
+        }
+        set_entry(&entry, "signals", &signals)?;
+        let label = if g.name.is_empty() { format!("{}.0x{:X}", g.channel, g.id) } else { format!("{}.{}", g.channel, g.name) };
+        messages.set(&JsValue::from_str(&label), &entry);
+    }
+    set_entry(&out, "messages", &messages)?;
+    set_traces(&out, dec.digital, dec.discrete_traces, &mut pool, rank)?;
+    if let Some(p) = pool {
+        p.set_on(&out)?;
+    }
+    Ok(out.into())
+}
+
+fn attribute_number(v: &AttributeValue) -> Option<f64> {
+    match v {
+        AttributeValue::AttributeValueU64(x) => Some(*x as f64),
+        AttributeValue::AttributeValueI64(x) => Some(*x as f64),
+        AttributeValue::AttributeValueF64(x) => Some(*x),
+        AttributeValue::AttributeValueCharString(s) => s.trim().parse().ok(),
+    }
+}
+
+// BA_DEF_DEF_ default of a numeric attribute
+fn attribute_default(dbc: &DBC, name: &str) -> Option<f64> {
+    dbc.attribute_defaults()
+        .iter()
+        .find(|d| d.attribute_name() == name)
+        .and_then(|d| attribute_number(d.attribute_value()))
+}
+
+// GenMsgCycleTime of a message (ms): its own BA_ entry, else the default
+fn cycle_time_ms(dbc: &DBC, msg: &Message) -> Option<f64> {
+    let own = dbc.attribute_values().iter().find_map(|a| match a.attribute_value() {
+        AttributeValuedForObjectType::MessageDefinitionAttributeValue(id, Some(v))
+            if a.attribute_name() == "GenMsgCycleTime" && id == msg.message_id() =>
+        {
+            attribute_number(v)
+        }
+        _ => None,
+    });
+    own.or_else(|| attribute_default(dbc, "GenMsgCycleTime"))
+}
+
+// Alive counter of a message: the first plain unsigned 2..8 bit signal named in
+// `names`, or without names one whose name says so ("AliveCounter", "MsgCnt", ...)
+fn alive_counter<'m>(msg: &'m Message, names: &[String]) -> Option<&'m Signal> {
+    const HINTS: [&str; 5] = ["alive", "counter", "rolling", "cnt", "ctr"];
+    msg.signals().iter().find(|s| {
+        let fits = *s.multiplexer_indicator() == MultiplexIndicator::Plain
+            && *s.value_type() == ValueType::Unsigned
+            && (2..=8).contains(s.signal_size());
+        let named = if names.is_empty() {
+            let lower = s.name().to_lowercase();
+            HINTS.iter().any(|h| lower.contains(h))
+        } else {
+            names.contains(s.name())
+        };
+        fits && named
+    })
+}
+
+// Raw GenSigStartValue of a signal: its own BA_ entry, else the BA_DEF_DEF_ default
+pub(crate) fn start_raw(dbc: &DBC, msg: &Message, sig: &Signal) -> Option<f64> {
+    let own = dbc.attribute_values().iter().find_map(|a| match a.attribute_value() {
+        AttributeValuedForObjectType::SignalAttributeValue(id, name, v)
+            if a.attribute_name() == "GenSigStartValue" && id == msg.message_id() && name == sig.name() =>
+        {
+            attribute_number(v)
+        }
+        _ => None,
+    });
+    own.or_else(|| attribute_default(dbc, "GenSigStartValue"))
+}
+
+// Two definitions of one id decode a payload identically
+fn same_layout(a: &Message, b: &Message) -> bool {
+    let key = |s: &Signal| {
+        (
+            s.name().clone(),
+            *s.start_bit(),
+            *s.signal_size(),
+            *s.byte_order(),
+            *s.value_type(),
+            s.factor().to_bits(),
+            s.offset().to_bits(),
+        )
+    };
+    a.message_name() == b.message_name()
+        && a.message_size() == b.message_size()
+        && a.signals().len() == b.signals().len()
+        && a.signals().iter().zip(b.signals()).all(|(x, y)| key(x) == key(y))
+}
+
+// set key on a JS Map (serde_wasm_bindgen output for json! objects) or plain object
+fn set_entry(target: &JsValue, key: &str, value: &JsValue) -> Result<(), JsValue> {
+    if let Some(map) = target.dyn_ref::<js_sys::Map>() {
+        map.set(&JsValue::from_str(key), value);
+        Ok(())
+    } else {
+        js_sys::Reflect::set(target, &JsValue::from_str(key), value).map(|_| ())
+    }
+}
+
+// -------------------------------
+// SECTION 2d: Session internals - signal access that works eager or lazy
+// -------------------------------
+
+// Per-message decode cache: a frame repeating its message's last payload (lazy) or
+// stored values (eager) reuses those rows instead of running the DBC / building them again
+type MessageKey = (Bus, u16, u32); // (bus, channel, raw id)
+type StoredColumns<'a> = (&'a [u32], &'a [f64]); // a stored frame's signal keys and values
+
+struct DecodeCache<'a> {
+    only: Option<&'a HashSet<String>>, // decode just these signals
+    last: HashMap<MessageKey, (&'a [u8], Rc<[SignalRow]>)>, // last payload and its decode
+    stored: HashMap<MessageKey, (StoredColumns<'a>, Rc<[SignalRow]>)>, // last stored columns and their rows
+}
+
+impl<'a> DecodeCache<'a> {
+    fn new(only: Option<&'a HashSet<String>>) -> Self {
+        DecodeCache { only, last: HashMap::new(), stored: HashMap::new() }
+    }
+
+    // Rows of a stored frame's signal columns, limited to `only`
+    fn rows(&mut self, f: &Frame<'a>, keys: &'a [u32], values: &'a [f64]) -> Rc<[SignalRow]> {
+        let key = (f.bus, f.channel_num, f.raw_id());
+        if let Some(((k, v), rows)) = self.stored.get(&key) {
+            let same = |a: &[f64], b: &[f64]| a.iter().zip(b).all(|(x, y)| x.to_bits() == y.to_bits());
+            if *k == keys && same(v, values) {
+                return rows.clone();
+            }
+        }
+        let only = self.only;
+        let rows: Rc<[SignalRow]> = f
+            .signals
+            .iter()
+            .filter(|r| only.is_none_or(|o| o.contains(r.signal)))
+            .map(|r| r.to_row())
+            .collect();
+        self.stored.insert(key, ((keys, values), rows.clone()));
+        rows
+    }
+
+    fn decode(&mut self, decoder: &Decoder, f: &Frame<'a>) -> Rc<[SignalRow]> {
+        let key = (f.bus, f.channel_num, f.raw_id());
+        if let Some((data, rows)) = self.last.get(&key) {
+            if *data == f.data {
+                return rows.clone();
+            }
+        }
+        let rows: Rc<[SignalRow]> = decoder.decode_frame(f, self.only).into();
+        self.last.insert(key, (f.data, rows.clone()));
+        rows
+    }
+}
+
+// Signals of one frame: stored rows or a (possibly shared) on-demand decode
+enum FrameSignals<'a> {
+    Stored(&'a [SignalRow]),
+    Decoded(Rc<[SignalRow]>),
+}
+
+impl std::ops::Deref for FrameSignals<'_> {
+    type Target = [SignalRow];
+
+    fn deref(&self) -> &[SignalRow] {
+        match self {
+            FrameSignals::Stored(rows) => rows,
+            FrameSignals::Decoded(rows) => rows,
+        }
+    }
+}
+
+struct CsvLayout {
+    selected: Vec<String>,
+    labelled: Vec<bool>, // parallel to selected
+    label_only: bool, // labelled signals: label in the signal column, no "_text" column
+    hold: bool, // wide layout: every row carries each signal's last value
+    start: Vec<Option<f64>>, // per selected signal: shown before its first sample
+    clock: Option<WallClock>, // absolute_time: wall-clock column after "Time [s]"
+}
+
+// Per-signal running state while writing rows (parallel to CsvLayout.selected)
+struct CsvState {
+    last: Vec<Option<f64>>,
+    seen: Vec<bool>,
+}
+
+impl CsvLayout {
+    fn header(&self) -> Vec<String> {
+        let mut header: Vec<String> = ["Time [s]", "Channel", "Channel Num", "ID", "Name", "Event Type", "Dir", "Flags", "DLC", "Data"]
+            .iter()
+            .map(|h| h.to_string())
+            .collect();
+        if self.clock.is_some() {
+            header.insert(1, "Time (wall clock)".to_string());
+        }
+        for (sname, with_text) in self.selected.iter().zip(&self.labelled) {
+            header.push(sname.clone());
+            if *with_text && !self.label_only {
+                header.push(format!("{}_text", sname));
+            }
+        }
+        header
+    }
+
+    fn state(&self) -> CsvState {
+        CsvState { last: vec![None; self.selected.len()], seen: vec![false; self.selected.len()] }
+    }
+
+    // signals a lazy session has to decode for the columns
+    fn wanted(&self) -> HashSet<String> {
+        self.selected.iter().cloned().collect()
+    }
+}
+
+impl BlfSession {
+    // The constructor past its JS arguments
+    fn open(blf_bytes: &[u8], decoder: Decoder, opts: SessionOptions) -> Result<BlfSession, ApiError> {
+        let timings = timing::Slot::default();
+        let _timed = timing::start("constructor", &timings);
+
+        let blf = LogReader::new(blf_bytes).map_err(log_error)?;
+        let mut build = SessionBuild::new(decoder, opts);
+        for obj in blf {
+            build.push(obj)?;
+        }
+        let mut session = build.finish(index::content_hash(blf_bytes), blf_bytes.len(), input::log_start(blf_bytes));
+        session.timings = timings;
+        Ok(session)
+    }
+
+    // diff_windows() of two checked windows
+    fn window_diff(&self, a: (f64, f64), b: (f64, f64)) -> WindowDiff {
+        let mut cache = DecodeCache::new(None);
+        let mut last: HashMap<String, (Option<f64>, Option<f64>)> = HashMap::new();
+        for ((t0, t1), in_a) in [(a, true), (b, false)] {
+            for f in self.frames.range(self.frames.window(t0, t1)) {
+                for s in self.frame_signals(&f, &mut cache).iter() {
+                    let (va, vb) = last.entry(s.signal.clone()).or_default();
+                    *(if in_a { va } else { vb }) = Some(s.value);
+                }
+            }
+        }
+        let names: Vec<String> = last.keys().cloned().collect();
+        let rank = self.decoder.signal_rank(&names, SignalOrder::Message);
+        let signals = ranked(last, &rank)
+            .into_iter()
+            .map(|(signal, (last_a, last_b))| SignalDiff {
+                unit: self.decoder.signal_meta.get(&signal).map_or_else(String::new, |m| m.unit.clone()),
+                signal,
+                changed: last_a != last_b,
+                last_a,
+                last_b,
+            })
+            .collect();
+        WindowDiff { messages: analysis::diff_messages(&self.frames, a, b), signals }
+    }
+
+    // clone_view()/slice() handles share storage with their session: anything that
+    // changes frames, signals or decoder state is refused on them
+    fn check_writable(&self, op: &str) -> Result<(), ApiError> {
+        if self.read_only {
+            let msg = format!("{} not allowed on a read-only view; call it on the original session", op);
+            return Err(ApiError::new(error::READ_ONLY, msg));
+        }
+        Ok(())
+    }
+
+    // session_index(): this log's signals and the decoder's message lookup
+    fn index(&self) -> SessionIndex {
+        let mut messages: Vec<(u8, u32, usize, usize)> = self
+            .decoder
+            .message_index
+            .iter()
+            .flat_map(|(&(chan, id), cands)| cands.iter().map(move |&(dbc, pos)| (chan, id, dbc, pos)))
+            .collect();
+        messages.sort_unstable();
+        SessionIndex {
+            version: index::INDEX_VERSION,
+            dbc_hash: format!("{:016x}", self.decoder.dbc_hash),
+            signals: self.signal_names.clone(),
+            messages,
+        }
+    }
+
+    // Every call after free_memory() fails instead of answering from emptied stores
+    fn check_alive(&self) -> Result<(), ApiError> {
+        if self.freed.get() {
+            return Err(ApiError::new(error::RELEASED, "session released: free_memory() was called on this session"));
+        }
+        if self.origins.iter().any(|f| f.get()) {
+            let msg = "session released: free_memory() was called on the session this view was made from";
+            return Err(ApiError::new(error::RELEASED, msg));
+        }
+        Ok(())
+    }
+
+    // `origins` of a view made from this session
+    fn lineage(&self) -> Vec<Rc<Cell<bool>>> {
+        self.origins.iter().cloned().chain([Rc::clone(&self.freed)]).collect()
+    }
+
+    // suggest_channel_mapping(): hit rates of each DBC text per logged channel
+    fn mapping_suggestion(&self, texts: &[String]) -> Result<analysis::MappingSuggestion, String> {
+        // J1939 sessions match by PGN, as decoding does
+        let j1939 = self.decoder.j1939;
+        let key = |id: u32| if j1939 && id & CAN_EFF_FLAG != 0 { j1939::pgn(id) | CAN_EFF_FLAG } else { id };
+        let mut dbc_ids = Vec::with_capacity(texts.len());
+        for (i, text) in texts.iter().enumerate() {
+            let dbc = DBC::try_from(text.as_str()).map_err(|e| format!("Failed to parse DBC {}: {:?}", i, e))?;
+            dbc_ids.push(dbc.messages().iter().map(|m| key(m.message_id().raw())).collect::<HashSet<u32>>());
+        }
+        Ok(analysis::suggest_mapping(&self.frames, &dbc_ids, key, 10_000))
+    }
+
+    // coverage(): matched frames and covered payload bits per CAN channel
+    fn coverage_report(&self) -> Vec<analysis::ChannelCoverage> {
+        let masks = self.covered_masks();
+        analysis::coverage(&self.frames, |f| masks.get(&(f.channel_num, f.raw_id(), f.data.len())).map(|m| m.as_slice()))
+    }
+
+    // dbc_consistency(): the highest-priority DBC message per (channel, id) against traffic
+    fn consistency_report(&self, opts: &ConsistencyOptions) -> consistency::ConsistencyReport {
+        let mut expected: Vec<consistency::Expected> = self
+            .decoder
+            .message_index
+            .iter()
+            .filter_map(|(&(chan, id), cands)| {
+                let &(dbc, pos) = cands.first()?;
+                let dbc = &self.decoder.dbcs[dbc].1;
+                let msg = dbc.messages().get(pos)?;
+                Some(consistency::Expected {
+                    channel_num: chan as u16,
+                    id,
+                    name: msg.message_name().clone(),
+                    dlc: *msg.message_size() as usize,
+                    cycle_ms: cycle_time_ms(dbc, msg),
+                })
+            })
+            .collect();
+        expected.sort_by_key(|e| (e.channel_num, e.id));
+        consistency::check(&self.frames, &expected, opts.cycle_tolerance_pct / 100.0, opts.min_frames)
+    }
+
+    // unmapped_bits(): set or toggling bits outside the DBC signals, per message
+    fn unmapped_report(&self) -> Vec<analysis::UnmappedBits> {
+        let masks = self.covered_masks();
+        analysis::unmapped_bits(&self.frames, |f| {
+            if f.is_error() {
+                return None;
+            }
+            masks.get(&(f.channel_num, f.raw_id(), f.data.len())).map(|m| m.as_slice())
+        })
+    }
+
+    // Covered-bit mask per (channel, id, payload length) of frames with a DBC message;
+    // the length can pick the message. Multiplexed signals all count as covered.
+    fn covered_masks(&self) -> HashMap<(u16, u32, usize), Vec<u8>> {
+        let mut masks: HashMap<(u16, u32, usize), Vec<u8>> = HashMap::new();
+        for f in self.frames.iter().filter(|f| f.is_can_message()) {
+            let key = (f.channel_num, f.raw_id(), f.data.len());
+            if masks.contains_key(&key) {
+                continue;
+            }
+            if let Some(msg) = self.decoder.message(f.channel_num, f.raw_id(), f.data.len()) {
+                let mut mask = vec![0u8; f.data.len()];
+                for sig in msg.signals() {
+                    for b in layout::signal_bits(*sig.start_bit(), *sig.signal_size(), *sig.byte_order()) {
+                        if let Some(byte) = mask.get_mut((b / 8) as usize) {
+                            *byte |= 1 << (b % 8);
+                        }
+                    }
+                }
+                masks.insert(key, mask);
+            }
+        }
+        masks
+    }
+
+    // ISO-TP messages on the pairs of TransportOptions, plus the flow control frame count
+    fn iso_tp(&self, options: JsValue) -> Result<(Vec<TransportMessage>, usize), JsValue> {
+        let opts: TransportOptions = parse_options(options, "transport options")?;
+        if opts.pairs.is_empty() {
+            return Err(JsValue::from_str("options.pairs must list at least one {request_id, response_id}"));
+        }
+        let mut r = isotp::Reassembler::default();
+        for f in self.frames.iter() {
+            r.push(&opts.pairs, &f);
+        }
+        let flow_control_frames = r.flow_control_frames;
+        Ok((r.finish(), flow_control_frames))
+    }
+
+    // channel_map / lin_databases channels with no frames in the log, and logged CAN
+    // channels without a DBC. Computed from the current frames, so merges are covered.
+    fn channel_warnings(&self) -> Vec<String> {
+        let mut can: BTreeMap<u16, usize> = BTreeMap::new();
+        let mut lin: BTreeMap<u16, usize> = BTreeMap::new();
+        for f in self.frames.iter() {
+            match f.bus {
+                Bus::Can => *can.entry(f.channel_num).or_default() += 1,
+                Bus::Lin => *lin.entry(f.channel_num).or_default() += 1,
+                _ => {}
+            }
+        }
+        let list = |prefix: &str, seen: &BTreeMap<u16, usize>| {
+            if seen.is_empty() {
+                "none".to_string()
+            } else {
+                seen.keys().map(|c| format!("{}{}", prefix, c)).collect::<Vec<_>>().join(", ")
+            }
+        };
+
+        let mut warnings = Vec::new();
+        for (d, (chan, _)) in self.decoder.dbcs.iter().enumerate() {
+            if !can.contains_key(&(*chan as u16)) {
+                warnings.push(format!(
+                    "channel_map maps DBC {} to CAN{}, but the log has no frames on CAN{} (CAN channels in the log: {})",
+                    d, chan, chan, list("CAN", &can)
+                ));
+            }
+        }
+        for src in &self.decoder.lin_sources {
+            if !lin.contains_key(&(src.channel as u16)) {
+                warnings.push(format!(
+                    "lin_databases entry {} is for LIN{}, but the log has no frames on LIN{} (LIN channels in the log: {})",
+                    src.index, src.channel, src.channel, list("LIN", &lin)
+                ));
+            }
+        }
+        if !self.decoder.dbcs.is_empty() {
+            for (chan, n) in &can {
+                if !self.decoder.dbcs.iter().any(|(c, _)| *c as u16 == *chan) {
+                    warnings.push(format!("CAN{} has {} frames in the log but no DBC in channel_map", chan, n));
+                }
+            }
+        }
+        warnings
+    }
+
+    // Decoded signals of one frame, limited to the cache's selection: stored rows, or
+    // decoded on demand in lazy mode
+    fn frame_signals<'a>(&self, f: &Frame<'a>, cache: &mut DecodeCache<'a>) -> FrameSignals<'a> {
+        if self.lazy && f.signals.is_empty() {
+            return FrameSignals::Decoded(cache.decode(&self.decoder, f));
+        }
+        match f.signals {
+            Signals::Stored { keys, values, .. } => FrameSignals::Decoded(cache.rows(f, keys, values)),
+            Signals::Rows(rows) => match cache.only {
+                Some(only) if rows.iter().any(|r| !only.contains(&r.signal)) => {
+                    FrameSignals::Decoded(rows.iter().filter(|r| only.contains(&r.signal)).cloned().collect())
+                }
+                _ => FrameSignals::Stored(rows),
+            },
+        }
+    }
+
+    // resample() grid and one value column per name (signals.rs), shared with export_parquet()
+    fn resampled(&self, names: &[String], rate_hz: f64, mode: &ResampleMode) -> Result<(Vec<f64>, Vec<Vec<f64>>), JsValue> {
+        if let ResampleMode::PerSignal(modes) = mode {
+            if let Some(n) = modes.keys().find(|n| !names.contains(n)) {
+                return Err(JsValue::from_str(&format!("resample mode given for {}, which is not among the signals", n)));
+            }
+        }
+
+        let series = self.collect_series(names);
+        let sampled = series.values().filter(|(t, _)| !t.is_empty());
+        let (t0, t1) = sampled.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), (t, _)| {
+            (lo.min(t[0]), hi.max(t[t.len() - 1]))
+        });
+        if t0 > t1 {
+            return Err(JsValue::from_str("none of the signals has samples"));
+        }
+        // same budget as decoding: grid points x signals
+        let grid = signals::uniform_grid(t0, t1, rate_hz, self.decoder.max_values / names.len().max(1))
+            .map_err(|e| JsValue::from_str(&e))?;
+        let columns = names
+            .iter()
+            .map(|name| {
+                let (t, v) = &series[name];
+                signals::resample(t, v, &grid, mode.get(name))
+            })
+            .collect();
+        Ok((grid, columns))
+    }
+
+    // Columns of export_parquet() / to_arrow(): the frame table or the resampled signals
+    fn table_columns(&self, names: &[String], opts: &TableOptions) -> Result<Vec<Column>, JsValue> {
+        let mut columns = Vec::new();
+        if opts.layout == TableLayout::Resampled {
+            let (grid, values) = self.resampled(names, opts.rate_hz, &opts.mode)?;
+            columns.push(Column::new("time_s", Values::Double(grid)));
+            for (name, v) in names.iter().zip(values) {
+                columns.push(Column::new(name.clone(), Values::nullable(v)));
+            }
+            return Ok(columns);
+        }
+
+        let index: HashMap<&str, usize> = names.iter().enumerate().map(|(i, n)| (n.as_str(), i)).collect();
+        let wanted: HashSet<String> = names.iter().cloned().collect();
+        let mut cache = DecodeCache::new(Some(&wanted));
+        let mut keep = frame_filter(&opts.event_types, &opts.query, opts.max_frames_per_id_per_second)?;
+        let (mut time, mut channel, mut id, mut extended, mut name) = (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        let (mut event_type, mut dir, mut dlc, mut data) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        let mut values: Vec<Vec<Option<f64>>> = vec![Vec::new(); names.len()];
+        let max_rows = if opts.max_rows == 0 { usize::MAX } else { opts.max_rows };
+        for f in self.frames.iter().filter(|f| keep(f)).take(max_rows) {
+            time.push(f.timestamp);
+            channel.push(f.channel.to_string());
+            id.push(f.id as i32);
+            extended.push(f.is_extended);
+            name.push(f.name.to_string());
+            event_type.push(f.event_type.label().to_string());
+            dir.push(f.dir.to_string());
+            dlc.push(f.dlc as i32);
+            data.push(f.data.to_vec());
+            values.iter_mut().for_each(|v| v.push(None));
+            for r in self.frame_signals(&f, &mut cache).iter() {
+                if let Some(&i) = index.get(r.signal.as_str()) {
+                    values[i][time.len() - 1] = Some(r.value).filter(|v| !v.is_nan());
+                }
+            }
+        }
+        let frame_columns = [
+            ("time_s", Values::Double(time)),
+            ("channel", Values::Utf8(channel)),
+            ("id", Values::Int32(id)),
+            ("extended", Values::Bool(extended)),
+            ("name", Values::Utf8(name)),
+            ("event_type", Values::Utf8(event_type)),
+            ("dir", Values::Utf8(dir)),
+            ("dlc", Values::Int32(dlc)),
+            ("data", Values::Bytes(data)),
+        ];
+        columns.extend(frame_columns.into_iter().map(|(n, values)| Column::new(n, values)));
+        for (n, v) in names.iter().zip(values) {
+            columns.push(Column::new(n.clone(), Values::OptDouble(v)));
+        }
+        Ok(columns)
+    }
+
+    // MDF channel of a decoded signal: raw storage with factor/offset and SI normalization
+    // folded into one linear conversion; physical values when there is no DBC signal (OBD-II)
+    fn mdf_channel(&self, name: String, unit: String) -> mdf::Channel {
+        let Some(meta) = self.decoder.signal_meta.get(&name) else {
+            return mdf::Channel { name, unit, storage: mdf::Storage::Physical };
+        };
+        let factor = if meta.factor == 0.0 { 1.0 } else { meta.factor };
+        let (factor, offset) = match &meta.si {
+            Some(conv) => (factor * conv.factor, meta.offset * conv.factor + conv.offset),
+            None => (factor, meta.offset),
+        };
+        let mut table: Vec<(i64, String)> = meta.value_table.iter().map(|(k, v)| (*k, v.clone())).collect();
+        table.sort();
+        mdf::Channel { name, unit, storage: mdf::Storage::Raw { factor, offset, table } }
+    }
+
+    // Up to n frames for preview(): the first ones, or with PreviewStrategy::Active one at
+    // every (active span / n) step across analysis::active_range()
+    fn preview_frames(&self, n: usize, opts: &PreviewOptions) -> Vec<Frame<'_>> {
+        let keep = event_filter(&opts.event_types);
+        let active = analysis::active_range(&self.frames).filter(|_| opts.strategy == PreviewStrategy::Active && n > 0);
+        let Some((start, end)) = active else {
+            return self.frames.iter().filter(|f| keep(f)).take(n).collect();
+        };
+        let step = (end - start) / n as f64;
+        let mut next = start;
+        let mut out = Vec::with_capacity(n);
+        for f in self.frames.range(self.frames.window(start, end)).filter(|f| keep(f)) {
+            if out.len() == n {
+                break;
+            }
+            if f.timestamp >= next {
+                next = f.timestamp + step;
+                out.push(f);
+            }
+        }
+        out
+    }
+
+    // events(): frames of the requested event types, error and remote frames by default
+    fn event_frames(&self, opts: &EventOptions) -> Result<Vec<Frame<'_>>, ApiError> {
+        let query = QueryFilter::from_query(&opts.query)
+            .map_err(|e| ApiError::new(error::INVALID_OPTIONS, format!("options.query invalid: {}", e)))?;
+        let types: &[EventType] = if opts.event_types.is_empty() { &[EventType::Error, EventType::CanRemote] } else { &opts.event_types };
+        let keep = event_filter(types);
+        let limit = if opts.limit == 0 { usize::MAX } else { opts.limit };
+        Ok(self.frames.iter().filter(|f| keep(f) && query.keeps(f)).take(limit).collect())
+    }
+
+    // Frames for JS (signals filled in when lazy)
+    fn frames_to_js<'a>(&'a self, frames: impl Iterator<Item = Frame<'a>>) -> Result<JsValue, JsValue> {
+        let frames: Vec<Frame> = frames.collect();
+        let mut cache = DecodeCache::new(None);
+        let signals: Vec<FrameSignals> = frames.iter().map(|f| self.frame_signals(f, &mut cache)).collect();
+        let rows: Vec<Frame> = frames.iter().zip(&signals).map(|(f, s)| Frame { signals: Signals::Rows(s), ..*f }).collect();
+        timing::span(Phase::Serialize, || serde_wasm_bindgen::to_value(&rows))
+            .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    }
+
+    // Wall clock from the first log's measurement start (merged logs share its timeline)
+    fn wall_clock(&self) -> Result<WallClock, JsValue> {
+        let start = self.config.logs.first().and_then(|l| l.start);
+        start
+            .map(|s| WallClock::new(&s))
+            .ok_or_else(|| JsValue::from_str("absolute time needs the measurement start, which this BLF header lacks"))
+    }
+
+    // export_csv_chunked(): hands each chunk with the manifest including it to `emit`,
+    // starting after opts.resume's last chunk when given
+    fn csv_chunks(
+        &self,
+        layout: &CsvLayout,
+        opts: &CsvOptions,
+        mut emit: impl FnMut(&[u8], &ExportManifest) -> Result<(), JsValue>,
+    ) -> Result<ExportManifest, JsValue> {
+        let chunk_frames = if opts.chunk_frames == 0 { 100_000 } else { opts.chunk_frames };
+        let mut fresh = ExportManifest::new(self.frames.len(), chunk_frames, layout.header());
+        fresh.split_by_day = opts.split_by_day;
+        let clock = if opts.split_by_day { Some(self.wall_clock()?) } else { None };
+        let mut manifest = match &opts.resume {
+            Some(m) => {
+                m.check_resume(&fresh)
+                    .map_err(|e| JsValue::from_str(&format!("cannot resume export: {}", e)))?;
+                m.clone()
+            }
+            None => fresh,
+        };
+
+        let mut first = manifest.next_frame();
+        // resumed: replay the skipped frames so held/start values continue correctly
+        let mut state = layout.state();
+        let wanted = layout.wanted();
+        let mut cache = DecodeCache::new(Some(&wanted));
+        let mut keep = opts.frame_filter()?;
+        for f in self.frames.range(0..first.min(self.frames.len())).filter(|f| keep(f)) {
+            self.csv_row(layout, &mut state, &mut cache, &f);
+        }
+        while first < self.frames.len() {
+            let mut last = (first + chunk_frames).min(self.frames.len()) - 1;
+            // split_by_day: end at midnight; every day starts with the header
+            let day = clock.map(|c| {
+                let t = self.frames.get(first).timestamp;
+                let next_day = self.frames.window(c.next_midnight(t), f64::INFINITY).start;
+                last = last.min(next_day.max(first + 1) - 1);
+                c.day(t)
+            });
+            let new_day = day.is_some() && manifest.chunks.last().is_none_or(|c| c.day != day);
+            let mut wtr = csv::WriterBuilder::new().has_headers(false).from_writer(vec![]);
+            if first == 0 || new_day {
+                wtr.write_record(&manifest.columns)
+                    .map_err(|e| JsValue::from_str(&format!("csv write failed: {:?}", e)))?;
+            }
+            for f in self.frames.range(first..last + 1).filter(|f| keep(f)) {
+                let row = self.csv_row(layout, &mut state, &mut cache, &f);
+                timing::sampled(Phase::Serialize, || wtr.write_record(row))
+                    .map_err(|e| JsValue::from_str(&format!("csv write failed: {:?}", e)))?;
+            }
+            let bytes = timing::span(Phase::Serialize, || {
+                wtr.into_inner().map_err(|e| JsValue::from_str(&format!("csv finalize failed: {:?}", e)))
+            })?;
+
+            manifest.record(first, last, bytes.len() as u64, day);
+            emit(&bytes, &manifest)?;
+            first = last + 1;
+        }
+        Ok(manifest)
+    }
+
+    // Column layout shared by export_csv() and export_csv_chunked()
+    fn csv_layout(&self, applied_signals: JsValue, opts: &CsvOptions) -> Result<CsvLayout, JsValue> {
+        let applied: Vec<String> = if applied_signals.is_null() || applied_signals.is_undefined() {
+            Vec::new()
+        } else {
+            serde_wasm_bindgen::from_value(applied_signals)
+                .map_err(|e| JsValue::from_str(&format!("applied_signals must be array of strings: {:?}", e)))?
+        };
+        let rank = self.decoder.signal_rank(&applied, opts.order);
+        let selected: Vec<String> = ranked(applied.into_iter().map(|n| (n, ())).collect(), &rank)
+            .into_iter()
+            .map(|(n, _)| n)
+            .collect();
+        // signals that get an extra "{name}_text" label column
+        let labelled = selected
+            .iter()
+            .map(|n| opts.value_labels && self.decoder.signal_meta.get(n).is_some_and(|m| !m.value_table.is_empty()))
+            .collect();
+        let start = selected
+            .iter()
+            .map(|n| self.decoder.signal_meta.get(n).and_then(|m| m.start_value).filter(|_| opts.start_values))
+            .collect();
+        let clock = if opts.absolute_time { Some(self.wall_clock()?) } else { None };
+        Ok(CsvLayout { selected, labelled, label_only: opts.label_only, hold: opts.hold_values, start, clock })
+    }
+
+    fn csv_row<'a>(&self, layout: &CsvLayout, state: &mut CsvState, cache: &mut DecodeCache<'a>, f: &Frame<'a>) -> Vec<String> {
+        let mut row: Vec<String> = vec![
+            format!("{:.6}", f.timestamp),
+            f.channel.to_string(),
+            f.channel_num.to_string(),
+            format!("0x{:X}", f.id),
+            f.name.to_string(),
+            f.event_type.label().to_string(),
+            f.dir.to_string(),
+            f.flags_label(),
+            f.dlc.to_string(),
+            f.data.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" "),
+        ];
+        if let Some(clock) = &layout.clock {
+            row.insert(1, clock.datetime(f.timestamp));
+        }
+
+        let signals = self.frame_signals(f, cache);
+        let sig_map: HashMap<&str, f64> = signals.iter().map(|s| (s.signal.as_str(), s.value)).collect();
+
+        for (i, (sname, with_text)) in layout.selected.iter().zip(&layout.labelled).enumerate() {
+            let fresh = sig_map.get(sname.as_str()).copied();
+            if fresh.is_some() {
+                state.last[i] = fresh;
+                state.seen[i] = true;
+            }
+            let val = match fresh {
+                Some(v) => Some(v),
+                None if !state.seen[i] => layout.start[i],
+                None if layout.hold => state.last[i],
+                None => None,
+            };
+            let cell = val.map_or(String::new(), |v| v.to_string());
+            if !*with_text {
+                row.push(cell);
+                continue;
+            }
+            let label = val.and_then(|v| self.decoder.signal_meta.get(sname)?.label(v));
+            if layout.label_only {
+                // values without a description keep their number
+                row.push(label.unwrap_or(cell));
+            } else {
+                row.push(cell);
+                row.push(label.unwrap_or_default());
+            }
+        }
+        row
+    }
+
+    // One signal's samples; errors if it never occurs
+    fn series(&self, name: &str) -> Result<Series, JsValue> {
+        self.collect_series(&[name.to_string()])
+            .remove(name)
+            .filter(|(t, _)| !t.is_empty())
+            .ok_or_else(|| JsValue::from_str(&format!("signal {} has no samples", name)))
+    }
+
+    // Actual samples (timestamps, values) per signal; pinned signals come from their store
+    fn collect_series(&self, names: &[String]) -> HashMap<String, Series> {
+        let mut out: HashMap<String, Series> = HashMap::new();
+        let mut wanted: HashSet<String> = HashSet::new();
+        for n in names {
+            match self.pinned.get(n) {
+                Some(series) => {
+                    out.insert(n.clone(), series.clone());
+                }
+                None => {
+                    out.insert(n.clone(), (Vec::new(), Vec::new()));
+                    wanted.insert(n.clone());
+                }
+            }
+        }
+        if wanted.is_empty() {
+            return out;
+        }
+        let mut cache = DecodeCache::new(Some(&wanted));
+        for f in self.frames.iter() {
+            for s in self.frame_signals(&f, &mut cache).iter() {
+                if let Some((t, v)) = out.get_mut(&s.signal).filter(|_| wanted.contains(&s.signal)) {
+                    t.push(f.timestamp);
+                    v.push(s.value);
+                }
+            }
+        }
+        out
+    }
+}
+
+// -------------------------------
+// SECTION 3: Helper - decode a single signal (from can_dbc::Signal)
+// -------------------------------
+pub(crate) fn decode_signal_value(sig: &Signal, data: &[u8]) -> Option<f64> {
+    let val_u64 = signal_raw(sig, data)?;
+    let len = *sig.signal_size() as usize;
+
+    // Signed vs unsigned (unsigned stays u64 so 64-bit values keep their sign)
+    let raw: f64 = if *sig.value_type() == ValueType::Signed {
+        let shift = 64usize - len;
+        (((val_u64 << shift) as i64) >> shift) as f64
+    } else {
+        val_u64 as f64
+    };
+
+    Some(raw * *sig.factor() + *sig.offset())
+}
+
+// Raw bits of a signal, unsigned and unscaled (also what multiplexor values compare)
+pub(crate) fn signal_raw(sig: &Signal, data: &[u8]) -> Option<u64> {
+    let start = *sig.start_bit() as usize;
+    let len = *sig.signal_size() as usize;
+    let frame_bits = data.len() * 8;
+    // whole payload (up to 64 bytes for CAN FD); signals past its end are absent
+    if len == 0 || len > 64 || start >= frame_bits {
+        return None;
+    }
+
+    // Byte order handling
+    let val_u64: u64 = if *sig.byte_order() == ByteOrder::BigEndian {
+        // Motorola: start_bit is the MSB; bits follow the DBC sawtooth (bit 7..0 of
+        // each byte, then on into the next byte), i.e. a byte-swapped value
+        let mut acc: u64 = 0;
+        for pos in layout::motorola_bits(start as u64, len as u64) {
+            let pos = pos as usize;
+            if pos >= frame_bits {
+                return None;
+            }
+            acc = (acc << 1) | ((data[pos / 8] >> (pos % 8)) & 1) as u64;
+        }
+        acc
+    } else {
+        if start + len > frame_bits {
+            return None;
+        }
+        // Intel: LSB at start_bit, bits run upwards. Load only the bytes the signal
+        // spans (at most 9) into a u128 window, wherever it sits in the payload.
+        let first = start / 8;
+        let last = (start + len - 1) / 8;
+        let window = data[first..=last].iter().rev().fold(0u128, |acc, b| (acc << 8) | *b as u128);
+        let mask = if len == 64 { u64::MAX } else { (1u64 << len) - 1 };
+        (window >> (start % 8)) as u64 & mask
+    };
+    Some(val_u64)
+}
+
+// -------------------------------
+// SECTION 4: Helper - decode one BLF object into a FrameRow (if CAN frame)
+// -------------------------------
+fn frame_from_obj(
+    obj: &BlfObject,
+    decoder: &Decoder,
+    seen_signals: Option<&mut Vec<String>>,
+    decode_signals: bool,
+) -> Option<FrameRow> {
+    if !decoder.filter.keeps(obj) {
+        return None;
+    }
+    if let BlfObject::Can(cf) = obj {
+        let ts = cf.timestamp_ns as f64 / 1e9;
+        let channel_str = format!("CAN{}", cf.channel);
+        let id = cf.id; // raw: IDE bit set for extended ids, like MessageId::raw()
+        let dlc = cf.dlc;
+        let data = Payload::from(cf.data.as_slice());
+
+        let selected = decoder.select(cf.channel, id, data.len());
+        let msg = selected.map(|(_, m)| m);
+        let frame_name = decoder.frame_name(&channel_str, id, msg.map(|m| m.message_name().as_str()).or_else(|| decoder.obd_name(id)));
+        let signal_rows: Vec<SignalRow> = if decode_signals {
+            decoder.decode(cf.channel, id, &data, None)
+        } else {
+            Vec::new()
+        };
+
+        // ✅ update seen_signals cleanly, after building signal_rows
+        // (lazy: names come from the matched message definition)
+        if let Some(seen) = seen_signals {
+            let names: Vec<String> = if decode_signals {
+                signal_rows.iter().map(|s| s.signal.clone()).collect()
+            } else {
+                selected
+                    .map(|(dbc, m)| decoder.message_signals(dbc, m).to_vec())
+                    // OBD-II PIDs depend on the payload
+                    .unwrap_or_else(|| decoder.decode(cf.channel, id, &data, None).into_iter().map(|s| s.signal).collect())
+            };
+            note_signals(seen, names);
+        }
+
+        let (ident, is_extended) = split_id(id);
+        let j1939 = (decoder.j1939 && is_extended).then(|| J1939Info {
+            reassembled: j1939::is_reassembled(cf),
+            ..J1939Info::from_id(id)
+        });
+        let event_type = match j1939 {
+            Some(J1939Info { reassembled: true, .. }) => EventType::J1939Tp,
+            _ if cf.fd => EventType::CanFd,
+            _ if cf.rtr => EventType::CanRemote,
+            _ => EventType::Can,
+        };
+        return Some(FrameRow {
+            timestamp: ts,
+            channel: channel_str,
+            channel_num: cf.channel,
+            id: ident,
+            is_extended,
+            name: frame_name,
+            event_type,
+            dir: if cf.tx { "Tx" } else if cf.tx_request { "TxRq" } else { "Rx" }.to_string(),
+            dlc,
+            data,
+            flags: FrameFlags {
+                rtr: cf.rtr,
+                fd: cf.fd,
+                brs: cf.brs,
+                esi: cf.esi,
+                wakeup: cf.wakeup,
+                nerr: cf.nerr,
+            },
+            signals: signal_rows,
+            error: None,
+            flexray: None,
+            ethernet: None,
+            j1939,
+            bus: Bus::Can,
+        });
+    }
+    match obj {
+        BlfObject::Error(e) => Some(error_frame(e)),
+        BlfObject::Lin(lf) => Some(lin_frame(lf, decoder, seen_signals, decode_signals)),
+        BlfObject::FlexRay(fr) => Some(flexray_frame(fr, decoder)),
+        BlfObject::Ethernet(e) if decoder.include_ethernet => Some(ethernet_frame(e, decoder)),
+        _ => None,
+    }
+}
+
+fn note_signals(seen: &mut Vec<String>, names: Vec<String>) {
+    for name in names {
+        if !seen.contains(&name) {
+            seen.push(name);
+        }
+    }
+}
+
+fn lin_frame(
+    lf: &LinFrame,
+    decoder: &Decoder,
+    seen_signals: Option<&mut Vec<String>>,
+    decode_signals: bool,
+) -> FrameRow {
+    let channel = format!("LIN{}", lf.channel);
+    let id = lf.id as u32;
+    let data = Payload::from(lf.data.as_slice());
+    let db = decoder.lin.get(&lf.channel);
+    let signal_rows = if decode_signals { decoder.decode_lin(lf.channel, id, &data, None) } else { Vec::new() };
+    if let Some(seen) = seen_signals {
+        let names = if decode_signals {
+            signal_rows.iter().map(|s| s.signal.clone()).collect()
+        } else {
+            db.map_or_else(Vec::new, |d| {
+                d.signal_names(lf.id).iter().map(|n| decoder.names.lin_name(lf.channel, d.frame_name(lf.id), n)).collect()
+            })
+        };
+        note_signals(seen, names);
+    }
+    FrameRow {
+        timestamp: lf.timestamp_ns as f64 / 1e9,
+        name: decoder.frame_name(&channel, id, db.and_then(|d| d.frame_name(lf.id))),
+        channel,
+        channel_num: lf.channel,
+        id,
+        is_extended: false,
+        event_type: EventType::Lin,
+        dir: if lf.tx { "Tx" } else if lf.tx_request { "TxRq" } else { "Rx" }.to_string(),
+        dlc: lf.dlc,
+        data,
+        flags: FrameFlags::default(),
+        signals: signal_rows,
+        error: None,
+        flexray: None,
+        ethernet: None,
+        j1939: None,
+        bus: Bus::Lin,
+    }
+}
+
+// FlexRay frames are passed through raw (slot id, cycle, payload) for the timeline
+fn flexray_frame(fr: &FlexRayFrame, decoder: &Decoder) -> FrameRow {
+    let channel = format!("FR{}", fr.channel);
+    let id = fr.slot_id as u32;
+    let data = Payload::from(fr.data.as_slice());
+    let channels = match fr.channel_mask & 0x3 {
+        1 => "A",
+        2 => "B",
+        _ => "AB",
+    };
+    FrameRow {
+        timestamp: fr.timestamp_ns as f64 / 1e9,
+        name: decoder.frame_name(&channel, id, None),
+        channel,
+        channel_num: fr.channel,
+        id,
+        is_extended: false,
+        event_type: EventType::FlexRay,
+        dir: if fr.tx { "Tx" } else if fr.tx_request { "TxRq" } else { "Rx" }.to_string(),
+        dlc: fr.data.len().min(u8::MAX as usize) as u8,
+        data,
+        flags: FrameFlags::default(),
+        signals: Vec::new(),
+        error: None,
+        flexray: Some(FlexRayInfo { cycle: fr.cycle, channels: channels.to_string() }),
+        ethernet: None,
+        j1939: None,
+        bus: Bus::FlexRay,
+    }
+}
+
+// Ethernet frames (options.include_ethernet) are passed through raw; data is the
+// payload after the MAC header
+fn ethernet_frame(e: &EthernetFrame, decoder: &Decoder) -> FrameRow {
+    let channel = format!("ETH{}", e.channel);
+    let id = e.ethertype as u32;
+    let mac = |m: &[u8; 6]| m.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":");
+    FrameRow {
+        timestamp: e.timestamp_ns as f64 / 1e9,
+        name: decoder.frame_name(&channel, id, None),
+        channel,
+        channel_num: e.channel,
+        id,
+        is_extended: false,
+        event_type: EventType::Ethernet,
+        dir: if e.tx { "Tx" } else if e.tx_request { "TxRq" } else { "Rx" }.to_string(),
+        dlc: 0,
+        data: Payload::from(e.payload.as_slice()),
+        flags: FrameFlags::default(),
+        signals: Vec::new(),
+        error: None,
+        flexray: None,
+        ethernet: Some(EthernetInfo {
+            source: mac(&e.source),
+            destination: mac(&e.destination),
+            ethertype: e.ethertype,
+            vlan_id: e.vlan_tci.map(|tci| tci & 0x0FFF),
+        }),
+        j1939: None,
+        bus: Bus::Ethernet,
+    }
+}
+
+// "0x1A0" / "1a0" / "18FEF100x" -> raw id (IDE bit set for extended)
+fn parse_hex_id(text: &str) -> Result<u32, String> {
+    let t = text.trim();
+    let t = t.strip_prefix("0x").or_else(|| t.strip_prefix("0X")).unwrap_or(t);
+    let (digits, ext) = match t.strip_suffix(['x', 'X']) {
+        Some(d) => (d, true),
+        None => (t, false),
+    };
+    let id = u32::from_str_radix(digits, 16).map_err(|_| format!("invalid CAN id \"{}\"", text))?;
+    if id > CAN_EFF_MASK {
+        return Err(format!("CAN id \"{}\" exceeds 29 bits", text));
+    }
+    Ok(if ext || id > 0x7FF { id | CAN_EFF_FLAG } else { id })
+}
+
+// "01 02 FF", "0102ff", "01-02", "0x01,0x02" -> bytes (at most 64)
+fn parse_hex_bytes(text: &str) -> Result<Vec<u8>, String> {
+    let digits: String = text
+        .split([' ', '\t', ',', '-', ':'])
+        .map(|tok| tok.strip_prefix("0x").or_else(|| tok.strip_prefix("0X")).unwrap_or(tok))
+        .collect();
+    if !digits.len().is_multiple_of(2) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("invalid hex payload \"{}\"", text));
+    }
+    let bytes: Vec<u8> = (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap_or(0))
+        .collect();
+    if bytes.len() > 64 {
+        return Err(format!("payload of {} bytes exceeds 64", bytes.len()));
+    }
+    Ok(bytes)
+}
+
+// payload length -> DLC code (CAN FD lengths above 8 use codes 9..15)
+fn len_to_dlc(len: usize) -> u8 {
+    match len {
+        0..=8 => len as u8,
+        9..=12 => 9,
+        13..=16 => 10,
+        17..=20 => 11,
+        21..=24 => 12,
+        25..=32 => 13,
+        33..=48 => 14,
+        _ => 15,
+    }
+}
+
+// Error frames are kept as rows so decode gaps can be lined up with bus errors
+fn error_frame(e: &CanError) -> FrameRow {
+    let (id, is_extended) = split_id(e.id);
+    FrameRow {
+        timestamp: e.timestamp_ns as f64 / 1e9,
+        channel: format!("CAN{}", e.channel),
+        channel_num: e.channel,
+        id,
+        is_extended,
+        name: String::new(),
+        event_type: EventType::Error,
+        dir: if e.tx == Some(true) { "Tx" } else { "Rx" }.to_string(),
+        dlc: e.dlc,
+        data: Payload::from(e.data.as_slice()),
+        flags: FrameFlags { fd: e.fd, ..FrameFlags::default() },
+        signals: Vec::new(),
+        error: Some(ErrorInfo {
+            error_type: e.error_type.map(str::to_string),
+            position: e.position,
+            ecc: e.ecc,
+            tx_errors: e.tx_errors,
+            rx_errors: e.rx_errors,
+        }),
+        flexray: None,
+        ethernet: None,
+        j1939: None,
+        bus: Bus::Can,
+    }
+}
+// Frame back to the BLF object it was read from (export_blf()); None for J1939
+// transfers, which were reassembled from TP frames that are exported themselves.
+// Ethernet VLAN tags keep their id only (priority bits are not stored).
+fn obj_from_frame(f: &Frame) -> Option<BlfObject> {
+    let timestamp_ns = (f.timestamp * 1e9).round().max(0.0) as u64;
+    let (tx, tx_request) = (f.dir == "Tx", f.dir == "TxRq");
+    let obj = match (f.bus, f.event_type) {
+        (_, EventType::J1939Tp) => return None,
+        (Bus::Can, EventType::Error) => {
+            let info = f.error.cloned().unwrap_or_default();
+            BlfObject::Error(CanError {
+                timestamp_ns,
+                channel: f.channel_num,
+                id: f.raw_id(),
+                dlc: f.dlc,
+                data: f.data.to_vec(),
+                fd: f.flags.fd,
+                error_type: info.error_type.and_then(|t| blf::ERROR_TYPES.iter().copied().find(|k| *k == t)),
+                tx: tx.then_some(true),
+                position: info.position,
+                ecc: info.ecc,
+                tx_errors: info.tx_errors,
+                rx_errors: info.rx_errors,
+            })
+        }
+        (Bus::Can, _) => BlfObject::Can(CanFrame {
+            timestamp_ns,
+            channel: f.channel_num,
+            id: f.raw_id(),
+            dlc: f.dlc,
+            data: f.data.to_vec(),
+            tx,
+            tx_request,
+            rtr: f.flags.rtr,
+            wakeup: f.flags.wakeup,
+            nerr: f.flags.nerr,
+            fd: f.flags.fd,
+            brs: f.flags.brs,
+            esi: f.flags.esi,
+        }),
+        (Bus::Lin, _) => BlfObject::Lin(LinFrame {
+            timestamp_ns,
+            channel: f.channel_num,
+            id: f.id as u8,
+            dlc: f.dlc,
+            data: f.data.to_vec(),
+            tx,
+            tx_request,
+        }),
+        (Bus::FlexRay, _) => {
+            let info = f.flexray?;
+            BlfObject::FlexRay(FlexRayFrame {
+                timestamp_ns,
+                channel: f.channel_num,
+                channel_mask: match info.channels.as_str() {
+                    "A" => 1,
+                    "B" => 2,
+                    _ => 3,
+                },
+                slot_id: f.id as u16,
+                cycle: info.cycle,
+                data: f.data.to_vec(),
+                tx,
+                tx_request,
+            })
+        }
+        (Bus::Ethernet, _) => {
+            let info = f.ethernet?;
+            let mac = |text: &str| {
+                let mut out = [0u8; 6];
+                for (b, part) in out.iter_mut().zip(text.split(':')) {
+                    *b = u8::from_str_radix(part, 16).unwrap_or(0);
+                }
+                out
+            };
+            BlfObject::Ethernet(EthernetFrame {
+                timestamp_ns,
+                channel: f.channel_num,
+                source: mac(&info.source),
+                destination: mac(&info.destination),
+                ethertype: info.ethertype,
+                vlan_tci: info.vlan_id,
+                payload: f.data.to_vec(),
+                tx,
+                tx_request,
+            })
+        }
+    };
+    Some(obj)
+}
 
 // -------------------------------
 // SECTION 5: count_frames (fast pass, capped at 100k frames)
 // -------------------------------
 #[wasm_bindgen]
 pub fn count_frames(blf_bytes: &[u8]) -> Result<JsValue, JsValue> {
-    let cursor = Cursor::new(blf_bytes);
-    let blf = BlfFile::from_reader(cursor)
-        .map_err(|(e, _)| JsValue::from_str(&format!("Failed to parse BLF: {:?}", e)))?;
+    let blf = LogReader::new(blf_bytes).map_err(log_error)?;
+
+    let mut count = 0usize;
+    let mut first_ts = 0.0;
+    let mut last_ts = 0.0;
+    let mut capped = false;
+
+    for obj in blf {
+        if let BlfObject::Can(cf) = obj {
+            if count == 0 {
+                first_ts = cf.timestamp_ns as f64 / 1e9;
+            }
+            last_ts = cf.timestamp_ns as f64 / 1e9;
+            count += 1;
+            if count >= 100_000 {
+                capped = true;
+                break;
+            }
+        }
+    }
+
+    serde_wasm_bindgen::to_value(&json!({
+        "frame_count": count,
+        "first_ts": first_ts,
+        "last_ts": last_ts,
+        "capped": capped
+    }))
+    .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+}
+
+
+// -------------------------------
+// SECTION 5b: aggregate_reports (fleet statistics over per-file reports, fleet.rs)
+// -------------------------------
+#[wasm_bindgen(js_name = aggregate_reports)]
+pub fn aggregate_reports(reports: JsValue) -> Result<JsValue, JsValue> {
+    let items: Vec<serde_json::Value> = serde_wasm_bindgen::from_value(reports)
+        .map_err(|e| JsValue::from_str(&format!("reports must be an array: {:?}", e)))?;
+    let mut parsed = Vec::with_capacity(items.len());
+    for (i, item) in items.into_iter().enumerate() {
+        // report objects or their JSON text
+        let report: Result<fleet::FileReport, String> = match item {
+            serde_json::Value::String(text) => serde_json::from_str(&text).map_err(|e| e.to_string()),
+            value => serde_json::from_value(value).map_err(|e| e.to_string()),
+        };
+        parsed.push(report.map_err(|e| JsValue::from_str(&format!("report {}: {}", i, e)))?);
+    }
+    serde_wasm_bindgen::to_value(&fleet::aggregate(&parsed))
+        .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+}
+
+
+// -------------------------------
+// SECTION 5c: version (build and format info for host apps)
+// -------------------------------
+#[wasm_bindgen(js_name = version)]
+pub fn version() -> Result<JsValue, JsValue> {
+    let objects: Vec<serde_json::Value> = blf::SUPPORTED_OBJECTS
+        .iter()
+        .map(|(object_type, name)| json!({ "type": object_type, "name": name }))
+        .collect();
+    serde_wasm_bindgen::to_value(&json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "crate_version": env!("CARGO_PKG_VERSION"),
+        "blf_object_types": objects,
+        "database_formats": ["DBC", "LDF", "DBC patch (JSON)"],
+        "event_types": [
+            EventType::Can, EventType::CanFd, EventType::CanRemote, EventType::Error,
+            EventType::J1939Tp, EventType::Lin, EventType::FlexRay, EventType::Ethernet,
+        ],
+        "decimate_encodings": ["json", "typed", "binary"],
+        "formats": {
+            "binary_decimation": binary::VERSION,
+            "pyramid_cache": pyramid::VERSION,
+            "session_index": index::INDEX_VERSION,
+            "export_manifest": export::MANIFEST_VERSION,
+            "dbc_patch": overrides::PATCH_VERSION,
+        },
+        // Cargo features compiled in (the crate defines none beyond default yet)
+        "features": Vec::<&str>::new(),
+        "capabilities": ["can_fd", "j1939", "obd2", "isotp", "uds", "lin", "flexray", "ethernet", "streaming"],
+        "debug_build": cfg!(debug_assertions),
+    }))
+    .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+}
+
+
+// -------------------------------
+// SECTION 6: Options accepted from JS (serde-deserializable)
+// -------------------------------
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct CellSpreadOptions {
+    pub cells: Vec<String>, // cell voltage signals, in cell order
+    pub prefix: Option<String>, // instead of cells: every seen signal starting with it, in DBC order
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub threshold: Option<f64>, // imbalance: delta above it (signal unit, e.g. V)
+    #[serde(deserialize_with = "numeric::f64")]
+    pub min_event_ms: f64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ChargingOptions {
+    pub plug_signal: Option<String>, // plugged while > 0.5
+    pub current_signal: Option<String>,
+    pub voltage_signal: Option<String>, // with current: power and energy
+    pub soc_signal: Option<String>,
+    #[serde(deserialize_with = "numeric::f64")]
+    pub current_threshold: f64, // charging above it (after invert_current)
+    pub invert_current: bool, // charge current logged negative
+    #[serde(deserialize_with = "numeric::f64")]
+    pub min_duration_ms: f64,
+    #[serde(deserialize_with = "numeric::f64")]
+    pub max_gap_ms: f64, // shorter interruptions stay in one session
+}
+
+impl Default for ChargingOptions {
+    fn default() -> Self {
+        ChargingOptions {
+            plug_signal: None,
+            current_signal: None,
+            voltage_signal: None,
+            soc_signal: None,
+            current_threshold: 1.0,
+            invert_current: false,
+            min_duration_ms: 10_000.0,
+            max_gap_ms: 30_000.0,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ThresholdSignal {
+    pub signal: String,
+    #[serde(deserialize_with = "numeric::f64")]
+    pub threshold: f64,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct DerateOptions {
+    pub temperatures: Vec<ThresholdSignal>,
+    pub currents: Vec<ThresholdSignal>,
+    pub derate_flags: Vec<String>, // active while the value is above 0.5
+    #[serde(deserialize_with = "numeric::f64")]
+    pub min_event_ms: f64, // shorter excursions are left out of events (not of the times)
+}
+
+// resample() mode: one interpolation for every signal, or one per signal name
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ResampleMode {
+    All(Interpolation),
+    PerSignal(HashMap<String, Interpolation>), // unlisted signals: hold
+}
+
+impl Default for ResampleMode {
+    fn default() -> Self {
+        ResampleMode::All(Interpolation::Hold)
+    }
+}
 
-    let mut count = 0usize;
-    let mut first_ts = 0.0;
-    let mut last_ts = 0.0;
-    let mut capped = false;
+impl ResampleMode {
+    fn get(&self, name: &str) -> Interpolation {
+        match self {
+            ResampleMode::All(mode) => *mode,
+            ResampleMode::PerSignal(modes) => modes.get(name).copied().unwrap_or_default(),
+        }
+    }
+}
 
-    for obj in blf {
-        if let ObjectTypes::CanMessage86(cf) = obj.data {
-            if count == 0 {
-                first_ts = cf.header.timestamp_ns as f64 / 1e9;
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct SignalStatsOptions {
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub t0: Option<f64>, // s; default first sample
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub t1: Option<f64>, // s; default last sample
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub threshold: Option<f64>, // duty cycle: time with value > threshold
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TransportOptions {
+    pub pairs: Vec<IsoTpPair>, // request/response id pairs to reassemble
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct MergeOptions {
+    pub dedup: bool,
+    #[serde(deserialize_with = "numeric::f64")]
+    pub dedup_tolerance_ms: f64,
+    pub correct_clock: bool,
+    #[serde(deserialize_with = "numeric::f64")]
+    pub max_clock_offset_ms: f64,
+    #[serde(deserialize_with = "numeric::f64")]
+    pub clock_match_tolerance_ms: f64,
+    #[serde(deserialize_with = "numeric::usize")]
+    pub min_clock_pairs: usize,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        MergeOptions {
+            dedup: true,
+            dedup_tolerance_ms: 1.0,
+            correct_clock: true,
+            max_clock_offset_ms: 2000.0,
+            clock_match_tolerance_ms: 5.0,
+            min_clock_pairs: 10,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct SessionOptions {
+    pub normalize_units: bool,
+    pub unit_conversions: HashMap<String, UnitConversion>, // extends/overrides the built-in table
+    pub pyramid_signals: Vec<String>, // min/max pyramids built at construction
+    pub pinned_signals: Vec<String>, // decode only these eagerly; everything else on demand
+    pub lazy_signals: bool, // decode nothing at construction; every signal on demand
+    #[serde(skip_serializing)]
+    pub warm_start: Option<SessionIndex>, // session_index() of a previous log with the same DBCs
+    #[serde(deserialize_with = "numeric::usize_vec")]
+    pub dbc_priority: Vec<usize>, // dbc_texts indices, preferred first, for ids defined by several DBCs
+    #[serde(deserialize_with = "numeric::usize")]
+    pub max_signals_per_message: usize, // 0 -> 1024; extra signals are skipped (see warnings())
+    #[serde(deserialize_with = "numeric::usize")]
+    pub max_decoded_values: usize, // 0 -> 20M; construction/merge fails beyond it instead of hanging
+    pub unknown_name: Option<String>, // name for ids not in the DBC, e.g. "UNKNOWN({id})"; {id}, {channel}
+    #[serde(skip_serializing)]
+    pub lin_databases: Vec<LinDatabaseText>, // LDF or DBC per LIN channel (hashes in session_config())
+    pub include_ethernet: bool, // keep Ethernet frame objects as FrameRows (off: skipped)
+    pub j1939: bool, // J1939 ids (FrameRow.j1939), DBC messages matched by PGN, TP reassembly
+    pub obd2: bool, // decode OBD-II mode 01 responses without a DBC message (obd.rs)
+    pub filter: FrameFilter, // frames to keep; the rest are skipped while parsing
+    pub signal_overrides: HashMap<String, SignalOverride>, // "CAN1.Msg.Sig", "CAN1.Sig" or "Sig" -> DBC fixes (overrides.rs)
+    pub patch: Option<DbcPatch>, // saved export_patch(); signal_overrides apply on top
+    pub name_collisions: CollisionPolicy, // one signal name in several messages of a channel
+    pub signal_naming: SignalNaming,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SignalNaming {
+    #[default]
+    Channel, // "CAN1.Sig" (see name_collisions)
+    Message, // "CAN1.{Message}.Sig" / "LIN1.{Frame}.Sig" for every signal
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CollisionPolicy {
+    #[default]
+    Shared, // one "CAN1.Sig" holding every message's values (listed in warnings())
+    Qualify, // "CAN1.{Message}.Sig" for each defining message
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct FrameFilter {
+    pub ids: Vec<u32>, // CAN ids (bit 31 set: that extended id only); empty -> all
+    pub channels: Vec<String>, // "CAN1", "LIN2", "FR1", "ETH1"; empty -> all
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LinDatabaseText {
+    pub channel: u8, // BLF LIN channel
+    pub text: String, // LDF, or a DBC whose message ids are the LIN frame ids
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct PreviewOptions {
+    pub event_types: Vec<EventType>, // e.g. ["can_fd", "Error Frame"]; empty -> all
+    pub strategy: PreviewStrategy,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct EventOptions {
+    pub event_types: Vec<EventType>, // empty -> error and remote frames
+    pub query: FrameQuery, // as CsvOptions
+    #[serde(deserialize_with = "numeric::usize")]
+    pub limit: usize, // 0 -> all
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviewStrategy {
+    #[default]
+    Head, // the first frames of the log
+    Active, // spread over the active time range, skipping a quiet start (analysis.rs)
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct CsvOptions {
+    pub value_labels: bool, // add "{Signal}_text" columns for value-table signals
+    pub label_only: bool, // with value_labels: write the label in place of the code instead
+    pub hold_values: bool, // wide layout: carry each signal's last value into every row
+    pub start_values: bool, // before a signal's first sample, show its DBC GenSigStartValue
+    pub order: SignalOrder, // signal column order
+    #[serde(deserialize_with = "numeric::usize")]
+    pub chunk_frames: usize, // export_csv_chunked only; 0 -> 100k frames per chunk
+    pub absolute_time: bool, // add "Time (wall clock)": BLF measurement start + timestamp
+    pub split_by_day: bool, // export_csv_chunked only; chunks never span midnight (wall clock)
+    pub event_types: Vec<EventType>, // rows of these event types only; empty -> all
+    pub query: FrameQuery, // rows of matching frames only
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub max_frames_per_id_per_second: Option<f64>, // thin chatty ids (RateLimit); None -> all
+    pub resume: Option<ExportManifest>, // export_csv_chunked only; continue after its last chunk
+}
+
+impl CsvOptions {
+    fn frame_filter(&self) -> Result<impl FnMut(&Frame) -> bool + '_, JsValue> {
+        frame_filter(&self.event_types, &self.query, self.max_frames_per_id_per_second)
+    }
+}
+
+// a frame's signal without its "channel." / "Message." qualification (Influx fields, XLSX columns)
+fn short_signal_name<'s>(f: &Frame, signal: &'s str) -> &'s str {
+    let short = signal.strip_prefix(f.channel).and_then(|s| s.strip_prefix('.')).unwrap_or(signal);
+    short.strip_prefix(f.name).and_then(|s| s.strip_prefix('.')).unwrap_or(short)
+}
+
+// options.event_types, options.query and options.max_frames_per_id_per_second combined
+// (CSV, ASC and BLF exports); frames must be passed in time order
+fn frame_filter<'a>(
+    types: &'a [EventType],
+    query: &FrameQuery,
+    max_rate: Option<f64>,
+) -> Result<impl FnMut(&Frame) -> bool + 'a, JsValue> {
+    let query = QueryFilter::from_query(query).map_err(|e| JsValue::from_str(&format!("options.query invalid: {}", e)))?;
+    let mut limit = RateLimit::new(max_rate)
+        .map_err(|e| JsValue::from_str(&format!("options.max_frames_per_id_per_second invalid: {}", e)))?;
+    let keep = event_filter(types);
+    Ok(move |f: &Frame| keep(f) && query.keeps(f) && limit.as_mut().is_none_or(|l| l.keeps(f)))
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct NpzOptions {
+    #[serde(deserialize_with = "numeric::f64")]
+    pub rate_hz: f64, // grid rate, as resample()
+    pub mode: ResampleMode, // as resample()
+    pub compressed: bool, // deflate members (np.savez_compressed()); floats shrink little
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum XlsxLayout {
+    #[default]
+    Messages, // a sheet per message, a row per frame
+    Pivot, // one sheet, a column per signal
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct XlsxOptions {
+    pub layout: XlsxLayout,
+    pub event_types: Vec<EventType>, // as CsvOptions
+    pub query: FrameQuery, // as CsvOptions
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub max_frames_per_id_per_second: Option<f64>, // as CsvOptions
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct SqliteOptions {
+    pub signals: Option<Vec<String>>, // decoded signals in the signals table; None -> all
+    pub event_types: Vec<EventType>, // as CsvOptions
+    pub query: FrameQuery, // as CsvOptions
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub max_frames_per_id_per_second: Option<f64>, // as CsvOptions
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct InfluxOptions {
+    pub value_labels: bool, // add a "{Signal}_text" string field for value-table signals
+    pub relative_time: bool, // nanoseconds since the measurement start instead of the epoch
+    #[serde(deserialize_with = "numeric::f64")]
+    pub utc_offset_minutes: f64, // logger clock minus UTC, e.g. 120 for CEST
+    pub event_types: Vec<EventType>, // as CsvOptions
+    pub query: FrameQuery, // lines of matching frames only (as CsvOptions.query)
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub max_frames_per_id_per_second: Option<f64>, // as CsvOptions
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct JsonlOptions {
+    pub signals: Option<Vec<String>>, // decoded signals to include; None -> all
+    #[serde(deserialize_with = "numeric::usize")]
+    pub chunk_bytes: usize, // a chunk ends at the first line past this; 0 -> 1 MiB
+    pub absolute_time: bool, // add "wall_clock" (as the CSV column)
+    pub event_types: Vec<EventType>, // as CsvOptions
+    pub query: FrameQuery, // as CsvOptions
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub max_frames_per_id_per_second: Option<f64>, // as CsvOptions
+}
+
+// One export_jsonl_stream() line: the preview() frame object, plus the wall clock
+#[derive(Serialize)]
+struct JsonlLine<'a> {
+    #[serde(flatten)]
+    frame: Frame<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    wall_clock: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TableLayout {
+    #[default]
+    Frames, // one row per frame: frame columns, then each signal's value in that frame
+    Resampled, // time grid at rate_hz (as resample()), one column per signal
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TableOptions {
+    pub layout: TableLayout,
+    #[serde(deserialize_with = "numeric::f64")]
+    pub rate_hz: f64, // resampled only
+    pub mode: ResampleMode, // resampled only
+    pub event_types: Vec<EventType>, // frames only, as CsvOptions
+    pub query: FrameQuery, // frames only, as CsvOptions
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub max_frames_per_id_per_second: Option<f64>, // frames only, as CsvOptions
+    #[serde(deserialize_with = "numeric::usize")]
+    pub max_rows: usize, // frames only: the first kept frames; 0 -> all
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct BlfExportOptions {
+    pub event_types: Vec<EventType>, // objects of these event types only; empty -> all
+    pub query: FrameQuery, // objects of matching frames only (as CsvOptions.query)
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub max_frames_per_id_per_second: Option<f64>, // as CsvOptions
+    pub uncompressed: bool, // store log containers without zlib (faster, ~3-5x larger)
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct AscOptions {
+    pub event_types: Vec<EventType>, // lines of these event types only; empty -> all
+    pub query: FrameQuery, // lines of matching frames only (as CsvOptions.query)
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub max_frames_per_id_per_second: Option<f64>, // as CsvOptions
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct FrameQuery {
+    pub ids: Vec<u32>, // CAN ids (bit 31 set: that extended id only); empty -> all
+    pub channels: Vec<String>, // "CAN1", "LIN2", "FR1", "ETH1"; empty -> all
+    pub names: Vec<String>, // message names, exact; empty -> all
+    pub text: Option<String>, // case-insensitive, in the name, the channel or the hex id
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub start_s: Option<f64>,
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub end_s: Option<f64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DecimateOptions {
+    pub include_endpoints: bool, // keep first/last sample of every signal
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub anchor_ms: Option<f64>, // bucket at multiples of this instead of frame stride
+    pub group_by_message: bool, // one shared time array per message (decimated() only)
+    pub share_times: bool, // identical time arrays sent once ("times" + per-entry "time_ref")
+    pub encoding: DecimateEncoding, // "binary": decimated() only
+    pub order: SignalOrder, // order of signals / traces in the result
+    pub method: DecimateMethod,
+}
+
+impl DecimateOptions {
+    // lttb / envelope: per-signal results of their own layout
+    fn check_per_signal(&self) -> Result<(), JsValue> {
+        if self.group_by_message || self.encoding == DecimateEncoding::Binary {
+            let method = if self.method == DecimateMethod::Lttb { "lttb" } else { "envelope" };
+            return Err(JsValue::from_str(&format!(
+                "method \"{}\" supports neither group_by_message nor binary encoding",
+                method
+            )));
+        }
+        Ok(())
+    }
+
+    // envelope bucket width: anchor_ms rounded like the stride method, else span / max_points
+    fn envelope_bucket(&self, span_s: f64, max_points: usize) -> f64 {
+        match self.anchor_ms {
+            Some(a) => decimate::anchored_bucket(a / 1000.0, span_s, max_points),
+            None => span_s / max_points.max(1) as f64,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DecimateMethod {
+    #[default]
+    Stride, // every n-th frame (or anchored time buckets), signals forward-filled
+    Lttb, // Largest-Triangle-Three-Buckets per signal on its own samples
+    Envelope, // per signal min / max / mean in max_points time buckets
+}
+
+impl Default for DecimateOptions {
+    fn default() -> Self {
+        DecimateOptions {
+            include_endpoints: true,
+            anchor_ms: None,
+            group_by_message: false,
+            share_times: false,
+            encoding: DecimateEncoding::Json,
+            order: SignalOrder::Selection,
+            method: DecimateMethod::Stride,
+        }
+    }
+}
+
+// options.order: signal order of CSV columns and decimation results. Other multi-signal
+// outputs have a fixed order: diff_windows() and cell_spread() prefixes by message, the
+// table exports (parquet, arrow, npz, resample) as the list passed in, XLSX pivot columns
+// as first seen
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SignalOrder {
+    #[default]
+    Selection, // as listed by the caller; without a list, signals() order (sorted by name),
+    // or first seen in the log for decimated_stream()
+    Alphabetical,
+    Message, // by bus/channel, message id, then position in the message
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DecimateEncoding {
+    #[default]
+    Json, // nested JS object
+    Typed, // same object with Float64Array time / signal arrays, NaN for gaps
+    Binary, // one Uint8Array (layout in binary.rs), transferable without structured clone
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct StepOptions {
+    #[serde(deserialize_with = "numeric::f64")]
+    pub window_ms: f64, // analysed span after t_event
+    #[serde(deserialize_with = "numeric::f64")]
+    pub pre_ms: f64, // span before t_event averaged for the initial level
+    #[serde(deserialize_with = "numeric::f64")]
+    pub settle_band_pct: f64, // of |step|
+    #[serde(deserialize_with = "numeric::f64")]
+    pub rise_low_pct: f64,
+    #[serde(deserialize_with = "numeric::f64")]
+    pub rise_high_pct: f64,
+}
+
+impl Default for StepOptions {
+    fn default() -> Self {
+        StepOptions { window_ms: 5000.0, pre_ms: 500.0, settle_band_pct: 2.0, rise_low_pct: 10.0, rise_high_pct: 90.0 }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DrivePhaseOptions {
+    #[serde(deserialize_with = "numeric::f64")]
+    pub speed_to_mps: f64, // speed signal unit -> m/s (default km/h)
+    #[serde(deserialize_with = "numeric::f64")]
+    pub idle_speed: f64, // at or below: idle (signal units)
+    #[serde(deserialize_with = "numeric::f64")]
+    pub accel_mps2: f64, // at or above: accel
+    #[serde(deserialize_with = "numeric::f64")]
+    pub brake_mps2: f64, // deceleration magnitude at or above: brake
+    #[serde(deserialize_with = "numeric::f64")]
+    pub smooth_ms: f64,
+    #[serde(deserialize_with = "numeric::f64")]
+    pub min_duration_ms: f64, // shorter intervals join their neighbour
+}
+
+impl Default for DrivePhaseOptions {
+    fn default() -> Self {
+        DrivePhaseOptions {
+            speed_to_mps: 1.0 / 3.6,
+            idle_speed: 1.0,
+            accel_mps2: 0.3,
+            brake_mps2: 0.3,
+            smooth_ms: 1000.0,
+            min_duration_ms: 2000.0,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct SnapshotOptions {
+    pub signals: Option<Vec<String>>, // default: all signals of the session
+    pub start_values: bool, // unseen signals -> DBC GenSigStartValue, flagged is_default
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ConsistencyOptions {
+    #[serde(deserialize_with = "numeric::f64")]
+    pub cycle_tolerance_pct: f64, // allowed deviation of the median period
+    #[serde(deserialize_with = "numeric::usize")]
+    pub min_frames: usize, // fewer frames -> no cycle-time check
+}
+
+impl Default for ConsistencyOptions {
+    fn default() -> Self {
+        ConsistencyOptions { cycle_tolerance_pct: 20.0, min_frames: 5 }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct BusLoadOptions {
+    #[serde(deserialize_with = "numeric::f64")]
+    pub bitrate: f64, // bit/s; default 500k
+    #[serde(deserialize_with = "numeric::f64")]
+    pub data_bitrate: f64, // CAN FD data phase (BRS frames); default 2M
+    pub channels: HashMap<String, ChannelBitrate>, // "CAN2" -> its own rates
+    pub stuffing: bool, // count worst-case stuff bits; default true
+}
+
+impl Default for BusLoadOptions {
+    fn default() -> Self {
+        BusLoadOptions { bitrate: 500_000.0, data_bitrate: 2_000_000.0, channels: HashMap::new(), stuffing: true }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ChannelBitrate {
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub bitrate: Option<f64>, // None -> options.bitrate
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub data_bitrate: Option<f64>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Mf4Options {
+    pub signals: Vec<String>, // channel-tagged names; empty -> every decoded signal
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct MessageStatsOptions {
+    pub counter_signals: Vec<String>, // DBC signal names of alive counters; empty -> by name
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct TimingOptions {
+    #[serde(deserialize_with = "numeric::f64")]
+    pub tolerance_pct: f64, // allowed deviation of a period from GenMsgCycleTime
+    #[serde(deserialize_with = "numeric::f64")]
+    pub max_violation_pct: f64, // share of periods outside it before a message violates
+}
+
+impl Default for TimingOptions {
+    fn default() -> Self {
+        TimingOptions { tolerance_pct: 20.0, max_violation_pct: 1.0 }
+    }
+}
+
+// null/undefined -> defaults, anything else must deserialize cleanly
+fn parse_options<T: DeserializeOwned + Default>(value: JsValue, what: &str) -> Result<T, ApiError> {
+    if value.is_null() || value.is_undefined() {
+        return Ok(T::default());
+    }
+    serde_wasm_bindgen::from_value(value).map_err(|e| options_error(what, e))
+}
+
+fn options_error(what: &str, detail: impl std::fmt::Debug) -> ApiError {
+    ApiError::new(error::INVALID_OPTIONS, format!("{} invalid: {:?}", what, detail))
+}
+
+// LogReader / BlfStream failures
+fn log_error(e: String) -> ApiError {
+    ApiError::new(error::INVALID_LOG, format!("Failed to parse BLF: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // one 64-byte message "M" holding the given SG_ lines
+    fn signals(sg_lines: &[&str]) -> Vec<Signal> {
+        let text = format!(
+            "VERSION \"\"\n\nNS_ :\n\nBS_:\n\nBU_: ECU\n\nBO_ 256 M: 64 ECU\n{}\n\n",
+            sg_lines.iter().map(|l| format!(" SG_ {} (1,0) [0|0] \"\" ECU\n", l)).collect::<String>()
+        );
+        let dbc = DBC::try_from(text.as_str()).expect("test DBC parses");
+        dbc.messages()[0].signals().clone()
+    }
+
+    fn decode(sg: &str, data: &[u8]) -> Option<f64> {
+        decode_signal_value(&signals(&[sg])[0], data)
+    }
+
+    #[test]
+    fn motorola_byte_aligned() {
+        // MSB at byte 0 bit 7: bytes read big-endian
+        assert_eq!(decode("S : 7|16@0+", &[0x12, 0x34, 0, 0, 0, 0, 0, 0]), Some(0x1234 as f64));
+        assert_eq!(decode("S : 7|32@0+", &[0xDE, 0xAD, 0xBE, 0xEF, 0, 0, 0, 0]), Some(0xDEAD_BEEFu32 as f64));
+    }
+
+    #[test]
+    fn motorola_sawtooth() {
+        // bits 3..0 of byte 0, then all of byte 1
+        assert_eq!(decode("S : 3|12@0+", &[0x0A, 0xBC, 0, 0, 0, 0, 0, 0]), Some(0xABC as f64));
+        // bits 5..0 of byte 1 (0b101010), then bits 7..4 of byte 2 (0b1111)
+        assert_eq!(decode("S : 13|10@0+", &[0x00, 0x2A, 0xF0, 0, 0, 0, 0, 0]), Some(0x2AF as f64));
+        // inside a single byte
+        assert_eq!(decode("S : 6|3@0+", &[0b0101_0000, 0, 0, 0, 0, 0, 0, 0]), Some(0b101 as f64));
+    }
+
+    #[test]
+    fn event_type_labels() {
+        let types: Vec<EventType> = serde_json::from_str(r#"["can_fd", "Error Frame", "j1939_tp"]"#).unwrap();
+        assert_eq!(types, [EventType::CanFd, EventType::Error, EventType::J1939Tp]);
+        for t in types {
+            assert_eq!(serde_json::to_value(t).unwrap(), t.label());
+        }
+    }
+
+    #[test]
+    fn parse_filter() {
+        let can = |channel, id| {
+            BlfObject::Can(CanFrame {
+                timestamp_ns: 0,
+                channel,
+                id,
+                dlc: 0,
+                data: Vec::new(),
+                tx: false,
+                tx_request: false,
+                rtr: false,
+                wakeup: false,
+                nerr: false,
+                fd: false,
+                brs: false,
+                esi: false,
+            })
+        };
+        let opts = FrameFilter { ids: vec![0x100, 0x8000_0200], channels: vec!["can2".to_string(), " LIN1".to_string()] };
+        let f = ParseFilter::from_options(&opts).unwrap();
+        assert!(f.keeps(&can(2, 0x100)) && f.keeps(&can(2, 0x8000_0100)));
+        // 0x200 only as an extended id, CAN1 not listed
+        assert!(!f.keeps(&can(2, 0x200)) && !f.keeps(&can(1, 0x100)));
+        assert!(f.keeps(&BlfObject::Other));
+        assert!(ParseFilter::from_options(&FrameFilter { ids: vec![], channels: vec!["MOST1".to_string()] }).is_err());
+    }
+
+    #[test]
+    fn motorola_signed() {
+        assert_eq!(decode("S : 7|8@0-", &[0xFF, 0, 0, 0, 0, 0, 0, 0]), Some(-1.0));
+        assert_eq!(decode("S : 7|16@0-", &[0xFF, 0x38, 0, 0, 0, 0, 0, 0]), Some(-200.0));
+    }
+
+    #[test]
+    fn motorola_can_fd_payload() {
+        let mut data = vec![0u8; 64];
+        data[62] = 0xBE;
+        data[63] = 0xEF;
+        assert_eq!(decode("S : 503|16@0+", &data), Some(0xBEEF as f64));
+        // runs past the end of a classic frame
+        assert_eq!(decode("S : 63|16@0+", &data[..8]), None);
+    }
+
+    #[test]
+    fn intel_reference() {
+        assert_eq!(decode("S : 0|16@1+", &[0x34, 0x12, 0, 0, 0, 0, 0, 0]), Some(0x1234 as f64));
+        assert_eq!(decode("S : 4|12@1-", &[0xF0, 0xFF, 0, 0, 0, 0, 0, 0]), Some(-1.0));
+        assert_eq!(decode("S : 0|64@1+", &[0xFF; 8]), Some(u64::MAX as f64));
+        assert_eq!(decode("S : 56|16@1+", &[0; 8]), None);
+    }
+
+    #[test]
+    fn extended_ids() {
+        // can-dbc marks extended ids with bit 31, as BLF does
+        let text = "VERSION \"\"\n\nNS_ :\n\nBS_:\n\nBU_: ECU\n\nBO_ 2566844672 EEC1: 8 ECU\n\nBO_ 256 STD: 8 ECU\n\n";
+        let dbc = DBC::try_from(text).expect("test DBC parses");
+        assert_eq!(split_id(dbc.messages()[0].message_id().raw()), (0x18FEF100, true));
+        assert_eq!(split_id(dbc.messages()[1].message_id().raw()), (0x100, false));
+
+        assert!(id_matches(0x18FEF100, 0x18FEF100 | CAN_EFF_FLAG));
+        assert!(id_matches(0x100, 0x100) && id_matches(0x100, 0x100 | CAN_EFF_FLAG));
+        assert!(!id_matches(0x100 | CAN_EFF_FLAG, 0x100));
+    }
+
+    #[test]
+    fn motorola_matches_layout_bits() {
+        // the decoder reads exactly the positions the layout diagram shows
+        for sig in signals(&["A : 7|16@0+", "B : 13|10@0+", "C : 3|12@0+"]) {
+            let bits = layout::signal_bits(*sig.start_bit(), *sig.signal_size(), ByteOrder::BigEndian);
+            for (i, &b) in bits.iter().enumerate() {
+                let mut data = [0u8; 8];
+                data[(b / 8) as usize] |= 1 << (b % 8);
+                let expected = (1u64 << (bits.len() - 1 - i)) as f64;
+                assert_eq!(decode_signal_value(&sig, &data), Some(expected), "{} bit {}", sig.name(), b);
             }
-            last_ts = cf.header.timestamp_ns as f64 / 1e9;
-            count += 1;
-            if count >= 100_000 {
-                capped = true;
-                break;
+        }
+    }
+
+    #[test]
+    fn signal_name_collisions() {
+        let dbc = |msgs: &str| DBC::try_from(format!("VERSION \"\"\n\nNS_ :\n\nBS_:\n\nBU_: ECU\n\n{}", msgs).as_str()).unwrap();
+        let a = dbc("BO_ 256 Wheel: 8 ECU\n SG_ Speed : 0|16@1+ (1,0) [0|0] \"\" ECU\n SG_ Only : 16|8@1+ (1,0) [0|0] \"\" ECU\n\n");
+        // same id again (an ambiguity, not a collision) and Speed in a second message
+        let b = dbc("BO_ 256 Wheel: 8 ECU\n SG_ Speed : 0|16@1+ (1,0) [0|0] \"\" ECU\n\nBO_ 512 Gps: 8 ECU\n SG_ Speed : 0|16@1+ (1,0) [0|0] \"\" ECU\n\n");
+        let dbcs = vec![(1, a), (1, b)];
+
+        let (names, collisions) = SignalNamer::for_dbcs(&dbcs, 1024, SignalNaming::Channel, CollisionPolicy::Shared);
+        assert_eq!(collisions.len(), 1);
+        let defs: Vec<(usize, &str, &str)> =
+            collisions[0].messages.iter().map(|m| (m.dbc, m.name.as_str(), m.signal.as_str())).collect();
+        assert_eq!(defs, [(0, "Wheel", "CAN1.Speed"), (1, "Gps", "CAN1.Speed")]);
+        assert_eq!(names.name(1, "Gps", "Speed"), "CAN1.Speed");
+
+        let (names, collisions) = SignalNamer::for_dbcs(&dbcs, 1024, SignalNaming::Channel, CollisionPolicy::Qualify);
+        assert!(collisions[0].qualified);
+        assert_eq!(names.name(1, "Gps", "Speed"), "CAN1.Gps.Speed");
+        assert_eq!(names.name(1, "Wheel", "Only"), "CAN1.Only");
+        assert_eq!(names.name(2, "Gps", "Speed"), "CAN2.Speed");
+
+        let (names, collisions) = SignalNamer::for_dbcs(&dbcs, 1024, SignalNaming::Message, CollisionPolicy::Shared);
+        assert!(collisions[0].qualified);
+        assert_eq!(names.name(1, "Wheel", "Only"), "CAN1.Wheel.Only");
+        assert_eq!(names.lin_name(2, Some("Seat"), "Pos"), "LIN2.Seat.Pos");
+        assert_eq!(names.lin_name(2, None, "Pos"), "LIN2.Pos");
+    }
+
+    #[test]
+    fn ambiguous_ids_by_priority_and_length() {
+        let dbc = |name: &str, size: u8| {
+            format!("VERSION \"\"\n\nNS_ :\n\nBS_:\n\nBU_: ECU\n\nBO_ 256 {}: {} ECU\n SG_ S : 0|8@1+ (1,0) [0|0] \"\" ECU\n\n", name, size)
+        };
+        let texts = || vec![dbc("Long", 8), dbc("Short", 4), dbc("Other", 4)];
+        let chosen = |d: &Decoder, len: usize| d.select(1, 0x100, len).map(|(dbc, m)| (dbc, m.message_name().clone()));
+
+        // default: later DBC first; a candidate whose length fits wins over priority
+        let decoder = Decoder::from_texts(texts(), vec![1, 1, 1], &SessionOptions::default()).unwrap();
+        assert!(decoder.conflicts.contains(&(1, 0x100)));
+        assert_eq!(chosen(&decoder, 8), Some((0, "Long".to_string())));
+        assert_eq!(chosen(&decoder, 4), Some((2, "Other".to_string())));
+        // no length fits: the highest-priority candidate
+        assert_eq!(chosen(&decoder, 2), Some((2, "Other".to_string())));
+        assert_eq!(decoder.select(2, 0x100, 8).map(|(dbc, _)| dbc), None);
+
+        let opts = SessionOptions { dbc_priority: vec![1], ..SessionOptions::default() };
+        let decoder = Decoder::from_texts(texts(), vec![1, 1, 1], &opts).unwrap();
+        assert_eq!(chosen(&decoder, 4), Some((1, "Short".to_string())));
+        assert_eq!(chosen(&decoder, 2), Some((1, "Short".to_string())));
+        assert_eq!(chosen(&decoder, 8), Some((0, "Long".to_string())));
+    }
+
+    #[test]
+    fn hex_input() {
+        assert_eq!(parse_hex_id("0x1A0"), Ok(0x1A0));
+        assert_eq!(parse_hex_id(" 1a0 "), Ok(0x1A0));
+        assert_eq!(parse_hex_id("100x"), Ok(0x100 | CAN_EFF_FLAG));
+        assert_eq!(parse_hex_id("18FEF100"), Ok(0x18FE_F100 | CAN_EFF_FLAG));
+        assert!(parse_hex_id("0x").is_err());
+        assert!(parse_hex_id("3FFFFFFF").is_err());
+        assert_eq!(parse_hex_bytes("01 02 ff"), Ok(vec![1, 2, 0xFF]));
+        assert_eq!(parse_hex_bytes("0x01,0x0A-0B:0c"), Ok(vec![1, 0x0A, 0x0B, 0x0C]));
+        assert_eq!(parse_hex_bytes(""), Ok(vec![]));
+        assert!(parse_hex_bytes("012").is_err());
+        assert!(parse_hex_bytes("zz").is_err());
+        assert_eq!(len_to_dlc(12), 9);
+        assert_eq!(len_to_dlc(64), 15);
+    }
+
+    #[test]
+    fn query_filter_predicates() {
+        let row = |t: f64, bus: Bus, channel: u16, id: u32, extended: bool, name: &str| FrameRow {
+            timestamp: t,
+            bus,
+            channel: format!("{}{}", if bus == Bus::Lin { "LIN" } else { "CAN" }, channel),
+            channel_num: channel,
+            id,
+            is_extended: extended,
+            event_type: if bus == Bus::Lin { EventType::Lin } else { EventType::Can },
+            name: name.to_string(),
+            ..FrameRow::default()
+        };
+        let rows = [
+            row(0.5, Bus::Can, 1, 0x100, false, "Engine"),
+            row(1.5, Bus::Can, 2, 0x100, true, "EngineExt"),
+            row(2.5, Bus::Can, 1, 0x200, false, "Brake"),
+            row(3.5, Bus::Lin, 1, 0x100 & 0x3F, false, "Door"),
+        ];
+        let kept = |q: FrameQuery| -> Vec<usize> {
+            let filter = QueryFilter::from_query(&q).unwrap();
+            (0..rows.len()).filter(|&i| filter.keeps(&rows[i].view())).collect()
+        };
+        assert_eq!(kept(FrameQuery::default()), [0, 1, 2, 3]);
+        // a plain id matches standard and extended frames, bit 31 the extended one only
+        assert_eq!(kept(FrameQuery { ids: vec![0x100], ..FrameQuery::default() }), [0, 1]);
+        assert_eq!(kept(FrameQuery { ids: vec![0x100 | CAN_EFF_FLAG], ..FrameQuery::default() }), [1]);
+        assert_eq!(kept(FrameQuery { start_s: Some(1.5), end_s: Some(2.5), ..FrameQuery::default() }), [1, 2]);
+        assert_eq!(kept(FrameQuery { channels: vec!["can1".into()], ..FrameQuery::default() }), [0, 2]);
+        assert_eq!(kept(FrameQuery { channels: vec!["LIN1".into()], ..FrameQuery::default() }), [3]);
+        assert_eq!(kept(FrameQuery { names: vec!["Engine".into()], ..FrameQuery::default() }), [0]);
+        assert_eq!(kept(FrameQuery { text: Some(" ENGINE ".into()), ..FrameQuery::default() }), [0, 1]);
+        assert_eq!(kept(FrameQuery { ids: vec![0x100], channels: vec!["CAN2".into()], ..FrameQuery::default() }), [1]);
+        assert!(QueryFilter::from_query(&FrameQuery { channels: vec!["XY1".into()], ..FrameQuery::default() }).is_err());
+    }
+
+    #[test]
+    fn rate_limit_thins_chatty_ids_only() {
+        let frame = |t: f64, id: u32| FrameRow { timestamp: t, channel_num: 1, id, ..FrameRow::default() };
+        let mut limit = RateLimit::new(Some(10.0)).unwrap().unwrap();
+        // over ten seconds: 0x100 at 1 kHz, 0x200 at 10 Hz with +-4 ms jitter
+        let mut kept = HashMap::new();
+        for i in 0..10_000 {
+            let t = i as f64 / 1000.0;
+            if limit.keeps(&frame(t, 0x100).view()) {
+                *kept.entry(0x100).or_insert(0) += 1;
+            }
+            let jitter = if i % 200 == 0 { 0.004 } else { -0.004 };
+            if i % 100 == 0 && limit.keeps(&frame(t + jitter, 0x200).view()) {
+                *kept.entry(0x200).or_insert(0) += 1;
             }
         }
+        assert!((100..=101).contains(&kept[&0x100]), "{}", kept[&0x100]);
+        assert_eq!(kept[&0x200], 100);
+        let error = FrameRow { event_type: EventType::Error, ..frame(0.0, 0x100) };
+        assert!(limit.keeps(&error.view()));
+        assert!(RateLimit::new(Some(0.0)).is_err());
+        assert!(RateLimit::new(None).unwrap().is_none());
     }
 
-    serde_wasm_bindgen::to_value(&json!({
-        "frame_count": count,
-        "first_ts": first_ts,
-        "last_ts": last_ts,
-        "capped": capped
-    }))
-    .map_err(|e| JsValue::from_str(&format!("serde failed: {:?}", e)))
+    // Engine (0x100) with Speed (0.1 km/h) and Gear on CAN1
+    const ENGINE_DBC: &str = "VERSION \"\"\n\nNS_ :\n\nBS_:\n\nBU_: ECU\n\n\
+        BO_ 256 Engine: 8 ECU\n SG_ Speed : 0|16@1+ (0.1,0) [0|6553.5] \"km/h\" ECU\n SG_ Gear : 16|4@1+ (1,0) [0|15] \"\" ECU\n\n";
+
+    fn can(t: f64, channel: u16, id: u32, data: &[u8]) -> CanFrame {
+        CanFrame {
+            timestamp_ns: (t * 1e9).round() as u64,
+            channel,
+            id,
+            dlc: data.len() as u8,
+            data: data.to_vec(),
+            tx: false,
+            tx_request: false,
+            rtr: false,
+            wakeup: false,
+            nerr: false,
+            fd: false,
+            brs: false,
+            esi: false,
+        }
+    }
+
+    fn blf_of(objects: &[BlfObject]) -> Vec<u8> {
+        let mut w = BlfWriter::new(false);
+        objects.iter().for_each(|o| w.push(o).unwrap());
+        w.finish(None, None)
+    }
+
+    // BLF of classic CAN frames: (time s, channel, id, data)
+    fn blf(frames: &[(f64, u16, u32, &[u8])]) -> Vec<u8> {
+        blf_of(&frames.iter().map(|&(t, channel, id, data)| BlfObject::Can(can(t, channel, id, data))).collect::<Vec<_>>())
+    }
+
+    fn session(frames: &[(f64, u16, u32, &[u8])], opts: SessionOptions) -> Result<BlfSession, ApiError> {
+        let decoder = Decoder::from_texts(vec![ENGINE_DBC.to_string()], vec![1], &opts)?;
+        BlfSession::open(&blf(frames), decoder, opts)
+    }
+
+    // Speed/Gear changes with repeated payloads, plus a frame no DBC message matches
+    const ENGINE_FRAMES: [(f64, u16, u32, &[u8]); 5] = [
+        (0.1, 1, 0x100, &[100, 0, 3, 0, 0, 0, 0, 0]),
+        (0.2, 1, 0x100, &[100, 0, 3, 0, 0, 0, 0, 0]),
+        (0.3, 1, 0x200, &[1, 2]),
+        (0.4, 1, 0x100, &[125, 0, 4, 0, 0, 0, 0, 0]),
+        (0.5, 1, 0x100, &[125, 0, 4, 0, 0, 0, 0, 0]),
+    ];
+
+    // Every frame with its signals as frames() serializes them
+    fn frames_json(s: &BlfSession) -> serde_json::Value {
+        let mut cache = DecodeCache::new(None);
+        let rows: Vec<serde_json::Value> = s
+            .frames
+            .iter()
+            .map(|f| {
+                let signals = s.frame_signals(&f, &mut cache);
+                serde_json::to_value(Frame { signals: Signals::Rows(&signals), ..f }).unwrap()
+            })
+            .collect();
+        serde_json::Value::Array(rows)
+    }
+
+    #[test]
+    fn lazy_matches_eager() {
+        let eager = session(&ENGINE_FRAMES, SessionOptions::default()).unwrap();
+        let opts = |lazy_signals, pinned: &[&str]| SessionOptions {
+            lazy_signals,
+            pinned_signals: pinned.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        for lazy in [opts(true, &[]), opts(false, &["CAN1.Gear"])] {
+            let lazy = session(&ENGINE_FRAMES, lazy).unwrap();
+            assert_eq!(lazy.signal_names, eager.signal_names);
+            assert_eq!(frames_json(&lazy), frames_json(&eager));
+        }
+        assert_eq!(eager.signal_names, ["CAN1.Gear", "CAN1.Speed"]);
+    }
+
+    #[test]
+    fn pinned_series_match_eager_decode() {
+        let pinned = SessionOptions { pinned_signals: vec!["CAN1.Speed".to_string()], ..Default::default() };
+        let pinned = session(&ENGINE_FRAMES, pinned).unwrap();
+        let eager = session(&ENGINE_FRAMES, SessionOptions::default()).unwrap();
+        assert_eq!(pinned.pinned["CAN1.Speed"], (vec![0.1, 0.2, 0.4, 0.5], vec![10.0, 10.0, 12.5, 12.5]));
+        let names = ["CAN1.Speed".to_string(), "CAN1.Gear".to_string()];
+        assert_eq!(pinned.collect_series(&names), eager.collect_series(&names));
+    }
+
+    #[test]
+    fn decode_cache_reuses_repeated_payloads() {
+        let s = session(&ENGINE_FRAMES, SessionOptions { lazy_signals: true, ..Default::default() }).unwrap();
+        let frames: Vec<Frame> = s.frames.iter().collect();
+        let mut cache = DecodeCache::new(None);
+        let rows: Vec<Rc<[SignalRow]>> = frames.iter().map(|f| cache.decode(&s.decoder, f)).collect();
+        assert!(Rc::ptr_eq(&rows[0], &rows[1]));
+        assert!(rows[2].is_empty());
+        assert!(!Rc::ptr_eq(&rows[1], &rows[3]));
+        assert!(Rc::ptr_eq(&rows[3], &rows[4]));
+        let values: Vec<f64> = rows[3].iter().map(|r| r.value).collect();
+        assert_eq!(values, [12.5, 4.0]);
+
+        // a selection limits both on-demand decodes and stored rows
+        let only = HashSet::from(["CAN1.Gear".to_string()]);
+        let eager = session(&ENGINE_FRAMES, SessionOptions::default()).unwrap();
+        for s in [&s, &eager] {
+            let mut cache = DecodeCache::new(Some(&only));
+            let gear: Vec<(String, f64)> = s
+                .frames
+                .iter()
+                .flat_map(|f| s.frame_signals(&f, &mut cache).iter().map(|r| (r.signal.clone(), r.value)).collect::<Vec<_>>())
+                .collect();
+            let expected: Vec<(String, f64)> = [3.0, 3.0, 4.0, 4.0].iter().map(|&v| ("CAN1.Gear".to_string(), v)).collect();
+            assert_eq!(gear, expected);
+        }
+    }
+
+    #[test]
+    fn window_diff_signals() {
+        // Speed 10.0 -> 12.5 km/h, Gear 3 in both windows; 0x200 (no DBC message) only in b
+        let frames = [
+            (0.1, 1, 0x100, &[100u8, 0, 3, 0, 0, 0, 0, 0][..]),
+            (1.1, 1, 0x100, &[125, 0, 3, 0, 0, 0, 0, 0]),
+            (1.2, 1, 0x200, &[0]),
+        ];
+        let s = session(&frames, SessionOptions::default()).unwrap();
+        let diff = s.window_diff((0.0, 1.0), (1.0, 2.0));
+        let signals: Vec<(&str, Option<f64>, Option<f64>, bool)> =
+            diff.signals.iter().map(|d| (d.signal.as_str(), d.last_a, d.last_b, d.changed)).collect();
+        assert_eq!(signals, [("CAN1.Speed", Some(10.0), Some(12.5), true), ("CAN1.Gear", Some(3.0), Some(3.0), false)]);
+        assert_eq!(diff.signals[0].unit, "km/h");
+        let presence: Vec<(u32, analysis::Presence)> = diff.messages.iter().map(|m| (m.id, m.presence)).collect();
+        assert_eq!(presence, [(0x100, analysis::Presence::Both), (0x200, analysis::Presence::OnlyB)]);
+    }
+
+    #[test]
+    fn events_list_error_and_remote_frames() {
+        let remote = CanFrame { rtr: true, ..can(0.2, 1, 0x100, &[0; 8]) };
+        let error = BlfObject::Error(CanError {
+            timestamp_ns: 300_000_000,
+            channel: 1,
+            id: 0,
+            dlc: 0,
+            data: Vec::new(),
+            fd: false,
+            error_type: None,
+            tx: None,
+            position: None,
+            ecc: None,
+            tx_errors: None,
+            rx_errors: None,
+        });
+        let objects = [BlfObject::Can(can(0.1, 1, 0x100, &[1; 8])), BlfObject::Can(remote), error, BlfObject::Can(can(0.4, 1, 0x200, &[2]))];
+        let opts = SessionOptions::default();
+        let decoder = Decoder::from_texts(vec![ENGINE_DBC.to_string()], vec![1], &opts).unwrap();
+        let s = BlfSession::open(&blf_of(&objects), decoder, opts).unwrap();
+        let events = |opts: EventOptions| -> Vec<(f64, EventType)> {
+            s.event_frames(&opts).unwrap().iter().map(|f| (f.timestamp, f.event_type)).collect()
+        };
+        assert_eq!(events(EventOptions::default()), [(0.2, EventType::CanRemote), (0.3, EventType::Error)]);
+        assert_eq!(events(EventOptions { limit: 1, ..Default::default() }), [(0.2, EventType::CanRemote)]);
+        let late = FrameQuery { start_s: Some(0.25), ..Default::default() };
+        assert_eq!(events(EventOptions { query: late, ..Default::default() }), [(0.3, EventType::Error)]);
+        let can_only = EventOptions { event_types: vec![EventType::Can], ..Default::default() };
+        assert_eq!(events(can_only), [(0.1, EventType::Can), (0.4, EventType::Can)]);
+        let bad = FrameQuery { channels: vec!["XYZ1".to_string()], ..Default::default() };
+        assert_eq!(s.event_frames(&EventOptions { query: bad, ..Default::default() }).unwrap_err().code, error::INVALID_OPTIONS);
+    }
+
+    #[test]
+    fn warm_start_lists_only_seen_signals() {
+        let first = session(&ENGINE_FRAMES, SessionOptions::default()).unwrap();
+        let index = first.index();
+        assert_eq!(index.signals, ["CAN1.Gear", "CAN1.Speed"]);
+        let opts = SessionOptions { warm_start: Some(index), ..Default::default() };
+        let second = session(&[(0.1, 1, 0x200, &[1, 2])], opts).unwrap();
+        assert!(second.decoder.warm);
+        assert!(second.signal_names.is_empty());
+    }
+
+    #[test]
+    fn numeric_channel_and_bus() {
+        let s = session(&ENGINE_FRAMES, SessionOptions::default()).unwrap();
+        let first = &frames_json(&s)[0];
+        assert_eq!((&first["channel"], &first["channel_num"], &first["bus"]), (&json!("CAN1"), &json!(1), &json!("can")));
+
+        let layout = CsvLayout { selected: Vec::new(), labelled: Vec::new(), label_only: false, hold: false, start: Vec::new(), clock: None };
+        assert_eq!(layout.header()[..4], ["Time [s]", "Channel", "Channel Num", "ID"]);
+        let f = s.frames.iter().next().unwrap();
+        let row = s.csv_row(&layout, &mut layout.state(), &mut DecodeCache::new(None), &f);
+        assert_eq!(row[..4], ["0.100000", "CAN1", "1", "0x100"]);
+    }
+
+    #[test]
+    fn channels_above_255_have_no_dbc() {
+        let frames = [(0.1, 257, 0x100, &[100u8, 0, 3, 0, 0, 0, 0, 0][..]), (0.2, 1, 0x100, &[100, 0, 3, 0, 0, 0, 0, 0])];
+        let s = session(&frames, SessionOptions::default()).unwrap();
+        let rows = frames_json(&s);
+        // CAN257 is not CAN1: no message, no signals
+        assert_eq!((&rows[0]["channel"], &rows[0]["name"], &rows[0]["signals"]), (&json!("CAN257"), &json!(""), &json!([])));
+        assert_eq!(rows[1]["signals"][0]["signal"], "CAN1.Speed");
+        assert_eq!(s.signal_names, ["CAN1.Gear", "CAN1.Speed"]);
+    }
+
+    #[test]
+    fn chunked_csv_resumes_after_last_chunk() {
+        let s = session(&ENGINE_FRAMES, SessionOptions::default()).unwrap();
+        // held values: a resumed export must replay the frames before its first chunk
+        let selected = vec!["CAN1.Speed".to_string(), "CAN1.Gear".to_string()];
+        let layout = CsvLayout { labelled: vec![false; 2], start: vec![None; 2], selected, label_only: false, hold: true, clock: None };
+        let export = |opts: &CsvOptions| {
+            let mut chunks: Vec<(Vec<u8>, ExportManifest)> = Vec::new();
+            let manifest = s
+                .csv_chunks(&layout, opts, |bytes, m| {
+                    chunks.push((bytes.to_vec(), m.clone()));
+                    Ok(())
+                })
+                .unwrap();
+            (chunks, manifest)
+        };
+
+        let (single, _) = export(&CsvOptions { chunk_frames: 10, ..Default::default() });
+        let opts = CsvOptions { chunk_frames: 2, ..Default::default() };
+        let (chunks, manifest) = export(&opts);
+        assert_eq!(chunks.len(), 3);
+        assert!(manifest.complete);
+        let whole: Vec<u8> = chunks.iter().flat_map(|(bytes, _)| bytes.clone()).collect();
+        assert_eq!(whole, single[0].0);
+
+        // interrupted after the first chunk: continue from its manifest
+        let (rest, resumed) = export(&CsvOptions { resume: Some(chunks[0].1.clone()), ..opts.clone() });
+        assert_eq!(rest.len(), 2);
+        assert_eq!(rest[0].1.chunks[1].first_frame, 2);
+        let mut joined = chunks[0].0.clone();
+        rest.iter().for_each(|(bytes, _)| joined.extend_from_slice(bytes));
+        assert_eq!(joined, whole);
+        assert_eq!((resumed.complete, resumed.bytes_written), (true, whole.len() as u64));
+        // the manifest of another chunking does not resume this export
+        assert!(chunks[0].1.check_resume(&ExportManifest::new(5, 3, layout.header())).is_err());
+    }
+
+    #[test]
+    fn channel_mapping_follows_matching_ids() {
+        let mut frames = ENGINE_FRAMES.to_vec();
+        frames.extend([(0.15, 2, 0x300, &[1u8, 2][..]), (0.25, 2, 0x300, &[1, 2][..]), (0.35, 2, 0x301, &[3][..])]);
+        frames.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let s = session(&frames, SessionOptions::default()).unwrap();
+        let body = "VERSION \"\"\n\nNS_ :\n\nBS_:\n\nBU_: ECU\n\nBO_ 768 Body: 2 ECU\n\n";
+        let unrelated = "VERSION \"\"\n\nNS_ :\n\nBS_:\n\nBU_: ECU\n\nBO_ 1024 Door: 2 ECU\n\n";
+        let texts = [body.to_string(), ENGINE_DBC.to_string(), unrelated.to_string()];
+
+        let suggestion = s.mapping_suggestion(&texts).unwrap();
+        assert_eq!(suggestion.channel_map, [Some(2), Some(1), None]);
+        // best channel first per DBC: 2 of 3 frames on CAN2 carry Body, none on CAN1
+        let body_rates: Vec<(u16, usize, usize, f64)> = suggestion.candidates[..2]
+            .iter()
+            .map(|c| (c.channel, c.sampled, c.matched_ids, c.hit_rate))
+            .collect();
+        assert_eq!(body_rates, [(2, 3, 1, 2.0 / 3.0), (1, 5, 0, 0.0)]);
+        assert_eq!((suggestion.candidates[2].channel, suggestion.candidates[2].hit_rate), (1, 0.8));
+        assert!(s.mapping_suggestion(&["BO_ oops".to_string()]).unwrap_err().starts_with("Failed to parse DBC 0"));
+    }
+
+    #[test]
+    fn coverage_counts_matched_frames_and_bits() {
+        let mut frames = ENGINE_FRAMES.to_vec();
+        frames.push((0.6, 2, 0x100, &[0; 8][..]));
+        let s = session(&frames, SessionOptions::default()).unwrap();
+        let report = s.coverage_report();
+        let rows: Vec<(u16, usize, usize, usize, usize, usize)> = report
+            .iter()
+            .map(|c| (c.channel_num, c.frames, c.matched_frames, c.unmatched_ids, c.payload_bits, c.covered_bits))
+            .collect();
+        // Speed (16) + Gear (4) bits of each Engine frame; 0x200 and CAN2 have no DBC message
+        assert_eq!(rows, [(1, 5, 4, 1, 4 * 64 + 16, 4 * 20), (2, 1, 0, 1, 64, 0)]);
+        assert_eq!(report[0].frame_pct, 80.0);
+        assert_eq!(report[0].bit_pct, 100.0 * 80.0 / 272.0);
+        assert_eq!((report[1].frame_pct, report[1].bit_pct), (0.0, 0.0));
+    }
+
+    #[test]
+    fn consistency_scores_dlc_cycle_and_unknown_ids() {
+        use consistency::DiscrepancyKind::{CycleTime, Dlc, UnknownId};
+        let kinds = |r: &consistency::ConsistencyReport| -> Vec<(consistency::DiscrepancyKind, u32, Option<f64>, Option<f64>)> {
+            r.discrepancies.iter().map(|d| (d.kind, d.id, d.expected, d.observed)).collect()
+        };
+
+        // Engine matches its DLC; 0x200 is traffic the DBC does not define
+        let s = session(&ENGINE_FRAMES, SessionOptions::default()).unwrap();
+        let report = s.consistency_report(&ConsistencyOptions::default());
+        assert_eq!((report.checks, report.passed, report.score), (2, 1, 50.0));
+        assert_eq!(kinds(&report), [(UnknownId, 0x200, None, None)]);
+
+        let mut frames = ENGINE_FRAMES.to_vec();
+        frames.extend([(0.6, 1, 0x100, &[0u8; 6][..]), (0.7, 2, 0x300, &[0u8; 2][..])]);
+        let s = session(&frames, SessionOptions::default()).unwrap();
+        let report = s.consistency_report(&ConsistencyOptions::default());
+        // CAN2 has no DBC: its ids are not checked
+        assert_eq!((report.checks, report.passed), (2, 0));
+        assert_eq!(kinds(&report), [(Dlc, 0x100, Some(8.0), Some(6.0)), (UnknownId, 0x200, None, None)]);
+
+        // GenMsgCycleTime 50 ms against a median period of 100 ms
+        let text = format!("{}BA_DEF_ BO_ \"GenMsgCycleTime\" INT 0 10000;\n\nBA_ \"GenMsgCycleTime\" BO_ 256 50;\n", ENGINE_DBC);
+        let opts = SessionOptions::default();
+        let s = BlfSession::open(&blf(&ENGINE_FRAMES), Decoder::from_texts(vec![text], vec![1], &opts).unwrap(), opts).unwrap();
+        let consistency = |min_frames| s.consistency_report(&ConsistencyOptions { min_frames, ..Default::default() });
+        assert_eq!(consistency(5).checks, 2);
+        let report = consistency(4);
+        assert_eq!((report.checks, report.passed), (3, 1));
+        let (kind, id, expected, observed) = kinds(&report)[0];
+        assert_eq!((kind, id, expected), (CycleTime, 0x100, Some(50.0)));
+        assert!((observed.unwrap() - 100.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn unmapped_bits_outside_dbc_signals() {
+        // Speed and Gear cover bits 0..19: the ENGINE_FRAMES payloads stay inside them
+        let s = session(&ENGINE_FRAMES, SessionOptions::default()).unwrap();
+        assert!(s.unmapped_report().is_empty());
+
+        let mut frames = ENGINE_FRAMES.to_vec();
+        frames.extend([(0.6, 1, 0x100, &[125u8, 0, 0x14, 0, 0, 0, 0, 0x80][..]), (0.7, 1, 0x100, &[125u8, 0, 0x14, 0, 0, 0, 0, 0][..])]);
+        let s = session(&frames, SessionOptions::default()).unwrap();
+        let report = s.unmapped_report();
+        assert_eq!(report.len(), 1);
+        let r = &report[0];
+        assert_eq!((r.channel_num, r.id, r.name.as_str()), (1, 0x100, "Engine"));
+        assert_eq!((r.frames, r.nonzero_frames), (6, 2));
+        // bit 20 is set from 0.6 s on, bit 63 only in that frame
+        assert_eq!(r.set_bits, [20, 63]);
+        assert_eq!(r.changing_bits, [20, 63]);
+        assert!((r.first_nonzero.unwrap() - 0.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn views_refuse_mutation() {
+        let s = session(&ENGINE_FRAMES, SessionOptions::default()).unwrap();
+        assert_eq!(s.check_writable("merge()"), Ok(()));
+        for view in [s.clone_view().unwrap(), s.slice(0.0, 0.3).unwrap()] {
+            let err = view.check_writable("merge()").unwrap_err();
+            assert_eq!(err.code, error::READ_ONLY);
+            assert_eq!(err.message, "merge() not allowed on a read-only view; call it on the original session");
+        }
+    }
+
+    #[test]
+    fn released_sessions_refuse_calls() {
+        let mut s = session(&ENGINE_FRAMES, SessionOptions::default()).unwrap();
+        let view = s.clone_view().unwrap();
+        let slice = view.slice(0.0, 0.3).unwrap();
+        let mut own = s.clone_view().unwrap();
+        own.free_memory();
+        // a view's free_memory() leaves the others alone
+        assert_eq!(own.check_alive().unwrap_err().code, error::RELEASED);
+        assert!(s.check_alive().is_ok() && view.check_alive().is_ok() && !slice.is_freed());
+
+        let generation = s.generation();
+        s.free_memory();
+        assert_eq!(s.generation(), generation + 1);
+        assert!(s.is_freed() && s.frames.is_empty());
+        let err = s.check_alive().unwrap_err();
+        assert_eq!((err.code, err.message.as_str()), (error::RELEASED, "session released: free_memory() was called on this session"));
+        // views made before the release, also a slice of a view, refuse to continue
+        for v in [&view, &slice] {
+            assert!(v.is_freed());
+            let err = v.check_alive().unwrap_err();
+            assert_eq!(err.code, error::RELEASED);
+            assert!(err.message.ends_with("on the session this view was made from"));
+        }
+    }
+
+    #[test]
+    fn structured_errors() {
+        let texts = vec!["a".to_string(), "a".to_string()];
+        let code = |raw: serde_json::Value| channel_map_entries(raw.as_array().unwrap(), &texts).unwrap_err().code;
+        for bad in [json!([1]), json!([0, 1]), json!([1, "x"]), json!([2, 2])] {
+            assert_eq!(code(bad), error::INVALID_CHANNEL_MAP);
+        }
+        assert_eq!(channel_map_entries(&[json!(1), json!(2)], &texts), Ok(vec![1, 2]));
+        let dup = channel_map_entries(&[json!(2), json!(2)], &texts).unwrap_err();
+        assert_eq!(dup.message, "channel_map[1] maps the same DBC as channel_map[0] to CAN2 again");
+
+        let opts = SessionOptions::default();
+        let dbc = Decoder::from_texts(vec!["BO_ x".to_string()], vec![1], &opts).err().unwrap();
+        assert_eq!(dbc.code, error::INVALID_DBC);
+        let decoder = Decoder::from_texts(vec![ENGINE_DBC.to_string()], vec![1], &opts).unwrap();
+        let log = BlfSession::open(b"not a log", decoder, opts).err().unwrap();
+        assert_eq!((log.code, log.message.starts_with("Failed to parse BLF")), (error::INVALID_LOG, true));
+
+        let budget = SessionOptions { max_decoded_values: 3, ..SessionOptions::default() };
+        let frames = [(0.0, 1, 0x100, &[0u8; 8][..]), (0.1, 1, 0x100, &[0; 8]), (0.2, 1, 0x100, &[0; 8])];
+        assert_eq!(session(&frames, budget).err().unwrap().code, error::DECODE_BUDGET);
+        assert_eq!(options_error("csv options", "missing field").code, error::INVALID_OPTIONS);
+    }
 }
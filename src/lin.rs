@@ -0,0 +1,535 @@
+// ###############################################################
+// lin.rs
+// can-blf-parser (WASM)
+// LIN signal databases: LDF files (Signals, Frames, Signal_encoding_types,
+// Signal_representation sections) or a DBC describing the LIN frames.
+// One database per LIN channel (options.lin_databases).
+// ###############################################################
+
+use std::collections::HashMap;
+
+use can_dbc::{Message, DBC};
+
+use crate::{decode_signal_value, signal_raw, start_raw, CAN_EFF_MASK};
+
+// One decoded LIN signal, name without the "LIN{n}." prefix
+pub(crate) struct LinValue {
+    pub name: String,
+    pub value: f64,
+    pub unit: String,
+    pub label: Option<String>, // logical_value / VAL_ text of the raw value
+}
+
+// Static facts for SignalMeta (linear part of the encoding)
+pub(crate) struct LinSignalInfo {
+    pub name: String,
+    pub bits: u64,
+    pub factor: f64,
+    pub offset: f64,
+    pub unit: String,
+    pub start_raw: Option<f64>, // LDF init_value / DBC GenSigStartValue
+    pub value_table: HashMap<i64, String>,
+    pub position: (u32, usize), // (frame id, index in the frame); u32::MAX when in no frame
+}
+
+pub(crate) enum LinDatabase {
+    Ldf(Ldf),
+    Dbc(Box<DBC>, HashMap<u8, usize>), // frame id -> message position
+}
+
+impl LinDatabase {
+    // LDF when the text starts with "LIN_description_file", else DBC
+    pub(crate) fn parse(text: &str) -> Result<LinDatabase, String> {
+        let toks = tokenize(text)?;
+        if matches!(toks.first(), Some(Tok::Word(w)) if w == "LIN_description_file") {
+            return Ldf::parse(&toks).map(LinDatabase::Ldf);
+        }
+        let dbc = DBC::try_from(text).map_err(|e| format!("neither an LDF nor a DBC: {:?}", e))?;
+        let mut index = HashMap::new();
+        for (pos, m) in dbc.messages().iter().enumerate() {
+            let id = m.message_id().raw() & CAN_EFF_MASK;
+            if id < 64 {
+                index.entry(id as u8).or_insert(pos);
+            }
+        }
+        Ok(LinDatabase::Dbc(Box::new(dbc), index))
+    }
+
+    fn message(&self, id: u8) -> Option<&Message> {
+        match self {
+            LinDatabase::Dbc(dbc, index) => dbc.messages().get(*index.get(&id)?),
+            LinDatabase::Ldf(_) => None,
+        }
+    }
+
+    pub(crate) fn frame_name(&self, id: u8) -> Option<&str> {
+        match self {
+            LinDatabase::Ldf(ldf) => ldf.frames.get(&id).map(|f| f.name.as_str()),
+            LinDatabase::Dbc(..) => self.message(id).map(|m| m.message_name().as_str()),
+        }
+    }
+
+    pub(crate) fn signal_names(&self, id: u8) -> Vec<String> {
+        match self {
+            LinDatabase::Ldf(ldf) => ldf.frames.get(&id).map_or_else(Vec::new, |f| f.signals.iter().map(|(s, _)| s.clone()).collect()),
+            LinDatabase::Dbc(..) => self.message(id).map_or_else(Vec::new, |m| m.signals().iter().map(|s| s.name().clone()).collect()),
+        }
+    }
+
+    pub(crate) fn decode(&self, id: u8, data: &[u8]) -> Vec<LinValue> {
+        match self {
+            LinDatabase::Ldf(ldf) => ldf.decode(id, data),
+            LinDatabase::Dbc(dbc, _) => {
+                let Some(msg) = self.message(id) else { return Vec::new() };
+                msg.signals()
+                    .iter()
+                    .filter_map(|sig| {
+                        let value = decode_signal_value(sig, data)?;
+                        let label = signal_raw(sig, data).and_then(|raw| {
+                            let descs = dbc.value_descriptions_for_signal(*msg.message_id(), sig.name())?;
+                            descs.iter().find(|d| *d.a() as i64 == raw as i64).map(|d| d.b().clone())
+                        });
+                        Some(LinValue { name: sig.name().clone(), value, unit: sig.unit().clone(), label })
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    pub(crate) fn signals(&self) -> Vec<LinSignalInfo> {
+        match self {
+            LinDatabase::Ldf(ldf) => ldf.signal_info(),
+            LinDatabase::Dbc(dbc, index) => index
+                .values()
+                .filter_map(|&pos| dbc.messages().get(pos))
+                .flat_map(|msg| {
+                    msg.signals().iter().enumerate().map(move |(i, sig)| LinSignalInfo {
+                        name: sig.name().clone(),
+                        bits: *sig.signal_size(),
+                        factor: *sig.factor(),
+                        offset: *sig.offset(),
+                        unit: sig.unit().clone(),
+                        start_raw: start_raw(dbc, msg, sig),
+                        value_table: dbc
+                            .value_descriptions_for_signal(*msg.message_id(), sig.name())
+                            .map(|descs| descs.iter().map(|d| (*d.a() as i64, d.b().clone())).collect())
+                            .unwrap_or_default(),
+                        position: (msg.message_id().raw(), i),
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+// -------------------------------
+// LDF model
+// -------------------------------
+struct LdfSignal {
+    size: u32,
+    init: Option<u64>, // None for byte-array init values
+    encoding: Option<String>,
+}
+
+struct LdfFrame {
+    name: String,
+    signals: Vec<(String, u32)>, // (signal, bit offset)
+}
+
+struct PhysicalRange {
+    min: u64,
+    max: u64,
+    scale: f64,
+    offset: f64,
+    unit: String,
+}
+
+#[derive(Default)]
+struct Encoding {
+    physical: Vec<PhysicalRange>,
+    logical: HashMap<u64, String>,
+}
+
+pub(crate) struct Ldf {
+    signals: HashMap<String, LdfSignal>,
+    frames: HashMap<u8, LdfFrame>,
+    encodings: HashMap<String, Encoding>,
+}
+
+impl Ldf {
+    fn parse(toks: &[Tok]) -> Result<Ldf, String> {
+        let sections = sections(toks);
+        let mut ldf = Ldf { signals: HashMap::new(), frames: HashMap::new(), encodings: HashMap::new() };
+
+        for name in ["Signals", "Diagnostic_signals"] {
+            let mut c = Cursor::new(sections.get(name).copied().unwrap_or(&[]));
+            while !c.at_end() {
+                let sig = c.word()?;
+                c.expect(':')?;
+                let size = c.number()? as u32;
+                c.expect(',')?;
+                let init = if c.eat('{') {
+                    c.skip_past('}');
+                    None
+                } else {
+                    Some(c.number()? as u64)
+                };
+                c.skip_past(';');
+                ldf.signals.insert(sig, LdfSignal { size, init, encoding: None });
+            }
+        }
+
+        for name in ["Frames", "Diagnostic_frames"] {
+            let mut c = Cursor::new(sections.get(name).copied().unwrap_or(&[]));
+            while !c.at_end() {
+                let frame = c.word()?;
+                c.expect(':')?;
+                let id = c.number()? as u8 & 0x3F;
+                c.skip_past('{'); // publisher, length
+                let mut signals = Vec::new();
+                while !c.eat('}') {
+                    let sig = c.word()?;
+                    c.expect(',')?;
+                    let offset = c.number()? as u32;
+                    c.expect(';')?;
+                    signals.push((sig, offset));
+                }
+                c.eat(';');
+                ldf.frames.insert(id, LdfFrame { name: frame, signals });
+            }
+        }
+
+        let mut c = Cursor::new(sections.get("Signal_encoding_types").copied().unwrap_or(&[]));
+        while !c.at_end() {
+            let enc_name = c.word()?;
+            c.expect('{')?;
+            let mut enc = Encoding::default();
+            while !c.eat('}') {
+                let kind = c.word()?;
+                let values = c.list()?;
+                let num = |i: usize| values.get(i).and_then(|v| parse_number(v));
+                match kind.as_str() {
+                    "physical_value" => {
+                        if let (Some(min), Some(max), Some(scale), Some(offset)) = (num(0), num(1), num(2), num(3)) {
+                            let unit = values.get(4).cloned().unwrap_or_default();
+                            enc.physical.push(PhysicalRange { min: min as u64, max: max as u64, scale, offset, unit });
+                        }
+                    }
+                    "logical_value" => {
+                        if let Some(v) = num(0) {
+                            enc.logical.insert(v as u64, values.get(1).cloned().unwrap_or_default());
+                        }
+                    }
+                    _ => {} // bcd_value, ascii_value
+                }
+            }
+            ldf.encodings.insert(enc_name, enc);
+        }
+
+        let mut c = Cursor::new(sections.get("Signal_representation").copied().unwrap_or(&[]));
+        while !c.at_end() {
+            let enc_name = c.word()?;
+            c.expect(':')?;
+            for sig in c.list()? {
+                if let Some(s) = ldf.signals.get_mut(&sig) {
+                    s.encoding = Some(enc_name.clone());
+                }
+            }
+        }
+        Ok(ldf)
+    }
+
+    // physical value, unit and label of a raw value
+    fn physical(&self, sig: &LdfSignal, raw: u64) -> (f64, String, Option<String>) {
+        let Some(enc) = sig.encoding.as_ref().and_then(|e| self.encodings.get(e)) else {
+            return (raw as f64, String::new(), None);
+        };
+        let label = enc.logical.get(&raw).cloned();
+        // raw values outside every range (usually logical ones) use the first range
+        let range = enc.physical.iter().find(|r| (r.min..=r.max).contains(&raw)).or(enc.physical.first());
+        match range {
+            Some(r) => (raw as f64 * r.scale + r.offset, r.unit.clone(), label),
+            None => (raw as f64, String::new(), label),
+        }
+    }
+
+    fn decode(&self, id: u8, data: &[u8]) -> Vec<LinValue> {
+        let Some(frame) = self.frames.get(&id) else { return Vec::new() };
+        frame
+            .signals
+            .iter()
+            .filter_map(|(name, offset)| {
+                let sig = self.signals.get(name)?;
+                let raw = lin_raw(data, *offset, sig.size)?;
+                let (value, unit, label) = self.physical(sig, raw);
+                Some(LinValue { name: name.clone(), value, unit, label })
+            })
+            .collect()
+    }
+
+    fn signal_info(&self) -> Vec<LinSignalInfo> {
+        self.signals
+            .iter()
+            .map(|(name, sig)| {
+                let enc = sig.encoding.as_ref().and_then(|e| self.encodings.get(e));
+                let first = enc.and_then(|e| e.physical.first());
+                LinSignalInfo {
+                    name: name.clone(),
+                    bits: sig.size as u64,
+                    factor: first.map_or(1.0, |r| r.scale),
+                    offset: first.map_or(0.0, |r| r.offset),
+                    unit: first.map_or_else(String::new, |r| r.unit.clone()),
+                    start_raw: sig.init.map(|v| v as f64),
+                    value_table: enc.map_or_else(HashMap::new, |e| e.logical.iter().map(|(k, v)| (*k as i64, v.clone())).collect()),
+                    position: self
+                        .frames
+                        .iter()
+                        .find_map(|(id, f)| f.signals.iter().position(|(s, _)| s == name).map(|i| (*id as u32, i)))
+                        .unwrap_or((u32::MAX, 0)),
+                }
+            })
+            .collect()
+    }
+}
+
+// LIN signals are little endian: bit n of the frame is bit n % 8 of byte n / 8
+fn lin_raw(data: &[u8], offset: u32, size: u32) -> Option<u64> {
+    if size == 0 || size > 64 || (offset + size).div_ceil(8) as usize > data.len() {
+        return None;
+    }
+    let raw = (0..size).fold(0u64, |acc, k| {
+        let bit = offset + k;
+        acc | (((data[(bit / 8) as usize] >> (bit % 8)) & 1) as u64) << k
+    });
+    Some(raw)
+}
+
+fn parse_number(s: &str) -> Option<f64> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok().map(|v| v as f64),
+        None => s.parse().ok(),
+    }
+}
+
+// -------------------------------
+// LDF tokens
+// -------------------------------
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Word(String), // identifiers and numbers
+    Str(String),
+    Punct(char),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Tok>, String> {
+    let mut toks = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => s.push(c),
+                        None => return Err("unterminated string in LDF".to_string()),
+                    }
+                }
+                toks.push(Tok::Str(s));
+            }
+            '{' | '}' | ';' | ',' | ':' | '=' => toks.push(Tok::Punct(c)),
+            _ => {
+                let mut w = String::from(c);
+                while let Some(&n) = chars.peek() {
+                    if n.is_whitespace() || "{};,:=\"".contains(n) {
+                        break;
+                    }
+                    w.push(n);
+                    chars.next();
+                }
+                toks.push(Tok::Word(w));
+            }
+        }
+    }
+    Ok(toks)
+}
+
+// Top-level "Name { ... }" blocks, braces stripped
+fn sections(toks: &[Tok]) -> HashMap<String, &[Tok]> {
+    let mut out = HashMap::new();
+    let mut i = 0;
+    while i < toks.len() {
+        if let (Tok::Word(name), Some(Tok::Punct('{'))) = (&toks[i], toks.get(i + 1)) {
+            let mut depth = 0;
+            let mut end = toks.len();
+            for (j, t) in toks.iter().enumerate().skip(i + 1) {
+                match t {
+                    Tok::Punct('{') => depth += 1,
+                    Tok::Punct('}') => {
+                        depth -= 1;
+                        if depth == 0 {
+                            end = j;
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            out.insert(name.clone(), &toks[i + 2..end]);
+            i = end + 1;
+        } else {
+            i += 1;
+        }
+    }
+    out
+}
+
+struct Cursor<'a> {
+    toks: &'a [Tok],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(toks: &'a [Tok]) -> Self {
+        Cursor { toks, pos: 0 }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.toks.len()
+    }
+
+    fn word(&mut self) -> Result<String, String> {
+        match self.toks.get(self.pos) {
+            Some(Tok::Word(w)) => {
+                self.pos += 1;
+                Ok(w.clone())
+            }
+            other => Err(format!("LDF: expected a name, found {:?}", other)),
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, String> {
+        let w = self.word()?;
+        parse_number(&w).ok_or_else(|| format!("LDF: expected a number, found {}", w))
+    }
+
+    fn eat(&mut self, p: char) -> bool {
+        let hit = self.toks.get(self.pos) == Some(&Tok::Punct(p));
+        if hit {
+            self.pos += 1;
+        }
+        hit
+    }
+
+    fn expect(&mut self, p: char) -> Result<(), String> {
+        if self.eat(p) { Ok(()) } else { Err(format!("LDF: expected '{}', found {:?}", p, self.toks.get(self.pos))) }
+    }
+
+    // past the next `p` at this nesting level
+    fn skip_past(&mut self, p: char) {
+        let mut depth = 0;
+        while let Some(t) = self.toks.get(self.pos) {
+            self.pos += 1;
+            match t {
+                Tok::Punct(c) if *c == p && depth == 0 => return,
+                Tok::Punct('{') => depth += 1,
+                Tok::Punct('}') => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+
+    // "a, b, "c";" -> [a, b, c]
+    fn list(&mut self) -> Result<Vec<String>, String> {
+        let mut out = Vec::new();
+        self.eat(',');
+        loop {
+            match self.toks.get(self.pos) {
+                Some(Tok::Word(w)) | Some(Tok::Str(w)) => out.push(w.clone()),
+                Some(Tok::Punct(',')) => {}
+                Some(Tok::Punct(';')) => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                other => return Err(format!("LDF: unexpected {:?} in value list", other)),
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LDF: &str = r#"
+LIN_description_file;
+LIN_protocol_version = "2.1";
+LIN_speed = 19.2 kbps;
+Nodes { Master: BCM, 5 ms, 0.1 ms; Slaves: Motor; }
+Signals {
+  MotorSpeed: 16, 0, Motor, BCM;
+  MotorState: 2, 0, Motor, BCM;
+  Serial: 16, {0, 0}, Motor, BCM; // byte array
+}
+Frames {
+  MotorStatus: 0x21, Motor, 4 {
+    MotorSpeed, 0;
+    MotorState, 16;
+  }
+}
+/* encodings */
+Signal_encoding_types {
+  SpeedEnc {
+    physical_value, 0, 65534, 0.5, -100, "rpm";
+    logical_value, 65535, "Invalid";
+  }
+  StateEnc {
+    logical_value, 0, "OFF";
+    logical_value, 1, "ON";
+  }
+}
+Signal_representation {
+  SpeedEnc: MotorSpeed;
+  StateEnc: MotorState;
+}
+"#;
+
+    #[test]
+    fn ldf_decode() {
+        let db = LinDatabase::parse(LDF).expect("LDF parses");
+        assert_eq!(db.frame_name(0x21), Some("MotorStatus"));
+        let vals = db.decode(0x21, &[0xE8, 0x03, 0x01, 0x00]);
+        assert_eq!(vals.len(), 2);
+        assert_eq!((vals[0].value, vals[0].unit.as_str()), (400.0, "rpm"));
+        assert_eq!((vals[1].value, vals[1].label.as_deref()), (1.0, Some("ON")));
+        let invalid = db.decode(0x21, &[0xFF, 0xFF, 0x00, 0x00]);
+        assert_eq!(invalid[0].label.as_deref(), Some("Invalid"));
+        // payload too short for MotorState
+        assert_eq!(db.decode(0x21, &[0, 0]).len(), 1);
+    }
+
+    #[test]
+    fn lin_bits_little_endian() {
+        assert_eq!(lin_raw(&[0b1010_0000, 0b0000_0011], 5, 5), Some(0b11101));
+        assert_eq!(lin_raw(&[0xFF], 4, 8), None);
+    }
+}
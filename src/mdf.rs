@@ -0,0 +1,273 @@
+// ###############################################################
+// mdf.rs
+// can-blf-parser (WASM)
+// ASAM MDF 4.10 writer for export_mf4(): one data group / channel group per
+// message, raw values with the DBC conversion attached, so CANape, asammdf etc.
+// show physical values, units and value tables
+// ###############################################################
+
+// -------------------------------
+// Layout (little endian, every block 8-byte aligned):
+//   ID (64 bytes) | HD | FH + MD (tool comment)
+//   per group: DG -> CG -> CN time (master) -> CN per signal [TX name, TX unit, CC] -> DT
+// Records (sorted: one CG per DG, no record id):
+//   f64 time [s] | 8 bytes per signal | invalidation bits (bit i set: signal i absent
+//   from that frame, e.g. an inactive multiplexed signal)
+// Raw signals are stored as i64 with a linear CC (phys = offset + factor * raw), or a
+// value-to-text CC whose default falls back to the linear one; signals without DBC
+// scaling are stored as f64 physical values.
+// -------------------------------
+const BLOCK_HEADER: usize = 24;
+const MDF_VERSION: u16 = 410;
+
+const DT_SIGNED: u8 = 2; // signed integer, little endian
+const DT_FLOAT: u8 = 4; // IEEE 754, little endian
+
+pub(crate) enum Storage {
+    Raw { factor: f64, offset: f64, table: Vec<(i64, String)> },
+    Physical,
+}
+
+pub(crate) struct Channel {
+    pub name: String,
+    pub unit: String,
+    pub storage: Storage,
+}
+
+pub(crate) struct Group {
+    name: String, // acquisition name, e.g. "CAN1.EngineData"
+    channels: Vec<Channel>,
+    records: Vec<u8>,
+    cycles: u64,
+}
+
+impl Group {
+    pub(crate) fn new(name: String, channels: Vec<Channel>) -> Group {
+        Group { name, channels, records: Vec::new(), cycles: 0 }
+    }
+
+    fn inval_bytes(&self) -> usize {
+        self.channels.len().div_ceil(8)
+    }
+
+    // values parallel to the channels: raw integers for Storage::Raw, None when absent
+    pub(crate) fn push(&mut self, time: f64, values: &[Option<f64>]) {
+        self.records.extend_from_slice(&time.to_le_bytes());
+        let mut inval = vec![0u8; self.inval_bytes()];
+        for (i, ch) in self.channels.iter().enumerate() {
+            let bytes = match (values.get(i).copied().flatten(), &ch.storage) {
+                (Some(v), Storage::Raw { .. }) => (v as i64).to_le_bytes(),
+                (Some(v), Storage::Physical) => v.to_le_bytes(),
+                (None, _) => {
+                    inval[i / 8] |= 1 << (i % 8);
+                    [0; 8]
+                }
+            };
+            self.records.extend_from_slice(&bytes);
+        }
+        self.records.extend_from_slice(&inval);
+        self.cycles += 1;
+    }
+}
+
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    // Appends a block and returns its file offset
+    fn block(&mut self, id: &[u8; 2], links: &[u64], data: &[u8]) -> u64 {
+        let at = self.buf.len() as u64;
+        let len = BLOCK_HEADER + links.len() * 8 + data.len();
+        self.buf.extend_from_slice(b"##");
+        self.buf.extend_from_slice(id);
+        self.buf.extend_from_slice(&[0; 4]);
+        self.buf.extend_from_slice(&(len as u64).to_le_bytes());
+        self.buf.extend_from_slice(&(links.len() as u64).to_le_bytes());
+        for l in links {
+            self.buf.extend_from_slice(&l.to_le_bytes());
+        }
+        self.buf.extend_from_slice(data);
+        self.buf.resize(self.buf.len().next_multiple_of(8), 0);
+        at
+    }
+
+    // Sets link `index` of the block at `at`, for links to blocks written later
+    fn link(&mut self, at: u64, index: usize, target: u64) {
+        let pos = at as usize + BLOCK_HEADER + index * 8;
+        self.buf[pos..pos + 8].copy_from_slice(&target.to_le_bytes());
+    }
+
+    // TX (or MD) block: zero-terminated UTF-8, zero padded
+    fn text(&mut self, id: &[u8; 2], text: &str) -> u64 {
+        let mut data = text.as_bytes().to_vec();
+        data.push(0);
+        data.resize(data.len().next_multiple_of(8), 0);
+        self.block(id, &[], &data)
+    }
+
+    fn optional_text(&mut self, text: &str) -> u64 {
+        if text.is_empty() { 0 } else { self.text(b"TX", text) }
+    }
+
+    // CC block: links tx_name, md_unit, md_comment, cc_inverse, then refs
+    fn conversion(&mut self, cc_type: u8, refs: &[u64], values: &[f64]) -> u64 {
+        let mut links = vec![0; 4];
+        links.extend_from_slice(refs);
+        let mut data = vec![cc_type, 0];
+        data.extend_from_slice(&0u16.to_le_bytes()); // flags
+        data.extend_from_slice(&(refs.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(values.len() as u16).to_le_bytes());
+        data.extend_from_slice(&[0; 16]); // physical range (not valid)
+        for v in values {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        self.block(b"CC", &links, &data)
+    }
+
+    fn channel_conversion(&mut self, storage: &Storage) -> u64 {
+        let Storage::Raw { factor, offset, table } = storage else {
+            return 0;
+        };
+        let linear = if *factor == 1.0 && *offset == 0.0 { 0 } else { self.conversion(1, &[], &[*offset, *factor]) };
+        if table.is_empty() {
+            return linear;
+        }
+        let mut refs: Vec<u64> = table.iter().map(|(_, text)| self.text(b"TX", text)).collect();
+        refs.push(linear); // default: the numeric value
+        let keys: Vec<f64> = table.iter().map(|(raw, _)| *raw as f64).collect();
+        self.conversion(7, &refs, &keys)
+    }
+
+    // CN block: links cn_next, composition, tx_name, si_source, cc, data, md_unit, md_comment.
+    // index None is the time master (cn_type 2, sync type 1) at record offset 0; signal i
+    // follows at 8 + 8 * i with invalidation bit i.
+    fn channel(&mut self, name: &str, unit: &str, cc: u64, data_type: u8, index: Option<u32>) -> u64 {
+        let name = self.text(b"TX", name);
+        let unit = self.optional_text(unit);
+        let (kind, sync) = if index.is_some() { (0, 0) } else { (2, 1) };
+        let mut data = vec![kind, sync, data_type, 0];
+        data.extend_from_slice(&index.map_or(0, |i| 8 + 8 * i).to_le_bytes());
+        data.extend_from_slice(&64u32.to_le_bytes()); // bit count
+        data.extend_from_slice(&(if index.is_some() { 0x02u32 } else { 0 }).to_le_bytes());
+        data.extend_from_slice(&index.unwrap_or(0).to_le_bytes());
+        data.extend_from_slice(&[0, 0, 0, 0]); // precision, reserved, attachment count
+        data.extend_from_slice(&[0; 48]); // value range and limits (not valid)
+        self.block(b"CN", &[0, 0, name, 0, cc, 0, unit, 0], &data)
+    }
+
+    fn group(&mut self, group: &Group) -> u64 {
+        let dg = self.block(b"DG", &[0; 4], &[0; 8]);
+        let acq_name = self.text(b"TX", &group.name);
+        let mut cg_data = Vec::with_capacity(32);
+        cg_data.extend_from_slice(&0u64.to_le_bytes()); // record id
+        cg_data.extend_from_slice(&group.cycles.to_le_bytes());
+        cg_data.extend_from_slice(&[0; 8]); // flags, path separator, reserved
+        cg_data.extend_from_slice(&((8 + 8 * group.channels.len()) as u32).to_le_bytes());
+        cg_data.extend_from_slice(&(group.inval_bytes() as u32).to_le_bytes());
+        let cg = self.block(b"CG", &[0, 0, acq_name, 0, 0, 0], &cg_data);
+        self.link(dg, 1, cg);
+
+        let mut prev = self.channel("time", "s", 0, DT_FLOAT, None);
+        self.link(cg, 1, prev);
+        for (i, ch) in group.channels.iter().enumerate() {
+            let cc = self.channel_conversion(&ch.storage);
+            let data_type = if matches!(ch.storage, Storage::Raw { .. }) { DT_SIGNED } else { DT_FLOAT };
+            let cn = self.channel(&ch.name, &ch.unit, cc, data_type, Some(i as u32));
+            self.link(prev, 0, cn);
+            prev = cn;
+        }
+
+        let dt = self.block(b"DT", &[], &group.records);
+        self.link(dg, 2, dt);
+        dg
+    }
+}
+
+pub(crate) fn encode(groups: &[Group]) -> Vec<u8> {
+    let mut w = Writer { buf: Vec::new() };
+    w.buf.extend_from_slice(b"MDF     4.10    canblfp ");
+    w.buf.extend_from_slice(&[0; 4]);
+    w.buf.extend_from_slice(&MDF_VERSION.to_le_bytes());
+    w.buf.resize(64, 0);
+
+    // HD: links dg_first, fh_first, ch_first, at_first, ev_first, md_comment;
+    // start time 0 (timestamps are relative to the measurement start)
+    let hd = w.block(b"HD", &[0; 6], &[0; 32]);
+    let comment = format!(
+        "<FHcomment><TX>export_mf4</TX><tool_id>can-blf-parser</tool_id><tool_vendor>can-blf-parser</tool_vendor><tool_version>{}</tool_version></FHcomment>",
+        env!("CARGO_PKG_VERSION")
+    );
+    let md = w.text(b"MD", &comment);
+    let fh = w.block(b"FH", &[0, md], &[0; 16]);
+    w.link(hd, 1, fh);
+
+    let mut prev: Option<u64> = None;
+    for g in groups {
+        let dg = w.group(g);
+        match prev {
+            Some(p) => w.link(p, 0, dg),
+            None => w.link(hd, 0, dg),
+        }
+        prev = Some(dg);
+    }
+    w.buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u64_at(buf: &[u8], pos: u64) -> u64 {
+        u64::from_le_bytes(buf[pos as usize..pos as usize + 8].try_into().unwrap())
+    }
+
+    fn link(buf: &[u8], block: u64, index: usize) -> u64 {
+        u64_at(buf, block + 24 + 8 * index as u64)
+    }
+
+    #[test]
+    fn groups_records_and_links() {
+        let raw = Storage::Raw { factor: 0.5, offset: 0.0, table: vec![(3, "SNA".into())] };
+        let channels = vec![
+            Channel { name: "CAN1.Speed".into(), unit: "km/h".into(), storage: raw },
+            Channel { name: "CAN1.Mode".into(), unit: String::new(), storage: Storage::Physical },
+        ];
+        let mut g = Group::new("CAN1.Vehicle".into(), channels);
+        g.push(0.1, &[Some(200.0), None]);
+        g.push(0.2, &[Some(-4.0), Some(1.5)]);
+        let buf = encode(&[g, Group::new("CAN1.Empty".into(), Vec::new())]);
+
+        assert_eq!(&buf[..8], b"MDF     ");
+        assert_eq!(u16::from_le_bytes([buf[28], buf[29]]), 410);
+        assert_eq!(&buf[64..68], b"##HD");
+        assert_eq!(buf.len() % 8, 0);
+
+        let dg = link(&buf, 64, 0);
+        assert_eq!(&buf[dg as usize..dg as usize + 4], b"##DG");
+        let cg = link(&buf, dg, 1);
+        assert_eq!(u64_at(&buf, cg + 24 + 48 + 8), 2); // cycle count
+
+        let dt = link(&buf, dg, 2);
+        assert_eq!(u64_at(&buf, dt + 8), 24 + 2 * 25); // 8 time + 2 * 8 values + 1 inval
+        let rec = dt as usize + 24;
+        assert_eq!(f64::from_le_bytes(buf[rec..rec + 8].try_into().unwrap()), 0.1);
+        assert_eq!(i64::from_le_bytes(buf[rec + 8..rec + 16].try_into().unwrap()), 200);
+        assert_eq!(buf[rec + 24], 0b10); // Mode absent in the first record
+        assert_eq!(i64::from_le_bytes(buf[rec + 33..rec + 41].try_into().unwrap()), -4);
+
+        // time -> Speed -> Mode, Speed with a value table falling back to the linear CC
+        let time = link(&buf, cg, 1);
+        let speed = link(&buf, time, 0);
+        let mode = link(&buf, speed, 0);
+        assert_eq!(link(&buf, mode, 0), 0);
+        let cc = link(&buf, speed, 4);
+        assert_eq!(buf[cc as usize + 24 + 8 * 6], 7);
+        let linear = link(&buf, cc, 5);
+        assert_eq!(buf[linear as usize + 24 + 32], 1);
+
+        let empty = link(&buf, dg, 0);
+        assert_eq!(&buf[empty as usize..empty as usize + 4], b"##DG");
+        assert_eq!(link(&buf, empty, 0), 0);
+    }
+}
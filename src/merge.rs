@@ -0,0 +1,236 @@
+// ###############################################################
+// merge.rs
+// can-blf-parser (WASM)
+// Helpers for merging captures from several loggers on one bus
+// ###############################################################
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::store::FrameStore;
+
+// Linear fit t_existing = offset_s + (1 + drift_ppm * 1e-6) * t_incoming
+#[derive(Serialize, Debug, Clone)]
+pub struct ClockFit {
+    pub offset_s: f64,
+    pub drift_ppm: f64,
+    pub matched_pairs: usize,
+    pub applied: bool,
+}
+
+impl ClockFit {
+    pub(crate) fn correct(&self, t: f64) -> f64 {
+        self.offset_s + (1.0 + self.drift_ppm * 1e-6) * t
+    }
+}
+
+// (channel, id, payload) identifies "the same bus frame" across loggers
+type FrameKey<'a> = (&'a str, u32, &'a [u8]);
+
+// -------------------------------
+// Match each incoming frame to an unmatched existing frame with the same key
+// whose timestamp lies within tol_s (after mapping the incoming timestamp
+// through `map`). One-to-one, so genuine back-to-back repeats of a frame on
+// one logger are never collapsed.
+// -------------------------------
+pub(crate) fn match_frames(
+    existing: &FrameStore,
+    incoming: &FrameStore,
+    tol_s: f64,
+    map: impl Fn(f64) -> f64,
+) -> Vec<Option<usize>> {
+    let mut index: HashMap<FrameKey, Vec<(f64, usize)>> = HashMap::new();
+    for (i, f) in existing.iter().enumerate() {
+        index
+            .entry((f.channel, f.raw_id(), f.data))
+            .or_default()
+            .push((f.timestamp, i));
+    }
+    for list in index.values_mut() {
+        list.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+
+    let mut taken = vec![false; existing.len()];
+    incoming
+        .iter()
+        .map(|f| {
+            let list = index.get(&(f.channel, f.raw_id(), f.data))?;
+            let ts = map(f.timestamp);
+            let lo = list.partition_point(|(t, _)| *t < ts - tol_s);
+            let best = list[lo..]
+                .iter()
+                .take_while(|(t, _)| *t <= ts + tol_s)
+                .filter(|(_, i)| !taken[*i])
+                .min_by(|a, b| (a.0 - ts).abs().total_cmp(&(b.0 - ts).abs()))
+                .map(|(_, i)| *i)?;
+            taken[best] = true;
+            Some(best)
+        })
+        .collect()
+}
+
+// -------------------------------
+// Drop incoming frames that duplicate an existing one.
+// Returns the surviving frames and how many were removed.
+// -------------------------------
+pub(crate) fn dedup_incoming(existing: &FrameStore, incoming: &FrameStore, tol_s: f64) -> (FrameStore, usize) {
+    let keep: Vec<bool> = match_frames(existing, incoming, tol_s, |t| t).iter().map(|m| m.is_none()).collect();
+    let kept = incoming.filtered(&keep);
+    let removed = incoming.len() - kept.len();
+    (kept, removed)
+}
+
+// -------------------------------
+// Estimate the clock relation between two loggers from frames both saw.
+// Pass 1 pairs frames within max_offset_s and takes the median difference as
+// a coarse offset; pass 2 re-pairs tightly around it and least-squares fits
+// offset + drift, dropping pairs whose residual exceeds tol_s once.
+// -------------------------------
+pub(crate) fn estimate_clock(
+    existing: &FrameStore,
+    incoming: &FrameStore,
+    max_offset_s: f64,
+    tol_s: f64,
+    min_pairs: usize,
+) -> Option<ClockFit> {
+    let coarse_matches = match_frames(existing, incoming, max_offset_s, |t| t);
+    let mut diffs: Vec<f64> = coarse_matches
+        .iter()
+        .zip(incoming.iter())
+        .filter_map(|(m, f)| m.map(|i| existing.get(i).timestamp - f.timestamp))
+        .collect();
+    if diffs.len() < min_pairs.max(2) {
+        return None;
+    }
+    diffs.sort_by(|a, b| a.total_cmp(b));
+    let coarse = diffs[diffs.len() / 2];
+
+    let fine_matches = match_frames(existing, incoming, tol_s, |t| t + coarse);
+    let mut pairs: Vec<(f64, f64)> = fine_matches
+        .iter()
+        .zip(incoming.iter())
+        .filter_map(|(m, f)| m.map(|i| (f.timestamp, existing.get(i).timestamp)))
+        .collect();
+
+    let (mut a, mut b) = fit_line(&pairs)?;
+    pairs.retain(|(x, y)| (a + b * x - y).abs() <= tol_s);
+    if pairs.len() < min_pairs.max(2) {
+        return None;
+    }
+    (a, b) = fit_line(&pairs)?;
+
+    Some(ClockFit {
+        offset_s: a,
+        drift_ppm: (b - 1.0) * 1e6,
+        matched_pairs: pairs.len(),
+        applied: false,
+    })
+}
+
+// ordinary least squares y = a + b*x (centered for precision on large timestamps)
+fn fit_line(pairs: &[(f64, f64)]) -> Option<(f64, f64)> {
+    if pairs.len() < 2 {
+        return None;
+    }
+    let n = pairs.len() as f64;
+    let mx = pairs.iter().map(|p| p.0).sum::<f64>() / n;
+    let my = pairs.iter().map(|p| p.1).sum::<f64>() / n;
+    let sxx: f64 = pairs.iter().map(|p| (p.0 - mx) * (p.0 - mx)).sum();
+    let sxy: f64 = pairs.iter().map(|p| (p.0 - mx) * (p.1 - my)).sum();
+    // all pairs at one instant: offset only
+    let b = if sxx > 0.0 { sxy / sxx } else { 1.0 };
+    Some((my - b * mx, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::Payload;
+    use crate::FrameRow;
+
+    // CAN1 frames: (time s, id, data)
+    fn store(frames: &[(f64, u32, Vec<u8>)]) -> FrameStore {
+        let mut store = FrameStore::default();
+        for (t, id, data) in frames {
+            let row = FrameRow {
+                timestamp: *t,
+                channel: "CAN1".to_string(),
+                channel_num: 1,
+                id: *id,
+                dlc: data.len() as u8,
+                data: Payload::from(&data[..]),
+                ..FrameRow::default()
+            };
+            store.push(row.view());
+        }
+        store
+    }
+
+    #[test]
+    fn matches_shared_frames_one_to_one() {
+        let existing = store(&[
+            (0.0, 0x100, vec![1]),
+            (0.1, 0x100, vec![2]),
+            (0.2, 0x100, vec![3]),
+            (0.3, 0x100, vec![4]),
+            (0.5, 0x100, vec![5]),
+            (0.5005, 0x100, vec![5]),
+        ]);
+        let incoming = store(&[
+            (0.0004, 0x100, vec![1]),
+            (0.1003, 0x100, vec![2]),
+            // only this logger saw it
+            (0.15, 0x200, vec![9]),
+            // a genuine back-to-back repeat: one partner in existing, so one survives
+            (0.2002, 0x100, vec![3]),
+            (0.2006, 0x100, vec![3]),
+            // 1.1 ms away, just outside the 1 ms tolerance
+            (0.3011, 0x100, vec![4]),
+            // both loggers saw the repeat: each copy pairs with its nearest partner
+            (0.5001, 0x100, vec![5]),
+            (0.5006, 0x100, vec![5]),
+        ]);
+        let matches = match_frames(&existing, &incoming, 0.001, |t| t);
+        assert_eq!(matches, [Some(0), Some(1), None, Some(2), None, None, Some(4), Some(5)]);
+
+        let (kept, removed) = dedup_incoming(&existing, &incoming, 0.001);
+        assert_eq!(removed, 5);
+        let times: Vec<f64> = kept.iter().map(|f| f.timestamp).collect();
+        assert_eq!(times, [0.15, 0.2006, 0.3011]);
+    }
+
+    #[test]
+    fn recovers_offset_and_drift() {
+        // 100 s at 10 Hz, each frame with its own payload; the incoming clock runs
+        // 50 ppm fast and 250 ms ahead
+        let existing: Vec<(f64, u32, Vec<u8>)> =
+            (0..1000u16).map(|i| (i as f64 * 0.1, 0x100, i.to_le_bytes().to_vec())).collect();
+        let incoming: Vec<(f64, u32, Vec<u8>)> = existing
+            .iter()
+            .enumerate()
+            .map(|(i, (t, id, data))| {
+                let t_in = match i {
+                    // outliers 6 ms off the line at both ends, still inside the fine
+                    // match window around the median offset, so only pruning drops them
+                    0 | 1 => t + 0.256,
+                    998 | 999 => t + 0.249,
+                    _ => t * (1.0 + 50e-6) + 0.25,
+                };
+                (t_in, *id, data.clone())
+            })
+            .collect();
+        let (existing, incoming) = (store(&existing), store(&incoming));
+
+        let fit = estimate_clock(&existing, &incoming, 1.0, 0.004, 10).unwrap();
+        assert_eq!(fit.matched_pairs, 996);
+        // existing = (incoming - 0.25) / (1 + 50e-6)
+        assert!((fit.offset_s + 0.25 / (1.0 + 50e-6)).abs() < 1e-6, "{}", fit.offset_s);
+        assert!((fit.drift_ppm + 50.0).abs() < 0.01, "{}", fit.drift_ppm);
+        assert!((fit.correct(50.0025 + 0.25) - 50.0).abs() < 1e-6);
+
+        // too few pairs before (1000) or after pruning (996)
+        assert!(estimate_clock(&existing, &incoming, 1.0, 0.004, 1001).is_none());
+        assert!(estimate_clock(&existing, &incoming, 1.0, 0.004, 997).is_none());
+    }
+}
@@ -0,0 +1,127 @@
+// ###############################################################
+// mux.rs
+// can-blf-parser (WASM)
+// Multiplexed messages: which signals a frame actually carries, from the
+// multiplexor value (simple "m<n>" signals) or SG_MUL_VAL_ ranges
+// (extended multiplexing, possibly nested)
+// ###############################################################
+
+use can_dbc::{Message, MultiplexIndicator, Signal, DBC};
+
+use crate::signal_raw;
+
+// (selector, ranges): present when signal `selector` is present and its raw value
+// lies in one of the inclusive ranges
+type Condition = (usize, Vec<(u64, u64)>);
+
+// Per signal of one message; None = always present
+pub(crate) struct MuxPlan {
+    conds: Vec<Option<Condition>>,
+}
+
+impl MuxPlan {
+    // None for messages without multiplexing
+    pub(crate) fn build(dbc: &DBC, msg: &Message) -> Option<MuxPlan> {
+        let signals = msg.signals();
+        if signals.iter().all(|s| *s.multiplexer_indicator() == MultiplexIndicator::Plain) {
+            return None;
+        }
+        let index_of = |name: &str| signals.iter().position(|s| s.name() == name);
+        // simple multiplexing: the one plain multiplexor switch
+        let switch = signals.iter().position(|s| *s.multiplexer_indicator() == MultiplexIndicator::Multiplexor);
+
+        let conds = signals
+            .iter()
+            .map(|sig| {
+                let extended: Vec<_> = dbc
+                    .extended_multiplex()
+                    .iter()
+                    .filter(|e| e.message_id() == msg.message_id() && e.signal_name() == sig.name())
+                    .collect();
+                if let Some(e) = extended.first() {
+                    let selector = index_of(e.multiplexor_signal_name())?;
+                    let ranges = extended
+                        .iter()
+                        .flat_map(|e| e.mappings().iter().map(|m| (*m.min_value(), *m.max_value())))
+                        .collect();
+                    return Some((selector, ranges));
+                }
+                match sig.multiplexer_indicator() {
+                    MultiplexIndicator::MultiplexedSignal(n) | MultiplexIndicator::MultiplexorAndMultiplexedSignal(n) => {
+                        switch.map(|sw| (sw, vec![(*n, *n)]))
+                    }
+                    _ => None,
+                }
+            })
+            .collect();
+        Some(MuxPlan { conds })
+    }
+
+    // Is signal `i` present in `data`? Follows nested selectors up to the root switch.
+    pub(crate) fn active(&self, i: usize, signals: &[Signal], data: &[u8]) -> bool {
+        let mut cur = i;
+        // a selector chain longer than the message is a cycle in a broken DBC
+        for _ in 0..=self.conds.len() {
+            let Some((selector, ranges)) = &self.conds[cur] else { return true };
+            let Some(raw) = signals.get(*selector).and_then(|s| signal_raw(s, data)) else { return false };
+            if !ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&raw)) {
+                return false;
+            }
+            cur = *selector;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "VERSION \"\"\n\nNS_ :\n\nBS_:\n\nBU_: ECU\n\n\
+BO_ 512 MUX: 8 ECU\n\
+ SG_ Sel M : 0|8@1+ (1,0) [0|0] \"\" ECU\n\
+ SG_ A m1 : 8|8@1+ (1,0) [0|0] \"\" ECU\n\
+ SG_ B m2 : 8|16@1+ (1,0) [0|0] \"\" ECU\n\
+ SG_ Sub m3M : 8|8@1+ (1,0) [0|0] \"\" ECU\n\
+ SG_ C m0 : 16|8@1+ (1,0) [0|0] \"\" ECU\n\
+ SG_ P : 56|8@1+ (1,0) [0|0] \"\" ECU\n\n\
+SG_MUL_VAL_ 512 Sub Sel 3-3;\n\
+SG_MUL_VAL_ 512 C Sub 0-1, 5-5;\n\n";
+
+    // names of the signals present in `data`
+    fn present(data: [u8; 8]) -> Vec<String> {
+        let dbc = DBC::try_from(TEXT).expect("test DBC parses");
+        let msg = &dbc.messages()[0];
+        let plan = MuxPlan::build(&dbc, msg).expect("multiplexed");
+        msg.signals()
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| plan.active(*i, msg.signals(), &data))
+            .map(|(_, s)| s.name().clone())
+            .collect()
+    }
+
+    #[test]
+    fn simple_mux() {
+        assert_eq!(present([1, 0, 0, 0, 0, 0, 0, 0]), ["Sel", "A", "P"]);
+        assert_eq!(present([2, 0, 0, 0, 0, 0, 0, 0]), ["Sel", "B", "P"]);
+        assert_eq!(present([9, 0, 0, 0, 0, 0, 0, 0]), ["Sel", "P"]);
+    }
+
+    #[test]
+    fn extended_nested_mux() {
+        // Sel = 3 enables Sub; C needs Sub in 0..=1 or 5
+        assert_eq!(present([3, 1, 0, 0, 0, 0, 0, 0]), ["Sel", "Sub", "C", "P"]);
+        assert_eq!(present([3, 5, 0, 0, 0, 0, 0, 0]), ["Sel", "Sub", "C", "P"]);
+        assert_eq!(present([3, 2, 0, 0, 0, 0, 0, 0]), ["Sel", "Sub", "P"]);
+        // Sub inactive -> C inactive even though its byte reads 0
+        assert_eq!(present([1, 0, 0, 0, 0, 0, 0, 0]), ["Sel", "A", "P"]);
+    }
+
+    #[test]
+    fn plain_message_has_no_plan() {
+        let text = "VERSION \"\"\n\nNS_ :\n\nBS_:\n\nBU_: ECU\n\nBO_ 1 M: 8 ECU\n SG_ S : 0|8@1+ (1,0) [0|0] \"\" ECU\n\n";
+        let dbc = DBC::try_from(text).expect("test DBC parses");
+        assert!(MuxPlan::build(&dbc, &dbc.messages()[0]).is_none());
+    }
+}
@@ -0,0 +1,58 @@
+// ###############################################################
+// npy.rs
+// can-blf-parser (WASM)
+// NumPy .npy arrays (format version 1.0) and .npz archives of them for
+// export_npz(): little-endian float64 vectors that np.load() maps as is.
+// ###############################################################
+
+use crate::zip;
+
+// header dict padded so the data starts 64-byte aligned, as numpy writes it
+fn f64_array(values: &[f64]) -> Vec<u8> {
+    let dict = format!("{{'descr': '<f8', 'fortran_order': False, 'shape': ({},), }}", values.len());
+    let unpadded = 6 + 2 + 2 + dict.len() + 1; // magic, version, length, dict, newline
+    let header_len = dict.len() + 1 + (64 - unpadded % 64) % 64;
+    let mut out = Vec::with_capacity(10 + header_len + values.len() * 8);
+    out.extend_from_slice(b"\x93NUMPY\x01\x00");
+    out.extend_from_slice(&(header_len as u16).to_le_bytes());
+    out.extend_from_slice(dict.as_bytes());
+    out.resize(10 + header_len - 1, b' ');
+    out.push(b'\n');
+    for v in values {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    out
+}
+
+// np.load() keys are the member names without ".npy"; '/' would make directories
+pub(crate) fn key(name: &str) -> String {
+    name.replace(['/', '\\'], "_")
+}
+
+// .npz archive of named arrays (np.savez(), or np.savez_compressed() with `compress`)
+pub(crate) fn npz(arrays: &[(String, &[f64])], compress: bool) -> Result<Vec<u8>, String> {
+    let names: Vec<String> = arrays.iter().map(|(k, _)| format!("{}.npy", k)).collect();
+    let entries: Vec<(&str, Vec<u8>)> = names.iter().zip(arrays).map(|(n, (_, v))| (n.as_str(), f64_array(v))).collect();
+    zip::archive(&entries, compress)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_alignment() {
+        for n in [0, 3, 12_345] {
+            let a = f64_array(&vec![1.5; n]);
+            let header_len = u16::from_le_bytes([a[8], a[9]]) as usize;
+            assert_eq!((10 + header_len) % 64, 0);
+            assert_eq!(a[10 + header_len - 1], b'\n');
+        }
+        let a = f64_array(&[1.5, -2.0]);
+        let text = String::from_utf8_lossy(&a[10..128]);
+        assert!(text.starts_with("{'descr': '<f8', 'fortran_order': False, 'shape': (2,), }   "));
+        assert_eq!(&a[128..136], &1.5f64.to_le_bytes());
+        assert_eq!(a.len(), 128 + 16);
+        assert_eq!(key("CAN1.Engine/Speed"), "CAN1.Engine_Speed");
+    }
+}
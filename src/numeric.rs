@@ -0,0 +1,172 @@
+// ###############################################################
+// numeric.rs
+// can-blf-parser (WASM)
+// Numbers in option objects: JS numbers or numeric strings straight from
+// form inputs ("1,5", "2 000", " 10 "), parsed the same way whatever the
+// browser locale. Used via #[serde(deserialize_with = "numeric::...")].
+// ###############################################################
+
+use std::fmt;
+
+use serde::de::{self, Deserializer, Visitor};
+use serde::Deserialize;
+
+// Decimal separator: '.' or ','. With both present the last one is the decimal
+// separator and the other groups thousands; several of one kind are grouping.
+// A single ',' followed by exactly three digits ("1,000") is rejected as ambiguous.
+// Spaces (incl. no-break / narrow no-break), '_' and '\'' group digits.
+pub(crate) fn parse_number(input: &str) -> Result<f64, String> {
+    let s: String = input
+        .trim()
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '\u{a0}' | '\u{202f}' | '_' | '\''))
+        .collect();
+    if s.is_empty() {
+        return Err("empty string is not a number".to_string());
+    }
+
+    let (mantissa, exponent) = match s.find(['e', 'E']) {
+        Some(p) => s.split_at(p),
+        None => (s.as_str(), ""),
+    };
+    let dots = mantissa.matches('.').count();
+    let commas = mantissa.matches(',').count();
+    let normalized = match (dots, commas) {
+        (_, 0) if dots <= 1 => mantissa.to_string(),
+        (_, 0) => mantissa.replace('.', ""),
+        (0, 1) => {
+            let frac = &mantissa[mantissa.find(',').unwrap_or(0) + 1..];
+            if frac.len() == 3 && frac.chars().all(|c| c.is_ascii_digit()) {
+                return Err(format!("ambiguous number \"{}\" (thousands or decimal comma?)", input));
+            }
+            mantissa.replace(',', ".")
+        }
+        (0, _) => mantissa.replace(',', ""),
+        _ => {
+            let (group, decimal) = if mantissa.rfind('.') > mantissa.rfind(',') { (',', '.') } else { ('.', ',') };
+            if mantissa.matches(decimal).count() > 1 {
+                return Err(format!("invalid number \"{}\"", input));
+            }
+            mantissa.replace(group, "").replace(decimal, ".")
+        }
+    };
+
+    let value: f64 = format!("{}{}", normalized, exponent)
+        .parse()
+        .map_err(|_| format!("invalid number \"{}\"", input))?;
+    if !value.is_finite() {
+        return Err(format!("number \"{}\" is not finite", input));
+    }
+    Ok(value)
+}
+
+// One option value: number or numeric string
+struct Number(f64);
+
+impl<'de> Deserialize<'de> for Number {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Number, D::Error> {
+        struct NumberVisitor;
+
+        impl Visitor<'_> for NumberVisitor {
+            type Value = Number;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a number or a numeric string")
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Number, E> {
+                if v.is_finite() { Ok(Number(v)) } else { Err(E::custom(format!("number {} is not finite", v))) }
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Number, E> {
+                Ok(Number(v as f64))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Number, E> {
+                Ok(Number(v as f64))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Number, E> {
+                parse_number(v).map(Number).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(NumberVisitor)
+    }
+}
+
+fn to_usize<E: de::Error>(v: f64) -> Result<usize, E> {
+    if v < 0.0 || v.fract() != 0.0 || v > 9_007_199_254_740_991.0 {
+        return Err(E::custom(format!("expected a non-negative integer, got {}", v)));
+    }
+    Ok(v as usize)
+}
+
+pub(crate) fn f64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    Number::deserialize(deserializer).map(|n| n.0)
+}
+
+pub(crate) fn opt_f64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    Ok(Option::<Number>::deserialize(deserializer)?.map(|n| n.0))
+}
+
+pub(crate) fn usize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+    to_usize(Number::deserialize(deserializer)?.0)
+}
+
+pub(crate) fn usize_vec<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<usize>, D::Error> {
+    Vec::<Number>::deserialize(deserializer)?.into_iter().map(|n| to_usize(n.0)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_and_grouped() {
+        assert_eq!(parse_number(" 10 "), Ok(10.0));
+        assert_eq!(parse_number("-2.5"), Ok(-2.5));
+        assert_eq!(parse_number("1e3"), Ok(1000.0));
+        assert_eq!(parse_number("2 000"), Ok(2000.0));
+        assert_eq!(parse_number("2\u{202f}000,5"), Ok(2000.5));
+        assert_eq!(parse_number("1,000,000"), Ok(1_000_000.0));
+        assert_eq!(parse_number("1.000.000"), Ok(1_000_000.0));
+    }
+
+    #[test]
+    fn decimal_comma() {
+        assert_eq!(parse_number("1,5"), Ok(1.5));
+        assert_eq!(parse_number("1.234,5"), Ok(1234.5));
+        assert_eq!(parse_number("1,234.5"), Ok(1234.5));
+        assert_eq!(parse_number("0,25e2"), Ok(25.0));
+    }
+
+    #[test]
+    fn rejected() {
+        assert!(parse_number("").is_err());
+        assert!(parse_number("abc").is_err());
+        assert!(parse_number("1,000").is_err()); // ambiguous
+        assert!(parse_number("1.2,3,4").is_err());
+        assert!(parse_number("inf").is_err());
+        assert!(parse_number("NaN").is_err());
+    }
+
+    #[test]
+    fn option_fields() {
+        #[derive(Deserialize)]
+        struct Opts {
+            #[serde(deserialize_with = "f64")]
+            a: f64,
+            #[serde(deserialize_with = "usize")]
+            n: usize,
+            #[serde(default, deserialize_with = "opt_f64")]
+            o: Option<f64>,
+        }
+        let o: Opts = serde_json::from_str(r#"{"a": "12,5", "n": "3", "o": null}"#).unwrap();
+        assert_eq!((o.a, o.n, o.o), (12.5, 3, None));
+        let o: Opts = serde_json::from_str(r#"{"a": 1, "n": 4.0, "o": "0.5"}"#).unwrap();
+        assert_eq!((o.a, o.n, o.o), (1.0, 4, Some(0.5)));
+        assert!(serde_json::from_str::<Opts>(r#"{"a": 1, "n": "2.5"}"#).is_err());
+        assert!(serde_json::from_str::<Opts>(r#"{"a": "x", "n": 1}"#).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+// ###############################################################
+// obd.rs
+// can-blf-parser (WASM)
+// Built-in OBD-II (SAE J1979 / ISO 15031-5) mode 01 decoding for logs taken
+// on the diagnostic port without a DBC (options.obd2): responses on
+// 0x7E8..0x7EF (or 29-bit 0x18DAF1xx) become signals "CAN{n}.{Pid}"
+// ###############################################################
+
+use crate::CAN_EFF_FLAG;
+
+const MODE_01_RESPONSE: u8 = 0x41;
+
+// (pid, name, data bytes, unit, value from A, B)
+type Pid = (u8, &'static str, usize, &'static str, fn(f64, f64) -> f64);
+
+const PIDS: &[Pid] = &[
+    (0x04, "CalculatedLoad", 1, "%", |a, _| a * 100.0 / 255.0),
+    (0x05, "CoolantTemp", 1, "degC", |a, _| a - 40.0),
+    (0x06, "ShortTermFuelTrimBank1", 1, "%", |a, _| (a - 128.0) * 100.0 / 128.0),
+    (0x07, "LongTermFuelTrimBank1", 1, "%", |a, _| (a - 128.0) * 100.0 / 128.0),
+    (0x08, "ShortTermFuelTrimBank2", 1, "%", |a, _| (a - 128.0) * 100.0 / 128.0),
+    (0x09, "LongTermFuelTrimBank2", 1, "%", |a, _| (a - 128.0) * 100.0 / 128.0),
+    (0x0A, "FuelPressure", 1, "kPa", |a, _| a * 3.0),
+    (0x0B, "IntakeManifoldPressure", 1, "kPa", |a, _| a),
+    (0x0C, "EngineRPM", 2, "rpm", |a, b| (256.0 * a + b) / 4.0),
+    (0x0D, "VehicleSpeed", 1, "km/h", |a, _| a),
+    (0x0E, "TimingAdvance", 1, "deg", |a, _| a / 2.0 - 64.0),
+    (0x0F, "IntakeAirTemp", 1, "degC", |a, _| a - 40.0),
+    (0x10, "MafAirFlowRate", 2, "g/s", |a, b| (256.0 * a + b) / 100.0),
+    (0x11, "ThrottlePosition", 1, "%", |a, _| a * 100.0 / 255.0),
+    (0x1F, "RunTimeSinceStart", 2, "s", |a, b| 256.0 * a + b),
+    (0x21, "DistanceWithMil", 2, "km", |a, b| 256.0 * a + b),
+    (0x2F, "FuelTankLevel", 1, "%", |a, _| a * 100.0 / 255.0),
+    (0x31, "DistanceSinceCodesCleared", 2, "km", |a, b| 256.0 * a + b),
+    (0x33, "BarometricPressure", 1, "kPa", |a, _| a),
+    (0x42, "ControlModuleVoltage", 2, "V", |a, b| (256.0 * a + b) / 1000.0),
+    (0x46, "AmbientAirTemp", 1, "degC", |a, _| a - 40.0),
+    (0x5C, "EngineOilTemp", 1, "degC", |a, _| a - 40.0),
+    (0x5E, "EngineFuelRate", 2, "L/h", |a, b| (256.0 * a + b) / 20.0),
+];
+
+// Raw id (IDE bit for extended) of an OBD-II request / response
+pub(crate) fn is_request(id: u32) -> bool {
+    // 29-bit: functional 0x18DB33F1, physical 0x18DA{ecu}F1
+    id == 0x7DF
+        || (0x7E0..=0x7E7).contains(&id)
+        || id == (0x18DB_33F1 | CAN_EFF_FLAG)
+        || (id & !0xFF00) == (0x18DA_00F1 | CAN_EFF_FLAG)
+}
+
+pub(crate) fn is_response(id: u32) -> bool {
+    // 29-bit: 0x18DAF1{ecu}
+    (0x7E8..=0x7EF).contains(&id) || (id & !0xFF) == (0x18DA_F100 | CAN_EFF_FLAG)
+}
+
+pub(crate) struct ObdValue {
+    pub name: &'static str,
+    pub value: f64,
+    pub unit: &'static str,
+}
+
+// Mode 01 values of a single-frame response (several PIDs may share one frame);
+// unknown PIDs end the frame since their length is unknown
+pub(crate) fn decode(data: &[u8]) -> Vec<ObdValue> {
+    let mut out = Vec::new();
+    let Some(&len) = data.first() else { return out };
+    let len = len as usize;
+    if !(2..=7).contains(&len) || data.len() <= len || data[1] != MODE_01_RESPONSE {
+        return out;
+    }
+    let body = &data[2..=len];
+    let mut i = 0;
+    while i < body.len() {
+        let Some(&(_, name, size, unit, f)) = PIDS.iter().find(|p| p.0 == body[i]) else { break };
+        let Some(bytes) = body.get(i + 1..i + 1 + size) else { break };
+        let b = bytes.get(1).copied().unwrap_or(0) as f64;
+        out.push(ObdValue { name, value: f(bytes[0] as f64, b), unit });
+        i += 1 + size;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mode_01_response() {
+        let v = decode(&[0x04, 0x41, 0x0C, 0x1A, 0xF8, 0x55, 0x55, 0x55]);
+        assert_eq!(v.len(), 1);
+        assert_eq!((v[0].name, v[0].value, v[0].unit), ("EngineRPM", 1726.0, "rpm"));
+        // two PIDs in one frame: coolant 90 degC, speed 50 km/h
+        let v = decode(&[0x05, 0x41, 0x05, 0x82, 0x0D, 0x32, 0x00, 0x00]);
+        assert_eq!(v.iter().map(|x| x.value).collect::<Vec<_>>(), [90.0, 50.0]);
+        // not a mode 01 response
+        assert!(decode(&[0x03, 0x7F, 0x01, 0x12, 0, 0, 0, 0]).is_empty());
+    }
+
+    #[test]
+    fn ids() {
+        assert!(is_request(0x7DF) && is_response(0x7E8) && !is_response(0x7DF));
+        assert!(is_response(0x18DA_F110 | CAN_EFF_FLAG));
+        assert!(is_request(0x18DB_33F1 | CAN_EFF_FLAG));
+    }
+}
@@ -0,0 +1,350 @@
+// ###############################################################
+// overrides.rs
+// can-blf-parser (WASM)
+// Runtime signal overrides (options.signal_overrides): byte order, value type,
+// factor and offset replaced on top of a delivered DBC, so one wrong signal can
+// be hotfixed without editing the database. Applied to the SG_ lines before the
+// DBC is parsed, so every path (decode, layout, unmapped bits, CSV) agrees.
+// Patches (options.patch, export_patch()) save overrides and added messages as
+// one JSON document to re-apply to later sessions.
+// ###############################################################
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::numeric;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OverrideByteOrder {
+    #[default]
+    #[serde(alias = "intel")]
+    LittleEndian,
+    #[serde(alias = "motorola")]
+    BigEndian,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OverrideValueType {
+    #[default]
+    Unsigned,
+    Signed,
+}
+
+// Fields left out keep the DBC's definition. A changed byte order keeps the start bit
+// as written (for Motorola the DBC start bit is the MSB).
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct SignalOverride {
+    pub byte_order: Option<OverrideByteOrder>,
+    pub value_type: Option<OverrideValueType>,
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub factor: Option<f64>,
+    #[serde(deserialize_with = "numeric::opt_f64")]
+    pub offset: Option<f64>,
+}
+
+pub const PATCH_FORMAT: &str = "can-blf-parser/dbc-patch";
+pub(crate) const PATCH_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct DbcPatch {
+    pub format: String, // PATCH_FORMAT; checked when present
+    pub version: u32,
+    pub signal_overrides: BTreeMap<String, SignalOverride>, // as options.signal_overrides
+    pub messages: Vec<PatchMessage>, // added to the first DBC mapped to their channel
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct PatchMessage {
+    pub channel: u8, // as in channel_map
+    pub id: u32, // bit 31 set for an extended id, as DBC BO_ ids
+    pub name: String,
+    pub size: u8, // bytes
+    pub transmitter: Option<String>,
+    pub signals: Vec<PatchSignal>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PatchSignal {
+    pub name: String,
+    pub start_bit: u32, // DBC convention: MSB for big endian
+    pub size: u32,
+    pub byte_order: OverrideByteOrder,
+    pub value_type: OverrideValueType,
+    #[serde(deserialize_with = "numeric::f64")]
+    pub factor: f64,
+    #[serde(deserialize_with = "numeric::f64")]
+    pub offset: f64,
+    #[serde(deserialize_with = "numeric::f64")]
+    pub min: f64,
+    #[serde(deserialize_with = "numeric::f64")]
+    pub max: f64,
+    pub unit: String,
+}
+
+impl Default for PatchSignal {
+    fn default() -> Self {
+        PatchSignal {
+            name: String::new(),
+            start_bit: 0,
+            size: 1,
+            byte_order: OverrideByteOrder::LittleEndian,
+            value_type: OverrideValueType::Unsigned,
+            factor: 1.0,
+            offset: 0.0,
+            min: 0.0,
+            max: 0.0,
+            unit: String::new(),
+        }
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl DbcPatch {
+    // The patch with options.signal_overrides on top, stamped with the current format
+    pub(crate) fn merged(&self, overrides: &HashMap<String, SignalOverride>) -> DbcPatch {
+        let mut signal_overrides = self.signal_overrides.clone();
+        signal_overrides.extend(overrides.iter().map(|(k, o)| (k.clone(), o.clone())));
+        DbcPatch {
+            format: PATCH_FORMAT.to_string(),
+            version: PATCH_VERSION,
+            signal_overrides,
+            messages: self.messages.clone(),
+        }
+    }
+
+    pub(crate) fn check(&self) -> Result<(), String> {
+        if !self.format.is_empty() && self.format != PATCH_FORMAT {
+            return Err(format!("not a DBC patch (format \"{}\")", self.format));
+        }
+        if self.version > PATCH_VERSION {
+            return Err(format!("patch version {} is newer than this build supports ({})", self.version, PATCH_VERSION));
+        }
+        for m in &self.messages {
+            if !is_identifier(&m.name) {
+                return Err(format!("message name \"{}\" is not a DBC identifier", m.name));
+            }
+            if m.size == 0 || m.size > 64 {
+                return Err(format!("message {}: size {} outside 1..64 bytes", m.name, m.size));
+            }
+            for sig in &m.signals {
+                if !is_identifier(&sig.name) {
+                    return Err(format!("message {}: signal name \"{}\" is not a DBC identifier", m.name, sig.name));
+                }
+                if sig.size == 0 || sig.size > 64 || sig.start_bit >= m.size as u32 * 8 {
+                    return Err(format!("message {}: signal {} does not fit the message", m.name, sig.name));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn messages_for(&self, channel: u8) -> impl Iterator<Item = &PatchMessage> {
+        self.messages.iter().filter(move |m| m.channel == channel)
+    }
+}
+
+impl PatchMessage {
+    fn key(&self) -> String {
+        format!("CAN{}.{}", self.channel, self.name)
+    }
+
+    fn to_dbc(&self) -> String {
+        let mut text = format!(
+            "BO_ {} {}: {} {}\n",
+            self.id,
+            self.name,
+            self.size,
+            self.transmitter.as_deref().unwrap_or("Vector__XXX")
+        );
+        for s in &self.signals {
+            text.push_str(&format!(
+                " SG_ {} : {}|{}@{}{} ({},{}) [{}|{}] \"{}\" Vector__XXX\n",
+                s.name,
+                s.start_bit,
+                s.size,
+                if s.byte_order == OverrideByteOrder::LittleEndian { '1' } else { '0' },
+                if s.value_type == OverrideValueType::Signed { '-' } else { '+' },
+                s.factor,
+                s.offset,
+                s.min,
+                s.max,
+                s.unit.replace('"', "'"),
+            ));
+        }
+        text.push('\n');
+        text
+    }
+}
+
+// Sections that follow the messages in a DBC; added messages go before the first of them
+const AFTER_MESSAGES: [&str; 9] = ["BO_TX_BU_", "EV_", "CM_", "BA_DEF_", "BA_", "VAL_ ", "SIG_", "SG_MUL_VAL_", "SIG_GROUP_"];
+
+// DBC text for `channel` with the patch applied: overrides keyed
+// "CAN{channel}.{Message}.{Signal}", "CAN{channel}.{Signal}" or the bare signal name (every channel), then the channel's added messages unless
+// `used` already has them (an earlier DBC on the channel) or the id is taken. Override
+// keys that matched and messages added go into `used` ("CAN{channel}.{Message}").
+pub(crate) fn apply(text: &str, channel: u8, patch: &DbcPatch, used: &mut HashSet<String>) -> Result<String, String> {
+    let overrides = &patch.signal_overrides;
+    if overrides.is_empty() && patch.messages_for(channel).next().is_none() {
+        return Ok(text.to_string());
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut message: Option<&str> = None; // BO_ the following SG_ lines belong to
+    for line in text.split_inclusive('\n') {
+        let body = line.trim_start();
+        if let Some(rest) = body.strip_prefix("BO_ ") {
+            message = rest.split_whitespace().nth(1).map(|n| n.trim_end_matches(':'));
+        }
+        let name = body
+            .strip_prefix("SG_ ")
+            .and_then(|rest| rest.trim_start().split(|c: char| c.is_whitespace() || c == ':').next());
+        // the most specific key wins: "CAN1.Msg.Sig", "CAN1.Sig", then the bare name
+        let hit = name.and_then(|n| {
+            message
+                .and_then(|m| overrides.get_key_value(&format!("CAN{}.{}.{}", channel, m, n)))
+                .or_else(|| overrides.get_key_value(&format!("CAN{}.{}", channel, n)))
+                .or_else(|| overrides.get_key_value(n))
+        });
+        match hit {
+            Some((k, o)) => {
+                out.push_str(&patch_signal(line, o).map_err(|e| format!("{}: {}", k, e))?);
+                used.insert(k.clone());
+            }
+            None => out.push_str(line),
+        }
+    }
+
+    let defined: HashSet<u32> = out
+        .lines()
+        .filter_map(|l| l.trim_start().strip_prefix("BO_ ")?.split_whitespace().next()?.parse().ok())
+        .collect();
+    let mut added = String::new();
+    for m in patch.messages_for(channel) {
+        if !used.contains(&m.key()) && !defined.contains(&m.id) {
+            added.push_str(&m.to_dbc());
+            used.insert(m.key());
+        }
+    }
+    if added.is_empty() {
+        return Ok(out);
+    }
+    // before the first message, else before the sections that follow messages
+    let starts = |prefixes: &[&str]| {
+        let mut at = 0;
+        for line in out.split_inclusive('\n') {
+            if prefixes.iter().any(|p| line.trim_start().starts_with(p)) {
+                return Some(at);
+            }
+            at += line.len();
+        }
+        None
+    };
+    let at = starts(&["BO_ "]).or_else(|| starts(&AFTER_MESSAGES)).unwrap_or(out.len());
+    out.insert_str(at, &added);
+    Ok(out)
+}
+
+// " SG_ Name m1 : 7|16@0+ (0.1,-40) [..] ..." with order/sign and (factor,offset) replaced
+fn patch_signal(line: &str, o: &SignalOverride) -> Result<String, String> {
+    let colon = line.find(':').ok_or("SG_ line without ':'")?;
+    let at = colon + line[colon..].find('@').ok_or("SG_ line without '@'")?;
+    let open = at + line[at..].find('(').ok_or("SG_ line without '('")?;
+    let close = open + line[open..].find(')').ok_or("SG_ line without ')'")?;
+    let mut order_sign: Vec<char> = line[at + 1..].chars().take(2).collect();
+    if order_sign.len() != 2 {
+        return Err("truncated SG_ line".to_string());
+    }
+    match o.byte_order {
+        Some(OverrideByteOrder::LittleEndian) => order_sign[0] = '1',
+        Some(OverrideByteOrder::BigEndian) => order_sign[0] = '0',
+        None => {}
+    }
+    match o.value_type {
+        Some(OverrideValueType::Signed) => order_sign[1] = '-',
+        Some(OverrideValueType::Unsigned) => order_sign[1] = '+',
+        None => {}
+    }
+    let (factor, offset) = line[open + 1..close].split_once(',').ok_or("factor/offset without ','")?;
+    let factor = o.factor.map_or(factor.trim().to_string(), |f| f.to_string());
+    let offset = o.offset.map_or(offset.trim().to_string(), |f| f.to_string());
+    Ok(format!(
+        "{}@{}{}{}({},{}){}",
+        &line[..at],
+        order_sign[0],
+        order_sign[1],
+        &line[at + 3..open],
+        factor,
+        offset,
+        &line[close + 1..]
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patches_matching_sg_lines() {
+        let text = "BO_ 256 M: 8 ECU\n SG_ Temp : 7|16@0+ (0.1,-40) [0|0] \"C\" ECU\n SG_ Other : 16|8@1+ (1,0) [0|0] \"\" ECU\n";
+        let overrides: HashMap<String, SignalOverride> = serde_json::from_value(serde_json::json!({
+            "CAN1.Temp": { "byte_order": "intel", "value_type": "signed", "factor": "0,5" },
+            "Missing": { "offset": 1 }
+        }))
+        .unwrap();
+        let patch = DbcPatch::default().merged(&overrides);
+        let mut used = HashSet::new();
+        let patched = apply(text, 1, &patch, &mut used).unwrap();
+        assert!(patched.contains(" SG_ Temp : 7|16@1- (0.5,-40) [0|0] \"C\" ECU\n"));
+        assert!(patched.contains(" SG_ Other : 16|8@1+ (1,0) [0|0] \"\" ECU\n"));
+        assert_eq!(used, HashSet::from(["CAN1.Temp".to_string()]));
+        // another channel: the tagged key does not apply
+        assert_eq!(apply(text, 2, &patch, &mut HashSet::new()).unwrap(), text);
+
+        // a message-qualified key beats the channel-tagged one
+        let overrides: HashMap<String, SignalOverride> = serde_json::from_value(serde_json::json!({
+            "CAN1.Other": { "factor": 2 },
+            "CAN1.M.Other": { "factor": 3 }
+        }))
+        .unwrap();
+        let mut used = HashSet::new();
+        let patched = apply(text, 1, &DbcPatch::default().merged(&overrides), &mut used).unwrap();
+        assert!(patched.contains(" SG_ Other : 16|8@1+ (3,0) [0|0] \"\" ECU\n"));
+        assert_eq!(used, HashSet::from(["CAN1.M.Other".to_string()]));
+    }
+
+    #[test]
+    fn adds_patch_messages() {
+        let text = "VERSION \"\"\n\nNS_ :\n\nBS_:\n\nBU_: ECU\n\nBO_ 256 M: 8 ECU\n SG_ S : 0|8@1+ (1,0) [0|0] \"\" ECU\n\nCM_ SG_ 256 S \"c\";\n";
+        let patch: DbcPatch = serde_json::from_value(serde_json::json!({
+            "format": PATCH_FORMAT,
+            "messages": [
+                { "channel": 1, "id": 512, "name": "Added", "size": 8,
+                  "signals": [{ "name": "Speed", "start_bit": 0, "size": 16, "factor": 0.01, "unit": "km/h" }] },
+                { "channel": 1, "id": 256, "name": "Taken", "size": 8 },
+                { "channel": 2, "id": 768, "name": "Other", "size": 8 }
+            ]
+        }))
+        .unwrap();
+        patch.check().unwrap();
+        let mut used = HashSet::new();
+        let patched = apply(text, 1, &patch, &mut used).unwrap();
+        let dbc = can_dbc::DBC::try_from(patched.as_str()).unwrap();
+        let names: Vec<&str> = dbc.messages().iter().map(|m| m.message_name().as_str()).collect();
+        assert_eq!(names, ["Added", "M"]);
+        assert_eq!(*dbc.messages()[0].signals()[0].factor(), 0.01);
+        assert_eq!(used, HashSet::from(["CAN1.Added".to_string()]));
+        // a second DBC on the channel does not get it again
+        assert_eq!(apply(text, 1, &patch, &mut used).unwrap(), text);
+    }
+}
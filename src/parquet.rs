@@ -0,0 +1,269 @@
+// ###############################################################
+// parquet.rs
+// can-blf-parser (WASM)
+// Minimal Apache Parquet writer for export_parquet(): one row group, one
+// uncompressed PLAIN data page (v1) per column, metadata in Thrift compact
+// protocol. Enough for pandas / pyarrow / polars / DuckDB to read typed columns.
+// ###############################################################
+
+use crate::table::{bits, Column, Values};
+
+// -------------------------------
+// Layout: "PAR1" | per column: PageHeader + page | FileMetaData | u32 length | "PAR1"
+// Pages of optional columns start with the definition levels (u32 length, then one
+// bit-packed RLE-hybrid run of 1-bit levels); null values are not stored.
+// -------------------------------
+const MAGIC: &[u8; 4] = b"PAR1";
+
+// parquet.thrift enums
+const TYPE_BOOLEAN: i32 = 0;
+const TYPE_INT32: i32 = 1;
+const TYPE_DOUBLE: i32 = 5;
+const TYPE_BYTE_ARRAY: i32 = 6;
+const REQUIRED: i32 = 0;
+const OPTIONAL: i32 = 1;
+const CONVERTED_UTF8: i32 = 0;
+const ENCODING_PLAIN: i32 = 0;
+const ENCODING_RLE: i32 = 3;
+const CODEC_UNCOMPRESSED: i32 = 0;
+const PAGE_DATA: i32 = 0;
+
+// Thrift compact protocol field types
+const CT_I32: u8 = 5;
+const CT_I64: u8 = 6;
+const CT_BINARY: u8 = 8;
+const CT_LIST: u8 = 9;
+const CT_STRUCT: u8 = 12;
+
+fn physical_type(values: &Values) -> i32 {
+    match values {
+        Values::Double(_) | Values::OptDouble(_) => TYPE_DOUBLE,
+        Values::Int32(_) => TYPE_INT32,
+        Values::Bool(_) => TYPE_BOOLEAN,
+        Values::Utf8(_) | Values::Bytes(_) => TYPE_BYTE_ARRAY,
+    }
+}
+
+// definition levels (optional columns) then PLAIN values
+fn page(values: &Values) -> Vec<u8> {
+    let mut out = Vec::new();
+    match values {
+        Values::Double(v) => v.iter().for_each(|x| out.extend_from_slice(&x.to_le_bytes())),
+        Values::OptDouble(v) => {
+            let levels = bit_packed(&v.iter().map(Option::is_some).collect::<Vec<_>>());
+            out.extend_from_slice(&(levels.len() as u32).to_le_bytes());
+            out.extend(levels);
+            v.iter().flatten().for_each(|x| out.extend_from_slice(&x.to_le_bytes()));
+        }
+        Values::Int32(v) => v.iter().for_each(|x| out.extend_from_slice(&x.to_le_bytes())),
+        Values::Bool(v) => out.extend(bits(v)),
+        Values::Utf8(v) => v.iter().for_each(|s| byte_array(&mut out, s.as_bytes())),
+        Values::Bytes(v) => v.iter().for_each(|b| byte_array(&mut out, b)),
+    }
+    out
+}
+
+fn byte_array(out: &mut Vec<u8>, b: &[u8]) {
+    out.extend_from_slice(&(b.len() as u32).to_le_bytes());
+    out.extend_from_slice(b);
+}
+
+// RLE/bit-packed hybrid with bit width 1: a single bit-packed run
+fn bit_packed(v: &[bool]) -> Vec<u8> {
+    let mut out = Vec::new();
+    varint(&mut out, ((v.len().div_ceil(8) as u64) << 1) | 1);
+    out.extend(bits(v));
+    out
+}
+
+fn varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+// Thrift compact protocol writer (only what the Parquet structs below need)
+#[derive(Default)]
+struct Compact {
+    out: Vec<u8>,
+    last: Vec<i16>, // last field id of each open struct
+}
+
+impl Compact {
+    fn zigzag(&mut self, v: i64) {
+        varint(&mut self.out, ((v << 1) ^ (v >> 63)) as u64);
+    }
+
+    fn field(&mut self, id: i16, ty: u8) {
+        let last = self.last.last_mut().expect("field outside a struct");
+        let delta = id - std::mem::replace(last, id);
+        if (1..=15).contains(&delta) {
+            self.out.push((delta as u8) << 4 | ty);
+        } else {
+            self.out.push(ty);
+            self.zigzag(id as i64);
+        }
+    }
+
+    fn i32(&mut self, id: i16, v: i32) {
+        self.field(id, CT_I32);
+        self.zigzag(v as i64);
+    }
+
+    fn i64(&mut self, id: i16, v: i64) {
+        self.field(id, CT_I64);
+        self.zigzag(v);
+    }
+
+    fn binary(&mut self, id: i16, b: &[u8]) {
+        self.field(id, CT_BINARY);
+        self.raw_binary(b);
+    }
+
+    fn raw_binary(&mut self, b: &[u8]) {
+        varint(&mut self.out, b.len() as u64);
+        self.out.extend_from_slice(b);
+    }
+
+    fn list(&mut self, id: i16, elem: u8, len: usize) {
+        self.field(id, CT_LIST);
+        if len < 15 {
+            self.out.push((len as u8) << 4 | elem);
+        } else {
+            self.out.push(0xF0 | elem);
+            varint(&mut self.out, len as u64);
+        }
+    }
+
+    // struct field (id Some) or list element (None)
+    fn begin(&mut self, id: Option<i16>) {
+        if let Some(id) = id {
+            self.field(id, CT_STRUCT);
+        }
+        self.last.push(0);
+    }
+
+    fn end(&mut self) {
+        self.out.push(0);
+        self.last.pop();
+    }
+}
+
+struct ChunkMeta {
+    offset: i64,
+    size: i64, // page header + page
+    rows: usize,
+}
+
+pub(crate) fn encode(columns: &[Column]) -> Vec<u8> {
+    let rows = columns.first().map_or(0, |c| c.values.len());
+    let mut out = MAGIC.to_vec();
+    let mut chunks = Vec::with_capacity(columns.len());
+    for c in columns {
+        let page = page(&c.values);
+        let mut h = Compact::default();
+        h.begin(None);
+        h.i32(1, PAGE_DATA);
+        h.i32(2, page.len() as i32);
+        h.i32(3, page.len() as i32);
+        h.begin(Some(5)); // DataPageHeader
+        h.i32(1, c.values.len() as i32);
+        h.i32(2, ENCODING_PLAIN);
+        h.i32(3, ENCODING_RLE);
+        h.i32(4, ENCODING_RLE);
+        h.end();
+        h.end();
+        chunks.push(ChunkMeta { offset: out.len() as i64, size: (h.out.len() + page.len()) as i64, rows: c.values.len() });
+        out.extend(h.out);
+        out.extend(page);
+    }
+
+    // FileMetaData
+    let mut m = Compact::default();
+    m.begin(None);
+    m.i32(1, 1); // version
+    m.list(2, CT_STRUCT, columns.len() + 1);
+    m.begin(None);
+    m.binary(4, b"schema");
+    m.i32(5, columns.len() as i32);
+    m.end();
+    for c in columns {
+        m.begin(None);
+        m.i32(1, physical_type(&c.values));
+        m.i32(3, if matches!(c.values, Values::OptDouble(_)) { OPTIONAL } else { REQUIRED });
+        m.binary(4, c.name.as_bytes());
+        if matches!(c.values, Values::Utf8(_)) {
+            m.i32(6, CONVERTED_UTF8);
+        }
+        m.end();
+    }
+    m.i64(3, rows as i64);
+    m.list(4, CT_STRUCT, 1);
+    m.begin(None); // RowGroup
+    m.list(1, CT_STRUCT, columns.len());
+    for (c, chunk) in columns.iter().zip(&chunks) {
+        m.begin(None); // ColumnChunk
+        m.i64(2, chunk.offset);
+        m.begin(Some(3)); // ColumnMetaData
+        m.i32(1, physical_type(&c.values));
+        m.list(2, CT_I32, 2);
+        m.zigzag(ENCODING_PLAIN as i64);
+        m.zigzag(ENCODING_RLE as i64);
+        m.list(3, CT_BINARY, 1);
+        m.raw_binary(c.name.as_bytes());
+        m.i32(4, CODEC_UNCOMPRESSED);
+        m.i64(5, chunk.rows as i64);
+        m.i64(6, chunk.size);
+        m.i64(7, chunk.size);
+        m.i64(9, chunk.offset);
+        m.end();
+        m.end();
+    }
+    m.i64(2, chunks.iter().map(|c| c.size).sum());
+    m.i64(3, rows as i64);
+    m.end();
+    m.binary(6, concat!("can-blf-parser version ", env!("CARGO_PKG_VERSION")).as_bytes());
+    m.end();
+
+    out.extend_from_slice(&m.out);
+    out.extend_from_slice(&(m.out.len() as u32).to_le_bytes());
+    out.extend_from_slice(MAGIC);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_and_page_encoding() {
+        // field deltas, long jumps, negative zigzag, list headers
+        let mut c = Compact::default();
+        c.begin(None);
+        c.i32(1, -1);
+        c.i64(20, 300);
+        c.list(21, CT_I32, 15);
+        c.end();
+        assert_eq!(c.out, [0x15, 0x01, 0x06, 0x28, 0xD8, 0x04, 0x19, 0xF5, 0x0F, 0x00]);
+
+        let opt = Values::OptDouble(vec![Some(1.0), None, Some(2.0)]);
+        let levels_and_values = page(&opt);
+        assert_eq!(&levels_and_values[..6], &[2, 0, 0, 0, 0x03, 0b101]);
+        assert_eq!(levels_and_values.len(), 6 + 16);
+        assert_eq!(page(&Values::Bool(vec![true, false, true, true])), [0b1101]);
+        assert_eq!(page(&Values::Utf8(vec!["ab".into()])), [2, 0, 0, 0, b'a', b'b']);
+
+        let file = encode(&[
+            Column::new("time_s", Values::Double(vec![0.0, 0.5, 1.0])),
+            Column::new("CAN1.Speed", opt),
+        ]);
+        assert_eq!(&file[..4], MAGIC);
+        assert_eq!(&file[file.len() - 4..], MAGIC);
+        let meta_len = u32::from_le_bytes(file[file.len() - 8..file.len() - 4].try_into().unwrap()) as usize;
+        let meta = &file[file.len() - 8 - meta_len..file.len() - 8];
+        assert_eq!(meta[..2], [0x15, 0x02]); // version 1
+        assert!(meta.windows(10).any(|w| w == b"CAN1.Speed"));
+    }
+}
@@ -0,0 +1,51 @@
+// ###############################################################
+// payload.rs
+// can-blf-parser (WASM)
+// Frame payloads of FrameRows: immutable bytes that clone without copying
+// (stored frames pack theirs in the FrameStore buffer, see store.rs)
+// ###############################################################
+
+use std::ops::Deref;
+use std::rc::Rc;
+
+use serde::{Serialize, Serializer};
+
+// Immutable payload bytes; cloning only bumps a reference count.
+// Serializes exactly like the Vec<u8> it replaces.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Payload(Rc<[u8]>);
+
+impl Payload {
+    pub fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Deref for Payload {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<'a> IntoIterator for &'a Payload {
+    type Item = &'a u8;
+    type IntoIter = std::slice::Iter<'a, u8>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl From<&[u8]> for Payload {
+    fn from(bytes: &[u8]) -> Self {
+        Payload(bytes.into())
+    }
+}
+
+impl Serialize for Payload {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter())
+    }
+}
@@ -0,0 +1,64 @@
+// ###############################################################
+// provenance.rs
+// can-blf-parser (WASM)
+// Effective configuration of a session (inputs, options, merges), returned by
+// session_config() so a host app can reproduce an analysis later
+// ###############################################################
+
+use serde::Serialize;
+
+use crate::blf::LogStart;
+use crate::merge::ClockFit;
+use crate::{MergeOptions, SessionOptions};
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SessionConfig {
+    pub crate_version: String,
+    pub logs: Vec<LogSource>, // constructor BLF first, then merged logs in order
+    pub dbcs: Vec<DbcSource>, // in dbc_texts order
+    pub lin_databases: Vec<DbcSource>, // in options.lin_databases order (index refers to it)
+    pub dbc_set_hash: String, // as in session_index()
+    pub warm_start: bool, // message lookup taken from options.warm_start
+    pub options: SessionOptions, // defaults resolved; warm_start omitted
+    pub pyramid_cache: Option<String>, // hash of the blob for from_pyramid_cache() sessions
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct LogSource {
+    pub hash: String, // hex FNV-1a of the file bytes
+    pub bytes: usize,
+    pub frames_read: usize,
+    pub start: Option<LogStart>, // measurement start from the BLF header
+    pub merge: Option<MergeRecord>, // None for the constructor log
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MergeRecord {
+    pub options: MergeOptions,
+    pub clock: Option<ClockFit>, // time offset/drift correction fitted for this log
+    pub frames_added: usize,
+    pub duplicates_removed: usize,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DbcSource {
+    pub index: usize, // position in dbc_texts (what dbc_priority refers to)
+    pub channel: u8,
+    pub hash: String, // hex FNV-1a of the DBC text
+    pub bytes: usize,
+}
+
+impl SessionConfig {
+    pub(crate) fn new(dbcs: Vec<DbcSource>, dbc_set_hash: u64, warm_start: bool, options: SessionOptions) -> Self {
+        SessionConfig {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            logs: Vec::new(),
+            dbcs,
+            lin_databases: Vec::new(),
+            dbc_set_hash: format!("{:016x}", dbc_set_hash),
+            warm_start,
+            options,
+            pyramid_cache: None,
+        }
+    }
+}
@@ -0,0 +1,231 @@
+// ###############################################################
+// pyramid.rs
+// can-blf-parser (WASM)
+// Multi-level min/max pyramid (waveform tiles) for fast pan/zoom
+// ###############################################################
+
+// Level 0 holds the raw samples; each further level merges FANOUT buckets
+// of the level below, so total memory is ~1.33x the raw series.
+pub(crate) const FANOUT: usize = 4;
+
+// Coarsest level a series of u32::MAX samples can reach: deeper ones in a cache are corrupt
+const MAX_LEVEL: usize = 16;
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Level {
+    pub t_start: Vec<f64>,
+    pub t_end: Vec<f64>,
+    pub min: Vec<f64>,
+    pub max: Vec<f64>,
+}
+
+impl Level {
+    pub(crate) fn len(&self) -> usize {
+        self.t_start.len()
+    }
+
+    // bucket index range overlapping [t0, t1]
+    fn range(&self, t0: f64, t1: f64) -> std::ops::Range<usize> {
+        let lo = self.t_end.partition_point(|t| *t < t0);
+        let hi = self.t_start.partition_point(|t| *t <= t1);
+        lo..hi.max(lo)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Pyramid {
+    pub base_level: usize, // >0 when fine levels were dropped for a compact cache
+    pub levels: Vec<Level>,
+}
+
+pub(crate) struct PyramidSlice {
+    pub level: usize,
+    pub bucket_size: usize, // raw samples per bucket at that level
+    pub time: Vec<f64>,
+    pub min: Vec<f64>,
+    pub max: Vec<f64>,
+}
+
+impl Pyramid {
+    pub(crate) fn build(times: &[f64], values: &[f64]) -> Pyramid {
+        let mut levels = vec![Level {
+            t_start: times.to_vec(),
+            t_end: times.to_vec(),
+            min: values.to_vec(),
+            max: values.to_vec(),
+        }];
+        while levels.last().is_some_and(|l| l.len() > 1) {
+            let below = levels.last().unwrap();
+            let mut next = Level::default();
+            let mut i = 0;
+            while i < below.len() {
+                let j = (i + FANOUT).min(below.len());
+                next.t_start.push(below.t_start[i]);
+                next.t_end.push(below.t_end[j - 1]);
+                next.min.push(below.min[i..j].iter().copied().fold(f64::INFINITY, f64::min));
+                next.max.push(below.max[i..j].iter().copied().fold(f64::NEG_INFINITY, f64::max));
+                i = j;
+            }
+            levels.push(next);
+        }
+        Pyramid { base_level: 0, levels }
+    }
+
+    // Finest level whose bucket count inside [t0, t1] stays within max_points
+    pub(crate) fn query(&self, t0: f64, t1: f64, max_points: usize) -> PyramidSlice {
+        let max_points = max_points.max(1);
+        let mut chosen = self.levels.len().saturating_sub(1);
+        for (idx, level) in self.levels.iter().enumerate() {
+            if level.range(t0, t1).len() <= max_points {
+                chosen = idx;
+                break;
+            }
+        }
+        match self.levels.get(chosen) {
+            Some(level) => {
+                let r = level.range(t0, t1);
+                let level_no = self.base_level + chosen;
+                PyramidSlice {
+                    level: level_no,
+                    bucket_size: FANOUT.checked_pow(level_no as u32).unwrap_or(usize::MAX),
+                    time: level.t_start[r.clone()].to_vec(),
+                    min: level.min[r.clone()].to_vec(),
+                    max: level.max[r].to_vec(),
+                }
+            }
+            None => PyramidSlice { level: 0, bucket_size: 1, time: vec![], min: vec![], max: vec![] },
+        }
+    }
+}
+
+// -------------------------------
+// Binary cache format (little-endian), for IndexedDB round-trips:
+//   "BPYR" | u32 version | u32 signal_count
+//   per signal: u32 name_len | name utf8 | u32 base_level | u32 level_count
+//   per level:  u8 raw | u32 len | t_start[len] | (t_end[len] if !raw) | min[len] | (max[len] if !raw)
+// Raw levels (t_start == t_end, min == max) store only one time and one value array.
+// -------------------------------
+const MAGIC: &[u8; 4] = b"BPYR";
+pub(crate) const VERSION: u32 = 1;
+
+pub(crate) fn encode<'a>(pyramids: impl Iterator<Item = (&'a String, &'a Pyramid)>, skip_levels: usize) -> Vec<u8> {
+    let pyramids: Vec<_> = pyramids.collect();
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.extend_from_slice(&(pyramids.len() as u32).to_le_bytes());
+    for (name, pyr) in pyramids {
+        // never drop the coarsest level
+        let skip = skip_levels.min(pyr.levels.len().saturating_sub(1));
+        let levels = &pyr.levels[skip..];
+        out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(&((pyr.base_level + skip) as u32).to_le_bytes());
+        out.extend_from_slice(&(levels.len() as u32).to_le_bytes());
+        for level in levels {
+            let raw = level.t_start == level.t_end && level.min == level.max;
+            out.push(raw as u8);
+            out.extend_from_slice(&(level.len() as u32).to_le_bytes());
+            let mut arrays: Vec<&[f64]> = vec![&level.t_start];
+            if !raw {
+                arrays.push(&level.t_end);
+            }
+            arrays.push(&level.min);
+            if !raw {
+                arrays.push(&level.max);
+            }
+            for arr in arrays {
+                for v in arr {
+                    out.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+        }
+    }
+    out
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(n).filter(|e| *e <= self.buf.len())
+            .ok_or_else(|| format!("pyramid cache truncated at byte {}", self.pos))?;
+        let slice = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f64s(&mut self, n: usize) -> Result<Vec<f64>, String> {
+        let bytes = self.take(n.checked_mul(8).ok_or("pyramid cache length overflow")?)?;
+        Ok(bytes.chunks_exact(8).map(|c| f64::from_le_bytes(c.try_into().unwrap())).collect())
+    }
+}
+
+pub(crate) fn decode(buf: &[u8]) -> Result<Vec<(String, Pyramid)>, String> {
+    let mut r = Reader { buf, pos: 0 };
+    if r.take(4)? != MAGIC {
+        return Err("not a pyramid cache (bad magic)".to_string());
+    }
+    let version = r.u32()?;
+    if version != VERSION {
+        return Err(format!("unsupported pyramid cache version {}", version));
+    }
+    let count = r.u32()? as usize;
+    let mut out = Vec::with_capacity(count.min(1024));
+    for _ in 0..count {
+        let name_len = r.u32()? as usize;
+        let name = String::from_utf8(r.take(name_len)?.to_vec()).map_err(|e| format!("bad signal name: {}", e))?;
+        let base_level = r.u32()? as usize;
+        let level_count = r.u32()? as usize;
+        if base_level.saturating_add(level_count) > MAX_LEVEL + 1 {
+            return Err(format!("pyramid cache for {} claims {} levels from level {}, past level {}", name, level_count, base_level, MAX_LEVEL));
+        }
+        let mut levels = Vec::with_capacity(level_count.min(64));
+        for _ in 0..level_count {
+            let raw = r.take(1)?[0] != 0;
+            let len = r.u32()? as usize;
+            let t_start = r.f64s(len)?;
+            let t_end = if raw { t_start.clone() } else { r.f64s(len)? };
+            let min = r.f64s(len)?;
+            let max = if raw { min.clone() } else { r.f64s(len)? };
+            levels.push(Level { t_start, t_end, min, max });
+        }
+        out.push((name, Pyramid { base_level, levels }));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_round_trip_and_level_bounds() {
+        let t: Vec<f64> = (0..100).map(|i| i as f64).collect();
+        let name = "CAN1.Speed".to_string();
+        let pyr = Pyramid::build(&t, &t);
+        let bytes = encode([(&name, &pyr)].into_iter(), 1);
+        let back = decode(&bytes).unwrap();
+        assert_eq!(back[0].1.base_level, 1);
+        assert_eq!(back[0].1.levels.len(), pyr.levels.len() - 1);
+        assert_eq!(back[0].1.query(0.0, 99.0, 10).bucket_size, 16);
+
+        // header of one signal claiming base level u32::MAX
+        let mut bad = bytes[..12].to_vec();
+        bad.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        bad.extend_from_slice(name.as_bytes());
+        bad.extend_from_slice(&u32::MAX.to_le_bytes());
+        bad.extend_from_slice(&1u32.to_le_bytes());
+        assert!(decode(&bad).unwrap_err().contains("past level"));
+
+        let deep = Pyramid { base_level: 40, levels: vec![Level::default()] };
+        assert_eq!(deep.query(0.0, 1.0, 10).bucket_size, usize::MAX);
+    }
+}
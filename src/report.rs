@@ -0,0 +1,290 @@
+// ###############################################################
+// report.rs
+// can-blf-parser (WASM)
+// Report templates over decoded series (pure Rust, no JS types).
+// Derating: time above temperature / current thresholds, excursion events,
+// maxima, and derate-flag activity with the conditions at each onset.
+// Charging: sessions from plug state / current with duration, energy and peaks.
+// ###############################################################
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+// One interval with the held value above the threshold
+#[derive(Serialize, Debug, Clone)]
+pub struct Excursion {
+    pub start: f64,
+    pub end: f64,
+    pub duration_s: f64,
+    pub peak: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub at_onset: Option<BTreeMap<String, f64>>, // derate flags: temperatures / currents at start
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ThresholdSummary {
+    pub signal: String,
+    pub unit: String,
+    pub threshold: f64,
+    pub max: f64,
+    pub max_time: f64,
+    pub time_above_s: f64,
+    pub fraction_above: f64, // of the signal's held span
+    pub events: Vec<Excursion>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DerateReport {
+    pub temperatures: Vec<ThresholdSummary>,
+    pub currents: Vec<ThresholdSummary>,
+    pub derate_flags: Vec<ThresholdSummary>, // threshold 0.5: active when set
+    pub derate_events: usize,
+    pub derate_time_s: f64,
+}
+
+// Input series with its unit and threshold
+pub(crate) struct Tracked<'a> {
+    pub signal: &'a str,
+    pub unit: &'a str,
+    pub threshold: f64,
+    pub times: &'a [f64],
+    pub values: &'a [f64],
+}
+
+// Held value at t (last sample at or before t)
+fn held(times: &[f64], values: &[f64], t: f64) -> Option<f64> {
+    let i = times.partition_point(|x| *x <= t);
+    (i > 0).then(|| values[i - 1])
+}
+
+// Excursions shorter than min_event_s are dropped from events but still count
+// toward time_above_s
+pub(crate) fn summarize(s: &Tracked, min_event_s: f64) -> ThresholdSummary {
+    let (t, v) = (s.times, s.values);
+    let (mut max, mut max_time) = (f64::NEG_INFINITY, t[0]);
+    let mut events = Vec::new();
+    let mut open: Option<(f64, f64)> = None; // (start, peak)
+    let mut above = 0.0;
+    for i in 0..t.len() {
+        if v[i] > max {
+            max = v[i];
+            max_time = t[i];
+        }
+        let end = t.get(i + 1).copied().unwrap_or(t[i]);
+        if v[i] > s.threshold {
+            above += end - t[i];
+            let (_, peak) = open.get_or_insert((t[i], v[i]));
+            *peak = peak.max(v[i]);
+        } else if let Some((start, peak)) = open.take() {
+            events.push(Excursion { start, end: t[i], duration_s: t[i] - start, peak, at_onset: None });
+        }
+    }
+    if let Some((start, peak)) = open {
+        let end = t[t.len() - 1];
+        events.push(Excursion { start, end, duration_s: end - start, peak, at_onset: None });
+    }
+    events.retain(|e| e.duration_s >= min_event_s);
+    let span = t[t.len() - 1] - t[0];
+    ThresholdSummary {
+        signal: s.signal.to_string(),
+        unit: s.unit.to_string(),
+        threshold: s.threshold,
+        max,
+        max_time,
+        time_above_s: above,
+        fraction_above: if span > 0.0 { above / span } else { 0.0 },
+        events,
+    }
+}
+
+pub(crate) fn derating(temperatures: &[Tracked], currents: &[Tracked], flags: &[Tracked], min_event_s: f64) -> DerateReport {
+    let conditions: Vec<&Tracked> = temperatures.iter().chain(currents).collect();
+    let mut derate_flags = Vec::new();
+    for f in flags {
+        let mut summary = summarize(f, min_event_s);
+        for e in summary.events.iter_mut() {
+            let at = conditions
+                .iter()
+                .filter_map(|s| held(s.times, s.values, e.start).map(|v| (s.signal.to_string(), v)))
+                .collect();
+            e.at_onset = Some(at);
+        }
+        derate_flags.push(summary);
+    }
+    DerateReport {
+        temperatures: temperatures.iter().map(|s| summarize(s, min_event_s)).collect(),
+        currents: currents.iter().map(|s| summarize(s, min_event_s)).collect(),
+        derate_events: derate_flags.iter().map(|f| f.events.len()).sum(),
+        derate_time_s: derate_flags.iter().map(|f| f.time_above_s).sum(),
+        derate_flags,
+    }
+}
+
+// -------------------------------
+// Charging sessions: intervals where the plug signal is set (held value > 0.5) and
+// the charge current exceeds current_threshold, each signal only if given. Gaps up
+// to max_gap_s are bridged (e.g. a current pause during balancing). Power and
+// energy take volts x amperes.
+// -------------------------------
+#[derive(Serialize, Debug, Clone)]
+pub struct ChargingSession {
+    pub start: f64,
+    pub end: f64,
+    pub duration_s: f64,
+    pub energy_kwh: Option<f64>, // needs current and voltage
+    pub charge_ah: Option<f64>, // needs current
+    pub max_current: Option<f64>,
+    pub max_power_kw: Option<f64>,
+    pub mean_power_kw: Option<f64>,
+    pub soc_start: Option<f64>,
+    pub soc_end: Option<f64>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ChargingReport {
+    pub sessions: Vec<ChargingSession>,
+    pub total_duration_s: f64,
+    pub total_energy_kwh: Option<f64>,
+}
+
+type Samples<'a> = Option<(&'a [f64], &'a [f64])>;
+
+pub(crate) struct ChargingParams {
+    pub current_threshold: f64,
+    pub invert_current: bool, // charge current logged negative
+    pub min_duration_s: f64,
+    pub max_gap_s: f64,
+}
+
+pub(crate) fn charging(plug: Samples, current: Samples, voltage: Samples, soc: Samples, p: &ChargingParams) -> ChargingReport {
+    let sign = if p.invert_current { -1.0 } else { 1.0 };
+    let at = |s: Samples, t: f64| s.and_then(|(times, values)| held(times, values, t));
+    let current_at = |t: f64| at(current, t).map(|i| i * sign);
+    // before its first sample a signal counts as not charging
+    let active = |t: f64| {
+        let plugged = plug.is_none() || at(plug, t).is_some_and(|v| v > 0.5);
+        let flowing = current.is_none() || current_at(t).is_some_and(|i| i > p.current_threshold);
+        plugged && flowing
+    };
+
+    // state changes happen at plug / current samples
+    let mut times: Vec<f64> = [plug, current].iter().flatten().flat_map(|(t, _)| t.iter().copied()).collect();
+    times.sort_by(|a, b| a.total_cmp(b));
+    times.dedup();
+    let mut intervals: Vec<(f64, f64)> = Vec::new();
+    let mut open: Option<f64> = None;
+    for &t in &times {
+        match (open, active(t)) {
+            (None, true) => open = Some(t),
+            (Some(start), false) => {
+                intervals.push((start, t));
+                open = None;
+            }
+            _ => {}
+        }
+    }
+    if let (Some(start), Some(&end)) = (open, times.last()) {
+        intervals.push((start, end));
+    }
+    let mut bridged: Vec<(f64, f64)> = Vec::new();
+    for (start, end) in intervals {
+        match bridged.last_mut() {
+            Some(last) if start - last.1 <= p.max_gap_s => last.1 = end,
+            _ => bridged.push((start, end)),
+        }
+    }
+
+    let sessions: Vec<ChargingSession> = bridged
+        .into_iter()
+        .filter(|(start, end)| end - start >= p.min_duration_s && end > start)
+        .map(|(start, end)| {
+            // held current / voltage between consecutive samples inside the session
+            let mut ts: Vec<f64> = [current, voltage]
+                .iter()
+                .flatten()
+                .flat_map(|(t, _)| t.iter().copied())
+                .filter(|t| (start..end).contains(t))
+                .chain([start])
+                .collect();
+            ts.sort_by(|a, b| a.total_cmp(b));
+            ts.dedup();
+            let (mut charge, mut energy, mut max_current, mut max_power) = (0.0, 0.0, f64::NEG_INFINITY, f64::NEG_INFINITY);
+            for (k, &t) in ts.iter().enumerate() {
+                let dt = ts.get(k + 1).copied().unwrap_or(end) - t;
+                let Some(i) = current_at(t) else { continue };
+                charge += i * dt;
+                max_current = max_current.max(i);
+                if let Some(v) = at(voltage, t) {
+                    energy += v * i * dt;
+                    max_power = max_power.max(v * i);
+                }
+            }
+            let duration = end - start;
+            let with_current = current.is_some();
+            let with_power = with_current && voltage.is_some() && max_power.is_finite();
+            ChargingSession {
+                start,
+                end,
+                duration_s: duration,
+                energy_kwh: with_power.then_some(energy / 3.6e6),
+                charge_ah: with_current.then_some(charge / 3600.0),
+                max_current: max_current.is_finite().then_some(max_current),
+                max_power_kw: with_power.then_some(max_power / 1000.0),
+                mean_power_kw: with_power.then_some(energy / duration / 1000.0),
+                soc_start: at(soc, start),
+                soc_end: at(soc, end),
+            }
+        })
+        .collect();
+
+    ChargingReport {
+        total_duration_s: sessions.iter().map(|s| s.duration_s).sum(),
+        total_energy_kwh: sessions.iter().map(|s| s.energy_kwh).sum(),
+        sessions,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn excursions_and_onset() {
+        let t = [0.0, 1.0, 2.0, 3.0, 4.0, 5.0];
+        let temp = [60.0, 85.0, 95.0, 70.0, 90.0, 90.0];
+        let flag = [0.0, 0.0, 1.0, 1.0, 0.0, 0.0];
+        let tracked = |signal, threshold, values| Tracked { signal, unit: "", threshold, times: &t, values };
+        let r = derating(&[tracked("T", 80.0, &temp[..])], &[], &[tracked("Derate", 0.5, &flag[..])], 0.0);
+        let s = &r.temperatures[0];
+        assert_eq!((s.max, s.max_time, s.time_above_s), (95.0, 2.0, 3.0));
+        // second excursion is still open at the end of the log
+        assert_eq!(s.events.len(), 2);
+        assert_eq!((s.events[0].start, s.events[0].end, s.events[0].peak), (1.0, 3.0, 95.0));
+        assert_eq!((s.events[1].start, s.events[1].end), (4.0, 5.0));
+        assert_eq!((r.derate_events, r.derate_time_s), (1, 2.0));
+        assert_eq!(r.derate_flags[0].events[0].at_onset.as_ref().unwrap()["T"], 95.0);
+    }
+
+    #[test]
+    fn charging_sessions() {
+        // plugged 0..100 s; current pauses 40..45 s (bridged) and stops at 80 s
+        let plug = ([0.0, 100.0], [1.0, 0.0]);
+        let current = ([0.0, 10.0, 40.0, 45.0, 80.0], [0.0, 100.0, 0.0, 100.0, 0.0]);
+        let voltage = ([0.0], [400.0]);
+        let p = ChargingParams { current_threshold: 1.0, invert_current: false, min_duration_s: 0.0, max_gap_s: 10.0 };
+        let r = charging(
+            Some((&plug.0, &plug.1)),
+            Some((&current.0, &current.1)),
+            Some((&voltage.0, &voltage.1)),
+            None,
+            &p,
+        );
+        assert_eq!(r.sessions.len(), 1);
+        let s = &r.sessions[0];
+        assert_eq!((s.start, s.end), (10.0, 80.0));
+        // 65 s at 40 kW
+        assert!((s.energy_kwh.unwrap() - 40.0 * 65.0 / 3600.0).abs() < 1e-9);
+        assert_eq!((s.max_current, s.max_power_kw), (Some(100.0), Some(40.0)));
+    }
+}
@@ -0,0 +1,840 @@
+// ###############################################################
+// signals.rs
+// can-blf-parser (WASM)
+// Signal-level analyses over decoded (time, value) series
+// (pure Rust, no JS types)
+// ###############################################################
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+// -------------------------------
+// Shared helpers
+// -------------------------------
+
+// Resample onto t0, t0+dt, ... (n points) holding the last value
+fn resample_hold(times: &[f64], values: &[f64], t0: f64, dt: f64, n: usize) -> Vec<f64> {
+    let mut out = Vec::with_capacity(n);
+    let mut i = 0;
+    for k in 0..n {
+        let t = t0 + k as f64 * dt;
+        while i + 1 < times.len() && times[i + 1] <= t {
+            i += 1;
+        }
+        out.push(values[i]);
+    }
+    out
+}
+
+fn median_interval(times: &[f64]) -> Option<f64> {
+    let mut d: Vec<f64> = times.windows(2).map(|w| w[1] - w[0]).filter(|d| *d > 0.0).collect();
+    if d.is_empty() {
+        return None;
+    }
+    d.sort_by(|a, b| a.total_cmp(b));
+    Some(d[d.len() / 2])
+}
+
+// -------------------------------
+// Lag finder: peak of the normalized cross-correlation.
+// Positive lag_ms means b follows a (b(t) ~ a(t - lag)).
+// -------------------------------
+#[derive(Serialize, Debug, Clone)]
+pub struct LagEstimate {
+    pub lag_ms: f64,
+    pub correlation: f64, // Pearson r at the peak, -1..1
+    pub step_ms: f64, // resampling grid
+    pub samples: usize, // grid points in the overlap
+}
+
+const MAX_GRID: usize = 50_000;
+const MAX_LAG_STEPS: usize = 2_000;
+
+pub(crate) fn find_lag(a: (&[f64], &[f64]), b: (&[f64], &[f64]), max_lag_s: f64) -> Result<LagEstimate, String> {
+    let (ta, va) = a;
+    let (tb, vb) = b;
+    if !(max_lag_s >= 0.0 && max_lag_s.is_finite()) {
+        return Err("max_lag_ms must be >= 0".to_string());
+    }
+    let t0 = ta[0].max(tb[0]);
+    let t1 = ta[ta.len() - 1].min(tb[tb.len() - 1]);
+    if t1 <= t0 {
+        return Err("signals do not overlap in time".to_string());
+    }
+
+    // grid: the coarser native rate, bounded in size and lag steps
+    let native = median_interval(ta).unwrap_or(0.0).max(median_interval(tb).unwrap_or(0.0));
+    let dt = native.max((t1 - t0) / MAX_GRID as f64).max(max_lag_s / MAX_LAG_STEPS as f64);
+    let n = ((t1 - t0) / dt).floor() as usize + 1;
+    let max_k = ((max_lag_s / dt).round() as usize).min(n.saturating_sub(2));
+
+    let mut xa = resample_hold(ta, va, t0, dt, n);
+    let mut xb = resample_hold(tb, vb, t0, dt, n);
+    for x in [&mut xa, &mut xb] {
+        let mean = x.iter().sum::<f64>() / n as f64;
+        x.iter_mut().for_each(|v| *v -= mean);
+    }
+
+    // r(k) over the overlap of a[i] and b[i + k]
+    let corr = |k: isize| -> f64 {
+        let (mut sab, mut saa, mut sbb) = (0.0, 0.0, 0.0);
+        for i in 0..n as isize {
+            let j = i + k;
+            if j < 0 || j >= n as isize {
+                continue;
+            }
+            let (x, y) = (xa[i as usize], xb[j as usize]);
+            sab += x * y;
+            saa += x * x;
+            sbb += y * y;
+        }
+        if saa > 0.0 && sbb > 0.0 { sab / (saa * sbb).sqrt() } else { 0.0 }
+    };
+
+    let lags: Vec<isize> = (-(max_k as isize)..=max_k as isize).collect();
+    let rs: Vec<f64> = lags.iter().map(|&k| corr(k)).collect();
+    let best = (0..rs.len())
+        .max_by(|&i, &j| rs[i].total_cmp(&rs[j]))
+        .ok_or_else(|| "no lags to evaluate".to_string())?;
+    if rs[best] == 0.0 {
+        return Err("signals are constant over the overlap".to_string());
+    }
+
+    // parabolic refinement between grid steps
+    let mut k = lags[best] as f64;
+    if best > 0 && best + 1 < rs.len() {
+        let (l, c, r) = (rs[best - 1], rs[best], rs[best + 1]);
+        let denom = l - 2.0 * c + r;
+        if denom < 0.0 {
+            k += 0.5 * (l - r) / denom;
+        }
+    }
+
+    Ok(LagEstimate { lag_ms: k * dt * 1000.0, correlation: rs[best], step_ms: dt * 1000.0, samples: n })
+}
+
+// -------------------------------
+// Step response around t_event: initial level from the pre-window, final level
+// from the tail of the post-window, then rise/overshoot/settling against those.
+// -------------------------------
+#[derive(Serialize, Debug, Clone)]
+pub struct StepMetrics {
+    pub t_event: f64,
+    pub initial: f64,
+    pub final_value: f64,
+    pub step: f64, // final_value - initial
+    pub dead_time_ms: Option<f64>, // t_event -> response leaves the settle band around initial
+    pub rise_time_ms: Option<f64>, // rise_low_pct -> rise_high_pct of the step
+    pub overshoot_pct: f64, // peak beyond final_value, % of |step|
+    pub peak_time_ms: Option<f64>,
+    pub settling_time_ms: Option<f64>, // t_event -> last exit from the settle band; None if never settled
+}
+
+pub(crate) struct StepParams {
+    pub window_s: f64,
+    pub pre_s: f64,
+    pub settle_band: f64, // fraction of |step|
+    pub rise_low: f64,
+    pub rise_high: f64,
+}
+
+// first time the (linearly interpolated) series crosses `level` in direction `dir`
+fn crossing(times: &[f64], values: &[f64], level: f64, dir: f64) -> Option<f64> {
+    if (values[0] - level) * dir >= 0.0 {
+        return Some(times[0]);
+    }
+    for i in 1..values.len() {
+        let (v0, v1) = (values[i - 1], values[i]);
+        if (v1 - level) * dir >= 0.0 {
+            let frac = if v1 != v0 { (level - v0) / (v1 - v0) } else { 1.0 };
+            return Some(times[i - 1] + frac * (times[i] - times[i - 1]));
+        }
+    }
+    None
+}
+
+pub(crate) fn step_metrics(times: &[f64], values: &[f64], t_event: f64, p: &StepParams) -> Result<StepMetrics, String> {
+    let lo = times.partition_point(|t| *t < t_event);
+    let hi = times.partition_point(|t| *t <= t_event + p.window_s);
+    if hi <= lo + 1 {
+        return Err("not enough samples after t_event".to_string());
+    }
+    let (wt, wv) = (&times[lo..hi], &values[lo..hi]);
+
+    // initial: mean over the pre-window, else the value held at t_event
+    let pre_lo = times.partition_point(|t| *t < t_event - p.pre_s);
+    let initial = if lo > pre_lo {
+        values[pre_lo..lo].iter().sum::<f64>() / (lo - pre_lo) as f64
+    } else {
+        wv[0]
+    };
+    // final: mean over the last 10% of the window
+    let tail_from = wt[0] + 0.9 * (wt[wt.len() - 1] - wt[0]);
+    let tail: Vec<f64> = wt.iter().zip(wv).filter(|(t, _)| **t >= tail_from).map(|(_, v)| *v).collect();
+    let final_value = tail.iter().sum::<f64>() / tail.len() as f64;
+
+    let step = final_value - initial;
+    if step == 0.0 || !step.is_finite() {
+        return Err("no step detected in the window".to_string());
+    }
+    let dir = step.signum();
+    let band = p.settle_band * step.abs();
+    let ms = |t: f64| (t - t_event) * 1000.0;
+
+    let dead_time_ms = crossing(wt, wv, initial + dir * band, dir).map(ms);
+    let rise_time_ms = match (
+        crossing(wt, wv, initial + p.rise_low * step, dir),
+        crossing(wt, wv, initial + p.rise_high * step, dir),
+    ) {
+        (Some(a), Some(b)) => Some((b - a) * 1000.0),
+        _ => None,
+    };
+
+    // peak in the step direction
+    let peak = (0..wv.len()).max_by(|&i, &j| (wv[i] * dir).total_cmp(&(wv[j] * dir)));
+    let (overshoot_pct, peak_time_ms) = match peak {
+        Some(i) => (((wv[i] - final_value) * dir).max(0.0) / step.abs() * 100.0, Some(ms(wt[i]))),
+        None => (0.0, None),
+    };
+
+    // settled once every later sample stays within the band around final_value
+    let settling_time_ms = match wv.iter().rposition(|v| (v - final_value).abs() > band) {
+        None => Some(0.0),
+        Some(i) if i + 1 < wv.len() => Some(ms(wt[i + 1])),
+        Some(_) => None,
+    };
+
+    Ok(StepMetrics {
+        t_event,
+        initial,
+        final_value,
+        step,
+        dead_time_ms,
+        rise_time_ms,
+        overshoot_pct,
+        peak_time_ms,
+        settling_time_ms,
+    })
+}
+
+// -------------------------------
+// Drive-cycle segmentation from a speed signal: smoothed speed -> acceleration,
+// each sample labelled idle/accel/cruise/brake, runs merged into intervals and
+// intervals shorter than min_duration folded into their predecessor.
+// -------------------------------
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DrivePhaseKind {
+    Idle,
+    Accel,
+    Cruise,
+    Brake,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DrivePhase {
+    pub phase: DrivePhaseKind,
+    pub t_start: f64,
+    pub t_end: f64, // = t_start of the next interval
+    pub mean_speed: f64, // signal units
+    pub distance_m: f64,
+}
+
+pub(crate) struct DriveParams {
+    pub to_mps: f64, // speed signal unit -> m/s
+    pub idle_speed: f64, // signal units
+    pub accel_mps2: f64,
+    pub brake_mps2: f64, // negative
+    pub smooth_s: f64,
+    pub min_duration_s: f64,
+}
+
+// centred moving average over a time window (non-uniform samples)
+fn smooth(times: &[f64], values: &[f64], window_s: f64) -> Vec<f64> {
+    if window_s <= 0.0 {
+        return values.to_vec();
+    }
+    let mut prefix = vec![0.0; values.len() + 1];
+    for (i, v) in values.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + v;
+    }
+    let (mut lo, mut hi) = (0, 0);
+    let mut out = Vec::with_capacity(values.len());
+    for &t in times {
+        while times[lo] < t - window_s / 2.0 {
+            lo += 1;
+        }
+        while hi < times.len() && times[hi] <= t + window_s / 2.0 {
+            hi += 1;
+        }
+        out.push((prefix[hi] - prefix[lo]) / (hi - lo) as f64);
+    }
+    out
+}
+
+pub(crate) fn drive_phases(times: &[f64], values: &[f64], p: &DriveParams) -> Vec<DrivePhase> {
+    let n = times.len();
+    if n < 2 {
+        return Vec::new();
+    }
+    let speed = smooth(times, values, p.smooth_s);
+    let label = |i: usize| {
+        let (a, b) = (i.saturating_sub(1), (i + 1).min(n - 1));
+        let dt = times[b] - times[a];
+        let accel = if dt > 0.0 { (speed[b] - speed[a]) * p.to_mps / dt } else { 0.0 };
+        if speed[i].abs() <= p.idle_speed {
+            DrivePhaseKind::Idle
+        } else if accel >= p.accel_mps2 {
+            DrivePhaseKind::Accel
+        } else if accel <= p.brake_mps2 {
+            DrivePhaseKind::Brake
+        } else {
+            DrivePhaseKind::Cruise
+        }
+    };
+
+    // runs of equal labels as (kind, first sample, end sample exclusive)
+    let mut runs: Vec<(DrivePhaseKind, usize, usize)> = Vec::new();
+    for i in 0..n {
+        let k = label(i);
+        match runs.last_mut() {
+            Some(r) if r.0 == k => r.2 = i + 1,
+            _ => runs.push((k, i, i + 1)),
+        }
+    }
+
+    // fold short runs into the previous one (the first into the next), then re-merge
+    let t_of = |r: &(DrivePhaseKind, usize, usize)| times[r.2.min(n - 1)] - times[r.1];
+    let mut merged: Vec<(DrivePhaseKind, usize, usize)> = Vec::new();
+    for r in runs {
+        match merged.last_mut() {
+            Some(prev) if t_of(&r) < p.min_duration_s || prev.0 == r.0 => prev.2 = r.2,
+            _ => merged.push(r),
+        }
+    }
+    if merged.len() > 1 && t_of(&merged[0]) < p.min_duration_s {
+        let first = merged.remove(0);
+        merged[0].1 = first.1;
+    }
+    let mut phases: Vec<(DrivePhaseKind, usize, usize)> = Vec::new();
+    for r in merged {
+        match phases.last_mut() {
+            Some(prev) if prev.0 == r.0 => prev.2 = r.2,
+            _ => phases.push(r),
+        }
+    }
+
+    phases
+        .into_iter()
+        .map(|(phase, a, b)| {
+            let t_end = times[b.min(n - 1)];
+            let distance_m = (a..b.min(n - 1))
+                .map(|i| 0.5 * (values[i] + values[i + 1]).abs() * p.to_mps * (times[i + 1] - times[i]))
+                .sum();
+            DrivePhase {
+                phase,
+                t_start: times[a],
+                t_end,
+                mean_speed: values[a..b].iter().sum::<f64>() / (b - a) as f64,
+                distance_m,
+            }
+        })
+        .collect()
+}
+
+// -------------------------------
+// Integral of a product of held signals over [t0, t1], with the resulting unit
+// -------------------------------
+#[derive(Serialize, Debug, Clone)]
+pub struct Accumulation {
+    pub value: f64,
+    pub unit: String, // integrand unit * s, simplified where known (W*s -> J, A*s -> C)
+    pub integrand_unit: String,
+    pub t0: f64, // actually covered span (all signals present)
+    pub t1: f64,
+    pub value_wh: Option<f64>, // energy results also in Wh
+}
+
+// product of units with the common electrical simplifications
+fn product_unit(units: &[&str]) -> String {
+    let mut us: Vec<String> = units.iter().filter(|u| !u.is_empty()).map(|u| u.to_string()).collect();
+    if let (Some(v), Some(a)) = (us.iter().position(|u| u == "V"), us.iter().position(|u| u == "A")) {
+        let (hi, lo) = (v.max(a), v.min(a));
+        us.remove(hi);
+        us.remove(lo);
+        us.insert(lo, "W".to_string());
+    }
+    us.join("*")
+}
+
+fn integrated_unit(integrand: &str) -> String {
+    match integrand {
+        "W" => "J".to_string(),
+        "A" => "C".to_string(),
+        "" => "s".to_string(),
+        u => format!("{}*s", u),
+    }
+}
+
+pub(crate) fn accumulate(series: &[(&[f64], &[f64])], units: &[&str], t0: f64, t1: f64) -> Result<Accumulation, String> {
+    if t1 < t0 || t0.is_nan() || t1.is_nan() {
+        return Err("t1 must be >= t0".to_string());
+    }
+    // union of sample times inside the range; every signal already present
+    let start = series.iter().map(|(t, _)| t[0]).fold(t0, f64::max);
+    let end = series.iter().map(|(t, _)| t[t.len() - 1]).fold(t1, f64::min).max(start);
+    let mut times: Vec<f64> = series
+        .iter()
+        .flat_map(|(t, _)| t.iter().copied().filter(|x| *x > start && *x < end))
+        .collect();
+    times.push(start);
+    times.push(end);
+    times.sort_by(|a, b| a.total_cmp(b));
+    times.dedup();
+
+    let mut cursors = vec![0usize; series.len()];
+    let mut value = 0.0;
+    for w in times.windows(2) {
+        let mut prod = 1.0;
+        for ((t, v), c) in series.iter().zip(cursors.iter_mut()) {
+            while *c + 1 < t.len() && t[*c + 1] <= w[0] {
+                *c += 1;
+            }
+            prod *= v[*c];
+        }
+        value += prod * (w[1] - w[0]);
+    }
+
+    let integrand_unit = product_unit(units);
+    let unit = integrated_unit(&integrand_unit);
+    let value_wh = (unit == "J").then_some(value / 3600.0);
+    Ok(Accumulation { value, unit, integrand_unit, t0: start, t1: end, value_wh })
+}
+
+// -------------------------------
+// Summary statistics over [t0, t1]: per sample, and time-weighted with each value
+// held until the next sample (so a 100 Hz burst does not outweigh an hour at 1 Hz)
+// -------------------------------
+#[derive(Serialize, Debug, Clone)]
+pub struct SignalStats {
+    pub samples: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64, // per sample
+    pub stddev: f64, // per sample, population
+    pub time_weighted_mean: Option<f64>, // None when the held span is zero (one sample)
+    pub duration_s: f64, // held span the time-weighted values cover
+    pub threshold: Option<f64>,
+    pub duty_cycle: Option<f64>, // fraction of the held span with value > threshold
+    pub t0: f64,
+    pub t1: f64,
+    pub first_time: Option<f64>, // first / last sample inside [t0, t1]
+    pub last_time: Option<f64>,
+    pub sample_rate_hz: Option<f64>, // mean rate between them; None below two distinct times
+}
+
+pub(crate) fn signal_stats(
+    times: &[f64],
+    values: &[f64],
+    t0: Option<f64>,
+    t1: Option<f64>,
+    threshold: Option<f64>,
+) -> Result<SignalStats, String> {
+    let t0 = t0.unwrap_or(times[0]);
+    let t1 = t1.unwrap_or(times[times.len() - 1]);
+    if t1 < t0 || t0.is_nan() || t1.is_nan() {
+        return Err("t1 must be >= t0".to_string());
+    }
+
+    let lo = times.partition_point(|t| *t < t0);
+    let hi = times.partition_point(|t| *t <= t1);
+    let inside = &values[lo..hi];
+    let (first_time, last_time) = (times[lo..hi].first().copied(), times[lo..hi].last().copied());
+    let n = inside.len() as f64;
+    let mean = inside.iter().sum::<f64>() / n;
+    let var = inside.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+
+    // held segments clipped to the window; a value before t0 holds into it
+    let (mut area, mut duration, mut above) = (0.0, 0.0, 0.0);
+    for (i, (&t, &v)) in times.iter().zip(values).enumerate() {
+        let start = t.max(t0);
+        let end = times.get(i + 1).copied().unwrap_or(t1).min(t1);
+        if end > start {
+            let dt = end - start;
+            area += v * dt;
+            duration += dt;
+            if threshold.is_some_and(|th| v > th) {
+                above += dt;
+            }
+        }
+    }
+
+    Ok(SignalStats {
+        samples: inside.len(),
+        min: inside.iter().copied().fold(f64::NAN, f64::min),
+        max: inside.iter().copied().fold(f64::NAN, f64::max),
+        mean,
+        stddev: var.sqrt(),
+        time_weighted_mean: (duration > 0.0).then(|| area / duration),
+        duration_s: duration,
+        threshold,
+        duty_cycle: threshold.filter(|_| duration > 0.0).map(|_| above / duration),
+        t0,
+        t1,
+        first_time,
+        last_time,
+        sample_rate_hz: first_time
+            .zip(last_time)
+            .filter(|(a, b)| b > a)
+            .map(|(a, b)| (inside.len() - 1) as f64 / (b - a)),
+    })
+}
+
+// -------------------------------
+// Rolling statistics over a trailing time window (t - window, t] at every sample.
+// Sums for mean / stddev, monotonic deques for min / max: O(n) for any window.
+// -------------------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum RollingStat {
+    Mean,
+    Min,
+    Max,
+    Stddev, // population
+}
+
+impl RollingStat {
+    pub(crate) fn parse(s: &str) -> Result<RollingStat, String> {
+        match s {
+            "mean" => Ok(RollingStat::Mean),
+            "min" => Ok(RollingStat::Min),
+            "max" => Ok(RollingStat::Max),
+            "stddev" => Ok(RollingStat::Stddev),
+            _ => Err(format!("unknown stat \"{}\" (mean, min, max, stddev)", s)),
+        }
+    }
+}
+
+pub(crate) fn rolling(times: &[f64], values: &[f64], window_s: f64, stat: RollingStat) -> Result<Vec<f64>, String> {
+    if !(window_s > 0.0 && window_s.is_finite()) {
+        return Err("window_ms must be > 0".to_string());
+    }
+    // sums relative to the first value keep the variance from cancelling
+    let shift = values.first().copied().unwrap_or(0.0);
+    let (mut sum, mut sum_sq) = (0.0, 0.0);
+    let mut deque: VecDeque<usize> = VecDeque::new();
+    let mut start = 0;
+    let mut out = Vec::with_capacity(values.len());
+    for (i, (&t, &v)) in times.iter().zip(values).enumerate() {
+        let x = v - shift;
+        sum += x;
+        sum_sq += x * x;
+        let keep = |old: f64| match stat {
+            RollingStat::Min => old < v,
+            _ => old > v,
+        };
+        while deque.back().is_some_and(|&j| !keep(values[j])) {
+            deque.pop_back();
+        }
+        deque.push_back(i);
+
+        while times[start] <= t - window_s {
+            let y = values[start] - shift;
+            sum -= y;
+            sum_sq -= y * y;
+            start += 1;
+        }
+        while deque.front().is_some_and(|&j| j < start) {
+            deque.pop_front();
+        }
+
+        let n = (i + 1 - start) as f64;
+        out.push(match stat {
+            RollingStat::Mean => shift + sum / n,
+            RollingStat::Stddev => (sum_sq / n - (sum / n).powi(2)).max(0.0).sqrt(),
+            RollingStat::Min | RollingStat::Max => values[deque[0]],
+        });
+    }
+    Ok(out)
+}
+
+// -------------------------------
+// Cell spread: min / max / delta across cell voltages at every time any cell
+// updates, from held values once every cell has reported (multiplexed BMS messages
+// refresh one cell group per frame). Min / max are rescanned only when the cell
+// holding them moves inward.
+// -------------------------------
+pub(crate) struct CellSpread {
+    pub time: Vec<f64>,
+    pub min: Vec<f64>,
+    pub max: Vec<f64>,
+    pub delta: Vec<f64>,
+    pub min_cell: Vec<u32>, // index into the cell list
+    pub max_cell: Vec<u32>,
+}
+
+pub(crate) fn cell_spread(cells: &[(&[f64], &[f64])]) -> CellSpread {
+    let mut out = CellSpread {
+        time: Vec::new(),
+        min: Vec::new(),
+        max: Vec::new(),
+        delta: Vec::new(),
+        min_cell: Vec::new(),
+        max_cell: Vec::new(),
+    };
+    // all samples in time order (cell index breaks ties)
+    let mut events: Vec<(f64, usize, f64)> = cells
+        .iter()
+        .enumerate()
+        .flat_map(|(c, (t, v))| t.iter().zip(v.iter()).map(move |(&t, &v)| (t, c, v)))
+        .collect();
+    events.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+    let mut held = vec![f64::NAN; cells.len()];
+    let mut missing = cells.len();
+    let (mut lo, mut hi) = (0, 0);
+    let scan = |held: &[f64]| {
+        let (mut lo, mut hi) = (0, 0);
+        for (c, v) in held.iter().enumerate() {
+            if *v < held[lo] {
+                lo = c;
+            }
+            if *v > held[hi] {
+                hi = c;
+            }
+        }
+        (lo, hi)
+    };
+    for (k, &(t, c, v)) in events.iter().enumerate() {
+        let old = held[c];
+        held[c] = v;
+        if old.is_nan() {
+            missing -= 1;
+            if missing == 0 {
+                (lo, hi) = scan(&held);
+            }
+        } else if missing == 0 {
+            if (c == lo && v > old) || (c == hi && v < old) {
+                (lo, hi) = scan(&held);
+            } else {
+                if v < held[lo] {
+                    lo = c;
+                }
+                if v > held[hi] {
+                    hi = c;
+                }
+            }
+        }
+        // one row per distinct time, after its last update
+        if missing > 0 || events.get(k + 1).is_some_and(|e| e.0 == t) {
+            continue;
+        }
+        out.time.push(t);
+        out.min.push(held[lo]);
+        out.max.push(held[hi]);
+        out.delta.push(held[hi] - held[lo]);
+        out.min_cell.push(lo as u32);
+        out.max_cell.push(hi as u32);
+    }
+    out
+}
+
+// -------------------------------
+// Uniform resampling onto t0, t0 + 1/rate, ... for tools that need a fixed step
+// (Simulink, ML pipelines). NaN where the mode has no value: before the first sample
+// (hold, linear) and after the last (linear, which does not extrapolate).
+// -------------------------------
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Interpolation {
+    #[default]
+    #[serde(alias = "previous")]
+    Hold, // last sample at or before t
+    Linear, // straight line between the samples around t
+    Nearest, // closest sample, the earlier one on ties
+}
+
+// t0 .. t1 (inclusive when it falls on the grid) at rate_hz
+pub(crate) fn uniform_grid(t0: f64, t1: f64, rate_hz: f64, max_points: usize) -> Result<Vec<f64>, String> {
+    if !(rate_hz > 0.0 && rate_hz.is_finite()) {
+        return Err("rate_hz must be > 0".to_string());
+    }
+    // the epsilon keeps t1 itself when (t1 - t0) * rate is whole
+    let n = ((t1 - t0) * rate_hz + 1e-9).floor() as usize + 1;
+    if n > max_points {
+        return Err(format!("{} Hz over {:.3} s gives {} points, more than {}", rate_hz, t1 - t0, n, max_points));
+    }
+    Ok((0..n).map(|k| t0 + k as f64 / rate_hz).collect())
+}
+
+pub(crate) fn resample(times: &[f64], values: &[f64], grid: &[f64], mode: Interpolation) -> Vec<f64> {
+    let mut i = 0; // samples at or before t
+    grid.iter()
+        .map(|&t| {
+            while i < times.len() && times[i] <= t {
+                i += 1;
+            }
+            let prev = i.checked_sub(1);
+            let next = times.get(i);
+            match (mode, prev, next) {
+                (Interpolation::Hold, Some(p), _) => values[p],
+                (Interpolation::Linear, Some(p), _) if times[p] == t => values[p],
+                (Interpolation::Linear, Some(p), Some(&tn)) => {
+                    values[p] + (values[i] - values[p]) * (t - times[p]) / (tn - times[p])
+                }
+                (Interpolation::Nearest, Some(p), Some(&tn)) if tn - t < t - times[p] => values[i],
+                (Interpolation::Nearest, Some(p), _) => values[p],
+                (Interpolation::Nearest, None, Some(_)) => values[i],
+                _ => f64::NAN,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lag_sign_and_size() {
+        // b is a delayed by 120 ms, both sampled every 10 ms
+        let f = |t: f64| (2.0 * std::f64::consts::PI * 0.3 * t).sin() + 0.5 * (2.0 * std::f64::consts::PI * 1.1 * t).sin();
+        let t: Vec<f64> = (0..1000).map(|i| i as f64 * 0.01).collect();
+        let a: Vec<f64> = t.iter().map(|&t| f(t)).collect();
+        let b: Vec<f64> = t.iter().map(|&t| f(t - 0.12)).collect();
+
+        let est = find_lag((&t, &a), (&t, &b), 0.5).unwrap();
+        assert!((est.lag_ms - 120.0).abs() < 2.0, "{}", est.lag_ms);
+        assert!(est.correlation > 0.99);
+        assert!((est.step_ms - 10.0).abs() < 1e-6);
+        // a follows b: negative
+        let back = find_lag((&t, &b), (&t, &a), 0.5).unwrap();
+        assert!((back.lag_ms + 120.0).abs() < 2.0, "{}", back.lag_ms);
+        assert!(find_lag((&t, &a), (&t, &b), -1.0).is_err());
+    }
+
+    fn step_params() -> StepParams {
+        StepParams { window_s: 2.5, pre_s: 0.5, settle_band: 0.02, rise_low: 0.1, rise_high: 0.9 }
+    }
+
+    #[test]
+    fn first_order_step() {
+        // step of 10 at t = 1 s: 50 ms dead time, then tau = 200 ms; 1 ms samples
+        let t: Vec<f64> = (0..4000).map(|i| i as f64 * 0.001).collect();
+        let v: Vec<f64> = t.iter().map(|&t| if t < 1.05 { 0.0 } else { 10.0 * (1.0 - (-(t - 1.05) / 0.2).exp()) }).collect();
+        let m = step_metrics(&t, &v, 1.0, &step_params()).unwrap();
+        assert_eq!(m.initial, 0.0);
+        assert!((m.step - 10.0).abs() < 1e-3);
+        // leaves the 2 % band after 50 ms + tau * ln(1 / 0.98)
+        assert!((m.dead_time_ms.unwrap() - 54.04).abs() < 1.0, "{:?}", m.dead_time_ms);
+        // 10 % -> 90 %: tau * ln 9
+        assert!((m.rise_time_ms.unwrap() - 439.4).abs() < 1.0, "{:?}", m.rise_time_ms);
+        // within 2 % for good after 50 ms + tau * ln 50
+        assert!((m.settling_time_ms.unwrap() - 832.4).abs() < 2.0, "{:?}", m.settling_time_ms);
+        assert!(m.overshoot_pct < 0.01);
+    }
+
+    #[test]
+    fn second_order_overshoot() {
+        // zeta = 0.5, omega_n = 10 rad/s from t = 1 s: 16.3 % overshoot at pi / omega_d
+        let (zeta, wn) = (0.5, 10.0);
+        let wd = wn * f64::sqrt(1.0 - zeta * zeta);
+        let y = |x: f64| 1.0 - (-zeta * wn * x).exp() * ((wd * x).cos() + zeta / f64::sqrt(1.0 - zeta * zeta) * (wd * x).sin());
+        let t: Vec<f64> = (0..4000).map(|i| i as f64 * 0.001).collect();
+        let v: Vec<f64> = t.iter().map(|&t| if t < 1.0 { 0.0 } else { y(t - 1.0) }).collect();
+        let m = step_metrics(&t, &v, 1.0, &step_params()).unwrap();
+        let expected = 100.0 * (-std::f64::consts::PI * zeta / f64::sqrt(1.0 - zeta * zeta)).exp();
+        assert!((m.overshoot_pct - expected).abs() < 0.2, "{} vs {}", m.overshoot_pct, expected);
+        assert!((m.peak_time_ms.unwrap() - 1000.0 * std::f64::consts::PI / wd).abs() < 1.0);
+        assert!(m.settling_time_ms.unwrap() > m.peak_time_ms.unwrap());
+        // flat signal: no step
+        assert!(step_metrics(&t, &vec![1.0; t.len()], 1.0, &step_params()).is_err());
+    }
+
+    #[test]
+    fn drive_cycle_phases() {
+        // 10 Hz km/h: idle, 10 s to 72 km/h, cruise, 10 s to a stop, idle; a one-sample blip
+        // at the start and a 0.3 s bump while cruising are too short to be phases
+        let t: Vec<f64> = (0..600).map(|i| i as f64 * 0.1).collect();
+        let v: Vec<f64> = (0..600)
+            .map(|i| match i {
+                0 => 5.0,
+                300..=302 => 75.0,
+                100..=200 => (i - 100) as f64 * 0.72,
+                201..=399 => 72.0,
+                400..=500 => 72.0 - (i - 400) as f64 * 0.72,
+                _ => 0.0,
+            })
+            .collect();
+        let p = DriveParams {
+            to_mps: 1.0 / 3.6,
+            idle_speed: 1.0,
+            accel_mps2: 0.5,
+            brake_mps2: -0.5,
+            smooth_s: 0.0,
+            min_duration_s: 1.0,
+        };
+        let phases = drive_phases(&t, &v, &p);
+        let kinds: Vec<DrivePhaseKind> = phases.iter().map(|p| p.phase).collect();
+        use DrivePhaseKind::*;
+        assert_eq!(kinds, [Idle, Accel, Cruise, Brake, Idle]);
+        for (phase, start) in phases.iter().zip([0.0, 10.2, 20.1, 40.0, 49.9]) {
+            assert!((phase.t_start - start).abs() < 1e-9, "{:?}", phase);
+        }
+        // contiguous, ending at the last sample
+        assert!(phases.windows(2).all(|w| w[0].t_end == w[1].t_start));
+        assert_eq!(phases[4].t_end, t[599]);
+        // cruise: 19.9 s at 20 m/s plus the bump
+        assert!((phases[2].distance_m - 398.0).abs() < 1.0, "{}", phases[2].distance_m);
+    }
+
+    #[test]
+    fn cell_spread_held() {
+        // cell 1 reports late; cell 0 rises past cell 2 and hands over the maximum
+        let t0: &[f64] = &[0.0, 1.0, 2.0];
+        let t1: &[f64] = &[1.0];
+        let cells = [(t0, &[3.60, 3.62, 3.70][..]), (t1, &[3.55][..]), (t0, &[3.65, 3.65, 3.64][..])];
+        let s = cell_spread(&cells);
+        assert_eq!(s.time, [1.0, 2.0]);
+        assert_eq!((s.min_cell.as_slice(), s.max_cell.as_slice()), (&[1, 1][..], &[2, 0][..]));
+        assert!((s.delta[0] - 0.10).abs() < 1e-9 && (s.delta[1] - 0.15).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rolling_window() {
+        let t = [0.0, 1.0, 2.0, 3.0, 4.0];
+        let v = [1.0, 5.0, 2.0, 4.0, 3.0];
+        // window 2 s: samples in (t - 2, t]
+        assert_eq!(rolling(&t, &v, 2.0, RollingStat::Max).unwrap(), [1.0, 5.0, 5.0, 4.0, 4.0]);
+        assert_eq!(rolling(&t, &v, 2.0, RollingStat::Min).unwrap(), [1.0, 1.0, 2.0, 2.0, 3.0]);
+        assert_eq!(rolling(&t, &v, 2.0, RollingStat::Mean).unwrap(), [1.0, 3.0, 3.5, 3.0, 3.5]);
+        let sd = rolling(&t, &v, 2.0, RollingStat::Stddev).unwrap();
+        assert!((sd[1] - 2.0).abs() < 1e-12 && (sd[4] - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn stats_sample_times() {
+        let t = [0.0, 0.5, 1.0, 1.5, 2.0];
+        let v = [1.0, 3.0, 1.0, 3.0, 1.0];
+        let s = signal_stats(&t, &v, Some(0.2), Some(2.0), None).unwrap();
+        assert_eq!((s.samples, s.first_time, s.last_time), (4, Some(0.5), Some(2.0)));
+        assert_eq!(s.sample_rate_hz, Some(2.0));
+        assert_eq!((s.min, s.max, s.mean, s.stddev), (1.0, 3.0, 2.0, 1.0));
+        assert_eq!(signal_stats(&t[..1], &v[..1], None, None, None).unwrap().sample_rate_hz, None);
+    }
+
+    #[test]
+    fn resample_modes() {
+        let t = [1.0, 2.0, 4.0];
+        let v = [10.0, 20.0, 40.0];
+        let grid = uniform_grid(0.0, 5.0, 2.0, 100).unwrap();
+        assert_eq!(grid.len(), 11);
+        let fmt = |xs: Vec<f64>| xs.iter().map(|x| if x.is_nan() { "-".to_string() } else { x.to_string() }).collect::<Vec<_>>().join(" ");
+        assert_eq!(fmt(resample(&t, &v, &grid, Interpolation::Hold)), "- - 10 10 20 20 20 20 40 40 40");
+        assert_eq!(fmt(resample(&t, &v, &grid, Interpolation::Linear)), "- - 10 15 20 25 30 35 40 - -");
+        assert_eq!(fmt(resample(&t, &v, &grid, Interpolation::Nearest)), "10 10 10 10 20 20 20 40 40 40 40");
+        assert!(resample(&[], &[], &grid, Interpolation::Nearest).iter().all(|x| x.is_nan()));
+        assert!(uniform_grid(0.0, 5.0, 2.0, 10).is_err());
+        assert!(uniform_grid(0.0, 5.0, 0.0, 10).is_err());
+    }
+}
@@ -0,0 +1,486 @@
+// ###############################################################
+// sqlite.rs
+// can-blf-parser (WASM)
+// SQLite 3 database writer for export_sqlite(): tables and their indexes
+// written once as packed B-trees (4 KiB pages, overflow pages for large
+// payloads), readable by sqlite3, sql.js and DB Browser for SQLite.
+// ###############################################################
+
+use std::cmp::Ordering;
+
+// -------------------------------
+// Everything is built bottom-up in one pass: leaves filled in key order, then interior
+// levels until one root page remains. Table B-trees hold rows by rowid (1..n, the row
+// order given); index B-trees hold (key columns, rowid) records sorted as SQLite
+// compares them. Page 1 carries the file header and sqlite_schema.
+// -------------------------------
+const PAGE: usize = 4096;
+const SQLITE_VERSION: u32 = 3_045_000; // written-by version in the header
+
+// page types
+const INTERIOR_INDEX: u8 = 0x02;
+const INTERIOR_TABLE: u8 = 0x05;
+const LEAF_INDEX: u8 = 0x0A;
+const LEAF_TABLE: u8 = 0x0D;
+
+// local payload limits (file format 1.6 "Cell Payload Overflow Pages")
+const MAX_LOCAL_TABLE: usize = PAGE - 35;
+const MAX_LOCAL_INDEX: usize = (PAGE - 12) * 64 / 255 - 23;
+const MIN_LOCAL: usize = (PAGE - 12) * 32 / 255 - 23;
+
+#[derive(Clone, Debug)]
+pub(crate) enum Value {
+    Null,
+    Int(i64),
+    Real(f64), // NaN is stored as NULL
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl Value {
+    fn class(&self) -> u8 {
+        match self {
+            Value::Null => 0,
+            Value::Real(x) if x.is_nan() => 0,
+            Value::Int(_) | Value::Real(_) => 1,
+            Value::Text(_) => 2,
+            Value::Blob(_) => 3,
+        }
+    }
+
+    // SQLite order: NULL < numbers < text (BINARY collation) < blobs
+    fn compare(&self, other: &Value) -> Ordering {
+        let num = |v: &Value| match v {
+            Value::Int(i) => *i as f64,
+            Value::Real(x) => *x,
+            _ => 0.0,
+        };
+        self.class().cmp(&other.class()).then_with(|| match (self, other) {
+            (Value::Text(a), Value::Text(b)) => a.as_bytes().cmp(b.as_bytes()),
+            (Value::Blob(a), Value::Blob(b)) => a.cmp(b),
+            (Value::Int(a), Value::Int(b)) => a.cmp(b),
+            _ => num(self).total_cmp(&num(other)),
+        })
+    }
+}
+
+pub(crate) struct Index {
+    pub name: &'static str,
+    pub columns: Vec<usize>, // positions in the table's rows
+}
+
+pub(crate) struct Table {
+    pub name: &'static str,
+    pub sql: String, // CREATE TABLE statement
+    pub rows: Vec<Vec<Value>>, // rowid = position + 1
+    pub indexes: Vec<Index>,
+}
+
+fn varint(out: &mut Vec<u8>, v: u64) {
+    if v >> 56 != 0 {
+        // nine bytes: the last one carries a full 8 bits
+        let mut buf = [0u8; 9];
+        buf[8] = v as u8;
+        let mut rest = v >> 8;
+        for b in buf[..8].iter_mut().rev() {
+            *b = (rest as u8 & 0x7F) | 0x80;
+            rest >>= 7;
+        }
+        out.extend_from_slice(&buf);
+        return;
+    }
+    let groups = (64 - v.leading_zeros()).div_ceil(7).max(1);
+    for g in (0..groups).rev() {
+        let byte = (v >> (7 * g)) as u8 & 0x7F;
+        out.push(if g > 0 { byte | 0x80 } else { byte });
+    }
+}
+
+fn varint_len(v: u64) -> usize {
+    let mut out = Vec::with_capacity(9);
+    varint(&mut out, v);
+    out.len()
+}
+
+// Record format: header (its size, one serial type per value), then the values
+pub(crate) fn record(values: &[Value]) -> Vec<u8> {
+    let mut types = Vec::new();
+    let mut body = Vec::new();
+    for v in values {
+        let serial = match v {
+            Value::Null => 0,
+            Value::Real(x) if x.is_nan() => 0,
+            Value::Int(0) => 8,
+            Value::Int(1) => 9,
+            Value::Int(i) => {
+                let (serial, bytes) = match *i {
+                    -0x80..=0x7F => (1, 1),
+                    -0x8000..=0x7FFF => (2, 2),
+                    -0x80_0000..=0x7F_FFFF => (3, 3),
+                    -0x8000_0000..=0x7FFF_FFFF => (4, 4),
+                    -0x8000_0000_0000..=0x7FFF_FFFF_FFFF => (5, 6),
+                    _ => (6, 8),
+                };
+                body.extend_from_slice(&i.to_be_bytes()[8 - bytes..]);
+                serial
+            }
+            Value::Real(x) => {
+                body.extend_from_slice(&x.to_be_bytes());
+                7
+            }
+            Value::Text(s) => {
+                body.extend_from_slice(s.as_bytes());
+                13 + 2 * s.len() as u64
+            }
+            Value::Blob(b) => {
+                body.extend_from_slice(b);
+                12 + 2 * b.len() as u64
+            }
+        };
+        varint(&mut types, serial);
+    }
+    let mut header_len = types.len() + 1;
+    if varint_len(header_len as u64) > 1 {
+        header_len = types.len() + varint_len(types.len() as u64 + 9);
+    }
+    let mut out = Vec::with_capacity(header_len + body.len());
+    varint(&mut out, header_len as u64);
+    out.extend(types);
+    out.extend(body);
+    out
+}
+
+// bytes of a payload kept on the B-tree page
+fn local_len(payload: usize, max_local: usize) -> usize {
+    if payload <= max_local {
+        return payload;
+    }
+    let k = MIN_LOCAL + (payload - MIN_LOCAL) % (PAGE - 4);
+    if k <= max_local { k } else { MIN_LOCAL }
+}
+
+// size of a cell: prefix, local payload, overflow page number if spilled
+fn cell_len(prefix: usize, payload: usize, max_local: usize) -> usize {
+    prefix + local_len(payload, max_local) + if payload > max_local { 4 } else { 0 }
+}
+
+fn fits(header: usize, cells: usize, used: usize) -> bool {
+    header + 2 * cells + used <= PAGE
+}
+
+// Groups of items per page, the items between groups moving up (index B-trees keep
+// their separators in the parent). A last group left empty borrows from the one before.
+fn split(sizes: impl Iterator<Item = usize>, header: usize) -> (Vec<Vec<usize>>, Vec<usize>) {
+    let mut groups = vec![Vec::new()];
+    let mut seps = Vec::new();
+    let mut used = 0;
+    for (i, size) in sizes.enumerate() {
+        let group = groups.last_mut().expect("at least one group");
+        if fits(header, group.len() + 1, used + size) {
+            group.push(i);
+            used += size;
+        } else {
+            seps.push(i);
+            groups.push(Vec::new());
+            used = 0;
+        }
+    }
+    if groups.len() > 1 && groups.last().is_some_and(|g| g.is_empty()) {
+        groups.pop();
+        let last = seps.pop().expect("a separator per extra group");
+        let moved = groups.last_mut().and_then(|g| g.pop()).expect("full pages hold several cells");
+        seps.push(moved);
+        groups.push(vec![last]);
+    }
+    (groups, seps)
+}
+
+struct Pages {
+    pages: Vec<Vec<u8>>, // page n at n - 1
+}
+
+impl Pages {
+    fn alloc(&mut self) -> u32 {
+        self.pages.push(vec![0; PAGE]);
+        self.pages.len() as u32
+    }
+
+    // prefix + local payload (+ first overflow page, the rest chained over new pages)
+    fn cell(&mut self, mut prefix: Vec<u8>, payload: &[u8], max_local: usize) -> Vec<u8> {
+        let local = local_len(payload.len(), max_local);
+        prefix.extend_from_slice(&payload[..local]);
+        if local < payload.len() {
+            let chunks: Vec<&[u8]> = payload[local..].chunks(PAGE - 4).collect();
+            let first = self.pages.len() as u32 + 1;
+            prefix.extend_from_slice(&first.to_be_bytes());
+            for (i, chunk) in chunks.iter().enumerate() {
+                let n = self.alloc();
+                let next = if i + 1 < chunks.len() { n + 1 } else { 0 };
+                let page = &mut self.pages[n as usize - 1];
+                page[..4].copy_from_slice(&next.to_be_bytes());
+                page[4..4 + chunk.len()].copy_from_slice(chunk);
+            }
+        }
+        prefix
+    }
+
+    fn write(&mut self, n: u32, kind: u8, cells: &[Vec<u8>], right: Option<u32>) {
+        let at = if n == 1 { 100 } else { 0 };
+        let page = &mut self.pages[n as usize - 1];
+        let header = if right.is_some() { 12 } else { 8 };
+        assert!(fits(at + header, cells.len(), cells.iter().map(Vec::len).sum()), "B-tree page overfilled");
+        let mut end = PAGE;
+        for (i, c) in cells.iter().enumerate() {
+            end -= c.len();
+            page[end..end + c.len()].copy_from_slice(c);
+            let ptr = at + header + 2 * i;
+            page[ptr..ptr + 2].copy_from_slice(&(end as u16).to_be_bytes());
+        }
+        page[at] = kind;
+        page[at + 3..at + 5].copy_from_slice(&(cells.len() as u16).to_be_bytes());
+        page[at + 5..at + 7].copy_from_slice(&(end as u16).to_be_bytes());
+        if let Some(r) = right {
+            page[at + 8..at + 12].copy_from_slice(&r.to_be_bytes());
+        }
+    }
+
+    // table B-tree of records with rowids 1..n; returns the root page
+    fn table(&mut self, records: &[Vec<u8>]) -> u32 {
+        let prefix = |i: usize, r: &[u8]| varint_len(r.len() as u64) + varint_len(i as u64 + 1);
+        let sizes = records.iter().enumerate().map(|(i, r)| cell_len(prefix(i, r), r.len(), MAX_LOCAL_TABLE));
+        // a table leaf group ends where the next row does not fit; no separators
+        let mut groups: Vec<Vec<usize>> = vec![Vec::new()];
+        let mut used = 0;
+        for (i, size) in sizes.enumerate() {
+            if !fits(8, groups.last().map_or(0, Vec::len) + 1, used + size) {
+                groups.push(Vec::new());
+                used = 0;
+            }
+            groups.last_mut().expect("at least one group").push(i);
+            used += size;
+        }
+        let mut level: Vec<(u32, u64)> = Vec::with_capacity(groups.len()); // (page, largest rowid)
+        for group in groups {
+            let mut cells = Vec::with_capacity(group.len());
+            for &i in &group {
+                let mut p = Vec::with_capacity(18);
+                varint(&mut p, records[i].len() as u64);
+                varint(&mut p, i as u64 + 1);
+                cells.push(self.cell(p, &records[i], MAX_LOCAL_TABLE));
+            }
+            let n = self.alloc();
+            self.write(n, LEAF_TABLE, &cells, None);
+            level.push((n, group.last().map_or(0, |i| *i as u64 + 1)));
+        }
+
+        while level.len() > 1 {
+            let mut groups: Vec<Vec<(u32, u64)>> = vec![Vec::new()];
+            let mut used = 0;
+            for child in level {
+                let size = 4 + varint_len(child.1);
+                let group = groups.last_mut().expect("at least one group");
+                if !group.is_empty() && !fits(12, group.len(), used + size) {
+                    groups.push(Vec::new());
+                    used = 0;
+                }
+                groups.last_mut().expect("at least one group").push(child);
+                used += size;
+            }
+            // every interior page needs a cell besides its right-most child
+            if groups.len() > 1 && groups.last().is_some_and(|g| g.len() == 1) {
+                let before = groups.len() - 2;
+                let moved = groups[before].pop().expect("full pages hold several cells");
+                groups.last_mut().expect("at least one group").insert(0, moved);
+            }
+            level = groups
+                .into_iter()
+                .map(|group| {
+                    let (&(right, key), rest) = group.split_last().expect("non-empty group");
+                    let cells: Vec<Vec<u8>> = rest
+                        .iter()
+                        .map(|(child, key)| {
+                            let mut c = child.to_be_bytes().to_vec();
+                            varint(&mut c, *key);
+                            c
+                        })
+                        .collect();
+                    let n = self.alloc();
+                    self.write(n, INTERIOR_TABLE, &cells, Some(right));
+                    (n, key)
+                })
+                .collect();
+        }
+        level[0].0
+    }
+
+    // index B-tree of sorted (key columns, rowid) records; returns the root page
+    fn index(&mut self, records: &[Vec<u8>]) -> u32 {
+        let prefix = |r: &[u8]| varint_len(r.len() as u64);
+        let (groups, mut seps) = split(records.iter().map(|r| cell_len(prefix(r), r.len(), MAX_LOCAL_INDEX)), 8);
+        let mut children = Vec::with_capacity(groups.len());
+        for group in groups {
+            let cells: Vec<Vec<u8>> = group
+                .iter()
+                .map(|&i| {
+                    let mut p = Vec::with_capacity(9);
+                    varint(&mut p, records[i].len() as u64);
+                    self.cell(p, &records[i], MAX_LOCAL_INDEX)
+                })
+                .collect();
+            let n = self.alloc();
+            self.write(n, LEAF_INDEX, &cells, None);
+            children.push(n);
+        }
+
+        // interior cell j: (children[j], seps[j]); children.last() is the right-most pointer
+        while children.len() > 1 {
+            let sizes = seps.iter().map(|&s| cell_len(4 + prefix(&records[s]), records[s].len(), MAX_LOCAL_INDEX));
+            let (groups, up) = split(sizes, 12);
+            let mut next = Vec::with_capacity(groups.len());
+            for (g, group) in groups.iter().enumerate() {
+                let cells: Vec<Vec<u8>> = group
+                    .iter()
+                    .map(|&j| {
+                        let mut p = children[j].to_be_bytes().to_vec();
+                        varint(&mut p, records[seps[j]].len() as u64);
+                        self.cell(p, &records[seps[j]], MAX_LOCAL_INDEX)
+                    })
+                    .collect();
+                // right-most child: the one left of the separator moving up, or the last
+                let right = up.get(g).map_or(children[children.len() - 1], |&j| children[j]);
+                let n = self.alloc();
+                self.write(n, INTERIOR_INDEX, &cells, Some(right));
+                next.push(n);
+            }
+            seps = up.iter().map(|&j| seps[j]).collect();
+            children = next;
+        }
+        children[0]
+    }
+}
+
+// The database file: sqlite_schema on page 1, then each table and its indexes
+pub(crate) fn encode(tables: &[Table]) -> Vec<u8> {
+    let mut pages = Pages { pages: vec![vec![0; PAGE]] };
+    let mut schema: Vec<Vec<Value>> = Vec::new();
+    for t in tables {
+        let records: Vec<Vec<u8>> = t.rows.iter().map(|r| record(r)).collect();
+        let root = pages.table(&records);
+        schema.push(vec![
+            Value::Text("table".into()),
+            Value::Text(t.name.into()),
+            Value::Text(t.name.into()),
+            Value::Int(root as i64),
+            Value::Text(t.sql.clone()),
+        ]);
+        drop(records);
+
+        for ix in &t.indexes {
+            let mut keys: Vec<(Vec<&Value>, usize)> =
+                t.rows.iter().enumerate().map(|(i, r)| (ix.columns.iter().map(|&c| &r[c]).collect(), i + 1)).collect();
+            keys.sort_by(|(a, ra), (b, rb)| {
+                a.iter().zip(b).map(|(x, y)| x.compare(y)).find(|o| o.is_ne()).unwrap_or(Ordering::Equal).then(ra.cmp(rb))
+            });
+            let records: Vec<Vec<u8>> = keys
+                .into_iter()
+                .map(|(key, rowid)| {
+                    let mut values: Vec<Value> = key.into_iter().cloned().collect();
+                    values.push(Value::Int(rowid as i64));
+                    record(&values)
+                })
+                .collect();
+            let root = pages.index(&records);
+            let columns: Vec<&str> = ix.columns.iter().map(|&c| column_name(&t.sql, c)).collect();
+            schema.push(vec![
+                Value::Text("index".into()),
+                Value::Text(ix.name.into()),
+                Value::Text(t.name.into()),
+                Value::Int(root as i64),
+                Value::Text(format!("CREATE INDEX {} ON {}({})", ix.name, t.name, columns.join(", "))),
+            ]);
+        }
+    }
+
+    let cells: Vec<Vec<u8>> = schema
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let r = record(row);
+            let mut c = Vec::new();
+            varint(&mut c, r.len() as u64);
+            varint(&mut c, i as u64 + 1);
+            c.extend(r);
+            c
+        })
+        .collect();
+    pages.write(1, LEAF_TABLE, &cells, None);
+
+    let count = pages.pages.len() as u32;
+    let h = &mut pages.pages[0];
+    h[..16].copy_from_slice(b"SQLite format 3\0");
+    h[16..18].copy_from_slice(&(PAGE as u16).to_be_bytes());
+    h[18] = 1; // legacy journal: file format write / read versions
+    h[19] = 1;
+    h[21] = 64; // payload fractions, fixed by the format
+    h[22] = 32;
+    h[23] = 32;
+    h[24..28].copy_from_slice(&1u32.to_be_bytes()); // change counter
+    h[28..32].copy_from_slice(&count.to_be_bytes());
+    h[40..44].copy_from_slice(&1u32.to_be_bytes()); // schema cookie
+    h[44..48].copy_from_slice(&4u32.to_be_bytes()); // schema format
+    h[56..60].copy_from_slice(&1u32.to_be_bytes()); // UTF-8
+    h[92..96].copy_from_slice(&1u32.to_be_bytes()); // version-valid-for = change counter
+    h[96..100].copy_from_slice(&SQLITE_VERSION.to_be_bytes());
+    pages.pages.concat()
+}
+
+// c-th column name of a "CREATE TABLE t(a TYPE, b TYPE, ...)" statement
+fn column_name(sql: &str, c: usize) -> &str {
+    let columns = sql.split_once('(').map_or("", |(_, rest)| rest);
+    columns.split(',').nth(c).and_then(|d| d.split_whitespace().next()).unwrap_or("")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_and_varints() {
+        let mut v = Vec::new();
+        varint(&mut v, 300);
+        assert_eq!(v, [0x82, 0x2C]);
+        assert_eq!(varint_len(u64::MAX), 9);
+        // header: size 5, NULL, int8, real, text "ab"
+        let r = record(&[Value::Null, Value::Int(-2), Value::Real(0.5), Value::Text("ab".into())]);
+        assert_eq!(&r[..5], &[5, 0, 1, 7, 17]);
+        assert_eq!(r[5], 0xFE);
+        assert_eq!(&r[r.len() - 2..], b"ab");
+        assert_eq!(Value::Int(3).compare(&Value::Real(2.5)), Ordering::Greater);
+        assert_eq!(Value::Text("a".into()).compare(&Value::Int(9)), Ordering::Greater);
+    }
+
+    #[test]
+    fn btrees_span_levels() {
+        let rows: Vec<Vec<Value>> = (0..20_000)
+            .map(|i| {
+                let blob = if i % 997 == 0 { vec![7u8; 9000] } else { vec![i as u8; 8] };
+                vec![Value::Null, Value::Real(i as f64 * 0.01), Value::Text(format!("sig{}", i % 13)), Value::Blob(blob)]
+            })
+            .collect();
+        let table = Table {
+            name: "t",
+            sql: "CREATE TABLE t(id INTEGER PRIMARY KEY, time_s REAL, name TEXT, data BLOB)".into(),
+            rows,
+            indexes: vec![Index { name: "t_name", columns: vec![2, 1] }],
+        };
+        let db = encode(&[table]);
+        assert_eq!(db.len() % PAGE, 0);
+        assert_eq!(u32::from_be_bytes(db[28..32].try_into().unwrap()) as usize, db.len() / PAGE);
+        assert_eq!(&db[..16], b"SQLite format 3\0");
+        // roots are interior pages
+        for root_kind in [INTERIOR_TABLE, INTERIOR_INDEX] {
+            assert!(db.chunks(PAGE).skip(1).any(|p| p[0] == root_kind));
+        }
+        assert_eq!(column_name("CREATE TABLE t(id INTEGER PRIMARY KEY, time_s REAL)", 1), "time_s");
+    }
+}
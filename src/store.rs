@@ -0,0 +1,459 @@
+// ###############################################################
+// store.rs
+// can-blf-parser (WASM)
+// Columnar frame storage for sessions: one Vec per field instead of a
+// FrameRow (four Strings, a payload and a signal Vec) per frame. Strings are
+// interned, payloads packed into one buffer, decoded signals kept as an
+// interned key (name, unit, label) plus an f64, and the rare per-bus details
+// (error / FlexRay / Ethernet) kept aside. Readers get Frame views.
+// ###############################################################
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use serde::{Serialize, Serializer};
+
+use crate::j1939::J1939Info;
+use crate::{Bus, ErrorInfo, EthernetInfo, EventType, FlexRayInfo, Frame, FrameFlags, SignalRow};
+
+// FrameStore::bits
+const RTR: u16 = 1 << 0;
+const FD: u16 = 1 << 1;
+const BRS: u16 = 1 << 2;
+const ESI: u16 = 1 << 3;
+const WAKEUP: u16 = 1 << 4;
+const NERR: u16 = 1 << 5;
+const EXTENDED: u16 = 1 << 6;
+const BUS_SHIFT: u16 = 7; // 2 bits
+const EXTRAS: u16 = 1 << 9;
+const J1939: u16 = 1 << 10;
+const J1939_REASSEMBLED: u16 = 1 << 11;
+
+#[derive(Debug, Clone, Default)]
+struct Interner {
+    strings: Vec<Box<str>>,
+    index: HashMap<Box<str>, u32>,
+}
+
+impl Interner {
+    fn intern(&mut self, s: &str) -> u32 {
+        if let Some(&i) = self.index.get(s) {
+            return i;
+        }
+        let i = self.strings.len() as u32;
+        self.strings.push(s.into());
+        self.index.insert(s.into(), i);
+        i
+    }
+}
+
+const ABSENT: u32 = u32::MAX; // SignalTable key: no original_unit / value_text
+
+// Text fields of the stored signal rows: [signal, unit, original_unit, value_text]
+// into strings, one entry per distinct combination (labels vary with the value, so a
+// value-table signal has one per label seen)
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SignalTable {
+    strings: Interner,
+    keys: Vec<[u32; 4]>,
+    index: HashMap<[u32; 4], u32>,
+}
+
+impl SignalTable {
+    fn intern(&mut self, r: &SignalRef) -> u32 {
+        let mut opt = |s: Option<&str>| s.map_or(ABSENT, |s| self.strings.intern(s));
+        let key = [opt(Some(r.signal)), opt(Some(r.unit)), opt(r.original_unit), opt(r.value_text)];
+        *self.index.entry(key).or_insert_with(|| {
+            self.keys.push(key);
+            self.keys.len() as u32 - 1
+        })
+    }
+
+    fn row(&self, key: u32, value: f64) -> SignalRef<'_> {
+        let text = |i: u32| (i != ABSENT).then(|| &*self.strings.strings[i as usize]);
+        let [signal, unit, original_unit, value_text] = self.keys[key as usize];
+        SignalRef {
+            signal: &self.strings.strings[signal as usize],
+            value,
+            unit: &self.strings.strings[unit as usize],
+            original_unit: text(original_unit),
+            value_text: text(value_text),
+        }
+    }
+}
+
+// Frame::signals: a FrameRow's rows, or a stored frame's keys and values
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Signals<'a> {
+    Rows(&'a [SignalRow]),
+    Stored { keys: &'a [u32], values: &'a [f64], table: &'a SignalTable },
+}
+
+// One signal of a Frame, borrowed; serialized as SignalRow
+#[derive(Serialize, Debug, Clone, Copy)]
+pub(crate) struct SignalRef<'a> {
+    pub signal: &'a str,
+    pub value: f64,
+    pub unit: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_unit: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_text: Option<&'a str>,
+}
+
+impl SignalRef<'_> {
+    pub(crate) fn to_row(self) -> SignalRow {
+        SignalRow {
+            signal: self.signal.to_string(),
+            value: self.value,
+            unit: self.unit.to_string(),
+            original_unit: self.original_unit.map(str::to_string),
+            value_text: self.value_text.map(str::to_string),
+        }
+    }
+}
+
+impl<'a> Signals<'a> {
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Signals::Rows(rows) => rows.len(),
+            Signals::Stored { keys, .. } => keys.len(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn iter(self) -> impl Iterator<Item = SignalRef<'a>> {
+        (0..self.len()).map(move |i| match self {
+            Signals::Rows(rows) => {
+                let r = &rows[i];
+                SignalRef {
+                    signal: &r.signal,
+                    value: r.value,
+                    unit: &r.unit,
+                    original_unit: r.original_unit.as_deref(),
+                    value_text: r.value_text.as_deref(),
+                }
+            }
+            Signals::Stored { keys, values, table } => table.row(keys[i], values[i]),
+        })
+    }
+}
+
+impl Serialize for Signals<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+// Details only error, FlexRay and Ethernet frames carry
+#[derive(Clone, Default)]
+struct Extras {
+    error: Option<ErrorInfo>,
+    flexray: Option<FlexRayInfo>,
+    ethernet: Option<EthernetInfo>,
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct FrameStore {
+    timestamps: Vec<f64>,
+    ids: Vec<u32>, // IDE bit masked off, as Frame::id
+    channel_nums: Vec<u16>,
+    channels: Vec<u32>, // into strings
+    names: Vec<u32>, // into strings
+    event_types: Vec<EventType>,
+    dirs: Vec<u16>, // into labels
+    dlcs: Vec<u8>,
+    bits: Vec<u16>, // flags, IDE, bus, J1939 (see the constants above)
+    data_at: Vec<u32>, // offset into data
+    data_len: Vec<u16>,
+    data: Vec<u8>,
+    last_payload: HashMap<(u16, u32), u32>, // (channel, raw id) -> offset of its last payload
+    signal_ends: Vec<u32>, // exclusive end into signal_keys / signal_values; empty while no frame has signals
+    signal_keys: Vec<u32>, // into signal_table
+    signal_values: Vec<f64>,
+    signal_table: SignalTable,
+    extras: HashMap<u32, Extras>,
+    strings: Interner, // channels and frame names
+    labels: Interner, // directions (a handful)
+}
+
+impl FrameStore {
+    pub(crate) fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    pub(crate) fn push(&mut self, f: Frame) {
+        let i = self.len();
+        self.timestamps.push(f.timestamp);
+        self.ids.push(f.id);
+        self.channel_nums.push(f.channel_num);
+        self.channels.push(self.strings.intern(f.channel));
+        self.names.push(self.strings.intern(f.name));
+        self.event_types.push(f.event_type);
+        self.dirs.push(self.labels.intern(f.dir) as u16);
+        self.dlcs.push(f.dlc);
+
+        let flags = &f.flags;
+        let mut bits = (f.bus as u16) << BUS_SHIFT;
+        for (set, bit) in [
+            (flags.rtr, RTR),
+            (flags.fd, FD),
+            (flags.brs, BRS),
+            (flags.esi, ESI),
+            (flags.wakeup, WAKEUP),
+            (flags.nerr, NERR),
+            (f.is_extended, EXTENDED),
+            (f.j1939.is_some(), J1939),
+            (f.j1939.is_some_and(|j| j.reassembled), J1939_REASSEMBLED),
+        ] {
+            if set {
+                bits |= bit;
+            }
+        }
+        if f.error.is_some() || f.flexray.is_some() || f.ethernet.is_some() {
+            bits |= EXTRAS;
+            self.extras.insert(
+                i as u32,
+                Extras { error: f.error.cloned(), flexray: f.flexray.cloned(), ethernet: f.ethernet.cloned() },
+            );
+        }
+        self.bits.push(bits);
+
+        // repeats of a message's last payload share its bytes
+        let key = (f.channel_num, f.raw_id());
+        let at = match self.last_payload.get(&key) {
+            Some(&at) if self.payload(at, f.data.len()) == f.data => at,
+            _ => {
+                let at = self.data.len() as u32;
+                self.data.extend_from_slice(f.data);
+                self.last_payload.insert(key, at);
+                at
+            }
+        };
+        self.data_at.push(at);
+        self.data_len.push(f.data.len() as u16);
+
+        if !f.signals.is_empty() || !self.signal_ends.is_empty() {
+            // first frame with signals: the ones before it get empty ranges
+            self.signal_ends.resize(i, 0);
+            for r in f.signals.iter() {
+                self.signal_keys.push(self.signal_table.intern(&r));
+                self.signal_values.push(r.value);
+            }
+            self.signal_ends.push(self.signal_keys.len() as u32);
+        }
+    }
+
+    fn payload(&self, at: u32, len: usize) -> &[u8] {
+        self.data.get(at as usize..at as usize + len).unwrap_or(&[])
+    }
+
+    pub(crate) fn get(&self, i: usize) -> Frame<'_> {
+        let bits = self.bits[i];
+        let extras = if bits & EXTRAS != 0 { self.extras.get(&(i as u32)) } else { None };
+        let signals = match self.signal_ends.get(i) {
+            Some(&end) => {
+                let start = if i == 0 { 0 } else { self.signal_ends[i - 1] } as usize;
+                (start, end as usize)
+            }
+            None => (0, 0),
+        };
+        let signals = Signals::Stored {
+            keys: &self.signal_keys[signals.0..signals.1],
+            values: &self.signal_values[signals.0..signals.1],
+            table: &self.signal_table,
+        };
+        let id = self.ids[i];
+        Frame {
+            timestamp: self.timestamps[i],
+            channel: &self.strings.strings[self.channels[i] as usize],
+            channel_num: self.channel_nums[i],
+            id,
+            is_extended: bits & EXTENDED != 0,
+            name: &self.strings.strings[self.names[i] as usize],
+            event_type: self.event_types[i],
+            dir: &self.labels.strings[self.dirs[i] as usize],
+            dlc: self.dlcs[i],
+            data: self.payload(self.data_at[i], self.data_len[i] as usize),
+            flags: FrameFlags {
+                rtr: bits & RTR != 0,
+                fd: bits & FD != 0,
+                brs: bits & BRS != 0,
+                esi: bits & ESI != 0,
+                wakeup: bits & WAKEUP != 0,
+                nerr: bits & NERR != 0,
+            },
+            signals,
+            error: extras.and_then(|e| e.error.as_ref()),
+            flexray: extras.and_then(|e| e.flexray.as_ref()),
+            ethernet: extras.and_then(|e| e.ethernet.as_ref()),
+            // recomputed from the id; only the reassembly flag is stored
+            j1939: (bits & J1939 != 0)
+                .then(|| J1939Info { reassembled: bits & J1939_REASSEMBLED != 0, ..J1939Info::from_id(id) }),
+            bus: match bits >> BUS_SHIFT & 0x3 {
+                0 => Bus::Can,
+                1 => Bus::Lin,
+                2 => Bus::FlexRay,
+                _ => Bus::Ethernet,
+            },
+        }
+    }
+
+    pub(crate) fn first(&self) -> Option<Frame<'_>> {
+        (!self.is_empty()).then(|| self.get(0))
+    }
+
+    pub(crate) fn last(&self) -> Option<Frame<'_>> {
+        (!self.is_empty()).then(|| self.get(self.len() - 1))
+    }
+
+    pub(crate) fn iter(&self) -> impl DoubleEndedIterator<Item = Frame<'_>> + ExactSizeIterator + '_ {
+        self.range(0..self.len())
+    }
+
+    pub(crate) fn range(&self, r: Range<usize>) -> impl DoubleEndedIterator<Item = Frame<'_>> + ExactSizeIterator + '_ {
+        r.map(move |i| self.get(i))
+    }
+
+    // Frames with start <= timestamp <= end, by binary search (frames in time order)
+    pub(crate) fn window(&self, start: f64, end: f64) -> Range<usize> {
+        let first = self.timestamps.partition_point(|t| *t < start);
+        let last = self.timestamps.partition_point(|t| *t <= end);
+        first..last.max(first)
+    }
+
+    pub(crate) fn sliced(&self, r: Range<usize>) -> FrameStore {
+        let mut out = FrameStore::default();
+        for f in self.range(r) {
+            out.push(f);
+        }
+        out
+    }
+
+    pub(crate) fn map_timestamps(&mut self, f: impl Fn(f64) -> f64) {
+        for t in self.timestamps.iter_mut() {
+            *t = f(*t);
+        }
+    }
+
+    // Frames of `self` then `other`, reordered by timestamp; the sort is stable, so
+    // equal timestamps keep self before other and each side's own order
+    pub(crate) fn merged(&self, other: &FrameStore) -> FrameStore {
+        let n = self.len();
+        let mut order: Vec<usize> = (0..n + other.len()).collect();
+        let time = |k: usize| if k < n { self.timestamps[k] } else { other.timestamps[k - n] };
+        order.sort_by(|a, b| time(*a).total_cmp(&time(*b)));
+        let mut out = FrameStore::default();
+        for k in order {
+            out.push(if k < n { self.get(k) } else { other.get(k - n) });
+        }
+        out
+    }
+
+    // Frames whose `keep` entry is true, in order
+    pub(crate) fn filtered(&self, keep: &[bool]) -> FrameStore {
+        let mut out = FrameStore::default();
+        for (i, _) in keep.iter().enumerate().filter(|(_, k)| **k) {
+            out.push(self.get(i));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::Payload;
+    use crate::FrameRow;
+
+    fn row(t: f64, channel_num: u16, id: u32, data: &[u8]) -> FrameRow {
+        FrameRow {
+            timestamp: t,
+            channel: format!("CAN{}", channel_num),
+            channel_num,
+            id,
+            name: format!("M{:X}", id),
+            event_type: EventType::Can,
+            dir: "Rx".to_string(),
+            dlc: data.len() as u8,
+            data: Payload::from(data),
+            ..FrameRow::default()
+        }
+    }
+
+    #[test]
+    fn round_trip_and_shared_payloads() {
+        let mut rows = vec![row(0.0, 1, 0x100, &[1, 2]), row(0.1, 2, 0x100, &[1, 2]), row(0.2, 1, 0x100, &[1, 2])];
+        rows[1].is_extended = true;
+        rows[1].flags.fd = true;
+        rows[2].error = Some(ErrorInfo { position: Some(12), ..ErrorInfo::default() });
+        let mut store = FrameStore::default();
+        for r in &rows {
+            store.push(r.view());
+        }
+        // frames 0 and 2 share the last payload of (CAN1, 0x100)
+        assert_eq!(store.data.len(), 4);
+        for (r, f) in rows.iter().zip(store.iter()) {
+            assert_eq!(serde_json::to_value(r.view()).unwrap(), serde_json::to_value(f).unwrap());
+        }
+        assert!(store.get(1).is_extended && store.get(1).flags.fd);
+        assert_eq!(store.get(2).error.and_then(|e| e.position), Some(12));
+    }
+
+    #[test]
+    fn signal_rows_interned() {
+        let sig = |value: f64, value_text: Option<&str>| SignalRow {
+            signal: "CAN1.Gear".to_string(),
+            value,
+            unit: String::new(),
+            original_unit: None,
+            value_text: value_text.map(str::to_string),
+        };
+        let mut rows = vec![row(0.0, 1, 0x100, &[1]), row(0.1, 1, 0x100, &[2]), row(0.2, 1, 0x100, &[1])];
+        rows[0].signals = vec![sig(1.0, Some("First"))];
+        rows[1].signals = vec![sig(2.0, Some("Second"))];
+        rows[2].signals = vec![sig(1.0, Some("First"))];
+        let mut store = FrameStore::default();
+        for r in &rows {
+            store.push(r.view());
+        }
+        // one key per label, names stored once
+        assert_eq!(store.signal_table.keys.len(), 2);
+        assert_eq!(store.signal_table.strings.strings.len(), 4);
+        for (r, f) in rows.iter().zip(store.iter()) {
+            assert_eq!(serde_json::to_value(r.view()).unwrap(), serde_json::to_value(f).unwrap());
+            let back: Vec<SignalRow> = f.signals.iter().map(SignalRef::to_row).collect();
+            assert_eq!(serde_json::to_value(&back).unwrap(), serde_json::to_value(&r.signals).unwrap());
+        }
+    }
+
+    #[test]
+    fn signals_after_frames_without() {
+        let mut with = row(1.0, 1, 0x200, &[0]);
+        with.signals.push(SignalRow { signal: "CAN1.S".to_string(), value: 3.0, unit: String::new(), original_unit: None, value_text: None });
+        let mut store = FrameStore::default();
+        for r in [row(0.0, 1, 0x100, &[0]), with, row(2.0, 1, 0x100, &[0])] {
+            store.push(r.view());
+        }
+        let counts: Vec<usize> = store.iter().map(|f| f.signals.len()).collect();
+        assert_eq!(counts, [0, 1, 0]);
+
+        let mut other = FrameStore::default();
+        other.push(row(0.5, 1, 0x300, &[9]).view());
+        let merged = store.merged(&other);
+        assert_eq!(merged.iter().map(|f| f.id).collect::<Vec<_>>(), [0x100, 0x300, 0x200, 0x100]);
+        assert_eq!(merged.get(2).signals.iter().next().map(|r| r.value), Some(3.0));
+
+        assert_eq!(merged.window(0.5, 1.0), 1..3);
+        assert_eq!(merged.window(3.0, 4.0), 4..4);
+        let sliced = merged.sliced(merged.window(0.8, 2.0));
+        assert_eq!(sliced.iter().map(|f| f.signals.len()).collect::<Vec<_>>(), [1, 0]);
+    }
+}
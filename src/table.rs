@@ -0,0 +1,53 @@
+// ###############################################################
+// table.rs
+// can-blf-parser (WASM)
+// Typed columns shared by the columnar exports: export_parquet()
+// (parquet.rs) and to_arrow() (arrow.rs).
+// ###############################################################
+
+pub(crate) enum Values {
+    Double(Vec<f64>),
+    OptDouble(Vec<Option<f64>>), // None -> null
+    Int32(Vec<i32>),
+    Bool(Vec<bool>),
+    Utf8(Vec<String>),
+    Bytes(Vec<Vec<u8>>),
+}
+
+impl Values {
+    // signal values with NaN (no sample yet, see signals.rs) as nulls
+    pub(crate) fn nullable(v: Vec<f64>) -> Values {
+        Values::OptDouble(v.into_iter().map(|x| Some(x).filter(|x| !x.is_nan())).collect())
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Values::Double(v) => v.len(),
+            Values::OptDouble(v) => v.len(),
+            Values::Int32(v) => v.len(),
+            Values::Bool(v) => v.len(),
+            Values::Utf8(v) => v.len(),
+            Values::Bytes(v) => v.len(),
+        }
+    }
+}
+
+pub(crate) struct Column {
+    pub name: String,
+    pub values: Values,
+}
+
+impl Column {
+    pub(crate) fn new(name: impl Into<String>, values: Values) -> Column {
+        Column { name: name.into(), values }
+    }
+}
+
+// LSB-first bits, as Parquet booleans / levels and Arrow bitmaps are packed
+pub(crate) fn bits(v: &[bool]) -> Vec<u8> {
+    let mut out = vec![0u8; v.len().div_ceil(8)];
+    for (i, _) in v.iter().enumerate().filter(|(_, b)| **b) {
+        out[i / 8] |= 1 << (i % 8);
+    }
+    out
+}
@@ -0,0 +1,228 @@
+// ###############################################################
+// timing.rs
+// can-blf-parser (WASM)
+// Phase breakdown of one session call for last_operation_timings(): BLF
+// iteration, DBC matching, signal decode and serialization, in milliseconds.
+// ###############################################################
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use serde::Serialize;
+
+// -------------------------------
+// Matching, decoding and per-row writes run once per frame, too often to read the
+// clock each time: every SAMPLE_EVERY-th call of a phase is timed and the mean is
+// scaled to all calls. One-off work (final serialization) is timed exactly. BLF
+// iteration is what remains of the call: reading objects (or walking stored frames),
+// filtering and bookkeeping.
+// -------------------------------
+const SAMPLE_EVERY: u64 = 32;
+
+#[derive(Clone, Copy)]
+pub(crate) enum Phase {
+    Match,
+    Decode,
+    Serialize,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, Default)]
+pub struct OperationTimings {
+    pub operation: &'static str,
+    pub total_ms: f64,
+    pub blf_iteration_ms: f64,
+    pub dbc_matching_ms: f64,
+    pub signal_decode_ms: f64,
+    pub serialization_ms: f64,
+}
+
+impl OperationTimings {
+    // streaming builder: append_chunk() / finalize() calls add up
+    fn plus(self, o: OperationTimings) -> OperationTimings {
+        OperationTimings {
+            operation: self.operation,
+            total_ms: self.total_ms + o.total_ms,
+            blf_iteration_ms: self.blf_iteration_ms + o.blf_iteration_ms,
+            dbc_matching_ms: self.dbc_matching_ms + o.dbc_matching_ms,
+            signal_decode_ms: self.signal_decode_ms + o.signal_decode_ms,
+            serialization_ms: self.serialization_ms + o.serialization_ms,
+        }
+    }
+}
+
+// Where a session keeps its last report; clone_view() handles get their own
+pub(crate) type Slot = Rc<Cell<Option<OperationTimings>>>;
+
+#[derive(Default, Clone, Copy)]
+struct PhaseTime {
+    exact_ms: f64,
+    calls: u64,
+    sampled_ms: f64,
+    sampled: u64,
+}
+
+impl PhaseTime {
+    fn ms(&self) -> f64 {
+        let scaled = if self.sampled > 0 { self.sampled_ms / self.sampled as f64 * self.calls as f64 } else { 0.0 };
+        self.exact_ms + scaled
+    }
+}
+
+struct Recorder {
+    start: f64,
+    phases: [PhaseTime; 3],
+}
+
+thread_local! {
+    static ACTIVE: RefCell<Option<Recorder>> = const { RefCell::new(None) };
+}
+
+// performance.now() where the host has it (browsers, workers, Node), else Date.now()
+#[cfg(target_arch = "wasm32")]
+fn now_ms() -> f64 {
+    use wasm_bindgen::{JsCast, JsValue};
+    thread_local! {
+        static PERFORMANCE: Option<(JsValue, js_sys::Function)> = {
+            let perf = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("performance")).ok();
+            perf.filter(|p| p.is_object()).and_then(|p| {
+                let now = js_sys::Reflect::get(&p, &JsValue::from_str("now")).ok()?;
+                Some((p, now.dyn_into().ok()?))
+            })
+        };
+    }
+    PERFORMANCE.with(|p| match p {
+        Some((perf, now)) => now.call0(perf).ok().and_then(|v| v.as_f64()).unwrap_or_else(js_sys::Date::now),
+        None => js_sys::Date::now(),
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn now_ms() -> f64 {
+    thread_local! {
+        static EPOCH: std::time::Instant = std::time::Instant::now();
+    }
+    EPOCH.with(|e| e.elapsed().as_secs_f64() * 1000.0)
+}
+
+fn add(phase: Phase, f: impl FnOnce(&mut PhaseTime)) {
+    ACTIVE.with(|a| {
+        if let Some(r) = a.borrow_mut().as_mut() {
+            f(&mut r.phases[phase as usize]);
+        }
+    });
+}
+
+// Per-frame work of a phase; timed on every SAMPLE_EVERY-th call while a call is recorded
+pub(crate) fn sampled<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    let mut clocked = false;
+    add(phase, |p| {
+        clocked = p.calls % SAMPLE_EVERY == 0;
+        p.calls += 1;
+    });
+    if !clocked {
+        return f();
+    }
+    let t0 = now_ms();
+    let out = f();
+    let dt = now_ms() - t0;
+    add(phase, |p| {
+        p.sampled_ms += dt;
+        p.sampled += 1;
+    });
+    out
+}
+
+// One-off work of a phase, always timed
+pub(crate) fn span<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    let t0 = now_ms();
+    let out = f();
+    let dt = now_ms() - t0;
+    add(phase, |p| p.exact_ms += dt);
+    out
+}
+
+// Records the call until dropped, then stores the report in the slot. A call made
+// from inside another recorded call (e.g. the constructor from load_preview_smart())
+// is part of the outer one.
+pub(crate) struct Timed {
+    operation: &'static str,
+    slot: Slot,
+    accumulate: bool,
+    outer: bool,
+}
+
+impl Timed {
+    fn begin(operation: &'static str, slot: &Slot, accumulate: bool) -> Timed {
+        let outer = ACTIVE.with(|a| {
+            let mut a = a.borrow_mut();
+            let outer = a.is_none();
+            if outer {
+                *a = Some(Recorder { start: now_ms(), phases: [PhaseTime::default(); 3] });
+            }
+            outer
+        });
+        Timed { operation, slot: slot.clone(), accumulate, outer }
+    }
+}
+
+pub(crate) fn start(operation: &'static str, slot: &Slot) -> Timed {
+    Timed::begin(operation, slot, false)
+}
+
+// as start(), adding to the report already in the slot
+pub(crate) fn resume(operation: &'static str, slot: &Slot) -> Timed {
+    Timed::begin(operation, slot, true)
+}
+
+impl Drop for Timed {
+    fn drop(&mut self) {
+        if !self.outer {
+            return;
+        }
+        let Some(r) = ACTIVE.with(|a| a.borrow_mut().take()) else { return };
+        let [matching, decode, serialize] = r.phases.map(|p| p.ms());
+        let total = now_ms() - r.start;
+        let t = OperationTimings {
+            operation: self.operation,
+            total_ms: total,
+            blf_iteration_ms: (total - matching - decode - serialize).max(0.0),
+            dbc_matching_ms: matching,
+            signal_decode_ms: decode,
+            serialization_ms: serialize,
+        };
+        let t = match self.slot.take() {
+            Some(prev) if self.accumulate => prev.plus(t),
+            _ => t,
+        };
+        self.slot.set(Some(t));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampled_phases_scale_and_nest() {
+        let slot = Slot::default();
+        {
+            let _t = start("outer", &slot);
+            let _inner = start("inner", &Slot::default());
+            for _ in 0..100 {
+                sampled(Phase::Decode, || std::hint::black_box((0..2000).sum::<u64>()));
+            }
+            span(Phase::Serialize, || std::thread::sleep(std::time::Duration::from_millis(2)));
+        }
+        let t = slot.get().unwrap();
+        assert_eq!(t.operation, "outer");
+        assert!(t.serialization_ms >= 2.0);
+        assert!(t.signal_decode_ms > 0.0);
+        let parts = t.blf_iteration_ms + t.dbc_matching_ms + t.signal_decode_ms + t.serialization_ms;
+        assert!(parts >= t.total_ms - 1e-9);
+
+        // nothing recorded outside a call; resume() adds up
+        assert_eq!(sampled(Phase::Match, || 7), 7);
+        drop(resume("outer", &slot));
+        assert!(slot.get().unwrap().total_ms >= t.total_ms);
+    }
+}
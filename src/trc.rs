@@ -0,0 +1,252 @@
+// ###############################################################
+// trc.rs
+// can-blf-parser (WASM)
+// PEAK PCAN trace (.trc) reader for input.rs: file versions 1.0, 1.1, 2.0
+// and 2.1 (PCAN-View, PCAN-Basic). CAN, CAN FD, remote and error frames
+// become the BLF objects they stand for; status and event lines are skipped.
+// ###############################################################
+
+use crate::blf::{BlfObject, CanError, CanFrame, LogStart};
+use crate::export::WallClock;
+
+// CAN FD lengths of DLC 9..15
+const FD_LENGTHS: [usize; 7] = [12, 16, 20, 24, 32, 48, 64];
+
+// 1970-01-01 00:00, a Thursday: WallClock origin for $STARTTIME
+const UNIX_EPOCH: LogStart =
+    LogStart { year: 1970, month: 1, day_of_week: 4, day: 1, hour: 0, minute: 0, second: 0, millisecond: 0 };
+const OLE_TO_UNIX_DAYS: f64 = 25_569.0; // 1899-12-30 to 1970-01-01
+
+// -------------------------------
+// Column layout per version, as the $COLUMNS header letters: N message number, O time
+// offset (ms), T type, B bus, I id (hex), d direction, R reserved, L DLC, l length
+// (bytes), D data (hex, rest of the line). 1.x files have fixed columns; 2.x name them
+// in $COLUMNS, the defaults below being PEAK's.
+// -------------------------------
+fn default_columns(version: &str) -> Result<&'static str, String> {
+    match version {
+        "1.0" => Ok("NOILD"), // no type column: every frame is received
+        "1.1" => Ok("NOTILD"), // T carries the direction ("Rx", "Tx") or "Error" / "Warng"
+        "2.0" => Ok("NOTIdlD"),
+        "2.1" => Ok("NOTBIdRLD"),
+        v => Err(format!("PCAN trace version {} is not supported (1.0, 1.1, 2.0 and 2.1 are)", v)),
+    }
+}
+
+// PCAN-View writes ";$FILEVERSION=..." first; version 1.0 files open with comment lines
+pub(crate) fn detect(bytes: &[u8]) -> bool {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(4096)]);
+    head.trim_start().starts_with(';') && (head.contains(";$FILEVERSION=") || head.contains("PCAN"))
+}
+
+fn dlc_of_length(len: usize) -> u8 {
+    match FD_LENGTHS.iter().position(|&l| l >= len) {
+        Some(i) if len > 8 => 9 + i as u8,
+        _ => len.min(8) as u8,
+    }
+}
+
+fn length_of_dlc(dlc: u8, fd: bool) -> usize {
+    match dlc {
+        9..=15 if fd => FD_LENGTHS[dlc as usize - 9],
+        _ => (dlc as usize).min(8),
+    }
+}
+
+// Error frame data bytes: error type (1 bit, 2 form, 4 stuff, 8 other), direction
+// (0 Tx, 1 Rx), position in the frame, RX and TX error counters
+fn error_frame(timestamp_ns: u64, channel: u16, data: &[&str]) -> CanError {
+    let byte = |i: usize| data.get(i).and_then(|b| u8::from_str_radix(b, 16).ok());
+    let error_type = byte(0).and_then(|t| match t {
+        1 => Some("Bit"),
+        2 => Some("Form"),
+        4 => Some("Stuff"),
+        8 => Some("Other"),
+        _ => None,
+    });
+    CanError {
+        timestamp_ns,
+        channel,
+        id: 0,
+        dlc: 0,
+        data: Vec::new(),
+        fd: false,
+        error_type,
+        tx: byte(1).and_then(|d| (d <= 1).then_some(d == 0)),
+        position: byte(2).map(u16::from),
+        ecc: None,
+        rx_errors: byte(3),
+        tx_errors: byte(4),
+    }
+}
+
+// One trace line by `columns`; None for lines that carry no frame
+fn frame(tok: &[&str], columns: &[u8], v1: bool) -> Option<BlfObject> {
+    let (mut time_ms, mut kind, mut bus, mut id, mut dir) = (0.0f64, "Rx", 1u16, "", "Rx");
+    let (mut dlc, mut len, mut data): (Option<u8>, Option<usize>, &[&str]) = (None, None, &[]);
+    // one token per column, the data bytes last
+    for (i, &c) in columns.iter().enumerate() {
+        if c == b'D' {
+            data = tok.get(i..).unwrap_or(&[]);
+            break;
+        }
+        let t = *tok.get(i)?;
+        match c {
+            b'O' => time_ms = t.parse().ok()?,
+            b'T' => kind = t,
+            b'B' => bus = t.parse().ok()?,
+            b'I' => id = t,
+            b'd' => dir = t,
+            b'L' => dlc = t.parse().ok().or_else(|| u8::from_str_radix(t, 16).ok()),
+            b'l' => len = t.parse().ok(),
+            _ => {} // N, R
+        }
+    }
+    let timestamp_ns = (time_ms.max(0.0) * 1e6).round() as u64;
+    // 1.1 keeps the direction in the type column
+    if v1 && matches!(kind, "Rx" | "Tx") {
+        dir = kind;
+        kind = "DT";
+    }
+    let (fd, brs, esi, rtr) = match kind {
+        "DT" => (false, false, false, data.first() == Some(&"RTR")),
+        "RR" => (false, false, false, true),
+        "FD" => (true, false, false, false),
+        "FB" => (true, true, false, false),
+        "FE" => (true, false, true, false),
+        "BI" => (true, true, true, false),
+        "ER" | "Error" => return Some(BlfObject::Error(error_frame(timestamp_ns, bus, data))),
+        _ => return None, // ST status, EC error counters, EV events, Warng
+    };
+
+    // 29-bit ids are written with 8 digits, 11-bit ones with 4
+    let raw = u32::from_str_radix(id, 16).ok()?;
+    let id = if id.len() > 4 || raw > 0x7FF { raw | 0x8000_0000 } else { raw };
+    let dlc = dlc.or(len.map(dlc_of_length))?;
+    let len = if rtr { 0 } else { len.unwrap_or_else(|| length_of_dlc(dlc, fd)) };
+    let bytes = data.get(..len)?.iter().map(|b| u8::from_str_radix(b, 16).ok()).collect::<Option<Vec<u8>>>()?;
+    Some(BlfObject::Can(CanFrame {
+        timestamp_ns,
+        channel: bus,
+        id,
+        dlc,
+        data: bytes,
+        tx: dir == "Tx",
+        tx_request: false,
+        rtr,
+        wakeup: false,
+        nerr: false,
+        fd,
+        brs,
+        esi,
+    }))
+}
+
+pub(crate) fn read(bytes: &[u8]) -> Result<Vec<BlfObject>, String> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut version = "1.0".to_string();
+    let mut named: Option<Vec<u8>> = None; // $COLUMNS
+    let mut columns: Option<Vec<u8>> = None; // fixed at the first frame line
+    let mut objects = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if let Some((key, value)) = line.strip_prefix(";$").and_then(|h| h.split_once('=')) {
+            match key {
+                "FILEVERSION" => version = value.trim().to_string(),
+                "COLUMNS" => named = Some(value.split(',').filter_map(|c| c.trim().bytes().next()).collect()),
+                _ => {}
+            }
+            continue;
+        }
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+        if columns.is_none() {
+            columns = Some(match named.take() {
+                Some(c) if version.starts_with('2') => c,
+                _ => default_columns(&version)?.as_bytes().to_vec(),
+            });
+        }
+        let Some(columns) = &columns else { continue };
+        let tok: Vec<&str> = line.split_whitespace().collect();
+        objects.extend(frame(&tok, columns, version.starts_with('1')));
+    }
+    Ok(objects)
+}
+
+// $STARTTIME (days since 1899-12-30, local time), else the "Start time: 12/18/2021
+// 14:28:07.062.0" comment of older files
+pub(crate) fn start(bytes: &[u8]) -> Option<LogStart> {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(4096)]);
+    let origin = WallClock::new(&UNIX_EPOCH);
+    for line in head.lines().map(str::trim).take_while(|l| l.starts_with(';') || l.is_empty()) {
+        if let Some(days) = line.strip_prefix(";$STARTTIME=").and_then(|v| v.trim().parse::<f64>().ok()) {
+            return Some(origin.system_time((days - OLE_TO_UNIX_DAYS) * 86_400.0));
+        }
+        if let Some(rest) = line.trim_start_matches(';').trim().strip_prefix("Start time:") {
+            let mut parts = rest.split_whitespace();
+            let mut date = parts.next()?.split('/').map(|p| p.parse::<u16>().ok());
+            let (month, day, year) = (date.next()??, date.next()??, date.next()??);
+            let mut time = parts.next()?.split(['.', ':']).map(|p| p.parse::<u16>().ok());
+            let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+            let millisecond = time.next().flatten().unwrap_or(0);
+            let start = LogStart { year, month, day_of_week: 0, day, hour, minute, second, millisecond };
+            // system_time() fills in the day of week
+            return (1..=12).contains(&month).then(|| WallClock::new(&start).system_time(0.0));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn can(obj: &BlfObject) -> &CanFrame {
+        match obj {
+            BlfObject::Can(cf) => cf,
+            _ => panic!("CAN frame expected"),
+        }
+    }
+
+    #[test]
+    fn versions_and_start_time() {
+        let v11 = ";$FILEVERSION=1.1\n;$STARTTIME=44548.6028595139\n;\n\
+                   ;---+--   ----+----  --+--  ----+---  +  -+ -- -- --\n\
+                        1)      2850.8  Rx         0300  8  00 01 02 03 04 05 06 07\n\
+                        2)      2851.4  Tx     18EFC862  2  AA BB\n\
+                        3)      2852.0  Rx         0100  4  RTR\n\
+                        4)      2853.0  Warng  FFFFFFFF  4  00 00 00 08 BUSHEAVY\n";
+        assert!(detect(v11.as_bytes()));
+        let objects = read(v11.as_bytes()).unwrap();
+        assert_eq!(objects.len(), 3);
+        assert_eq!((can(&objects[0]).id, can(&objects[0]).timestamp_ns, can(&objects[0]).data.len()), (0x300, 2_850_800_000, 8));
+        assert_eq!((can(&objects[1]).id, can(&objects[1]).tx), (0x18EFC862 | 0x8000_0000, true));
+        assert!(can(&objects[2]).rtr && can(&objects[2]).data.is_empty() && can(&objects[2]).dlc == 4);
+        let s = start(v11.as_bytes()).unwrap();
+        assert_eq!((s.year, s.month, s.day, s.hour, s.minute, s.second, s.day_of_week), (2021, 12, 18, 14, 28, 7, 6));
+
+        let v21 = ";$FILEVERSION=2.1\n;$STARTTIME=43474.6288583218\n;$COLUMNS=N,O,T,B,I,d,R,L,D\n\
+                        1      1059.900 DT     2      0300 Rx -  7    00 00 00 00 04 00 00\n\
+                        2      1283.231 FB     1  18EFC862 Tx -  9    00 11 22 33 44 55 66 77 88 99 AA BB\n\
+                        3      1300.000 ST     1  Rx    00 00 00 08\n\
+                        4      1310.000 ER     1  -  Rx -  5    04 00 02 00 7F\n";
+        let objects = read(v21.as_bytes()).unwrap();
+        assert_eq!(objects.len(), 3);
+        assert_eq!((can(&objects[0]).channel, can(&objects[0]).data.len()), (2, 7));
+        let fd = can(&objects[1]);
+        assert!(fd.fd && fd.brs && !fd.esi && fd.dlc == 9 && fd.data.len() == 12 && fd.data[11] == 0xBB);
+        let BlfObject::Error(e) = &objects[2] else { panic!("error frame expected") };
+        assert_eq!(e.timestamp_ns, 1_310_000_000);
+        assert_eq!((e.error_type, e.tx, e.position, e.rx_errors, e.tx_errors), (Some("Stuff"), Some(true), Some(2), Some(0), Some(0x7F)));
+
+        let v20 = ";$FILEVERSION=2.0\n;$COLUMNS=N,O,T,I,d,l,D\n      1      1.000 FD 0123 Rx 20 ".to_string() + &"01 ".repeat(20);
+        let fd = can(&read(v20.as_bytes()).unwrap()[0]).clone();
+        assert_eq!((fd.dlc, fd.data.len(), fd.channel), (11, 20, 1));
+
+        assert!(read(b";$FILEVERSION=1.3\n 1) 1.0 1 Rx 0300 - 1 00\n").is_err());
+        let old = ";##########\n;   Start time: 12/18/2021 14:28:07.062.0\n;   Generated by PCAN-View v3\n   1)  1841  0001  1  AB\n";
+        assert!(detect(old.as_bytes()));
+        assert_eq!(can(&read(old.as_bytes()).unwrap()[0]).data, [0xAB]);
+        assert_eq!(start(old.as_bytes()).unwrap().millisecond, 62);
+    }
+}
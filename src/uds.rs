@@ -0,0 +1,225 @@
+// ###############################################################
+// uds.rs
+// can-blf-parser (WASM)
+// UDS (ISO 14229) on reassembled ISO-TP payloads, for diagnostics(): service,
+// sub-function, DIDs / routine ids, negative response codes and
+// request/response pairing with response times
+// ###############################################################
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::isotp::{Direction, TransportMessage};
+
+const NEGATIVE_RESPONSE: u8 = 0x7F;
+const NRC_RESPONSE_PENDING: u8 = 0x78;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct UdsTransaction {
+    pub pair: usize, // index into options.pairs
+    pub channel: String,
+    pub request_time: f64,
+    pub response_time: Option<f64>, // final response (after any "response pending")
+    pub response_ms: Option<f64>,
+    pub service: u8,
+    pub service_name: String,
+    pub sub_function: Option<u8>, // suppress-positive-response bit masked off
+    pub suppress_response: bool,
+    pub identifiers: Vec<u16>, // DIDs (0x22 may list several), routine id for 0x31
+    pub positive: Option<bool>, // None: no response
+    pub nrc: Option<u8>,
+    pub nrc_name: Option<String>,
+    pub pending: usize, // NRC 0x78 "response pending" before the final response
+    pub request: Vec<u8>,
+    pub response: Vec<u8>,
+}
+
+pub(crate) fn service_name(sid: u8) -> &'static str {
+    match sid {
+        0x10 => "DiagnosticSessionControl",
+        0x11 => "ECUReset",
+        0x14 => "ClearDiagnosticInformation",
+        0x19 => "ReadDTCInformation",
+        0x22 => "ReadDataByIdentifier",
+        0x23 => "ReadMemoryByAddress",
+        0x24 => "ReadScalingDataByIdentifier",
+        0x27 => "SecurityAccess",
+        0x28 => "CommunicationControl",
+        0x29 => "Authentication",
+        0x2A => "ReadDataByPeriodicIdentifier",
+        0x2C => "DynamicallyDefineDataIdentifier",
+        0x2E => "WriteDataByIdentifier",
+        0x2F => "InputOutputControlByIdentifier",
+        0x31 => "RoutineControl",
+        0x34 => "RequestDownload",
+        0x35 => "RequestUpload",
+        0x36 => "TransferData",
+        0x37 => "RequestTransferExit",
+        0x38 => "RequestFileTransfer",
+        0x3D => "WriteMemoryByAddress",
+        0x3E => "TesterPresent",
+        0x83 => "AccessTimingParameter",
+        0x84 => "SecuredDataTransmission",
+        0x85 => "ControlDTCSetting",
+        0x86 => "ResponseOnEvent",
+        0x87 => "LinkControl",
+        _ => "",
+    }
+}
+
+pub(crate) fn nrc_name(nrc: u8) -> &'static str {
+    match nrc {
+        0x10 => "generalReject",
+        0x11 => "serviceNotSupported",
+        0x12 => "subFunctionNotSupported",
+        0x13 => "incorrectMessageLengthOrInvalidFormat",
+        0x14 => "responseTooLong",
+        0x21 => "busyRepeatRequest",
+        0x22 => "conditionsNotCorrect",
+        0x24 => "requestSequenceError",
+        0x25 => "noResponseFromSubnetComponent",
+        0x26 => "failurePreventsExecutionOfRequestedAction",
+        0x31 => "requestOutOfRange",
+        0x33 => "securityAccessDenied",
+        0x35 => "invalidKey",
+        0x36 => "exceedNumberOfAttempts",
+        0x37 => "requiredTimeDelayNotExpired",
+        0x70 => "uploadDownloadNotAccepted",
+        0x71 => "transferDataSuspended",
+        0x72 => "generalProgrammingFailure",
+        0x73 => "wrongBlockSequenceCounter",
+        0x78 => "requestCorrectlyReceivedResponsePending",
+        0x7E => "subFunctionNotSupportedInActiveSession",
+        0x7F => "serviceNotSupportedInActiveSession",
+        0x92 => "voltageTooHigh",
+        0x93 => "voltageTooLow",
+        _ => "",
+    }
+}
+
+fn has_sub_function(sid: u8) -> bool {
+    matches!(sid, 0x10 | 0x11 | 0x19 | 0x27 | 0x28 | 0x29 | 0x31 | 0x3E | 0x83 | 0x85 | 0x86 | 0x87)
+}
+
+fn u16_at(data: &[u8], i: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*data.get(i)?, *data.get(i + 1)?]))
+}
+
+fn request(pair: usize, msg: &TransportMessage) -> Option<UdsTransaction> {
+    let data = &msg.data;
+    let sid = *data.first()?;
+    let sub = has_sub_function(sid).then(|| data.get(1).copied()).flatten();
+    let identifiers = match sid {
+        0x22 => data[1..].chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect(),
+        0x24 | 0x2E | 0x2F => u16_at(data, 1).into_iter().collect(),
+        0x31 => u16_at(data, 2).into_iter().collect(),
+        _ => Vec::new(),
+    };
+    Some(UdsTransaction {
+        pair,
+        channel: msg.channel.clone(),
+        request_time: msg.timestamp,
+        response_time: None,
+        response_ms: None,
+        service: sid,
+        service_name: service_name(sid).to_string(),
+        sub_function: sub.map(|s| s & 0x7F),
+        suppress_response: sub.is_some_and(|s| s & 0x80 != 0),
+        identifiers,
+        positive: None,
+        nrc: None,
+        nrc_name: None,
+        pending: 0,
+        request: data.clone(),
+        response: Vec::new(),
+    })
+}
+
+// Complete transport messages in time order -> one row per request. A response closes
+// the pair's open request with the same service; a new request closes an unanswered one.
+// Responses without a matching request are skipped.
+pub(crate) fn transactions(messages: &[TransportMessage]) -> Vec<UdsTransaction> {
+    let mut open: HashMap<usize, UdsTransaction> = HashMap::new();
+    let mut out = Vec::new();
+    for msg in messages.iter().filter(|m| m.complete) {
+        match msg.direction {
+            Direction::Request => {
+                if let Some(t) = open.remove(&msg.pair) {
+                    out.push(t);
+                }
+                if let Some(t) = request(msg.pair, msg) {
+                    open.insert(msg.pair, t);
+                }
+            }
+            Direction::Response => {
+                let Some(&sid) = msg.data.first() else { continue };
+                let (service, nrc) = if sid == NEGATIVE_RESPONSE {
+                    (msg.data.get(1).copied(), msg.data.get(2).copied())
+                } else {
+                    (sid.checked_sub(0x40), None)
+                };
+                let Some(t) = open.get_mut(&msg.pair).filter(|t| Some(t.service) == service) else { continue };
+                if nrc == Some(NRC_RESPONSE_PENDING) {
+                    t.pending += 1;
+                    continue;
+                }
+                let mut t = open.remove(&msg.pair).expect("present");
+                t.response_time = Some(msg.timestamp);
+                t.response_ms = Some((msg.timestamp - t.request_time) * 1000.0);
+                t.positive = Some(nrc.is_none());
+                t.nrc = nrc;
+                t.nrc_name = nrc.map(|n| nrc_name(n).to_string());
+                t.response = msg.data.clone();
+                out.push(t);
+            }
+        }
+    }
+    out.extend(open.into_values());
+    out.sort_by(|a, b| a.request_time.total_cmp(&b.request_time));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(t: f64, direction: Direction, data: &[u8]) -> TransportMessage {
+        TransportMessage {
+            pair: 0,
+            direction,
+            channel: "CAN1".to_string(),
+            id: 0,
+            is_extended: false,
+            dir: "Rx".to_string(),
+            timestamp: t,
+            end_timestamp: t,
+            address: None,
+            length: data.len(),
+            data: data.to_vec(),
+            frames: 1,
+            complete: true,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn pairing_and_nrc() {
+        let out = transactions(&[
+            msg(0.000, Direction::Request, &[0x22, 0xF1, 0x90, 0xF1, 0x8C]),
+            msg(0.020, Direction::Response, &[0x62, 0xF1, 0x90, b'W']),
+            msg(1.000, Direction::Request, &[0x31, 0x01, 0xFF, 0x00]),
+            msg(1.010, Direction::Response, &[0x7F, 0x31, 0x78]),
+            msg(1.500, Direction::Response, &[0x7F, 0x31, 0x22]),
+            msg(2.000, Direction::Request, &[0x3E, 0x80]),
+        ]);
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[0].identifiers, [0xF190, 0xF18C]);
+        assert_eq!(out[0].positive, Some(true));
+        assert!((out[0].response_ms.unwrap() - 20.0).abs() < 1e-9);
+        assert_eq!((out[1].sub_function, out[1].identifiers.as_slice()), (Some(1), &[0xFF00][..]));
+        assert_eq!((out[1].positive, out[1].nrc, out[1].pending), (Some(false), Some(0x22), 1));
+        assert_eq!(out[1].nrc_name.as_deref(), Some("conditionsNotCorrect"));
+        assert!(out[2].suppress_response && out[2].positive.is_none());
+    }
+}
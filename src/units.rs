@@ -0,0 +1,178 @@
+// ###############################################################
+// units.rs
+// can-blf-parser (WASM)
+// DBC unit string -> SI conversion table
+// ###############################################################
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+// si_value = value * factor + offset
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UnitConversion {
+    pub si_unit: String,
+    #[serde(deserialize_with = "crate::numeric::f64")]
+    pub factor: f64,
+    #[serde(default, deserialize_with = "crate::numeric::f64")]
+    pub offset: f64,
+}
+
+impl UnitConversion {
+    pub(crate) fn apply(&self, value: f64) -> f64 {
+        value * self.factor + self.offset
+    }
+}
+
+pub(crate) struct UnitTable {
+    table: HashMap<String, UnitConversion>,
+}
+
+impl UnitTable {
+    // Built-in table, then user entries on top (same key replaces the built-in)
+    pub(crate) fn with_overrides(overrides: &HashMap<String, UnitConversion>) -> UnitTable {
+        let mut table = builtin();
+        for (unit, conv) in overrides {
+            table.insert(unit.trim().to_string(), conv.clone());
+        }
+        UnitTable { table }
+    }
+
+    // Exact match first, then case-insensitive ("KM/H", "Kph") - but only when
+    // unambiguous, so "mw" can't pick between mW and MW and "H" stays Henry.
+    pub(crate) fn lookup(&self, unit: &str) -> Option<&UnitConversion> {
+        let unit = unit.trim();
+        if unit.is_empty() {
+            return None;
+        }
+        if let Some(conv) = self.table.get(unit) {
+            return Some(conv);
+        }
+        if unit.chars().count() < 2 {
+            return None;
+        }
+        let lower = unit.to_lowercase();
+        let mut hits = self.table.iter().filter(|(k, _)| k.to_lowercase() == lower);
+        match (hits.next(), hits.next()) {
+            (Some((_, conv)), None) => Some(conv),
+            _ => None,
+        }
+    }
+}
+
+fn builtin() -> HashMap<String, UnitConversion> {
+    use std::f64::consts::PI;
+    let entries: &[(&str, &str, f64, f64)] = &[
+        // speed
+        ("km/h", "m/s", 1.0 / 3.6, 0.0),
+        ("kph", "m/s", 1.0 / 3.6, 0.0),
+        ("kmh", "m/s", 1.0 / 3.6, 0.0),
+        ("mph", "m/s", 0.44704, 0.0),
+        // pressure
+        ("bar", "Pa", 1e5, 0.0),
+        ("mbar", "Pa", 100.0, 0.0),
+        ("hPa", "Pa", 100.0, 0.0),
+        ("kPa", "Pa", 1e3, 0.0),
+        ("MPa", "Pa", 1e6, 0.0),
+        ("psi", "Pa", 6894.757293168, 0.0),
+        // temperature
+        ("degC", "K", 1.0, 273.15),
+        ("°C", "K", 1.0, 273.15),
+        ("deg C", "K", 1.0, 273.15),
+        ("C", "K", 1.0, 273.15),
+        ("degF", "K", 5.0 / 9.0, 273.15 - 32.0 * 5.0 / 9.0),
+        ("°F", "K", 5.0 / 9.0, 273.15 - 32.0 * 5.0 / 9.0),
+        // angle / rotation
+        ("deg", "rad", PI / 180.0, 0.0),
+        ("°", "rad", PI / 180.0, 0.0),
+        ("deg/s", "rad/s", PI / 180.0, 0.0),
+        ("°/s", "rad/s", PI / 180.0, 0.0),
+        ("rpm", "rad/s", 2.0 * PI / 60.0, 0.0),
+        ("1/min", "rad/s", 2.0 * PI / 60.0, 0.0),
+        // time
+        ("ms", "s", 1e-3, 0.0),
+        ("us", "s", 1e-6, 0.0),
+        ("µs", "s", 1e-6, 0.0),
+        ("min", "s", 60.0, 0.0),
+        ("h", "s", 3600.0, 0.0),
+        // electrical
+        ("mA", "A", 1e-3, 0.0),
+        ("kA", "A", 1e3, 0.0),
+        ("mV", "V", 1e-3, 0.0),
+        ("kV", "V", 1e3, 0.0),
+        ("mW", "W", 1e-3, 0.0),
+        ("kW", "W", 1e3, 0.0),
+        ("MW", "W", 1e6, 0.0),
+        ("Wh", "J", 3600.0, 0.0),
+        ("kWh", "J", 3.6e6, 0.0),
+        ("Ah", "C", 3600.0, 0.0),
+        ("mAh", "C", 3.6, 0.0),
+        ("mOhm", "Ohm", 1e-3, 0.0),
+        ("kOhm", "Ohm", 1e3, 0.0),
+        // length / mass / volume / force
+        ("km", "m", 1e3, 0.0),
+        ("cm", "m", 1e-2, 0.0),
+        ("mm", "m", 1e-3, 0.0),
+        ("g", "kg", 1e-3, 0.0),
+        ("l", "m^3", 1e-3, 0.0),
+        ("ml", "m^3", 1e-6, 0.0),
+        ("kN", "N", 1e3, 0.0),
+        ("kNm", "N*m", 1e3, 0.0),
+        ("Nm", "N*m", 1.0, 0.0),
+        ("g_n", "m/s^2", 9.80665, 0.0),
+    ];
+    entries
+        .iter()
+        .map(|(unit, si, factor, offset)| {
+            (
+                unit.to_string(),
+                UnitConversion { si_unit: si.to_string(), factor: *factor, offset: *offset },
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conv(si_unit: &str, factor: f64) -> UnitConversion {
+        UnitConversion { si_unit: si_unit.to_string(), factor, offset: 0.0 }
+    }
+
+    #[test]
+    fn case_insensitive_only_when_unambiguous() {
+        let table = UnitTable::with_overrides(&HashMap::new());
+        let factor = |unit: &str| table.lookup(unit).map(|c| c.factor);
+        assert_eq!(factor("KPH"), Some(1.0 / 3.6));
+        assert_eq!(factor(" KM/H "), Some(1.0 / 3.6));
+        // mW and MW differ only in case: exact spellings work, others don't guess
+        assert_eq!(factor("mW"), Some(1e-3));
+        assert_eq!(factor("MW"), Some(1e6));
+        assert_eq!(factor("mw"), None);
+        // single characters match exactly only ("H" is Henry, not hours)
+        assert_eq!(factor("h"), Some(3600.0));
+        assert_eq!(factor("H"), None);
+        assert_eq!(factor(""), None);
+    }
+
+    #[test]
+    fn temperature_offsets() {
+        let table = UnitTable::with_overrides(&HashMap::new());
+        let si = |unit: &str, v: f64| table.lookup(unit).map(|c| c.apply(v)).unwrap();
+        assert!((si("degC", 25.0) - 298.15).abs() < 1e-9);
+        assert!((si("degF", 212.0) - 373.15).abs() < 1e-9);
+        assert!((si("degF", -40.0) - si("degC", -40.0)).abs() < 1e-9);
+        assert_eq!(table.lookup("°F").unwrap().si_unit, "K");
+    }
+
+    #[test]
+    fn overrides_replace_builtins() {
+        let overrides = HashMap::from([(" km/h ".to_string(), conv("m/s", 0.25)), ("pct".to_string(), conv("1", 0.01))]);
+        let table = UnitTable::with_overrides(&overrides);
+        assert_eq!(table.lookup("km/h").unwrap().factor, 0.25);
+        assert_eq!(table.lookup("PCT").unwrap().factor, 0.01);
+        // built-ins the user did not touch stay
+        assert_eq!(table.lookup("kph").unwrap().factor, 1.0 / 3.6);
+    }
+}
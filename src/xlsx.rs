@@ -0,0 +1,201 @@
+// ###############################################################
+// xlsx.rs
+// can-blf-parser (WASM)
+// Office Open XML workbook for export_xlsx(): worksheets of numeric and
+// inline-string cells with a bold, frozen header row, zipped (zip.rs).
+// ###############################################################
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::zip;
+
+pub(crate) const MAX_ROWS: usize = 1_048_576; // per sheet, header included
+pub(crate) const MAX_COLUMNS: usize = 16_384;
+
+const MAIN_NS: &str = "http://schemas.openxmlformats.org/spreadsheetml/2006/main";
+const REL_NS: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+const PACKAGE_REL_NS: &str = "http://schemas.openxmlformats.org/package/2006/relationships";
+const XML_DECL: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n";
+
+pub(crate) enum Cell {
+    Empty,
+    Number(f64), // non-finite values are left empty
+    Text(String),
+}
+
+pub(crate) struct Sheet {
+    pub name: String,
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<Cell>>, // may be shorter than the header: missing cells are empty
+}
+
+// Excel's sheet name rules: at most 31 characters, none of []:*?/\, no leading or
+// trailing apostrophe, unique ignoring case
+pub(crate) fn sheet_name(raw: &str, taken: &mut HashSet<String>) -> String {
+    let clean: String = raw.chars().map(|c| if "[]:*?/\\".contains(c) { '_' } else { c }).collect();
+    let clean = clean.trim_matches('\'');
+    let base = if clean.is_empty() { "Sheet" } else { clean };
+    let mut n = 1;
+    loop {
+        let suffix = if n == 1 { String::new() } else { format!(" ({})", n) };
+        let keep = 31 - suffix.len();
+        let name: String = base.chars().take(keep).chain(suffix.chars()).collect();
+        if taken.insert(name.to_lowercase()) {
+            return name;
+        }
+        n += 1;
+    }
+}
+
+// A, B, ..., Z, AA, ...
+fn column(mut i: usize) -> String {
+    let mut letters = Vec::new();
+    loop {
+        letters.push(b'A' + (i % 26) as u8);
+        if i < 26 {
+            break;
+        }
+        i = i / 26 - 1;
+    }
+    letters.iter().rev().map(|&b| b as char).collect()
+}
+
+// text content / attribute value; characters XML 1.0 cannot carry are dropped
+fn escape(out: &mut String, s: &str) {
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            c if (c as u32) < 0x20 || c == '\u{FFFE}' || c == '\u{FFFF}' => {}
+            c => out.push(c),
+        }
+    }
+}
+
+fn text_cell(out: &mut String, r: &str, style: &str, s: &str) {
+    let _ = write!(out, "<c r=\"{}\" t=\"inlineStr\"{}><is><t xml:space=\"preserve\">", r, style);
+    escape(out, s);
+    out.push_str("</t></is></c>");
+}
+
+fn worksheet(sheet: &Sheet) -> Vec<u8> {
+    let letters: Vec<String> = (0..sheet.header.len().max(sheet.rows.iter().map(Vec::len).max().unwrap_or(0)))
+        .map(column)
+        .collect();
+    let mut out = String::from(XML_DECL);
+    let _ = write!(
+        out,
+        "<worksheet xmlns=\"{}\"><sheetViews><sheetView workbookViewId=\"0\">\
+         <pane ySplit=\"1\" topLeftCell=\"A2\" activePane=\"bottomLeft\" state=\"frozen\"/>\
+         </sheetView></sheetViews><sheetData><row r=\"1\">",
+        MAIN_NS
+    );
+    for (i, h) in sheet.header.iter().enumerate() {
+        text_cell(&mut out, &format!("{}1", letters[i]), " s=\"1\"", h);
+    }
+    out.push_str("</row>");
+    for (r, row) in sheet.rows.iter().enumerate() {
+        let r = r + 2;
+        let _ = write!(out, "<row r=\"{}\">", r);
+        for (i, cell) in row.iter().enumerate() {
+            match cell {
+                Cell::Number(x) if x.is_finite() => {
+                    let _ = write!(out, "<c r=\"{}{}\"><v>{}</v></c>", letters[i], r, x);
+                }
+                Cell::Text(s) => text_cell(&mut out, &format!("{}{}", letters[i], r), "", s),
+                _ => {}
+            }
+        }
+        out.push_str("</row>");
+    }
+    out.push_str("</sheetData></worksheet>");
+    out.into_bytes()
+}
+
+// one font plus its bold variant: cell style 1 is the header
+const STYLES: &str = "<fonts count=\"2\"><font><sz val=\"11\"/><name val=\"Calibri\"/></font>\
+<font><b/><sz val=\"11\"/><name val=\"Calibri\"/></font></fonts>\
+<fills count=\"2\"><fill><patternFill patternType=\"none\"/></fill><fill><patternFill patternType=\"gray125\"/></fill></fills>\
+<borders count=\"1\"><border><left/><right/><top/><bottom/><diagonal/></border></borders>\
+<cellStyleXfs count=\"1\"><xf numFmtId=\"0\" fontId=\"0\" fillId=\"0\" borderId=\"0\"/></cellStyleXfs>\
+<cellXfs count=\"2\"><xf numFmtId=\"0\" fontId=\"0\" fillId=\"0\" borderId=\"0\" xfId=\"0\"/>\
+<xf numFmtId=\"0\" fontId=\"1\" fillId=\"0\" borderId=\"0\" xfId=\"0\" applyFont=\"1\"/></cellXfs>\
+<cellStyles count=\"1\"><cellStyle name=\"Normal\" xfId=\"0\" builtinId=\"0\"/></cellStyles>";
+
+// The .xlsx package: content types, relationships, workbook, styles and one part per sheet
+pub(crate) fn workbook(sheets: &[Sheet]) -> Result<Vec<u8>, String> {
+    let ct = |part: &str, kind: &str| {
+        format!("<Override PartName=\"/xl/{}\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.{}+xml\"/>", part, kind)
+    };
+    let mut types = format!(
+        "{}<Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\
+         <Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>\
+         <Default Extension=\"xml\" ContentType=\"application/xml\"/>{}{}",
+        XML_DECL,
+        ct("workbook.xml", "sheet.main"),
+        ct("styles.xml", "styles")
+    );
+    let mut book = format!("{}<workbook xmlns=\"{}\" xmlns:r=\"{}\"><sheets>", XML_DECL, MAIN_NS, REL_NS);
+    let mut rels = format!("{}<Relationships xmlns=\"{}\">", XML_DECL, PACKAGE_REL_NS);
+    for (i, sheet) in sheets.iter().enumerate() {
+        let n = i + 1;
+        types.push_str(&ct(&format!("worksheets/sheet{}.xml", n), "worksheet"));
+        book.push_str("<sheet name=\"");
+        escape(&mut book, &sheet.name);
+        let _ = write!(book, "\" sheetId=\"{}\" r:id=\"rId{}\"/>", n, n);
+        let _ = write!(rels, "<Relationship Id=\"rId{}\" Type=\"{}/worksheet\" Target=\"worksheets/sheet{}.xml\"/>", n, REL_NS, n);
+    }
+    types.push_str("</Types>");
+    book.push_str("</sheets></workbook>");
+    let _ = write!(rels, "<Relationship Id=\"rId{}\" Type=\"{}/styles\" Target=\"styles.xml\"/></Relationships>", sheets.len() + 1, REL_NS);
+    let root_rels = format!(
+        "{}<Relationships xmlns=\"{}\"><Relationship Id=\"rId1\" Type=\"{}/officeDocument\" Target=\"xl/workbook.xml\"/></Relationships>",
+        XML_DECL, PACKAGE_REL_NS, REL_NS
+    );
+    let styles = format!("{}<styleSheet xmlns=\"{}\">{}</styleSheet>", XML_DECL, MAIN_NS, STYLES);
+
+    let names: Vec<String> = (1..=sheets.len()).map(|n| format!("xl/worksheets/sheet{}.xml", n)).collect();
+    let mut entries: Vec<(&str, Vec<u8>)> = vec![
+        ("[Content_Types].xml", types.into_bytes()),
+        ("_rels/.rels", root_rels.into_bytes()),
+        ("xl/workbook.xml", book.into_bytes()),
+        ("xl/_rels/workbook.xml.rels", rels.into_bytes()),
+        ("xl/styles.xml", styles.into_bytes()),
+    ];
+    for (name, sheet) in names.iter().zip(sheets) {
+        entries.push((name, worksheet(sheet)));
+    }
+    zip::archive(&entries, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_and_columns() {
+        let mut taken = HashSet::new();
+        assert_eq!(sheet_name("Engine/Data[1]", &mut taken), "Engine_Data_1_");
+        assert_eq!(sheet_name("engine/data[1]", &mut taken), "engine_data_1_ (2)");
+        let long = "A_Very_Long_Message_Name_Exceeding_Limits";
+        assert_eq!(sheet_name(long, &mut taken).chars().count(), 31);
+        assert_eq!(sheet_name(long, &mut taken), "A_Very_Long_Message_Name_Ex (2)");
+        assert_eq!(sheet_name("''", &mut taken), "Sheet");
+        assert_eq!([column(0), column(25), column(26), column(701), column(16_383)], ["A", "Z", "AA", "ZZ", "XFD"]);
+
+        let sheet = Sheet {
+            name: "M".into(),
+            header: vec!["Time [s]".into(), "Speed [km/h]".into()],
+            rows: vec![vec![Cell::Number(0.5), Cell::Text("a<b\u{1}".into())], vec![Cell::Empty, Cell::Number(f64::NAN)]],
+        };
+        let xml = String::from_utf8(worksheet(&sheet)).unwrap();
+        assert!(xml.contains("<row r=\"2\"><c r=\"A2\"><v>0.5</v></c><c r=\"B2\" t=\"inlineStr\"><is><t xml:space=\"preserve\">a&lt;b</t></is></c></row><row r=\"3\"></row>"));
+        let zip = workbook(&[sheet]).unwrap();
+        assert_eq!(&zip[..4], b"PK\x03\x04");
+        assert_eq!(&zip[zip.len() - 22..zip.len() - 18], b"PK\x05\x06");
+    }
+}
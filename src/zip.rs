@@ -0,0 +1,135 @@
+// ###############################################################
+// zip.rs
+// can-blf-parser (WASM)
+// Minimal ZIP archive writer for the XLSX package (export_xlsx()) and NPZ
+// arrays (export_npz()): deflated (deflate.rs) or stored entries, central
+// directory, no ZIP64.
+// ###############################################################
+
+use crate::deflate;
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+};
+
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |c, &b| CRC_TABLE[((c ^ b as u32) & 0xFF) as usize] ^ (c >> 8))
+}
+
+const DOS_DATE: u16 = (1 << 5) | 1; // 1980-01-01 00:00, the format's epoch: no clock needed
+
+// Archive of (path, content) entries in the given order, deflated where that makes them
+// smaller or stored as is; Err
+// past the 4 GiB of plain ZIP
+pub(crate) fn archive(entries: &[(&str, Vec<u8>)], compress: bool) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let mut central = Vec::new();
+    for (name, data) in entries {
+        // members deflate cannot shrink (float arrays, mostly) are stored
+        let deflated = if compress { Some(deflate::deflate(data)).filter(|d| d.len() < data.len()) } else { None };
+        let packed = deflated.as_ref().unwrap_or(data);
+        let (version, method) = if deflated.is_some() { (20u16, 8u16) } else { (10, 0) };
+        let (offset, size, packed_size) = (u32::try_from(out.len()), u32::try_from(data.len()), u32::try_from(packed.len()));
+        let (Ok(offset), Ok(size), Ok(packed_size)) = (offset, size, packed_size) else {
+            return Err(format!("{} too large for a ZIP archive (4 GiB)", name));
+        };
+        // fields shared by the local header and the central directory entry
+        let mut common = Vec::with_capacity(26);
+        common.extend_from_slice(&version.to_le_bytes()); // version needed
+        common.extend_from_slice(&0u16.to_le_bytes()); // flags
+        common.extend_from_slice(&method.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes()); // time
+        common.extend_from_slice(&DOS_DATE.to_le_bytes());
+        common.extend_from_slice(&crc32(data).to_le_bytes());
+        common.extend_from_slice(&packed_size.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+
+        out.extend_from_slice(&0x0403_4B50u32.to_le_bytes());
+        out.extend_from_slice(&common);
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(packed);
+
+        central.extend_from_slice(&0x0201_4B50u32.to_le_bytes());
+        central.extend_from_slice(&20u16.to_le_bytes()); // version made by: MS-DOS, 2.0
+        central.extend_from_slice(&common);
+        central.extend_from_slice(&[0; 10]); // comment length, disk, internal and external attributes
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+    }
+    let Ok(central_at) = u32::try_from(out.len()) else {
+        return Err("archive too large for ZIP (4 GiB)".to_string());
+    };
+    let count = entries.len() as u16;
+    out.extend_from_slice(&central);
+    out.extend_from_slice(&0x0605_4B50u32.to_le_bytes());
+    out.extend_from_slice(&[0; 4]); // this disk, central directory disk
+    out.extend_from_slice(&count.to_le_bytes());
+    out.extend_from_slice(&count.to_le_bytes());
+    out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+    out.extend_from_slice(&central_at.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes()); // comment length
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (method, packed size, size) of each local header
+    fn members(zip: &[u8]) -> Vec<(u16, u32, u32)> {
+        let mut out = Vec::new();
+        let mut at = 0;
+        while zip[at..at + 4] == 0x0403_4B50u32.to_le_bytes() {
+            let u16_at = |i: usize| u16::from_le_bytes([zip[at + i], zip[at + i + 1]]);
+            let u32_at = |i: usize| u32::from_le_bytes(zip[at + i..at + i + 4].try_into().unwrap());
+            out.push((u16_at(8), u32_at(18), u32_at(22)));
+            at += 30 + u16_at(26) as usize + u32_at(18) as usize;
+        }
+        out
+    }
+
+    #[test]
+    fn crc_and_methods() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+
+        let text = b"abcabcabc".repeat(100);
+        let mut x = 0x2545_F491u32;
+        let noise: Vec<u8> = (0..1000)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            })
+            .collect();
+        let entries = [("text", text.clone()), ("noise", noise.clone())];
+        let zip = archive(&entries, true).unwrap();
+        let m = members(&zip);
+        assert_eq!(m[0].0, 8);
+        assert!(m[0].1 < m[0].2);
+        assert_eq!(m[1], (0, 1000, 1000));
+        assert_eq!(members(&archive(&entries, false).unwrap()), [(0, 900, 900), (0, 1000, 1000)]);
+
+        // end of central directory: two entries, directory right after the members
+        let eocd = &zip[zip.len() - 22..];
+        assert_eq!(&eocd[..4], b"PK\x05\x06");
+        assert_eq!(u16::from_le_bytes([eocd[10], eocd[11]]), 2);
+        let central_at = u32::from_le_bytes(eocd[16..20].try_into().unwrap()) as usize;
+        assert_eq!(&zip[central_at..central_at + 4], b"PK\x01\x02");
+    }
+}